@@ -45,7 +45,7 @@ fn main() {
         .map_err(|_| ());
 
     let scan_range = client
-        .scan_range(vec![2], vec![5])
+        .scan_range(vec![2]..vec![5])
         .map(|(k, v)| println!("  ({:?}, {:?})", k, v))
         .map_err(|e| eprintln!("Error: {}", e))
         .collect()
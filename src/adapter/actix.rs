@@ -0,0 +1,67 @@
+//! Mount the API within an `actix-web` application.
+
+use actix_web::{self, AsyncResponder, FutureResponse, HttpMessage, HttpRequest, HttpResponse};
+use actix_web::Scope;
+use futures::{Future, Stream};
+use hyper::{Body, Request, Response, Uri};
+use server::Router;
+
+/// Handle all requests to the given `Scope` that are not otherwise matched via the `Router`.
+///
+/// The path of each request is taken from the portion following the scope's prefix, e.g.
+/// `App::new().scope("/db", |scope| sled_web::adapter::actix::scope(scope, router))`.
+///
+/// Requests that do not match any endpoint receive a `404 Not Found` response.
+pub fn scope<S>(scope: Scope<S>, router: Router) -> Scope<S>
+where
+    S: 'static,
+{
+    scope.default_resource(move |resource| {
+        resource.f(move |req| respond(&router, req))
+    })
+}
+
+/// Respond to the given request via the `Router`.
+fn respond<S>(router: &Router, req: &HttpRequest<S>) -> FutureResponse<HttpResponse>
+where
+    S: 'static,
+{
+    let router = router.clone();
+    let method = req.method().clone();
+    let headers = req.headers().clone();
+    let path = match req.match_info().unprocessed() {
+        path if path.starts_with('/') => path.to_string(),
+        path => format!("/{}", path),
+    };
+    req.body()
+        .limit(usize::max_value())
+        .from_err::<actix_web::Error>()
+        .and_then(move |bytes| {
+            let uri = path.parse::<Uri>().map_err(actix_web::error::ErrorBadRequest)?;
+            let mut request = Request::new(Body::from(bytes));
+            *request.method_mut() = method;
+            *request.uri_mut() = uri;
+            *request.headers_mut() = headers;
+            Ok(request)
+        })
+        .and_then(move |request| {
+            router
+                .respond(request)
+                .map_err(actix_web::error::ErrorInternalServerError)
+        })
+        .map(into_http_response)
+        .responder()
+}
+
+/// Convert the hyper `Response` into an `actix-web` response, streaming the body.
+fn into_http_response(response: Response<Body>) -> HttpResponse {
+    let (parts, body) = response.into_parts();
+    let mut builder = HttpResponse::build(parts.status);
+    for (name, value) in parts.headers.iter() {
+        builder.header(name.clone(), value.clone());
+    }
+    let body = body
+        .map(|chunk| chunk.into_bytes())
+        .map_err(actix_web::error::ErrorInternalServerError);
+    builder.streaming(body)
+}
@@ -0,0 +1,11 @@
+//! Adapters for mounting the API within servers built using other web frameworks.
+//!
+//! Each adapter is enabled via the cargo feature of the same name as the framework crate, e.g.
+//! `warp` or `actix-web`, and handles requests via a `server::Router`. The `service` adapter for
+//! `hyper` servers is always available.
+
+#[cfg(feature = "actix-web")]
+pub mod actix;
+pub mod service;
+#[cfg(feature = "warp")]
+pub mod warp;
@@ -0,0 +1,62 @@
+//! Mount the API within a `hyper` server via the `Service` trait.
+
+use futures::future::{self, FutureResult};
+use hyper::{self, Body, Request};
+use hyper::service::{NewService, Service};
+use response::ResponseFuture;
+use server::Router;
+
+/// A hyper `Service` that handles all requests to the API via a `Router`.
+///
+/// Allows for composing the API with other services, e.g. dispatching requests with paths
+/// beginning with `/tree` to the `TreeService` and all others to the application's own routes.
+/// Requests that do not match any endpoint receive a `404 Not Found` response. Applications
+/// wishing to fall back to their own handling instead may use `Router::route`.
+///
+/// The `TreeService` is also a `NewService`, and so may be served directly, e.g.
+/// `hyper::Server::bind(&addr).serve(TreeService::new(router))`. All clones share the same
+/// `Router` state.
+#[derive(Clone)]
+pub struct TreeService {
+    router: Router,
+}
+
+impl TreeService {
+    /// Handle all requests via the given `Router`.
+    pub fn new(router: Router) -> Self {
+        TreeService { router }
+    }
+
+    /// The `Router` via which requests are handled.
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+}
+
+impl From<Router> for TreeService {
+    fn from(router: Router) -> Self {
+        TreeService::new(router)
+    }
+}
+
+impl Service for TreeService {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = hyper::Error;
+    type Future = ResponseFuture;
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        self.router.respond(request)
+    }
+}
+
+impl NewService for TreeService {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = hyper::Error;
+    type Service = TreeService;
+    type Future = FutureResult<TreeService, hyper::Error>;
+    type InitError = hyper::Error;
+    fn new_service(&self) -> Self::Future {
+        future::ok(self.clone())
+    }
+}
@@ -0,0 +1,52 @@
+//! Mount the API within a `warp` server.
+
+use futures::future::{self, Either};
+use futures::Future;
+use hyper::{Body, HeaderMap, Method, Request, Response, Uri};
+use response::UnknownRequest;
+use server::Router;
+use warp::{self, Buf, Filter, Rejection};
+use warp::body::FullBody;
+use warp::path::Tail;
+
+/// A `warp` `Filter` that handles all requests to the API via the given `Router`.
+///
+/// The path of each request is taken from the portion that has not yet been matched by preceding
+/// filters, allowing the API to be mounted beneath some prefix, e.g.
+/// `warp::path("db").and(sled_web::adapter::warp::filter(router))`.
+///
+/// Requests that do not match any endpoint are rejected with `warp::reject::not_found`, allowing
+/// other filters to be tried.
+pub fn filter(
+    router: Router,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::path::tail())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::concat())
+        .and_then(move |method, tail: Tail, headers, body: FullBody| {
+            let path = format!("/{}", tail.as_str());
+            let request = match into_request(method, &path, headers, body) {
+                None => return Either::A(future::err(warp::reject::not_found())),
+                Some(request) => request,
+            };
+            match router.route(request) {
+                Err(UnknownRequest) => Either::A(future::err(warp::reject::not_found())),
+                Ok(response) => Either::B(response.map_err(warp::reject::custom)),
+            }
+        })
+}
+
+/// Construct a hyper `Request` from the parts extracted by the filter.
+fn into_request(
+    method: Method,
+    path: &str,
+    headers: HeaderMap,
+    body: FullBody,
+) -> Option<Request<Body>> {
+    let mut request = Request::new(Body::from(body.bytes().to_vec()));
+    *request.method_mut() = method;
+    *request.uri_mut() = path.parse::<Uri>().ok()?;
+    *request.headers_mut() = headers;
+    Some(request)
+}
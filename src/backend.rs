@@ -0,0 +1,328 @@
+//! The key-value stores that may be served by the server.
+//!
+//! The server handles all requests via the `KvBackend` trait, allowing the same protocol and
+//! client to be used with a `sled::Tree` (the default) or any other ordered key-value store, e.g.
+//! the in-memory `MemoryBackend` which is useful for testing.
+
+use sled;
+use sled_search;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::ops::Bound;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// An iterator yielding entries of a `KvBackend` in order of their keys.
+pub type Iter<'a> = Box<Iterator<Item = sled::Result<(Vec<u8>, Vec<u8>), ()>> + Send + 'a>;
+
+/// Combines the existing value of a key, if any, with a merged value, as with `sled`'s own merge
+/// operators.
+pub type MergeOperator = fn(key: &[u8], last_value: Option<&[u8]>, new_merge: &[u8])
+    -> Option<Vec<u8>>;
+
+/// An ordered key-value store that may be served by the server.
+///
+/// Errors are described using `sled::Error` so that they may be reported to clients in the same
+/// manner regardless of the backend.
+pub trait KvBackend: Send + Sync {
+    /// Retrieve the value associated with the given key.
+    fn get(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()>;
+
+    /// Delete the entry associated with the given key, returning the removed value.
+    fn del(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()>;
+
+    /// Set the entry with the given key and value, replacing the original if one exists.
+    fn set(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()>;
+
+    /// Compare and swap the value associated with the given key.
+    ///
+    /// Produces a `sled::Error::CasFailed` containing the current value if it differs from `old`.
+    fn cas(
+        &self,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), Option<Vec<u8>>>;
+
+    /// Merge the given value into the total state for the key.
+    fn merge(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()>;
+
+    /// Flush any pending IO buffers to disk.
+    fn flush(&self) -> sled::Result<(), ()>;
+
+    /// Iterate over all entries starting from the given key.
+    fn scan<'a>(&'a self, key: &[u8]) -> Iter<'a>;
+
+    /// Iterate over all entries.
+    fn iter<'a>(&'a self) -> Iter<'a> {
+        self.scan(&[])
+    }
+
+    /// Retrieve the entry with the greatest key.
+    fn max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()>;
+
+    /// Retrieve the entry preceding the given key.
+    fn pred(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()>;
+
+    /// Retrieve the entry preceding or including the given key.
+    fn pred_incl(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()>;
+}
+
+/// Opens and removes the named trees that may be served alongside the default tree.
+pub trait Store: Send + Sync {
+    /// Open the backend with the given name, creating it if it does not yet exist.
+    fn open(&self, name: &str) -> sled::Result<Arc<KvBackend>, ()>;
+
+    /// Permanently remove all entries of the backend with the given name.
+    ///
+    /// The backend will no longer be served by the time this is called.
+    fn remove(&self, name: &str) -> sled::Result<(), ()>;
+}
+
+/// A `Store` that opens each named tree as a `sled::Tree` within its own sub-directory.
+#[derive(Clone, Debug)]
+pub struct SledStore {
+    config: sled::ConfigBuilder,
+    dir: PathBuf,
+}
+
+/// A `Store` that opens each named tree as a new, empty `MemoryBackend`.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore;
+
+/// A `KvBackend` that stores all entries within an in-memory `BTreeMap`.
+///
+/// Entries are lost when the backend is dropped. Primarily useful for tests.
+#[derive(Default)]
+pub struct MemoryBackend {
+    map: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+    merge_operator: Option<MergeOperator>,
+}
+
+/// An iterator yielding the entries of a `MemoryBackend`.
+///
+/// The map is only locked while retrieving each entry, so entries inserted or removed during
+/// iteration may or may not be yielded, as with `sled::Iter`.
+struct MemoryIter<'a> {
+    map: &'a RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+    next: Option<Bound<Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    /// Create a new, empty `MemoryBackend`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Use the given merge operator for `merge` requests.
+    ///
+    /// Without a merge operator, `merge` produces a `sled::Error::Unsupported`.
+    pub fn merge_operator(mut self, merge_operator: MergeOperator) -> Self {
+        self.merge_operator = Some(merge_operator);
+        self
+    }
+
+    fn read(&self) -> RwLockReadGuard<BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.map.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.map.write().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl SledStore {
+    /// Open each named tree within a sub-directory of `dir` using the given configuration.
+    ///
+    /// The path of the given configuration is replaced with that of each sub-directory.
+    pub fn new<P>(config: sled::ConfigBuilder, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        SledStore { config, dir }
+    }
+
+    /// The sub-directory of the named tree.
+    ///
+    /// Produces an error unless the name is a single, plain path component, so that no name may
+    /// refer to a directory outside of the store's own.
+    fn tree_dir(&self, name: &str) -> sled::Result<PathBuf, ()> {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => Ok(self.dir.join(name)),
+            _ => {
+                let msg = format!("invalid tree name: {:?}", name);
+                Err(sled::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, msg)))
+            }
+        }
+    }
+}
+
+impl Store for SledStore {
+    fn open(&self, name: &str) -> sled::Result<Arc<KvBackend>, ()> {
+        let config = self.config.clone().path(self.tree_dir(name)?).build();
+        let tree = sled::Tree::start(config)?;
+        Ok(Arc::new(tree))
+    }
+
+    fn remove(&self, name: &str) -> sled::Result<(), ()> {
+        match fs::remove_dir_all(self.tree_dir(name)?) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(sled::Error::Io(err)),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn open(&self, _name: &str) -> sled::Result<Arc<KvBackend>, ()> {
+        Ok(Arc::new(MemoryBackend::new()))
+    }
+
+    fn remove(&self, _name: &str) -> sled::Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl KvBackend for sled::Tree {
+    fn get(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        sled::Tree::get(self, key)
+    }
+
+    fn del(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        sled::Tree::del(self, key)
+    }
+
+    fn set(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        sled::Tree::set(self, key, value)
+    }
+
+    fn cas(
+        &self,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), Option<Vec<u8>>> {
+        sled::Tree::cas(self, key, old, new)
+    }
+
+    fn merge(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        sled::Tree::merge(self, key, value)
+    }
+
+    fn flush(&self) -> sled::Result<(), ()> {
+        sled::Tree::flush(self)
+    }
+
+    fn scan<'a>(&'a self, key: &[u8]) -> Iter<'a> {
+        Box::new(sled::Tree::scan(self, key))
+    }
+
+    fn max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        sled_search::max(self)
+    }
+
+    fn pred(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        sled_search::pred(self, key)
+    }
+
+    fn pred_incl(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        sled_search::pred_incl(self, key)
+    }
+}
+
+impl KvBackend for MemoryBackend {
+    fn get(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        Ok(self.read().get(key).cloned())
+    }
+
+    fn del(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        Ok(self.write().remove(key))
+    }
+
+    fn set(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        self.write().insert(key, value);
+        Ok(())
+    }
+
+    fn cas(
+        &self,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), Option<Vec<u8>>> {
+        let mut map = self.write();
+        let current = map.get(&key).cloned();
+        if current != old {
+            return Err(sled::Error::CasFailed(current));
+        }
+        match new {
+            None => map.remove(&key),
+            Some(new) => map.insert(key, new),
+        };
+        Ok(())
+    }
+
+    fn merge(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        let merge_operator = match self.merge_operator {
+            Some(merge_operator) => merge_operator,
+            None => {
+                let msg = "no merge operator configured for the backend".to_string();
+                return Err(sled::Error::Unsupported(msg));
+            }
+        };
+        let mut map = self.write();
+        let merged = merge_operator(&key, map.get(&key).map(|v| &v[..]), &value);
+        match merged {
+            None => map.remove(&key),
+            Some(merged) => map.insert(key, merged),
+        };
+        Ok(())
+    }
+
+    fn flush(&self) -> sled::Result<(), ()> {
+        Ok(())
+    }
+
+    fn scan<'a>(&'a self, key: &[u8]) -> Iter<'a> {
+        let next = Some(Bound::Included(key.to_vec()));
+        Box::new(MemoryIter { map: &self.map, next })
+    }
+
+    fn max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        Ok(self.read().iter().next_back().map(|(k, v)| (k.clone(), v.clone())))
+    }
+
+    fn pred(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let map = self.read();
+        let range = (Bound::Unbounded, Bound::Excluded(key));
+        Ok(map.range::<[u8], _>(range).next_back().map(|(k, v)| (k.clone(), v.clone())))
+    }
+
+    fn pred_incl(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let map = self.read();
+        let range = (Bound::Unbounded, Bound::Included(key));
+        Ok(map.range::<[u8], _>(range).next_back().map(|(k, v)| (k.clone(), v.clone())))
+    }
+}
+
+impl<'a> Iterator for MemoryIter<'a> {
+    type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = {
+            let map = self.map.read().unwrap_or_else(|err| err.into_inner());
+            let start = match *self.next.as_ref()? {
+                Bound::Included(ref key) => Bound::Included(&key[..]),
+                Bound::Excluded(ref key) => Bound::Excluded(&key[..]),
+                Bound::Unbounded => Bound::Unbounded,
+            };
+            map.range::<[u8], _>((start, Bound::Unbounded))
+                .next()
+                .map(|(k, v)| (k.clone(), v.clone()))
+        };
+        self.next = entry.as_ref().map(|&(ref k, _)| Bound::Excluded(k.clone()));
+        entry.map(Ok)
+    }
+}
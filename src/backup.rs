@@ -0,0 +1,407 @@
+//! Periodic backups of the served tree, configured via `server::ConfigBuilder::backup`.
+//!
+//! Each backup is an `Export` stream (see `request::Export`) written to a `BackupTarget`, named by
+//! the moment at which it was taken so that backups sort oldest first. Only the most recent
+//! backups are retained.
+//!
+//! The tree may be restored to its state at any point in time following the oldest retained
+//! backup by replaying the tree's changelog onto the preceding backup. See `reconstruct`.
+//!
+//! Backups are written to a local directory via `DirTarget` by default. Other storage, e.g. an
+//! S3-compatible object store, may be used by implementing `BackupTarget` and giving it to the
+//! server's `Router` via `set_backup_target`.
+
+use request::{self, ExportItem, RestorePoint};
+use sled;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tree::Tree;
+
+/// The beginning of the name of every backup.
+pub const FILE_PREFIX: &'static str = "backup-";
+
+/// The end of the name of every backup.
+pub const FILE_SUFFIX: &'static str = ".sledweb";
+
+/// The schedule by which the server periodically backs up the served tree.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// The directory to which backups are written, created if it does not exist.
+    ///
+    /// Ignored if another target is given via `server::Router::set_backup_target`.
+    pub dir: PathBuf,
+    /// The duration between the beginning of each backup.
+    pub interval: Duration,
+    /// The number of the most recent backups that are retained. Older backups are removed.
+    pub keep: usize,
+}
+
+/// A backup written via `write_backup`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Backup {
+    /// The name of the backup within its target.
+    pub name: String,
+    /// The sequence number of the backup's header, up to which every change is reflected within
+    /// the backup. See `write_export`.
+    pub seq: u64,
+    /// The number of milliseconds since the UNIX epoch at which the backup was begun.
+    pub created_ms: u64,
+}
+
+/// Storage to which backups and exports are written, e.g. a local directory or an object store.
+pub trait BackupTarget: Send + Sync {
+    /// Begin writing a new backup with the given name.
+    ///
+    /// The backup must not be listed until it is committed via `BackupWriter::commit` and should
+    /// be discarded if the writer is dropped without being committed.
+    fn create(&self, name: &str) -> io::Result<Box<BackupWriter>>;
+
+    /// Begin reading the committed backup with the given name.
+    fn open(&self, name: &str) -> io::Result<Box<Read + Send>>;
+
+    /// The names of all committed backups in any order.
+    fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Remove the backup with the given name. Removing a missing backup is not an error.
+    fn remove(&self, name: &str) -> io::Result<()>;
+}
+
+/// A backup being written to a `BackupTarget`.
+pub trait BackupWriter: Write + Send {
+    /// Complete the backup, making it visible via `BackupTarget::list`.
+    fn commit(self: Box<Self>) -> io::Result<()>;
+}
+
+/// The entries of a tree at a point in time, reconstructed via `reconstruct`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Reconstruction {
+    /// The name of the backup onto which the changes were replayed, or `None` if the changes were
+    /// replayed onto an empty tree.
+    pub backup: Option<String>,
+    /// The sequence number of the last change reflected within the entries.
+    pub seq: u64,
+    /// The number of changes replayed onto the backup.
+    pub replayed: u64,
+    pub entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/// A `BackupTarget` storing each backup as a file within a local directory.
+///
+/// Each backup is first written to a temporary file that is renamed once committed, so that an
+/// interrupted backup is never mistaken for a complete one.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DirTarget {
+    dir: PathBuf,
+}
+
+// A backup being written to a temporary file by a `DirTarget`.
+struct DirWriter {
+    writer: Option<BufWriter<File>>,
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl Schedule {
+    /// A schedule writing a backup to `dir` every `interval`, retaining the `keep` most recent.
+    pub fn new<P>(dir: P, interval: Duration, keep: usize) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        Schedule { dir, interval, keep }
+    }
+}
+
+impl DirTarget {
+    /// A target writing backups to the given directory, created if it does not exist.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        DirTarget { dir }
+    }
+}
+
+impl BackupTarget for DirTarget {
+    fn create(&self, name: &str) -> io::Result<Box<BackupWriter>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(name);
+        let tmp_path = self.dir.join(format!("{}.tmp", name));
+        let writer = Some(BufWriter::new(File::create(&tmp_path)?));
+        Ok(Box::new(DirWriter { writer, tmp_path, path }))
+    }
+
+    fn open(&self, name: &str) -> io::Result<Box<Read + Send>> {
+        let file = File::open(self.dir.join(name))?;
+        Ok(Box::new(file))
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            result => result?,
+        };
+        let mut names = vec![];
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".tmp") {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(name)) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl Write for DirWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer {
+            Some(ref mut writer) => writer.write(buf),
+            None => Err(io::Error::new(io::ErrorKind::Other, "backup already committed")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer {
+            Some(ref mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl BackupWriter for DirWriter {
+    fn commit(mut self: Box<Self>) -> io::Result<()> {
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        let result = writer
+            .into_inner()
+            .map_err(|err| err.into_error())
+            .and_then(|file| file.sync_all())
+            .and_then(|()| fs::rename(&self.tmp_path, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+        result
+    }
+}
+
+impl Drop for DirWriter {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+/// Write an `Export` of the tree to the given writer, producing the sequence number of its header.
+///
+/// Mutations are not excluded while the export is written. See `Tree::snapshot`.
+pub fn write_export<W>(tree: &Tree, writer: &mut W) -> sled::Result<u64, ()>
+where
+    W: ?Sized + Write,
+{
+    let snapshot = tree.snapshot();
+    let mut count = 0;
+    writer.write_all(&request::encode_export_header(snapshot.seq)).map_err(sled::Error::Io)?;
+    for res in snapshot.entries {
+        let (k, v) = res?;
+        let entry = request::encode_export_entry(&k, &v);
+        writer.write_all(&entry).map_err(sled::Error::Io)?;
+        count += 1;
+    }
+    writer.write_all(&request::encode_export_end(count)).map_err(sled::Error::Io)?;
+    writer.flush().map_err(sled::Error::Io)?;
+    Ok(snapshot.seq)
+}
+
+/// Write a backup of the tree to the given target under a new name.
+///
+/// The backup is discarded by the target if it could not be written in full.
+pub fn write_backup(tree: &Tree, target: &BackupTarget) -> sled::Result<Backup, ()> {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let created_ms = since_epoch.as_secs() * 1_000 + u64::from(since_epoch.subsec_millis());
+    let name = format!("{}{:020}{}", FILE_PREFIX, created_ms, FILE_SUFFIX);
+    let mut writer = target.create(&name).map_err(sled::Error::Io)?;
+    let seq = write_export(tree, &mut *writer)?;
+    writer.commit().map_err(sled::Error::Io)?;
+    Ok(Backup { name, seq, created_ms })
+}
+
+/// The names of all backups within the target, oldest first.
+pub fn list_backups(target: &BackupTarget) -> sled::Result<Vec<String>, ()> {
+    let mut names: Vec<String> = target
+        .list()
+        .map_err(sled::Error::Io)?
+        .into_iter()
+        .filter(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Remove all but the `keep` most recent backups within the target, producing the names of the
+/// retained backups, oldest first.
+pub fn prune_backups(target: &BackupTarget, keep: usize) -> sled::Result<Vec<String>, ()> {
+    let mut names = list_backups(target)?;
+    let excess = names.len().saturating_sub(keep);
+    for name in names.drain(..excess) {
+        target.remove(&name).map_err(sled::Error::Io)?;
+    }
+    Ok(names)
+}
+
+/// The number of milliseconds since the UNIX epoch at which the backup with the given name was
+/// begun, or `None` if the name was not produced by `write_backup`.
+pub fn backup_time_ms(name: &str) -> Option<u64> {
+    if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_SUFFIX) {
+        return None;
+    }
+    name[FILE_PREFIX.len()..name.len() - FILE_SUFFIX.len()].parse().ok()
+}
+
+/// Decode the named backup within the target, calling `f` with each item until it returns
+/// `false`.
+///
+/// Fails if the backup is not an `Export` stream or ends before it is complete.
+fn decode_backup<F>(target: &BackupTarget, name: &str, mut f: F) -> sled::Result<(), ()>
+where
+    F: FnMut(ExportItem) -> bool,
+{
+    let decode_err = |msg: String| {
+        sled::Error::Unsupported(format!("failed to decode backup {}: {}", name, msg))
+    };
+    let mut reader = target.open(name).map_err(sled::Error::Io)?;
+    let mut decoder = request::ExportDecoder::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let len = reader.read(&mut buffer).map_err(sled::Error::Io)?;
+        if len == 0 {
+            break;
+        }
+        decoder.extend(&buffer[..len]);
+        while let Some(item) = decoder.next_item().map_err(&decode_err)? {
+            if let ExportItem::DeltaHeader { .. } = item {
+                return Err(decode_err("the backup is an incremental export".to_string()));
+            }
+            if !f(item) {
+                return Ok(());
+            }
+        }
+    }
+    if !decoder.is_complete() {
+        return Err(decode_err("the backup is incomplete".to_string()));
+    }
+    Ok(())
+}
+
+/// The sequence number of the most recent change reflected within the named backup.
+pub fn backup_seq(target: &BackupTarget, name: &str) -> sled::Result<u64, ()> {
+    let mut seq = None;
+    decode_backup(target, name, |item| match item {
+        ExportItem::Header { seq: header_seq } => {
+            seq = Some(header_seq);
+            false
+        }
+        _ => true,
+    })?;
+    seq.ok_or_else(|| sled::Error::Unsupported(format!("backup {} has no header", name)))
+}
+
+/// Read all entries of the named backup along with the sequence number of the most recent change
+/// reflected within it.
+pub fn read_backup(
+    target: &BackupTarget,
+    name: &str,
+) -> sled::Result<(u64, BTreeMap<Vec<u8>, Vec<u8>>), ()> {
+    let mut seq = 0;
+    let mut entries = BTreeMap::new();
+    decode_backup(target, name, |item| {
+        match item {
+            ExportItem::Header { seq: header_seq } => seq = header_seq,
+            ExportItem::Entry(key, value) => {
+                entries.insert(key, value);
+            }
+            _ => (),
+        }
+        true
+    })?;
+    Ok((seq, entries))
+}
+
+/// Reconstruct the entries of the tree at the given point from the most recent backup within the
+/// target preceding the point and the changes to the tree that followed the backup.
+///
+/// If no backup precedes the point, the changes are replayed onto an empty tree. Produces `None`
+/// if some of the changes that followed the backup are no longer retained by the tree. Changes
+/// are retained via `Tree::with_changelog` or `Tree::with_change_history`.
+///
+/// A backup may also reflect changes applied while it was written, some of which may follow the
+/// point. Each entry changed following the point is therefore restored to the previous value
+/// described by the first such change.
+pub fn reconstruct(
+    tree: &Tree,
+    target: &BackupTarget,
+    point: RestorePoint,
+) -> sled::Result<Option<Reconstruction>, ()> {
+    let mut base = None;
+    for name in list_backups(target)?.into_iter().rev() {
+        let precedes = match point {
+            RestorePoint::Seq(seq) => backup_seq(target, &name)? <= seq,
+            RestorePoint::TimeMs(ms) => backup_time_ms(&name).map_or(false, |time| time <= ms),
+        };
+        if precedes {
+            base = Some(name);
+            break;
+        }
+    }
+    let (seq, mut entries) = match base {
+        Some(ref name) => read_backup(target, name)?,
+        None => (0, BTreeMap::new()),
+    };
+    let mut reached = seq;
+    let mut replayed = 0;
+    let mut passed = false;
+    // The keys of the entries changed following the point.
+    let mut changed_after = BTreeSet::new();
+    let complete = tree.replay_changes(seq, |event| {
+        passed = passed || match point {
+            RestorePoint::Seq(seq) => event.seq > seq,
+            RestorePoint::TimeMs(ms) => event.time_ms > ms,
+        };
+        let value = if !passed {
+            reached = event.seq;
+            replayed += 1;
+            &event.new
+        } else if changed_after.insert(event.key.clone()) {
+            &event.old
+        } else {
+            return;
+        };
+        match *value {
+            Some(ref value) => {
+                entries.insert(event.key.clone(), value.clone());
+            }
+            None => {
+                entries.remove(&event.key);
+            }
+        }
+    })?;
+    if !complete {
+        return Ok(None);
+    }
+    let backup = base;
+    let seq = reached;
+    Ok(Some(Reconstruction { backup, seq, replayed, entries }))
+}
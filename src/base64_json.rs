@@ -0,0 +1,601 @@
+//! JSON in which byte arrays are represented as base64 strings, e.g. `"aGVsbG8="` in place of
+//! `[104, 101, 108, 108, 111]`, selected via `encoding::Encoding::Base64Json`.
+//!
+//! Every non-empty sequence of `u8`s is serialized as a padded standard base64 string, as
+//! produced by `btoa` in JavaScript. Empty sequences are serialized as `[]`, as an empty byte
+//! array cannot be told apart from any other empty sequence. When deserializing, a base64 string
+//! is accepted wherever a sequence is expected, as is the usual array of integers.
+
+use base64;
+use serde::de::{self, DeserializeSeed, Deserializer, IntoDeserializer, Visitor};
+use serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde::ser::{self, Serialize};
+use serde::Deserialize;
+use serde_json::{self, Map, Value};
+
+/// Serializes a value into a JSON `Value`, representing byte arrays as base64 strings.
+struct ValueSerializer;
+
+/// Deserializes a value from a JSON `Value`, accepting base64 strings in place of sequences.
+struct ValueDeserializer(Value);
+
+/// A serialized value, distinguishing bytes from other numbers so that sequences of bytes may be
+/// recognised.
+enum Output {
+    Byte(u8),
+    Value(Value),
+}
+
+struct SerializeSeq {
+    items: Vec<Output>,
+}
+
+struct SerializeVec {
+    values: Vec<Value>,
+}
+
+struct SerializeTupleVariant {
+    variant: &'static str,
+    values: Vec<Value>,
+}
+
+struct SerializeMap {
+    map: Map<String, Value>,
+    key: Option<String>,
+}
+
+struct SerializeStructVariant {
+    variant: &'static str,
+    map: Map<String, Value>,
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+struct VariantDeserializer(Value);
+
+/// Serialize the given value to JSON bytes, representing byte arrays as base64 strings.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>, serde_json::Error>
+where
+    T: ?Sized + Serialize,
+{
+    serde_json::to_vec(&to_value(value)?)
+}
+
+/// Serialize the given value to a JSON `Value`, representing byte arrays as base64 strings.
+pub fn to_value<T>(value: &T) -> Result<Value, serde_json::Error>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(ValueSerializer).map(Output::into_value)
+}
+
+/// Deserialize a `T` from JSON bytes, accepting base64 strings in place of byte arrays.
+pub fn from_slice<T>(bytes: &[u8]) -> Result<T, serde_json::Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    from_value(serde_json::from_slice(bytes)?)
+}
+
+/// Deserialize a `T` from a JSON `Value`, accepting base64 strings in place of byte arrays.
+pub fn from_value<T>(value: Value) -> Result<T, serde_json::Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    T::deserialize(ValueDeserializer(value))
+}
+
+/// The error produced upon encountering a string that is not valid base64 in place of a sequence.
+fn invalid_base64(s: &str) -> serde_json::Error {
+    de::Error::invalid_value(de::Unexpected::Str(s), &"a base64 string")
+}
+
+impl Output {
+    fn into_value(self) -> Value {
+        match self {
+            Output::Byte(byte) => Value::from(byte),
+            Output::Value(value) => value,
+        }
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Output;
+    type Error = serde_json::Error;
+    type SerializeSeq = SerializeSeq;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Output, Self::Error> {
+        Ok(Output::Byte(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::from(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::String(base64::encode(v))))
+    }
+
+    fn serialize_none(self) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Null))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Output, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Null))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Output, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Output, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut map = Map::new();
+        map.insert(variant.to_string(), to_value(value)?);
+        Ok(Output::Value(Value::Object(map)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeSeq, Self::Error> {
+        let items = Vec::with_capacity(len.unwrap_or(0));
+        Ok(SerializeSeq { items })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, Self::Error> {
+        let values = Vec::with_capacity(len);
+        Ok(SerializeVec { values })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, Self::Error> {
+        ser::Serializer::serialize_tuple(self, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariant, Self::Error> {
+        let values = Vec::with_capacity(len);
+        Ok(SerializeTupleVariant { variant, values })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, Self::Error> {
+        let map = Map::new();
+        let key = None;
+        Ok(SerializeMap { map, key })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap, Self::Error> {
+        ser::Serializer::serialize_map(self, Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeStructVariant, Self::Error> {
+        let map = Map::new();
+        Ok(SerializeStructVariant { variant, map })
+    }
+}
+
+impl ser::SerializeSeq for SerializeSeq {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        let bytes: Vec<u8> = self.items
+            .iter()
+            .filter_map(|item| match *item {
+                Output::Byte(byte) => Some(byte),
+                Output::Value(_) => None,
+            })
+            .collect();
+        if !bytes.is_empty() && bytes.len() == self.items.len() {
+            return Ok(Output::Value(Value::String(base64::encode(&bytes))));
+        }
+        let values = self.items.into_iter().map(Output::into_value).collect();
+        Ok(Output::Value(Value::Array(values)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Array(self.values)))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        ser::SerializeTuple::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        let mut map = Map::new();
+        map.insert(self.variant.to_string(), Value::Array(self.values));
+        Ok(Output::Value(Value::Object(map)))
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = match to_value(key)? {
+            Value::String(s) => s,
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(ser::Error::custom("map keys must be strings")),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take().expect("`serialize_value` called before `serialize_key`");
+        self.map.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        Ok(Output::Value(Value::Object(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.map.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Output;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.map.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Output, Self::Error> {
+        let mut map = Map::new();
+        map.insert(self.variant.to_string(), Value::Object(self.map));
+        Ok(Output::Value(Value::Object(map)))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Array(values) => {
+                let values = values.into_iter().map(ValueDeserializer);
+                let mut seq: SeqDeserializer<_, serde_json::Error> = SeqDeserializer::new(values);
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Object(map) => {
+                let entries = map.into_iter().map(|(k, v)| (k, ValueDeserializer(v)));
+                let mut map: MapDeserializer<_, serde_json::Error> = MapDeserializer::new(entries);
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(s) => {
+                let bytes = base64::decode(&s).map_err(|_| invalid_base64(&s))?;
+                let mut seq: SeqDeserializer<_, serde_json::Error> =
+                    SeqDeserializer::new(bytes.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            value => ValueDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(s) => {
+                let bytes = base64::decode(&s).map_err(|_| invalid_base64(&s))?;
+                visitor.visit_byte_buf(bytes)
+            }
+            value => ValueDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(ValueDeserializer(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::String(variant) => {
+                let variant: StringDeserializer<serde_json::Error> = variant.into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            Value::Object(map) => {
+                let mut entries = map.into_iter();
+                let (variant, value) = match (entries.next(), entries.next()) {
+                    (Some(entry), None) => entry,
+                    _ => {
+                        let unexpected = de::Unexpected::Map;
+                        return Err(de::Error::invalid_value(unexpected, &"a single variant"));
+                    }
+                };
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            value => ValueDeserializer(value).deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = serde_json::Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant: StringDeserializer<serde_json::Error> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = serde_json::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        <()>::deserialize(ValueDeserializer(self.0))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(ValueDeserializer(self.0))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(ValueDeserializer(self.0), visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(ValueDeserializer(self.0), visitor)
+    }
+}
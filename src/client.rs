@@ -1,18 +1,120 @@
-use futures::{Async, Poll};
+use backup::BackupTarget;
+use base64;
+use base64_json;
+use bincode;
+use compression::{self, Compression};
+use encoding::{self, Encoding};
+use futures::{self, Async, Poll};
+use futures::future::Either;
 use hyper::{self, Body, Request, Response, StatusCode, Uri};
 use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderValue};
 use hyper::rt::{Future, Stream};
 use request;
+use response::ResponseFuture;
 use serde::Deserialize;
 use serde_json;
+use server::Router;
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
 use std::error::Error as StdError;
 use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_timer::Delay;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::tungstenite::protocol::Role;
 
 /// A hyper `Client` wrapper that simplifies communication with the sled `Tree` server.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Client {
     uri: Uri,
-    client: hyper::Client<HttpConnector, Body>,
+    transport: Arc<Transport>,
+    deadline: Option<Duration>,
+    /// How long to wait for the response to each request to begin. See `Client::timeout`.
+    timeout: Option<Duration>,
+    /// The wire format revision pinned via the `request::PROTOCOL` header.
+    protocol: u32,
+    /// Prepended to the path of every request, e.g. to make requests via a named view.
+    path_prefix: String,
+    /// The normalized path beneath which the server serves its routes, if any.
+    base_path: Option<String>,
+    /// The name of the tree to which tree requests are made, or `None` for the default tree.
+    tree_name: Option<String>,
+    /// The base URIs of the replicas to which reads are made, if any. See `Client::replicas`.
+    replicas: Arc<Vec<Uri>>,
+    /// The index of the replica to which the next read is made, modulo the number of replicas.
+    next_replica: Arc<AtomicUsize>,
+    consistency: ReadConsistency,
+    /// The encoding of request and response bodies. See `Client::encoding`.
+    encoding: Encoding,
+    /// The compression requested for response bodies. See `Client::compression`.
+    compression: Option<Compression>,
+    /// Whether `Get`s of missing keys are answered with `404`. See `Client::missing_not_found`.
+    missing_not_found: bool,
+}
+
+/// Where a `Client` with replicas makes its reads. See `Client::replicas`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ReadConsistency {
+    /// Reads are made to the replicas round-robin, and so may not yet observe recent writes.
+    Eventual,
+    /// Reads are made to the primary, observing all writes that have completed.
+    Strong,
+}
+
+/// The result of a conditional `Get` performed via `Client::get_cached`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Cached {
+    /// The value is unchanged, still having the entity tag with which the request was made.
+    NotModified,
+    /// The current value along with its entity tag, or `None` for both if there is no entry.
+    Modified {
+        value: Option<Value>,
+        etag: Option<String>,
+    },
+}
+
+/// A handle to a `Client` that confines all operations to the keys beginning with some prefix.
+///
+/// The prefix is transparently prepended to all keys sent to the server and stripped from all keys
+/// received from it. This allows for several parts of an application to share a single tree
+/// without threading prefix concatenation through every call site.
+///
+/// Created via the `Client::namespaced` method.
+#[derive(Clone, Debug)]
+pub struct Namespaced {
+    client: Client,
+    prefix: Key,
+}
+
+/// A pair of `Client`s to which all writes are mirrored while reads are served by the primary.
+///
+/// Supports zero-downtime migrations between deployments: mirror writes to the new server while
+/// reading from the old, copy the existing entries across, e.g. via `Client::sync_to`, then
+/// cut over via `swapped`. Each write is applied via the primary and, once it succeeds, via the
+/// secondary, producing the result of the primary. A write fails if it fails via either server,
+/// in which case the secondary may differ from the primary until repaired.
+///
+/// Created via the `Client::mirrored` method.
+#[derive(Clone, Debug)]
+pub struct Mirrored {
+    primary: Client,
+    secondary: Client,
+}
+
+/// The means by which a `Client` submits requests to the server.
+///
+/// Implemented for the hyper `Client` (the default, submitting requests over HTTP) and for the
+/// server's `Router` (submitting requests directly within the same process).
+pub trait Transport: Send + Sync {
+    /// Submit the given request, producing a `Future` yielding the response.
+    fn request(&self, request: Request<Body>) -> ResponseFuture;
 }
 
 /// The possible errors that may be produced by the `Client` request methods.
@@ -20,42 +122,470 @@ pub struct Client {
 pub enum Error {
     Hyper(hyper::Error),
     SerdeJson(serde_json::Error),
+    /// The server responded with an error described by a bare message, as is the case for servers
+    /// speaking a wire format revision prior to 8, or the client failed to interpret a response.
     Server(String),
+    /// The server responded with an error described by the given body, allowing callers to
+    /// branch on its `code` and whether or not it is `retryable`.
+    Response(request::ErrorBody),
+    /// The server speaks a wire format that is incompatible with this client.
+    Incompatible(request::VersionInfo),
+    /// An error occurred on the WebSocket of a `subscribe_websocket` stream.
+    WebSocket(tungstenite::Error),
+    /// An error occurred while writing an `export_to_writer` stream.
+    Io(io::Error),
+    /// No response began within the duration configured via `Client::timeout`.
+    TimedOut,
+    /// A bincode response body could not be deserialized.
+    Bincode(bincode::Error),
 }
 
 pub type Key = Vec<u8>;
 pub type Value = Vec<u8>;
 pub type Entry = (Vec<u8>, Vec<u8>);
 
+/// The greatest number of entries within a differing group that `Client::sync_to` transfers in
+/// full rather than comparing the hashes of its subgroups.
+pub const SYNC_TRANSFER_LEN: u64 = 256;
+
 /// A stream that converts a hyper `Body` into a stream yielding JSON `Value`s.
 ///
-/// Assumes that the `Body` will never yield parts of two separate JSON objects within the same
-/// chunk, but may split individual JSON objects across multiple chunks.
+/// A chunk may contain any number of JSON values or parts thereof, e.g. once decompressed.
 #[derive(Debug)]
 pub struct BodyToJsonChunks {
     body: Body,
     buffer: Vec<u8>,
+    /// Whether or not the body has ended.
+    ended: bool,
+}
+
+/// A stream that converts a hyper `Body` of bincode values into a stream yielding each `T`.
+///
+/// As the length of each bincode value is known once it has been deserialized, a chunk may
+/// contain any number of values or parts thereof.
+#[derive(Debug)]
+pub struct BodyToBincodeItems<T> {
+    body: Body,
+    buffer: Vec<u8>,
+    /// Whether or not the body has ended.
+    ended: bool,
+    /// Whether or not any value has been yielded.
+    yielded: bool,
+    item: PhantomData<T>,
+}
+
+/// A stream that merges several key-ordered entry streams into a single key-ordered stream.
+///
+/// Each of the merged streams is assumed to yield its entries in ascending key order, as is the
+/// case for the `iter`, `scan` and `scan_range` streams. Entries with equal keys are yielded in the
+/// order in which their streams were given.
+///
+/// See the `merge_sorted` function.
+#[derive(Debug)]
+pub struct MergeSorted<S> {
+    sources: Vec<MergeSource<S>>,
+}
+
+/// One of the streams merged by a `MergeSorted` along with its next entry.
+#[derive(Debug)]
+struct MergeSource<S> {
+    stream: S,
+    head: Option<Entry>,
+    done: bool,
+}
+
+/// A stream of the changes to the entries whose keys begin with some prefix.
+///
+/// Events are received via the `Watch` request. Whenever the connection fails or is closed, the
+/// subscription reconnects after an exponential backoff, resuming from the sequence number of the
+/// last event produced. Events with a sequence number no greater than that of the last event
+/// produced are skipped, so each event is produced at most once and in order.
+///
+/// Changes made while disconnected are only replayed if they are still within the server's change
+/// history, see `Tree::with_change_history` and `Tree::with_changelog`.
+///
+/// Created via the `Client::subscribe`, `Client::subscribe_cursor` and `Client::subscribe_with`
+/// methods.
+pub struct Subscription {
+    client: Client,
+    /// The prefix, cursor and filter with which each `Watch` request is made.
+    watch: request::Watch,
+    last_seq: Option<u64>,
+    /// The number of consecutive failed attempts to connect.
+    attempts: u32,
+    state: SubscriptionState,
+}
+
+/// The states of a `Subscription`'s connection.
+enum SubscriptionState {
+    Connecting(Submitted),
+    Receiving { body: Body, buffer: Vec<u8> },
+    Waiting(Delay),
+    /// The server rejected the subscription.
+    Done,
 }
 
 impl Client {
+    /// The base URI used by `Client`s created via `Client::local`.
+    ///
+    /// The scheme and authority are ignored by the `Router`.
+    pub const LOCAL_URI: &'static str = "http://localhost";
+
+    /// The default duration in milliseconds within which responses must begin. See
+    /// `Client::timeout`.
+    ///
+    /// Exceeds `request::DEFAULT_CHANGES_TIMEOUT_MS` so that long-polls via `changes_since` with
+    /// the default timeout are not abandoned.
+    pub const DEFAULT_TIMEOUT_MS: u64 = 60_000;
+
     /// Create a new `Client` pointing towards the given `Uri`.
     ///
     /// The `Uri` should contain the `Scheme` and `Authority` parts of the URI but not the
     /// following path. This following path will be created as necessary within each of the request
     /// calls.
     pub fn new(uri: Uri) -> Self {
-        let client = hyper::Client::builder().build_http();
-        Client { uri, client }
+        Client::with_transport(uri, hyper::Client::builder().build_http())
+    }
+
+    /// Create a new `Client` pointing towards the given `Uri` that speaks HTTP/2 without TLS
+    /// (h2c), assuming the server supports it.
+    ///
+    /// All requests, including long-running streams such as `iter` and `subscribe`, are
+    /// multiplexed over a single connection rather than each occupying a connection of their own.
+    pub fn http2(uri: Uri) -> Self {
+        let transport = hyper::Client::builder().http2_only(true).build_http();
+        Client::with_transport(uri, transport)
+    }
+
+    /// Create a new `Client` that submits all requests via the given `Transport`.
+    pub fn with_transport<T>(uri: Uri, transport: T) -> Self
+    where
+        T: 'static + Transport,
+    {
+        let transport = Arc::new(transport);
+        let deadline = None;
+        let timeout = Some(Duration::from_millis(Client::DEFAULT_TIMEOUT_MS));
+        let protocol = request::WIRE_FORMAT;
+        let path_prefix = String::new();
+        let base_path = None;
+        let tree_name = None;
+        let replicas = Arc::new(vec![]);
+        let next_replica = Arc::new(AtomicUsize::new(0));
+        let consistency = ReadConsistency::Eventual;
+        let encoding = Encoding::Json;
+        let compression = None;
+        let missing_not_found = false;
+        Client {
+            uri,
+            transport,
+            deadline,
+            timeout,
+            protocol,
+            path_prefix,
+            base_path,
+            tree_name,
+            replicas,
+            next_replica,
+            consistency,
+            encoding,
+            compression,
+            missing_not_found,
+        }
+    }
+
+    /// Create a new `Client` that submits all requests directly to the given `Router` within the
+    /// same process, bypassing the network entirely.
+    ///
+    /// This is useful for tests and benchmarks, or for applications that wish to use the API
+    /// locally while retaining the option of moving the server to another host later.
+    pub fn local(router: Router) -> Self {
+        Client::with_transport(Client::LOCAL_URI.parse().expect("invalid local URI"), router)
+    }
+
+    /// Pin the wire format revision used for all requests made by the client.
+    ///
+    /// Defaults to `request::WIRE_FORMAT`, the latest revision known to this crate. Pinning an
+    /// older revision allows for communicating with older servers during a gradual rollout.
+    pub fn protocol(mut self, wire_format: u32) -> Self {
+        self.protocol = wire_format;
+        self
+    }
+
+    /// Create a new `Client` pointing towards the given `Uri`, first checking that the server
+    /// speaks a compatible wire format.
+    ///
+    /// Yields an `Error::Incompatible` if the server does not support the wire format of this
+    /// client, rather than producing confusing deserialization errors upon later requests.
+    pub fn connect(uri: Uri) -> impl Future<Item = Self, Error = Error> {
+        Client::new(uri).handshake()
+    }
+
+    /// Check that the server supports the wire format revision pinned by this client.
+    ///
+    /// Yields the `Client` if so, or an `Error::Incompatible` otherwise.
+    pub fn handshake(self) -> impl Future<Item = Self, Error = Error> {
+        self.version()
+            .and_then(move |info| {
+                if !info.supports(self.protocol) {
+                    return Err(Error::Incompatible(info));
+                }
+                Ok(self)
+            })
+    }
+
+    /// A method for performing the `Version` request.
+    ///
+    /// Produces the versions of the crate, wire format and `sled` with which the server was built.
+    pub fn version(&self) -> impl Future<Item = request::VersionInfo, Error = Error> {
+        let request = request::version(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Info` request.
+    ///
+    /// Produces the versions, `sled` configuration and uptime of the server.
+    pub fn info(&self) -> impl Future<Item = request::DbInfo, Error = Error> {
+        let request = request::info(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// Produce a `Client` that makes all tree requests via the view with the given name.
+    ///
+    /// See `request::View` for details.
+    pub fn view(&self, name: &str) -> Client {
+        let mut client = self.clone();
+        client.path_prefix = format!("{}{}{}", self.path_prefix, request::VIEWS_PATH, name);
+        client
+    }
+
+    /// Produce a `Client` that makes all tree requests to the tree with the given name.
+    ///
+    /// The tree must first be opened via `open_tree`.
+    pub fn tree(&self, name: &str) -> Client {
+        let mut client = self.clone();
+        client.tree_name = Some(name.to_string());
+        client
+    }
+
+    /// Specify how long the server should spend attempting to respond to each request.
+    ///
+    /// The deadline is sent along with every request via the `request::DEADLINE` header. If the
+    /// deadline passes before the server's response begins, the server abandons the request and
+    /// it yields an `Error::Response` with the `DeadlineExceeded` code. Streamed bodies, e.g. of
+    /// `iter` or `subscribe`, are ended by the server at the deadline.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Abandon requests whose responses have not begun within the given duration, rather than
+    /// waiting for an unresponsive server indefinitely.
+    ///
+    /// Requests that time out yield `Error::TimedOut`, as distinct from the `DeadlineExceeded`
+    /// responses of servers that exceed the request's `deadline`. Long-polls via `changes_since`
+    /// must be given a shorter `timeout` of their own, and requests whose responses only begin
+    /// once lengthy work completes, e.g. large `import`s, may need a longer timeout. Streamed
+    /// bodies, e.g. of `iter` or `subscribe`, are not bounded once begun. `None` waits
+    /// indefinitely.
+    ///
+    /// No timeout is applied to requests polled outside of a `tokio` runtime, as no timer is
+    /// available. Defaults to `Client::DEFAULT_TIMEOUT_MS`.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Make reads of entries to the given replicas of the server round-robin, e.g. servers
+    /// configured via `server::ConfigBuilder::replicate_from`, while all other requests are made
+    /// to the server itself as the primary.
+    ///
+    /// Reads include `get`, `get_many`, `get_prefix` and the `iter` and `scan` families. The
+    /// replicas are shared with all clones of the client. See `consistency` for reading from the
+    /// primary regardless.
+    pub fn replicas<I>(mut self, replicas: I) -> Self
+    where
+        I: IntoIterator<Item = Uri>,
+    {
+        self.replicas = Arc::new(replicas.into_iter().collect());
+        self
+    }
+
+    /// Specify whether reads are made to the replicas or to the primary.
+    ///
+    /// Defaults to `ReadConsistency::Eventual`. Has no effect without replicas. A clone of the
+    /// client may be used with `ReadConsistency::Strong` for reads that must observe earlier
+    /// writes, e.g. `client.clone().consistency(ReadConsistency::Strong).get(key)`.
+    pub fn consistency(mut self, consistency: ReadConsistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Specify the encoding of the bodies of requests made and responses received by the client.
+    ///
+    /// Defaults to `Encoding::Json`. Choosing `Encoding::Cbor` or `Encoding::MessagePack` reduces
+    /// the size of bodies on the wire. Choosing `Encoding::Bincode` also avoids the cost of
+    /// converting bodies to and from JSON on both ends, see the `encoding` module for details.
+    /// Choosing `Encoding::Base64Json` shrinks JSON bodies holding large keys and values. The
+    /// server must support the encoding, i.e. it must be a version of sled-web negotiating
+    /// encodings via the `Content-Type` and `Accept` headers.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Specify the compression requested for response bodies via the `Accept-Encoding` header.
+    ///
+    /// Defaults to `None`. Compression is worthwhile for large streams of entries or large values
+    /// over slow links, at the cost of CPU on both ends. Compressed responses are decompressed
+    /// regardless of this setting.
+    pub fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Specify whether the server should respond to a `Get` of a missing key with `404 Not Found`
+    /// rather than `200 OK` and `null`, via the `request::MISSING` header.
+    ///
+    /// Defaults to `false`. Either way `get` produces `None` for a missing key, while the status
+    /// allows caches and other HTTP tooling between the client and server to tell hits from misses.
+    pub fn missing_not_found(mut self, missing_not_found: bool) -> Self {
+        self.missing_not_found = missing_not_found;
+        self
+    }
+
+    /// Make all requests beneath the given path, e.g. `/kv`, for servers configured via
+    /// `server::ConfigBuilder::base_path` or deployed behind a reverse proxy routing by path.
+    ///
+    /// Should not be used with `Client::local`, as the `Router` routes requests without a base
+    /// path.
+    pub fn base_path(mut self, path: &str) -> Self {
+        self.base_path = request::normalize_base_path(path);
+        self
+    }
+
+    /// The base URI to which the next read is made.
+    fn read_uri(&self) -> Uri {
+        if self.consistency == ReadConsistency::Strong || self.replicas.is_empty() {
+            return self.uri.clone();
+        }
+        let ix = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        self.replicas[ix].clone()
+    }
+
+    /// Produce a handle that confines all operations to the keys beginning with the given prefix.
+    ///
+    /// See `Namespaced` for details.
+    pub fn namespaced(&self, prefix: Key) -> Namespaced {
+        let client = self.clone();
+        Namespaced { client, prefix }
+    }
+
+    /// Produce a handle that reads via this client while mirroring all writes to `secondary`.
+    ///
+    /// See `Mirrored` for details.
+    pub fn mirrored(&self, secondary: Client) -> Mirrored {
+        let primary = self.clone();
+        Mirrored { primary, secondary }
     }
 
     /// A method for performing the `Get` request.
     ///
     /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the value.
     pub fn get(&self, key: Key) -> impl Future<Item = Option<Value>, Error = Error> {
-        let request = request::get(self.uri.clone(), key);
+        let request = request::get(self.read_uri(), key);
+        let missing_not_found = self.missing_not_found;
+        submit(self, request).and_then(move |response| {
+            if missing_not_found && response.status() == StatusCode::NOT_FOUND {
+                return Either::A(futures::future::ok(None));
+            }
+            Either::B(concat_and_deserialize(response))
+        })
+    }
+
+    /// A method for performing the `Get` request conditionally on a previously retrieved value.
+    ///
+    /// Given the entity tag of a cached value, the server responds with `304 Not Modified` and no
+    /// body if the value is unchanged, producing `Cached::NotModified`. Otherwise, or given `None`,
+    /// produces the current value along with its entity tag. Useful for cheaply polling hot keys.
+    /// See `response::etag`.
+    pub fn get_cached(
+        &self,
+        key: Key,
+        etag: Option<String>,
+    ) -> impl Future<Item = Cached, Error = Error> {
+        let mut request = request::get(self.read_uri(), key);
+        if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+            request.headers_mut().insert(header::IF_NONE_MATCH, etag);
+        }
+        let missing_not_found = self.missing_not_found;
+        submit(self, request).and_then(move |response| {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                return Either::A(futures::future::ok(Cached::NotModified));
+            }
+            if missing_not_found && response.status() == StatusCode::NOT_FOUND {
+                let cached = Cached::Modified { value: None, etag: None };
+                return Either::A(futures::future::ok(cached));
+            }
+            let etag = response
+                .headers()
+                .get(header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_string);
+            let cached = concat_and_deserialize(response)
+                .map(move |value| Cached::Modified { value, etag });
+            Either::B(cached)
+        })
+    }
+
+    /// A method for performing the `GetMany` request.
+    ///
+    /// Given the keys for several entries, produce a `Future` with their values in the same order.
+    pub fn get_many(
+        &self,
+        keys: Vec<Key>,
+    ) -> impl Future<Item = Vec<Option<Value>>, Error = Error> {
+        let request = request::get_many(self.read_uri(), keys);
         request_concat_and_deserialize(self, request)
     }
 
+    /// A method for performing the `GetPrefix` request.
+    ///
+    /// Produces a `Future` with all entries whose keys begin with the given prefix, retrieved
+    /// within a single response rather than as a stream.
+    pub fn get_prefix(
+        &self,
+        prefix: Key,
+    ) -> impl Future<Item = BTreeMap<Key, Value>, Error = Error> {
+        let request = request::get_prefix(self.read_uri(), prefix);
+        request_concat_and_deserialize(self, request).map(|request::EntryMap(map)| map)
+    }
+
+    /// Check the presence and length of the value at the given key without retrieving it.
+    ///
+    /// Performs a `HEAD` request, producing a `Future` with the length of the value in bytes or
+    /// `None` if there is no entry. See `request::VALUE_LENGTH`.
+    pub fn value_len(&self, key: Key) -> impl Future<Item = Option<u64>, Error = Error> {
+        let request = request::head_entry(self.read_uri(), &key);
+        submit(self, request).and_then(|response| {
+            let status = response.status();
+            if status == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let len = response
+                .headers()
+                .get(request::VALUE_LENGTH)
+                .and_then(|len| len.to_str().ok())
+                .and_then(|len| len.parse().ok());
+            match len {
+                Some(len) if status.is_success() => Ok(Some(len)),
+                _ => {
+                    let msg = format!("`HEAD` request failed with status {}", status);
+                    Err(Error::Server(msg))
+                }
+            }
+        })
+    }
+
     /// A method for performing the `Del` request.
     ///
     /// Given the key for an entry in the `sled::Tree`, delete the entry and return a `Future` with
@@ -65,6 +595,28 @@ impl Client {
         request_concat_and_deserialize(self, request)
     }
 
+    /// A method for performing the `DelIfEqual` request.
+    ///
+    /// Deletes the entry at the given key only if its value is equal to `expected`, producing a
+    /// `Future` yielding whether or not the entry was deleted.
+    pub fn del_if_equal(
+        &self,
+        key: Key,
+        expected: Value,
+    ) -> impl Future<Item = bool, Error = Error> {
+        let request = request::del_if_equal(self.uri.clone(), key, expected);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `DelPrefix` request.
+    ///
+    /// Removes all entries whose keys begin with the given prefix, producing a `Future` with the
+    /// number of entries removed.
+    pub fn del_prefix(&self, prefix: Key) -> impl Future<Item = usize, Error = Error> {
+        let request = request::del_prefix(self.uri.clone(), prefix);
+        request_concat_and_deserialize(self, request)
+    }
+
     /// A method for performing the `Set` request.
     ///
     /// Send the given key and value to the database for insertion into the `sled::Tree`.
@@ -73,6 +625,30 @@ impl Client {
         request_concat_and_deserialize(self, request)
     }
 
+    /// A method for performing the `Replace` request.
+    ///
+    /// The same as `set`, but produces a `Future` with the value that was replaced, if any.
+    pub fn replace(
+        &self,
+        key: Key,
+        value: Value,
+    ) -> impl Future<Item = Option<Value>, Error = Error> {
+        let request = request::replace(self.uri.clone(), key, value);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `SetMany` request.
+    ///
+    /// Sets each of the given entries, producing a `Future` with the result of each in the same
+    /// order, where an `Err` contains a description of the error.
+    pub fn set_many(
+        &self,
+        entries: Vec<(Key, Value)>,
+    ) -> impl Future<Item = Vec<Result<(), String>>, Error = Error> {
+        let request = request::set_many(self.uri.clone(), entries);
+        request_concat_and_deserialize(self, request)
+    }
+
     /// A method for performing the `Cas` request.
     ///
     /// Compare and swap. Capable of unique creation, conditional modification, or deletion.
@@ -82,6 +658,10 @@ impl Client {
     /// old is correct.
     ///
     /// If Tree is read-only, will do nothing.
+    ///
+    /// Since wire format revision 7 the server responds to a failed swap with `409 Conflict` and
+    /// the current value, while earlier revisions respond with `200 OK` and an `Err`. Both are
+    /// produced as an `Err` with the current value.
     pub fn cas(
         &self,
         key: Key,
@@ -89,7 +669,41 @@ impl Client {
         new: Option<Value>,
     ) -> impl Future<Item = Result<(), Option<Value>>, Error = Error> {
         let request = request::cas(self.uri.clone(), key, old, new);
-        request_concat_and_deserialize(self, request)
+        submit(self, request).and_then(|response| {
+            if response.status() == StatusCode::CONFLICT {
+                let current = concat_and_deserialize::<Option<Value>>(response).map(Err);
+                return Either::A(current);
+            }
+            Either::B(concat_and_deserialize(response))
+        })
+    }
+
+    /// A method for performing the `BatchCas` request.
+    ///
+    /// Performs each of the given compare and swap operations independently within a single round
+    /// trip, producing a `Future` with the result of each in the same order.
+    ///
+    /// A client pinned to a wire format revision prior to 9 only produces `CasError::Conflict`,
+    /// while any other error fails the whole request.
+    pub fn batch_cas(
+        &self,
+        ops: Vec<request::Cas>,
+    ) -> impl Future<Item = Vec<Result<(), request::CasError>>, Error = Error> {
+        let request = request::batch_cas(self.uri.clone(), ops);
+        let legacy = self.protocol < 9;
+        submit(self, request).and_then(move |response| {
+            if legacy {
+                let results = concat_and_deserialize::<Vec<Result<(), Option<Value>>>>(response)
+                    .map(|results| {
+                        results
+                            .into_iter()
+                            .map(|res| res.map_err(request::CasError::Conflict))
+                            .collect()
+                    });
+                return Either::A(results);
+            }
+            Either::B(concat_and_deserialize(response))
+        })
     }
 
     /// A method for performing the `Merge` request.
@@ -100,137 +714,1691 @@ impl Client {
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `Flush` request.
+    /// A method for performing the `Incr` request.
     ///
-    /// Flushes any pending IO buffers to disk to ensure durability.
-    pub fn flush(&self) -> impl Future<Item = (), Error = Error> {
-        let request = request::flush(self.uri.clone());
+    /// Atomically adds `delta` to the little-endian `i64` value at the given key, producing a
+    /// `Future` with the new value. A missing entry is treated as `0`. An addition that would
+    /// overflow produces an `Error::Response` with the `Conflict` code.
+    pub fn incr(&self, key: Key, delta: i64) -> impl Future<Item = i64, Error = Error> {
+        let request = request::incr(self.uri.clone(), key, delta);
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `Iter` request.
+    /// A method for performing the `GetOrInsert` request.
     ///
-    /// The result is a `Stream` of ordered key value pairs.
-    pub fn iter(&self) -> impl Stream<Item = Entry, Error = Error> {
-        let request = request::iter(self.uri.clone());
-        request_stream_and_deserialize(self, request)
+    /// Atomically retrieves the value at the given key, first setting it to `default` if there is
+    /// no entry, producing a `Future` with the resulting value.
+    pub fn get_or_set(&self, key: Key, default: Value) -> impl Future<Item = Value, Error = Error> {
+        let request = request::get_or_insert(self.uri.clone(), key, default);
+        request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `Scan` request.
+    /// A method for performing the `PopMin` request.
     ///
-    /// The result is a `Stream` of ordered key value pairs, starting from the given key.
-    pub fn scan(&self, key: Key) -> impl Stream<Item = Entry, Error = Error> {
-        let request = request::scan(self.uri.clone(), key);
-        request_stream_and_deserialize(self, request)
-    }
-
-    /// A method for performing the `Scan` request.
+    /// Atomically removes the entry with the smallest key, producing a `Future` with the entry.
     ///
-    /// The result is a `Stream` of all ordered key value pairs within the given key range.
-    pub fn scan_range(&self, start: Key, end: Key) -> impl Stream<Item = Entry, Error = Error> {
-        let request = request::scan_range(self.uri.clone(), start, end);
-        request_stream_and_deserialize(self, request)
+    /// Returns `None` if there are no entries within the tree.
+    pub fn pop_min(&self) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::pop_min(self.uri.clone());
+        request_concat_and_deserialize(self, request)
     }
 
-    /// A method for perfomring the `Max` request.
+    /// A method for performing the `PopMax` request.
     ///
-    /// The result is a `Future` yielding the greatest entry in the `sled::Tree`.
+    /// Atomically removes the entry with the greatest key, producing a `Future` with the entry.
     ///
     /// Returns `None` if there are no entries within the tree.
-    pub fn max(&self) -> impl Future<Item = Option<Entry>, Error = Error> {
-        let request = request::max(self.uri.clone());
+    pub fn pop_max(&self) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::pop_max(self.uri.clone());
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `Pred` request.
+    /// A method for performing the `Flush` request.
     ///
-    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the preceding
-    /// entry.
-    pub fn pred(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
-        let request = request::pred(self.uri.clone(), key);
+    /// Flushes any pending IO buffers to disk to ensure durability.
+    pub fn flush(&self) -> impl Future<Item = (), Error = Error> {
+        let request = request::flush(self.uri.clone());
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `PredIncl` request.
+    /// A method for performing the `Batch` request.
     ///
-    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the preceding
-    /// entry or the entry associated with the key if there is one.
-    pub fn pred_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
-        let request = request::pred_incl(self.uri.clone(), key);
+    /// Applies all of the given operations in order within a single round trip.
+    pub fn batch(&self, ops: Vec<request::BatchOp>) -> impl Future<Item = (), Error = Error> {
+        let request = request::batch(self.uri.clone(), ops);
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `Succ` request.
+    /// A method for performing the `GenerateId` request.
     ///
-    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the following
-    /// entry.
-    pub fn succ(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
-        let request = request::succ(self.uri.clone(), key);
+    /// The result is a `Future` yielding an ID that is unique and greater than all IDs previously
+    /// generated by the server for the same tree.
+    pub fn generate_id(&self) -> impl Future<Item = u64, Error = Error> {
+        let request = request::generate_id(self.uri.clone());
         request_concat_and_deserialize(self, request)
     }
 
-    /// A method for performing the `SuccIncl` request.
+    /// A method for performing the `Len` request.
     ///
-    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the following
-    /// entry or the entry associated with the key if there is one.
-    pub fn succ_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
-        let request = request::succ_incl(self.uri.clone(), key);
+    /// The result is a `Future` yielding the number of entries within the tree.
+    ///
+    /// The server must iterate over every entry to count them. Prefer `is_empty` where possible.
+    pub fn len(&self) -> impl Future<Item = usize, Error = Error> {
+        let request = request::len(self.uri.clone());
         request_concat_and_deserialize(self, request)
     }
-}
 
-impl Stream for BodyToJsonChunks {
-    type Item = serde_json::Value;
-    type Error = Error;
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        loop {
-            match self.body.poll() {
-                Err(err) => return Err(err.into()),
-                Ok(Async::NotReady) => return Ok(Async::NotReady),
-                Ok(Async::Ready(None)) => return Ok(Async::Ready(None)),
-                Ok(Async::Ready(Some(chunk))) => self.buffer.extend(chunk),
-            }
-            let v = match serde_json::from_slice::<serde_json::Value>(&self.buffer) {
-                Err(_err) => continue,
-                Ok(v) => v,
-            };
-            self.buffer.clear();
-            return Ok(Async::Ready(Some(v)));
-        }
+    /// A method for performing the `Stats` request.
+    ///
+    /// The result is a `Future` yielding summary statistics over all entries within the tree. The
+    /// server must iterate over every entry to compute them.
+    pub fn stats(&self) -> impl Future<Item = request::TreeStats, Error = Error> {
+        let request = request::stats(self.uri.clone());
+        request_concat_and_deserialize(self, request)
     }
-}
 
-impl StdError for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::Hyper(ref err) => err.description(),
-            Error::SerdeJson(ref err) => err.description(),
-            Error::Server(ref s) => s,
-        }
-    }
-    fn cause(&self) -> Option<&StdError> {
-        match *self {
-            Error::Hyper(ref err) => Some(err),
-            Error::SerdeJson(ref err) => Some(err),
-            Error::Server(_) => None,
-        }
+    /// The same as `stats`, but the server may respond with statistics computed within the given
+    /// duration rather than iterating over every entry again.
+    pub fn stats_cached(
+        &self,
+        max_age: Duration,
+    ) -> impl Future<Item = request::TreeStats, Error = Error> {
+        let max_age_ms = max_age.as_secs() * 1_000 + u64::from(max_age.subsec_millis());
+        let req = request::Stats { max_age_ms: Some(max_age_ms) };
+        let request = request::from(self.uri.clone(), req);
+        request_concat_and_deserialize(self, request)
     }
-}
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description())
+    /// Whether or not the tree contains no entries.
+    ///
+    /// Performed via the `Max` request, avoiding a full iteration of the tree.
+    pub fn is_empty(&self) -> impl Future<Item = bool, Error = Error> {
+        self.max().map(|entry| entry.is_none())
     }
-}
 
-impl From<hyper::Error> for Error {
-    fn from(e: hyper::Error) -> Self {
-        Error::Hyper(e)
+    /// A method for performing the `Clear` request.
+    ///
+    /// Removes all entries from the tree, producing a `Future` with the number of entries removed.
+    /// The required confirmation is supplied automatically.
+    pub fn clear(&self) -> impl Future<Item = usize, Error = Error> {
+        let request = request::clear(self.uri.clone());
+        request_concat_and_deserialize(self, request)
     }
-}
 
-impl From<serde_json::Error> for Error {
-    fn from(e: serde_json::Error) -> Self {
+    /// A method for performing the `Transaction` request.
+    ///
+    /// Applies all of the given writes only if all of the given conditions hold, yielding whether
+    /// the transaction committed or aborted.
+    pub fn transaction(
+        &self,
+        conditions: Vec<request::Condition>,
+        writes: Vec<request::BatchOp>,
+    ) -> impl Future<Item = request::TransactionOutcome, Error = Error> {
+        let request = request::transaction(self.uri.clone(), conditions, writes);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Rename` request.
+    ///
+    /// Moves the value at `from` to the key `to` on the server in a single request. Unless
+    /// `overwrite` is `true`, nothing is changed if an entry already exists at `to`.
+    pub fn rename(
+        &self,
+        from: Key,
+        to: Key,
+        overwrite: bool,
+    ) -> impl Future<Item = request::RenameOutcome, Error = Error> {
+        let request = request::rename(self.uri.clone(), from, to, overwrite);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Iter` request.
+    ///
+    /// The result is a `Stream` of ordered key value pairs.
+    pub fn iter(&self) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::iter(self.read_uri());
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Scan` request.
+    ///
+    /// The result is a `Stream` of ordered key value pairs, starting from the given key.
+    pub fn scan(&self, key: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::scan(self.read_uri(), key);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Scan` request.
+    ///
+    /// The result is a `Stream` of all ordered key value pairs within the given key range, e.g.
+    /// `start..end`, `start..=end` or `..end`.
+    pub fn scan_range<R>(&self, range: R) -> impl Stream<Item = Entry, Error = Error>
+    where
+        R: RangeBounds<Key>,
+    {
+        let request = request::scan_range(self.read_uri(), range);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `iter`, but the server ends the stream after at most `limit` entries.
+    pub fn iter_limit(&self, limit: u64) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::Iter { limit: Some(limit), filter: None };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan`, but the server ends the stream after at most `limit` entries.
+    pub fn scan_limit(&self, key: Key, limit: u64) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::Scan { key, limit: Some(limit), filter: None };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `iter`, but the server only streams back entries whose value matches `filter`.
+    pub fn iter_filtered(
+        &self,
+        filter: request::ValueFilter,
+    ) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::Iter { limit: None, filter: Some(filter) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan`, but the server only streams back entries whose value matches `filter`.
+    pub fn scan_filtered(
+        &self,
+        key: Key,
+        filter: request::ValueFilter,
+    ) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::Scan { key, limit: None, filter: Some(filter) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan_range`, but the server ends the stream after at most `limit` entries.
+    pub fn scan_range_limit<R>(
+        &self,
+        range: R,
+        limit: u64,
+    ) -> impl Stream<Item = Entry, Error = Error>
+    where
+        R: RangeBounds<Key>,
+    {
+        let req = request::ScanRange { limit: Some(limit), ..request::ScanRange::new(range) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// Perform any request whose response is a stream of entries, e.g. an `Iter`, `Scan` or
+    /// `ScanRange` with any combination of its fields.
+    pub fn entries<T>(&self, req: T) -> impl Stream<Item = Entry, Error = Error>
+    where
+        T: request::IntoRequest,
+    {
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `CountRange` request.
+    ///
+    /// The result is a `Future` yielding the number of entries within the given key range, where
+    /// the range is non-inclusive of the `end` key.
+    pub fn count_range(&self, start: Key, end: Key) -> impl Future<Item = usize, Error = Error> {
+        let request = request::count_range(self.uri.clone(), start, end);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `HashPrefix` request.
+    ///
+    /// The result is a `Future` yielding the hash of each non-empty group of entries whose keys
+    /// begin with `prefix` followed by some byte. See `request::HashPrefix`.
+    pub fn hash_prefix(
+        &self,
+        prefix: Key,
+    ) -> impl Future<Item = Vec<request::PrefixHash>, Error = Error> {
+        let request = request::hash_prefix(self.uri.clone(), prefix);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// Make the entries of the `destination` server's tree equal to those of this client's.
+    ///
+    /// The trees are compared via `HashPrefix` one byte of the key space at a time, descending only
+    /// into the groups of entries whose hashes differ. Groups of up to `SYNC_TRANSFER_LEN` entries
+    /// are transferred in full, setting each differing entry and removing each entry missing from
+    /// this client's tree. Only the differing parts of the trees are transferred, making periodic
+    /// repair between replicas cheap.
+    ///
+    /// The result is a `Future` yielding the number of entries set or removed. Changes made to
+    /// either tree during the sync may or may not be reflected.
+    pub fn sync_to(&self, destination: &Client) -> impl Future<Item = u64, Error = Error> {
+        let source = self.clone();
+        let destination = destination.clone();
+        let queue: VecDeque<Key> = Some(vec![]).into_iter().collect();
+        futures::future::loop_fn((queue, 0), move |(mut queue, synced)| {
+            let prefix = match queue.pop_front() {
+                None => return Either::A(futures::future::ok(futures::future::Loop::Break(synced))),
+                Some(prefix) => prefix,
+            };
+            let (src, dst) = (source.clone(), destination.clone());
+            let exact = sync_key(&source, &destination, prefix.clone());
+            let groups = source
+                .hash_prefix(prefix.clone())
+                .join(destination.hash_prefix(prefix))
+                .and_then(move |(src_hashes, dst_hashes)| {
+                    let mut transfers = vec![];
+                    for (prefix, count) in differing_groups(src_hashes, dst_hashes) {
+                        if count <= SYNC_TRANSFER_LEN {
+                            transfers.push(sync_prefix(&src, &dst, prefix));
+                        } else {
+                            queue.push_back(prefix);
+                        }
+                    }
+                    futures::future::join_all(transfers)
+                        .map(move |counts| (queue, counts.into_iter().sum::<u64>()))
+                });
+            let step = groups.join(exact).map(move |((queue, transferred), set)| {
+                futures::future::Loop::Continue((queue, synced + transferred + set))
+            });
+            Either::B(step)
+        })
+    }
+
+    /// A method for performing the `TopValues` request.
+    ///
+    /// The result is a `Future` yielding the keys of the `count` entries with the largest values
+    /// along with the length of each value, in descending order of length.
+    pub fn top_values(&self, count: u64) -> impl Future<Item = Vec<(Key, usize)>, Error = Error> {
+        let request = request::top_values(self.uri.clone(), count);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Aggregate` request.
+    ///
+    /// The result is a `Future` yielding the result of applying `op` to the numeric values of all
+    /// entries within `start..end`, computed on the server.
+    pub fn aggregate(
+        &self,
+        start: Key,
+        end: Key,
+        op: request::AggregateOp,
+        value_type: request::ValueType,
+    ) -> impl Future<Item = request::AggregateResult, Error = Error> {
+        let request = request::aggregate(self.uri.clone(), start, end, op, value_type);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `IterPage` request.
+    ///
+    /// The result is a `Future` yielding a single page of at most `page_size` ordered entries. Pass
+    /// `None` for the first page and the `next_cursor` of the preceding page thereafter. The
+    /// `page_size` must be non-zero and is clamped to `request::MAX_PAGE_SIZE`.
+    pub fn iter_page(
+        &self,
+        cursor: Option<String>,
+        page_size: u64,
+    ) -> impl Future<Item = request::Page, Error = Error> {
+        let request = request::iter_page(self.uri.clone(), cursor, page_size);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `ScanSuffix` request.
+    ///
+    /// The result is a `Stream` of all key value pairs whose keys end with the given suffix,
+    /// ordered by their reversed keys. The server must maintain a suffix index for the tree.
+    pub fn scan_suffix(&self, suffix: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::scan_suffix(self.uri.clone(), suffix);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan_suffix`, but the server ends the stream after at most `limit` entries.
+    pub fn scan_suffix_limit(
+        &self,
+        suffix: Key,
+        limit: u64,
+    ) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::ScanSuffix { suffix, limit: Some(limit) };
+        let request = request::from(self.uri.clone(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `SetView` request.
+    ///
+    /// Defines the view with the given name, returning the view that was replaced if any.
+    pub fn set_view(
+        &self,
+        name: String,
+        view: request::View,
+    ) -> impl Future<Item = Option<request::View>, Error = Error> {
+        let request = request::set_view(self.uri.clone(), name, view);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `RemoveView` request.
+    ///
+    /// Removes the view with the given name, returning it if it existed.
+    pub fn remove_view(
+        &self,
+        name: String,
+    ) -> impl Future<Item = Option<request::View>, Error = Error> {
+        let request = request::remove_view(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Views` request.
+    ///
+    /// Produces all views defined on the server by name.
+    pub fn views(&self) -> impl Future<Item = BTreeMap<String, request::View>, Error = Error> {
+        let request = request::views(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `SetWebhook` request.
+    ///
+    /// Registers the webhook with the given name, returning the webhook that was replaced if any.
+    pub fn set_webhook(
+        &self,
+        name: String,
+        webhook: request::Webhook,
+    ) -> impl Future<Item = Option<request::Webhook>, Error = Error> {
+        let request = request::set_webhook(self.uri.clone(), name, webhook);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `RemoveWebhook` request.
+    ///
+    /// Removes the webhook with the given name, returning it if it existed.
+    pub fn remove_webhook(
+        &self,
+        name: String,
+    ) -> impl Future<Item = Option<request::Webhook>, Error = Error> {
+        let request = request::remove_webhook(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Webhooks` request.
+    ///
+    /// Produces all webhooks registered on the server by name.
+    pub fn webhooks(
+        &self,
+    ) -> impl Future<Item = BTreeMap<String, request::Webhook>, Error = Error> {
+        let request = request::webhooks(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Backups` request.
+    ///
+    /// Produces the status of the server's scheduled backups.
+    pub fn backups(&self) -> impl Future<Item = request::BackupStatus, Error = Error> {
+        let request = request::backups(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Restore` request.
+    ///
+    /// Restores the server's tree to its state at the given point from its scheduled backups and
+    /// its changelog, replacing all entries.
+    pub fn restore(
+        &self,
+        to: request::RestorePoint,
+    ) -> impl Future<Item = request::Restored, Error = Error> {
+        let request = request::restore(self.uri.clone(), to);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `PullRange` request.
+    ///
+    /// Starts copying the entries within `start..end` from the server at the `source` base URI,
+    /// returning the status of the finished migration of the same name that was replaced if any.
+    /// Progress may be followed via `migrations`.
+    pub fn pull_range(
+        &self,
+        name: String,
+        source: String,
+        start: Key,
+        end: Option<Key>,
+    ) -> impl Future<Item = Option<request::MigrationStatus>, Error = Error> {
+        let request = request::pull_range(self.uri.clone(), name, source, start, end);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Migrations` request.
+    ///
+    /// Produces the status of all migrations started on the server by name.
+    pub fn migrations(
+        &self,
+    ) -> impl Future<Item = BTreeMap<String, request::MigrationStatus>, Error = Error> {
+        let request = request::migrations(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Streams` request.
+    ///
+    /// Produces the number of streamed responses in progress on the server and the maximum
+    /// permitted.
+    pub fn streams(&self) -> impl Future<Item = request::StreamStatus, Error = Error> {
+        let request = request::streams(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `OpenTree` request.
+    ///
+    /// The result is a `Future` yielding whether or not the tree was newly opened.
+    pub fn open_tree(&self, name: String) -> impl Future<Item = bool, Error = Error> {
+        let request = request::open_tree(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `DropTree` request.
+    ///
+    /// The result is a `Future` yielding whether or not the tree was open.
+    pub fn drop_tree(&self, name: String) -> impl Future<Item = bool, Error = Error> {
+        let request = request::drop_tree(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `TreeNames` request.
+    ///
+    /// The result is a `Future` yielding the names of all open trees.
+    pub fn tree_names(&self) -> impl Future<Item = Vec<String>, Error = Error> {
+        let request = request::tree_names(self.uri.clone());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `CopyEntries` request.
+    ///
+    /// Copies the entries within `start..end` from the `source` tree to the `destination` tree,
+    /// where `None` refers to the default tree. The result is a `Future` yielding the number of
+    /// entries copied. If the copy fails part way through, the `Error::Response` body's `key` is
+    /// the `start` from which it may be resumed.
+    pub fn copy_entries(
+        &self,
+        source: Option<String>,
+        destination: Option<String>,
+        start: Key,
+        end: Option<Key>,
+    ) -> impl Future<Item = usize, Error = Error> {
+        let request = request::copy_entries(self.uri.clone(), source, destination, start, end);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `MoveEntries` request.
+    ///
+    /// The same as `copy_entries`, but the entries are removed from the `source` tree.
+    pub fn move_entries(
+        &self,
+        source: Option<String>,
+        destination: Option<String>,
+        start: Key,
+        end: Option<Key>,
+    ) -> impl Future<Item = usize, Error = Error> {
+        let request = request::move_entries(self.uri.clone(), source, destination, start, end);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// Copy the entry at the given key from the `source` tree to the `destination` tree.
+    ///
+    /// The result is a `Future` yielding whether or not there was an entry to copy.
+    pub fn copy_key(
+        &self,
+        source: Option<String>,
+        destination: Option<String>,
+        key: Key,
+    ) -> impl Future<Item = bool, Error = Error> {
+        let end = request::single_key_end(&key);
+        self.copy_entries(source, destination, key, Some(end)).map(|count| count > 0)
+    }
+
+    /// Move the entry at the given key from the `source` tree to the `destination` tree.
+    ///
+    /// The result is a `Future` yielding whether or not there was an entry to move.
+    pub fn move_key(
+        &self,
+        source: Option<String>,
+        destination: Option<String>,
+        key: Key,
+    ) -> impl Future<Item = bool, Error = Error> {
+        let end = request::single_key_end(&key);
+        self.move_entries(source, destination, key, Some(end)).map(|count| count > 0)
+    }
+
+    /// A method for performing the `IterKeys` request.
+    ///
+    /// The result is a `Stream` of all ordered keys, omitting their values.
+    pub fn iter_keys(&self) -> impl Stream<Item = Key, Error = Error> {
+        let request = request::iter_keys(self.read_uri());
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `ScanKeys` request.
+    ///
+    /// The result is a `Stream` of all ordered keys starting from the given key, omitting their
+    /// values.
+    pub fn scan_keys(&self, key: Key) -> impl Stream<Item = Key, Error = Error> {
+        let request = request::scan_keys(self.read_uri(), key);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `iter_keys`, but the server ends the stream after at most `limit` keys.
+    pub fn iter_keys_limit(&self, limit: u64) -> impl Stream<Item = Key, Error = Error> {
+        let req = request::IterKeys { limit: Some(limit) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan_keys`, but the server ends the stream after at most `limit` keys.
+    pub fn scan_keys_limit(&self, key: Key, limit: u64) -> impl Stream<Item = Key, Error = Error> {
+        let req = request::ScanKeys { key, limit: Some(limit) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `ScanMatching` request.
+    ///
+    /// The result is a `Stream` of ordered key value pairs whose keys match the given pattern,
+    /// filtered on the server.
+    pub fn scan_matching(
+        &self,
+        pattern: request::KeyPattern,
+    ) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::scan_matching(self.uri.clone(), pattern);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `IterRev` request.
+    ///
+    /// The result is a `Stream` of key value pairs in descending order of their keys.
+    pub fn iter_rev(&self) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::iter_rev(self.read_uri());
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `ScanRangeRev` request.
+    ///
+    /// The result is a `Stream` of all key value pairs within the given key range in descending
+    /// order of their keys. The server only visits as many entries as are consumed, so taking the
+    /// first `n` items of this stream efficiently yields the last `n` entries of the range.
+    pub fn scan_range_rev(&self, start: Key, end: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let request = request::scan_range_rev(self.read_uri(), start, end);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `iter_rev`, but the server ends the stream after at most `limit` entries.
+    pub fn iter_rev_limit(&self, limit: u64) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::IterRev { limit: Some(limit) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// The same as `scan_range_rev`, but the server ends the stream after at most `limit` entries.
+    pub fn scan_range_rev_limit(
+        &self,
+        start: Key,
+        end: Key,
+        limit: u64,
+    ) -> impl Stream<Item = Entry, Error = Error> {
+        let req = request::ScanRangeRev { start, end, limit: Some(limit) };
+        let request = request::from(self.read_uri(), req);
+        request_stream_and_deserialize(self, request)
+    }
+
+    /// Produce the last `n` entries within the given key range in ascending order of their keys.
+    ///
+    /// The entries are read from the `end` of the range via `scan_range_rev_limit`, so the rest of
+    /// the range is never visited.
+    pub fn scan_range_last(
+        &self,
+        start: Key,
+        end: Key,
+        n: u64,
+    ) -> impl Future<Item = Vec<Entry>, Error = Error> {
+        self.scan_range_rev_limit(start, end, n).collect().map(|mut entries| {
+            entries.reverse();
+            entries
+        })
+    }
+
+    /// A method for perfomring the `Max` request.
+    ///
+    /// The result is a `Future` yielding the greatest entry in the `sled::Tree`.
+    ///
+    /// Returns `None` if there are no entries within the tree.
+    pub fn max(&self) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::max(self.read_uri());
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Pred` request.
+    ///
+    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the preceding
+    /// entry.
+    pub fn pred(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::pred(self.read_uri(), key);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `PredIncl` request.
+    ///
+    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the preceding
+    /// entry or the entry associated with the key if there is one.
+    pub fn pred_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::pred_incl(self.read_uri(), key);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Succ` request.
+    ///
+    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the following
+    /// entry.
+    pub fn succ(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::succ(self.read_uri(), key);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `SuccIncl` request.
+    ///
+    /// Given the key for an entry in the `sled::Tree`, produce a `Future` with the following
+    /// entry or the entry associated with the key if there is one.
+    pub fn succ_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let request = request::succ_incl(self.read_uri(), key);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// Subscribe to the changes to all entries whose keys begin with the given prefix.
+    ///
+    /// The result is a `Stream` of every subsequent change that reconnects whenever the
+    /// connection is lost, see `Subscription` for details. The stream only produces an error if
+    /// the server rejects the subscription or sends a malformed event. Dropping the stream ends
+    /// the subscription.
+    pub fn subscribe(&self, prefix: Key) -> Subscription {
+        self.subscribe_with(request::Watch { prefix, ..Default::default() })
+    }
+
+    /// The same as `subscribe`, but first producing the changes following the sequence number
+    /// acknowledged via the named server-side cursor.
+    ///
+    /// Acknowledge each change via `ack_cursor` once it has been handled. Changes that were not
+    /// acknowledged are produced again by the next subscription via the same cursor, so each
+    /// change is handled at least once so long as the server retains it.
+    pub fn subscribe_cursor(&self, name: String, prefix: Key) -> Subscription {
+        self.subscribe_with(request::Watch { prefix, cursor: Some(name), ..Default::default() })
+    }
+
+    /// The same as `subscribe`, but with the prefix, cursor and event filter given by the `Watch`.
+    ///
+    /// The filter is evaluated by the server, so changes that do not satisfy it are never sent.
+    pub fn subscribe_with(&self, watch: request::Watch) -> Subscription {
+        let client = self.clone();
+        let state = SubscriptionState::Connecting(connect_watch(&client, &watch, None));
+        Subscription { client, watch, last_seq: None, attempts: 0, state }
+    }
+
+    /// A method for performing the `AckCursor` request.
+    ///
+    /// Acknowledges every change up to and including `seq` via the named cursor, producing the
+    /// previously acknowledged sequence number if any.
+    pub fn ack_cursor(
+        &self,
+        name: String,
+        seq: u64,
+    ) -> impl Future<Item = Option<u64>, Error = Error> {
+        let request = request::ack_cursor(self.uri.clone(), name, seq);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `GetCursor` request.
+    ///
+    /// Produces the sequence number acknowledged via the named cursor, if it exists.
+    pub fn cursor(&self, name: String) -> impl Future<Item = Option<u64>, Error = Error> {
+        let request = request::get_cursor(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `RemoveCursor` request.
+    ///
+    /// Removes the named cursor, producing its acknowledged sequence number if it existed.
+    pub fn remove_cursor(&self, name: String) -> impl Future<Item = Option<u64>, Error = Error> {
+        let request = request::remove_cursor(self.uri.clone(), name);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Export` request.
+    ///
+    /// Writes the exported stream to the given `writer` as it is received, producing the
+    /// `writer` once the export is complete. See `request::Export` for the format.
+    pub fn export_to_writer<W>(&self, writer: W) -> impl Future<Item = W, Error = Error>
+    where
+        W: Write,
+    {
+        let request = request::export(self.uri.clone());
+        request_and_write(self, request, writer)
+    }
+
+    /// A method for performing the `Export` request, writing the exported stream to a new backup
+    /// with the given `name` within the `target`.
+    ///
+    /// The backup is only committed once the export is complete and is discarded otherwise.
+    pub fn export_to_target(
+        &self,
+        target: &BackupTarget,
+        name: &str,
+    ) -> impl Future<Item = (), Error = Error> {
+        let writer = match target.create(name) {
+            Ok(writer) => writer,
+            Err(err) => return Either::A(futures::future::err(Error::Io(err))),
+        };
+        let committed = self
+            .export_to_writer(writer)
+            .and_then(|writer| writer.commit().map_err(Error::Io));
+        Either::B(committed)
+    }
+
+    /// A method for performing the `ExportSince` request.
+    ///
+    /// Writes the stream of changes following `since` to the given `writer` as it is received,
+    /// producing the `writer` once the stream is complete. The stream may later be applied to a
+    /// restored export via `import_from_reader`. See `request::ExportSince` for the format.
+    pub fn export_since_to_writer<W>(
+        &self,
+        since: u64,
+        writer: W,
+    ) -> impl Future<Item = W, Error = Error>
+    where
+        W: Write,
+    {
+        let request = request::export_since(self.uri.clone(), since);
+        request_and_write(self, request, writer)
+    }
+
+    /// A method for performing the `Import` request.
+    ///
+    /// Streams the export read from the given `reader` to the server, producing the number of
+    /// entries imported. The `reader` is read synchronously as the body is sent, so it should not
+    /// block for long, e.g. a file or an in-memory buffer.
+    pub fn import_from_reader<R>(
+        &self,
+        reader: R,
+        mode: request::ImportMode,
+    ) -> impl Future<Item = u64, Error = Error>
+    where
+        R: 'static + Read + Send,
+    {
+        const CHUNK_LEN: usize = 64 * 1024;
+        let chunks = futures::stream::unfold(reader, |mut reader| {
+            let mut chunk = vec![0; CHUNK_LEN];
+            let result = loop {
+                match reader.read(&mut chunk) {
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result,
+                }
+            };
+            match result {
+                Err(err) => Some(Err(err)),
+                Ok(0) => None,
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Some(Ok((hyper::Chunk::from(chunk), reader)))
+                }
+            }
+        });
+        let chunks = chunks.map_err(|err| -> Box<StdError + Send + Sync> { Box::new(err) });
+        let body = Body::from(Box::new(chunks) as Box<_>);
+        let request = request::import(self.uri.clone(), mode, body);
+        request_concat_and_deserialize(self, request)
+    }
+
+    /// A method for performing the `Subscribe` request.
+    ///
+    /// The result is a `Stream` of every subsequent change to the tree, ending once the server
+    /// closes the WebSocket. Unlike `subscribe`, the stream does not reconnect. Dropping the
+    /// stream ends the subscription.
+    ///
+    /// The WebSocket upgrade requires that the server is reached over HTTP/1.1, so subscriptions
+    /// are not available to `Client`s created via `Client::local` or `Client::http2`.
+    pub fn subscribe_websocket(&self) -> impl Stream<Item = request::Event, Error = Error> {
+        self.subscribe_websocket_with(Default::default())
+    }
+
+    /// The same as `subscribe_websocket`, but only producing the changes to the entries beginning
+    /// with the `Subscribe`'s prefix that satisfy its filter.
+    pub fn subscribe_websocket_with(
+        &self,
+        subscribe: request::Subscribe,
+    ) -> impl Stream<Item = request::Event, Error = Error> {
+        let request = request::subscribe_with(self.uri.clone(), &subscribe, &websocket_key());
+        submit(self, request).and_then(|response| {
+            let status = response.status();
+            if status != StatusCode::SWITCHING_PROTOCOLS {
+                let msg = format!("the server refused to subscribe with status {}", status);
+                return Either::A(futures::future::err(Error::Server(msg)));
+            }
+            Either::B(response.into_body().on_upgrade().map_err(Error::Hyper))
+        })
+            .map(|upgraded| {
+                WebSocketStream::from_raw_socket(upgraded, Role::Client, None)
+                    .map_err(Error::WebSocket)
+                    .filter_map(|message| match message {
+                        Message::Text(json) => Some(json),
+                        _ => None,
+                    })
+                    .and_then(|json| serde_json::from_str(&json).map_err(Error::SerdeJson))
+            })
+            .flatten_stream()
+    }
+
+    /// A method for performing the `ChangesSince` request.
+    ///
+    /// Produces the changes following the sequence number `since`, waiting for up to `timeout` for
+    /// a change if there are none yet. Pass the `seq` of the resulting `ChangeBatch` as `since` to
+    /// retrieve the following changes.
+    pub fn changes_since(
+        &self,
+        since: u64,
+        timeout: Duration,
+    ) -> impl Future<Item = request::ChangeBatch, Error = Error> {
+        self.changes_since_prefix(vec![], since, timeout)
+    }
+
+    /// The same as `changes_since`, but only producing changes to the entries whose keys begin
+    /// with `prefix`.
+    pub fn changes_since_prefix(
+        &self,
+        prefix: Key,
+        since: u64,
+        timeout: Duration,
+    ) -> impl Future<Item = request::ChangeBatch, Error = Error> {
+        self.changes_since_filtered(prefix, Default::default(), since, timeout)
+    }
+
+    /// The same as `changes_since_prefix`, but only producing the changes that satisfy the
+    /// `filter`.
+    pub fn changes_since_filtered(
+        &self,
+        prefix: Key,
+        filter: request::EventFilter,
+        since: u64,
+        timeout: Duration,
+    ) -> impl Future<Item = request::ChangeBatch, Error = Error> {
+        let timeout_ms = timeout.as_secs() * 1_000 + u64::from(timeout.subsec_millis());
+        let limit = None;
+        let req = request::ChangesSince { since, prefix, timeout_ms, limit, filter };
+        let request = request::from(self.uri.clone(), req);
+        request_concat_and_deserialize(self, request)
+    }
+}
+
+impl Namespaced {
+    /// The prefix prepended to the keys of all operations performed via this handle.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Produce a handle whose namespace is nested within the namespace of this handle.
+    pub fn namespaced(&self, prefix: Key) -> Namespaced {
+        let client = self.client.clone();
+        let prefix = self.key(prefix);
+        Namespaced { client, prefix }
+    }
+
+    /// Prepend the namespace prefix to the given key.
+    fn key(&self, key: Key) -> Key {
+        let mut prefixed = self.prefix.clone();
+        prefixed.extend(key);
+        prefixed
+    }
+
+    /// The same as `Client::get` but within the namespace.
+    pub fn get(&self, key: Key) -> impl Future<Item = Option<Value>, Error = Error> {
+        self.client.get(self.key(key))
+    }
+
+    /// The same as `Client::get_many` but within the namespace.
+    pub fn get_many(
+        &self,
+        keys: Vec<Key>,
+    ) -> impl Future<Item = Vec<Option<Value>>, Error = Error> {
+        let keys = keys.into_iter().map(|key| self.key(key)).collect();
+        self.client.get_many(keys)
+    }
+
+    /// The same as `Client::get_prefix` but within the namespace.
+    pub fn get_prefix(
+        &self,
+        prefix: Key,
+    ) -> impl Future<Item = BTreeMap<Key, Value>, Error = Error> {
+        let len = self.prefix.len();
+        self.client
+            .get_prefix(self.key(prefix))
+            .map(move |map| map.into_iter().map(|(k, v)| (k[len..].to_vec(), v)).collect())
+    }
+
+    /// The same as `Client::del` but within the namespace.
+    pub fn del(&self, key: Key) -> impl Future<Item = Option<Value>, Error = Error> {
+        self.client.del(self.key(key))
+    }
+
+    /// The same as `Client::del_if_equal` but within the namespace.
+    pub fn del_if_equal(
+        &self,
+        key: Key,
+        expected: Value,
+    ) -> impl Future<Item = bool, Error = Error> {
+        self.client.del_if_equal(self.key(key), expected)
+    }
+
+    /// The same as `Client::del_prefix` but within the namespace.
+    pub fn del_prefix(&self, prefix: Key) -> impl Future<Item = usize, Error = Error> {
+        self.client.del_prefix(self.key(prefix))
+    }
+
+    /// The same as `Client::set` but within the namespace.
+    pub fn set(&self, key: Key, value: Value) -> impl Future<Item = (), Error = Error> {
+        self.client.set(self.key(key), value)
+    }
+
+    /// The same as `Client::replace` but within the namespace.
+    pub fn replace(
+        &self,
+        key: Key,
+        value: Value,
+    ) -> impl Future<Item = Option<Value>, Error = Error> {
+        self.client.replace(self.key(key), value)
+    }
+
+    /// The same as `Client::set_many` but within the namespace.
+    pub fn set_many(
+        &self,
+        entries: Vec<(Key, Value)>,
+    ) -> impl Future<Item = Vec<Result<(), String>>, Error = Error> {
+        let entries = entries.into_iter().map(|(k, v)| (self.key(k), v)).collect();
+        self.client.set_many(entries)
+    }
+
+    /// The same as `Client::cas` but within the namespace.
+    pub fn cas(
+        &self,
+        key: Key,
+        old: Option<Value>,
+        new: Option<Value>,
+    ) -> impl Future<Item = Result<(), Option<Value>>, Error = Error> {
+        self.client.cas(self.key(key), old, new)
+    }
+
+    /// The same as `Client::batch_cas` but within the namespace.
+    pub fn batch_cas(
+        &self,
+        ops: Vec<request::Cas>,
+    ) -> impl Future<Item = Vec<Result<(), request::CasError>>, Error = Error> {
+        let ops = ops
+            .into_iter()
+            .map(|request::Cas { key, old, new }| request::Cas { key: self.key(key), old, new })
+            .collect();
+        self.client.batch_cas(ops)
+    }
+
+    /// The same as `Client::merge` but within the namespace.
+    pub fn merge(&self, key: Key, value: Value) -> impl Future<Item = (), Error = Error> {
+        self.client.merge(self.key(key), value)
+    }
+
+    /// The same as `Client::count_range` but within the namespace.
+    pub fn count_range(&self, start: Key, end: Key) -> impl Future<Item = usize, Error = Error> {
+        self.client.count_range(self.key(start), self.key(end))
+    }
+
+    /// The same as `Client::incr` but within the namespace.
+    pub fn incr(&self, key: Key, delta: i64) -> impl Future<Item = i64, Error = Error> {
+        self.client.incr(self.key(key), delta)
+    }
+
+    /// The same as `Client::get_or_set` but within the namespace.
+    pub fn get_or_set(&self, key: Key, default: Value) -> impl Future<Item = Value, Error = Error> {
+        self.client.get_or_set(self.key(key), default)
+    }
+
+    /// The same as `Client::flush`.
+    pub fn flush(&self) -> impl Future<Item = (), Error = Error> {
+        self.client.flush()
+    }
+
+    /// The same as `Client::batch` but within the namespace.
+    pub fn batch(&self, ops: Vec<request::BatchOp>) -> impl Future<Item = (), Error = Error> {
+        self.client.batch(namespaced_ops(&self.prefix, ops))
+    }
+
+    /// The same as `Client::rename` but within the namespace.
+    pub fn rename(
+        &self,
+        from: Key,
+        to: Key,
+        overwrite: bool,
+    ) -> impl Future<Item = request::RenameOutcome, Error = Error> {
+        self.client.rename(self.key(from), self.key(to), overwrite)
+    }
+
+    /// The same as `Client::transaction` but within the namespace.
+    pub fn transaction(
+        &self,
+        conditions: Vec<request::Condition>,
+        writes: Vec<request::BatchOp>,
+    ) -> impl Future<Item = request::TransactionOutcome, Error = Error> {
+        let conditions = conditions
+            .into_iter()
+            .map(|request::Condition { key, expected }| {
+                request::Condition { key: self.key(key), expected }
+            })
+            .collect();
+        let writes = namespaced_ops(&self.prefix, writes);
+        let prefix_len = self.prefix.len();
+        self.client
+            .transaction(conditions, writes)
+            .map(move |outcome| match outcome {
+                request::TransactionOutcome::Committed => request::TransactionOutcome::Committed,
+                request::TransactionOutcome::Aborted { conflicts } => {
+                    let conflicts = conflicts
+                        .into_iter()
+                        .map(|(mut k, v)| {
+                            k.drain(..prefix_len);
+                            (k, v)
+                        })
+                        .collect();
+                    request::TransactionOutcome::Aborted { conflicts }
+                }
+            })
+    }
+
+    /// Iterate over all entries within the namespace.
+    pub fn iter(&self) -> impl Stream<Item = Entry, Error = Error> {
+        let scan = self.client.scan(self.prefix.clone());
+        take_within_namespace(scan, self.prefix.clone())
+    }
+
+    /// The same as `Client::scan` but within the namespace.
+    pub fn scan(&self, key: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let scan = self.client.scan(self.key(key));
+        take_within_namespace(scan, self.prefix.clone())
+    }
+
+    /// The same as `Client::scan_range` but within the namespace.
+    ///
+    /// An unbounded side of the range is bounded by the extent of the namespace.
+    pub fn scan_range<R>(&self, range: R) -> impl Stream<Item = Entry, Error = Error>
+    where
+        R: RangeBounds<Key>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => Bound::Included(self.key(key.clone())),
+            Bound::Excluded(key) => Bound::Excluded(self.key(key.clone())),
+            Bound::Unbounded => Bound::Included(self.prefix.clone()),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => Bound::Included(self.key(key.clone())),
+            Bound::Excluded(key) => Bound::Excluded(self.key(key.clone())),
+            Bound::Unbounded => match prefix_successor(&self.prefix) {
+                Some(end) => Bound::Excluded(end),
+                // All keys following the prefix begin with the prefix.
+                None => Bound::Unbounded,
+            },
+        };
+        let len = self.prefix.len();
+        self.client
+            .scan_range((start, end))
+            .map(move |(k, v)| (k[len..].to_vec(), v))
+    }
+
+    /// The same as `Client::scan_suffix` but within the namespace.
+    ///
+    /// Note that the server must still visit all entries with the given suffix in the tree.
+    pub fn scan_suffix(&self, suffix: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let prefix = self.prefix.clone();
+        let len = prefix.len();
+        self.client
+            .scan_suffix(suffix)
+            .filter(move |entry| entry.0.starts_with(&prefix))
+            .map(move |(k, v)| (k[len..].to_vec(), v))
+    }
+
+    /// Iterate over all entries within the namespace in descending order of their keys.
+    pub fn iter_rev(&self) -> Box<Stream<Item = Entry, Error = Error> + Send> {
+        let prefix = self.prefix.clone();
+        let len = prefix.len();
+        match prefix_successor(&prefix) {
+            Some(end) => {
+                let scan = self.client
+                    .scan_range_rev(prefix, end)
+                    .map(move |(k, v)| (k[len..].to_vec(), v));
+                Box::new(scan)
+            }
+            // All keys following the prefix begin with the prefix.
+            None => Box::new(take_within_namespace(self.client.iter_rev(), prefix)),
+        }
+    }
+
+    /// The same as `Client::scan_range_rev` but within the namespace.
+    pub fn scan_range_rev(&self, start: Key, end: Key) -> impl Stream<Item = Entry, Error = Error> {
+        let len = self.prefix.len();
+        self.client
+            .scan_range_rev(self.key(start), self.key(end))
+            .map(move |(k, v)| (k[len..].to_vec(), v))
+    }
+
+    /// Retrieve the entry with the greatest key within the namespace.
+    pub fn max(&self) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let prefix = self.prefix.clone();
+        let entry = match prefix_successor(&prefix) {
+            Some(end) => Either::A(self.client.pred(end)),
+            None => Either::B(self.client.max()),
+        };
+        entry.map(move |entry| entry_within_namespace(entry, &prefix))
+    }
+
+    /// The same as `Client::pred` but within the namespace.
+    pub fn pred(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let prefix = self.prefix.clone();
+        self.client
+            .pred(self.key(key))
+            .map(move |entry| entry_within_namespace(entry, &prefix))
+    }
+
+    /// The same as `Client::pred_incl` but within the namespace.
+    pub fn pred_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let prefix = self.prefix.clone();
+        self.client
+            .pred_incl(self.key(key))
+            .map(move |entry| entry_within_namespace(entry, &prefix))
+    }
+
+    /// The same as `Client::succ` but within the namespace.
+    pub fn succ(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let prefix = self.prefix.clone();
+        self.client
+            .succ(self.key(key))
+            .map(move |entry| entry_within_namespace(entry, &prefix))
+    }
+
+    /// The same as `Client::succ_incl` but within the namespace.
+    pub fn succ_incl(&self, key: Key) -> impl Future<Item = Option<Entry>, Error = Error> {
+        let prefix = self.prefix.clone();
+        self.client
+            .succ_incl(self.key(key))
+            .map(move |entry| entry_within_namespace(entry, &prefix))
+    }
+
+    /// The same as `Client::changes_since` but only producing changes within the namespace.
+    pub fn changes_since(
+        &self,
+        since: u64,
+        timeout: Duration,
+    ) -> impl Future<Item = request::ChangeBatch, Error = Error> {
+        let len = self.prefix.len();
+        self.client
+            .changes_since_prefix(self.prefix.clone(), since, timeout)
+            .map(move |mut batch| {
+                for event in &mut batch.events {
+                    event.key.drain(..len);
+                }
+                batch
+            })
+    }
+
+    /// The same as `Client::subscribe` but within the namespace.
+    pub fn subscribe(&self, prefix: Key) -> impl Stream<Item = request::Event, Error = Error> {
+        let len = self.prefix.len();
+        self.client.subscribe(self.key(prefix)).map(move |mut event| {
+            event.key.drain(..len);
+            event
+        })
+    }
+
+    /// The same as `Client::subscribe_cursor` but within the namespace.
+    pub fn subscribe_cursor(
+        &self,
+        name: String,
+        prefix: Key,
+    ) -> impl Stream<Item = request::Event, Error = Error> {
+        let len = self.prefix.len();
+        self.client.subscribe_cursor(name, self.key(prefix)).map(move |mut event| {
+            event.key.drain(..len);
+            event
+        })
+    }
+
+    /// The same as `Client::subscribe_websocket` but only yielding changes within the namespace.
+    pub fn subscribe_websocket(&self) -> impl Stream<Item = request::Event, Error = Error> {
+        let len = self.prefix.len();
+        let subscribe = request::Subscribe { prefix: self.prefix.clone(), ..Default::default() };
+        self.client.subscribe_websocket_with(subscribe).map(move |mut event| {
+            event.key.drain(..len);
+            event
+        })
+    }
+}
+
+impl Mirrored {
+    /// The client from which reads are served and to which writes are applied first.
+    pub fn primary(&self) -> &Client {
+        &self.primary
+    }
+
+    /// The client to which writes are mirrored.
+    pub fn secondary(&self) -> &Client {
+        &self.secondary
+    }
+
+    /// The same pair of clients with their roles exchanged.
+    ///
+    /// Allows for cutting over to the new server while continuing to mirror writes to the old
+    /// server until the migration is known to have succeeded.
+    pub fn swapped(&self) -> Mirrored {
+        let primary = self.secondary.clone();
+        let secondary = self.primary.clone();
+        Mirrored { primary, secondary }
+    }
+
+    /// The same as `Client::get` via the primary.
+    pub fn get(&self, key: Key) -> impl Future<Item = Option<Value>, Error = Error> {
+        self.primary.get(key)
+    }
+
+    /// The same as `Client::get_many` via the primary.
+    pub fn get_many(
+        &self,
+        keys: Vec<Key>,
+    ) -> impl Future<Item = Vec<Option<Value>>, Error = Error> {
+        self.primary.get_many(keys)
+    }
+
+    /// The same as `Client::get_prefix` via the primary.
+    pub fn get_prefix(
+        &self,
+        prefix: Key,
+    ) -> impl Future<Item = BTreeMap<Key, Value>, Error = Error> {
+        self.primary.get_prefix(prefix)
+    }
+
+    /// The same as `Client::set`, mirrored to the secondary.
+    pub fn set(&self, key: Key, value: Value) -> impl Future<Item = (), Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .set(key.clone(), value.clone())
+            .and_then(move |()| secondary.set(key, value))
+    }
+
+    /// The same as `Client::replace`, mirrored to the secondary as a `set`.
+    pub fn replace(
+        &self,
+        key: Key,
+        value: Value,
+    ) -> impl Future<Item = Option<Value>, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .replace(key.clone(), value.clone())
+            .and_then(move |old| secondary.set(key, value).map(move |()| old))
+    }
+
+    /// The same as `Client::set_many`, mirroring only the entries set via the primary.
+    pub fn set_many(
+        &self,
+        entries: Vec<(Key, Value)>,
+    ) -> impl Future<Item = Vec<Result<(), String>>, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .set_many(entries.clone())
+            .and_then(move |results| {
+                let set = entries
+                    .into_iter()
+                    .zip(&results)
+                    .filter(|&(_, result)| result.is_ok())
+                    .map(|(entry, _)| entry)
+                    .collect();
+                secondary.set_many(set).map(move |_| results)
+            })
+    }
+
+    /// The same as `Client::del`, mirrored to the secondary.
+    pub fn del(&self, key: Key) -> impl Future<Item = Option<Value>, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .del(key.clone())
+            .and_then(move |old| secondary.del(key).map(move |_| old))
+    }
+
+    /// The same as `Client::del_prefix`, mirrored to the secondary.
+    pub fn del_prefix(&self, prefix: Key) -> impl Future<Item = usize, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .del_prefix(prefix.clone())
+            .and_then(move |count| secondary.del_prefix(prefix).map(move |_| count))
+    }
+
+    /// The same as `Client::cas`, mirroring the outcome to the secondary as a `set` or `del` if
+    /// the swap succeeded via the primary.
+    pub fn cas(
+        &self,
+        key: Key,
+        old: Option<Value>,
+        new: Option<Value>,
+    ) -> impl Future<Item = Result<(), Option<Value>>, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .cas(key.clone(), old, new.clone())
+            .and_then(move |result| {
+                let mirrored = match (&result, new) {
+                    (&Err(_), _) => Either::A(futures::future::ok(())),
+                    (&Ok(()), Some(new)) => Either::B(Either::A(secondary.set(key, new))),
+                    (&Ok(()), None) => Either::B(Either::B(secondary.del(key).map(|_| ()))),
+                };
+                mirrored.map(move |()| result)
+            })
+    }
+
+    /// The same as `Client::merge`, mirrored to the secondary.
+    ///
+    /// Both servers must be configured with the same merge operator.
+    pub fn merge(&self, key: Key, value: Value) -> impl Future<Item = (), Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .merge(key.clone(), value.clone())
+            .and_then(move |()| secondary.merge(key, value))
+    }
+
+    /// The same as `Client::incr`, mirroring the resulting value to the secondary as a `set`.
+    pub fn incr(&self, key: Key, delta: i64) -> impl Future<Item = i64, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .incr(key.clone(), delta)
+            .and_then(move |n| {
+                let value = (0..8).map(|i| ((n as u64) >> (i * 8)) as u8).collect();
+                secondary.set(key, value).map(move |()| n)
+            })
+    }
+
+    /// The same as `Client::batch`, mirrored to the secondary.
+    pub fn batch(&self, ops: Vec<request::BatchOp>) -> impl Future<Item = (), Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .batch(ops.clone())
+            .and_then(move |()| secondary.batch(ops))
+    }
+
+    /// The same as `Client::clear`, mirrored to the secondary.
+    pub fn clear(&self) -> impl Future<Item = usize, Error = Error> {
+        let secondary = self.secondary.clone();
+        self.primary
+            .clear()
+            .and_then(move |count| secondary.clear().map(move |_| count))
+    }
+}
+
+impl Stream for BodyToJsonChunks {
+    type Item = serde_json::Value;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            let (next, consumed) = {
+                let mut values = serde_json::Deserializer::from_slice(&self.buffer)
+                    .into_iter::<serde_json::Value>();
+                let next = values.next();
+                (next, values.byte_offset())
+            };
+            match next {
+                Some(Ok(v)) => {
+                    self.buffer.drain(..consumed);
+                    return Ok(Async::Ready(Some(v)));
+                }
+                // The value is completed by the following chunks.
+                Some(Err(ref err)) if err.is_eof() && !self.ended => (),
+                Some(Err(err)) => return Err(err.into()),
+                None if self.ended => return Ok(Async::Ready(None)),
+                None => (),
+            }
+            match self.body.poll() {
+                Err(err) => return Err(err.into()),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => self.ended = true,
+                Ok(Async::Ready(Some(chunk))) => self.buffer.extend(chunk),
+            }
+        }
+    }
+}
+
+impl<T> Stream for BodyToBincodeItems<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    type Item = T;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            // An empty body may still contain a single value of a zero-sized type, e.g. `()`.
+            if !self.buffer.is_empty() || (self.ended && !self.yielded) {
+                let (res, consumed) = {
+                    let mut cursor = io::Cursor::new(&self.buffer[..]);
+                    let res = bincode::deserialize_from::<_, T>(&mut cursor);
+                    (res, cursor.position() as usize)
+                };
+                let err = match res {
+                    Ok(item) => {
+                        self.buffer.drain(..consumed);
+                        self.yielded = true;
+                        return Ok(Async::Ready(Some(item)));
+                    }
+                    Err(err) => err,
+                };
+                let incomplete = match *err {
+                    bincode::ErrorKind::Io(ref err) => err.kind() == io::ErrorKind::UnexpectedEof,
+                    _ => false,
+                };
+                if !incomplete || (self.ended && !self.buffer.is_empty()) {
+                    return Err(Error::Bincode(err));
+                }
+            }
+            if self.ended {
+                return Ok(Async::Ready(None));
+            }
+            match self.body.poll() {
+                Err(err) => return Err(err.into()),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => self.ended = true,
+                Ok(Async::Ready(Some(chunk))) => self.buffer.extend(chunk),
+            }
+        }
+    }
+}
+
+impl Subscription {
+    /// The sequence number of the last event produced, if any.
+    pub fn last_seq(&self) -> Option<u64> {
+        self.last_seq
+    }
+}
+
+impl Stream for Subscription {
+    type Item = request::Event;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            let reconnect = match self.state {
+                SubscriptionState::Done => return Ok(Async::Ready(None)),
+                SubscriptionState::Waiting(ref mut delay) => match delay.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    _ => true,
+                },
+                SubscriptionState::Connecting(ref mut response) => match response.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(_) => false,
+                    Ok(Async::Ready(response)) => {
+                        let status = response.status();
+                        if status.is_client_error() {
+                            self.state = SubscriptionState::Done;
+                            let msg = format!("the server refused to watch with status {}", status);
+                            return Err(Error::Server(msg));
+                        }
+                        if !status.is_success() {
+                            false
+                        } else {
+                            let body = response.into_body();
+                            self.state = SubscriptionState::Receiving { body, buffer: vec![] };
+                            continue;
+                        }
+                    }
+                },
+                SubscriptionState::Receiving { ref mut body, ref mut buffer } => {
+                    if let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                        let block: Vec<u8> = buffer.drain(..end + 2).collect();
+                        let data = match sse_data(&block) {
+                            Some(data) => data,
+                            None => continue,
+                        };
+                        let event: request::Event = serde_json::from_str(&data)?;
+                        if self.last_seq.map_or(false, |last| event.seq <= last) {
+                            continue;
+                        }
+                        self.last_seq = Some(event.seq);
+                        self.attempts = 0;
+                        return Ok(Async::Ready(Some(event)));
+                    }
+                    match body.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(Some(chunk))) => {
+                            buffer.extend(chunk);
+                            continue;
+                        }
+                        Ok(Async::Ready(None)) | Err(_) => false,
+                    }
+                }
+            };
+            self.state = if reconnect {
+                let response = connect_watch(&self.client, &self.watch, self.last_seq);
+                SubscriptionState::Connecting(response)
+            } else {
+                let backoff = Duration::from_millis(100 << cmp::min(self.attempts, 7));
+                self.attempts += 1;
+                SubscriptionState::Waiting(Delay::new(Instant::now() + backoff))
+            };
+        }
+    }
+}
+
+impl<S> Stream for MergeSorted<S>
+where
+    S: Stream<Item = Entry>,
+{
+    type Item = Entry;
+    type Error = S::Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // Every unfinished stream must have an entry ready before the smallest can be known.
+        let mut ready = true;
+        for source in &mut self.sources {
+            if source.head.is_some() || source.done {
+                continue;
+            }
+            match source.stream.poll()? {
+                Async::NotReady => ready = false,
+                Async::Ready(None) => source.done = true,
+                Async::Ready(Some(entry)) => source.head = Some(entry),
+            }
+        }
+        if !ready {
+            return Ok(Async::NotReady);
+        }
+        let next = self.sources
+            .iter()
+            .enumerate()
+            .filter_map(|(i, source)| source.head.as_ref().map(|entry| (i, &entry.0)))
+            .min_by(|a, b| a.1.cmp(b.1))
+            .map(|(i, _)| i);
+        match next {
+            None => Ok(Async::Ready(None)),
+            Some(i) => Ok(Async::Ready(self.sources[i].head.take())),
+        }
+    }
+}
+
+impl Transport for hyper::Client<HttpConnector, Body> {
+    fn request(&self, request: Request<Body>) -> ResponseFuture {
+        Box::new(hyper::Client::request(self, request))
+    }
+}
+
+impl Transport for Router {
+    fn request(&self, request: Request<Body>) -> ResponseFuture {
+        self.respond(request)
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("client", &self.client)
+            .field("watch", &self.watch)
+            .field("last_seq", &self.last_seq)
+            .field("attempts", &self.attempts)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("uri", &self.uri)
+            .field("deadline", &self.deadline)
+            .field("timeout", &self.timeout)
+            .field("protocol", &self.protocol)
+            .field("path_prefix", &self.path_prefix)
+            .field("base_path", &self.base_path)
+            .field("tree_name", &self.tree_name)
+            .field("replicas", &self.replicas)
+            .field("consistency", &self.consistency)
+            .field("encoding", &self.encoding)
+            .field("compression", &self.compression)
+            .field("missing_not_found", &self.missing_not_found)
+            .finish()
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Hyper(ref err) => err.description(),
+            Error::SerdeJson(ref err) => err.description(),
+            Error::Server(ref s) => s,
+            Error::Response(ref body) => &body.message,
+            Error::Incompatible(_) => "the server speaks an incompatible wire format",
+            Error::WebSocket(ref err) => err.description(),
+            Error::Io(ref err) => err.description(),
+            Error::TimedOut => "the request timed out",
+            Error::Bincode(ref err) => err.description(),
+        }
+    }
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Hyper(ref err) => Some(err),
+            Error::SerdeJson(ref err) => Some(err),
+            Error::Server(_) => None,
+            Error::Response(_) => None,
+            Error::Incompatible(_) => None,
+            Error::WebSocket(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            Error::TimedOut => None,
+            Error::Bincode(ref err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Self {
+        Error::Hyper(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
         Error::SerdeJson(e)
     }
 }
@@ -238,36 +2406,353 @@ impl From<serde_json::Error> for Error {
 impl From<Body> for BodyToJsonChunks {
     fn from(body: Body) -> Self {
         let buffer = vec![];
-        BodyToJsonChunks { body, buffer }
+        let ended = false;
+        BodyToJsonChunks { body, buffer, ended }
+    }
+}
+
+impl<T> From<Body> for BodyToBincodeItems<T> {
+    fn from(body: Body) -> Self {
+        let buffer = vec![];
+        let ended = false;
+        let yielded = false;
+        let item = PhantomData;
+        BodyToBincodeItems { body, buffer, ended, yielded, item }
+    }
+}
+
+/// Merge several key-ordered entry streams into a single stream ordered by key.
+///
+/// This is useful for combining the results of multiple `scan` or `scan_range` requests, whether
+/// they are made to a single server or to several. See `MergeSorted` for details.
+pub fn merge_sorted<I>(streams: I) -> MergeSorted<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Stream<Item = Entry>,
+{
+    let sources = streams
+        .into_iter()
+        .map(|stream| MergeSource { stream, head: None, done: false })
+        .collect();
+    MergeSorted { sources }
+}
+
+/// Prepend the given namespace prefix to the keys of all of the given operations.
+fn namespaced_ops(prefix: &[u8], ops: Vec<request::BatchOp>) -> Vec<request::BatchOp> {
+    let key = |key: Key| {
+        let mut prefixed = prefix.to_vec();
+        prefixed.extend(key);
+        prefixed
+    };
+    ops.into_iter()
+        .map(|op| match op {
+            request::BatchOp::Set { key: k, value } => request::BatchOp::Set { key: key(k), value },
+            request::BatchOp::Del { key: k } => request::BatchOp::Del { key: key(k) },
+        })
+        .collect()
+}
+
+/// The smallest key that is greater than every key beginning with the given prefix.
+///
+/// Returns `None` if there is no such key, i.e. if the prefix is empty or consists only of `0xFF`.
+fn prefix_successor(prefix: &[u8]) -> Option<Key> {
+    let mut end = prefix.to_vec();
+    while let Some(byte) = end.pop() {
+        if byte < 0xFF {
+            end.push(byte + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// Take entries from the given key-ordered stream for as long as they remain within the namespace
+/// of the given prefix, stripping the prefix from their keys.
+fn take_within_namespace<S>(stream: S, prefix: Key) -> impl Stream<Item = Entry, Error = Error>
+where
+    S: Stream<Item = Entry, Error = Error>,
+{
+    let len = prefix.len();
+    stream
+        .take_while(move |entry| Ok(entry.0.starts_with(&prefix)))
+        .map(move |(k, v)| (k[len..].to_vec(), v))
+}
+
+/// Strip the namespace prefix from the given entry's key, or discard it if it lies outside the
+/// namespace.
+fn entry_within_namespace(entry: Option<Entry>, prefix: &[u8]) -> Option<Entry> {
+    entry.and_then(|(k, v)| {
+        if k.starts_with(prefix) {
+            Some((k[prefix.len()..].to_vec(), v))
+        } else {
+            None
+        }
+    })
+}
+
+/// The prefix of each group that is missing from either list of hashes or whose hashes differ,
+/// along with the greater number of entries within the group.
+fn differing_groups(
+    source: Vec<request::PrefixHash>,
+    destination: Vec<request::PrefixHash>,
+) -> Vec<(Key, u64)> {
+    let mut groups = BTreeMap::new();
+    for hash in source {
+        let prefix = hash.prefix.clone();
+        groups.entry(prefix).or_insert((None, None)).0 = Some(hash);
     }
+    for hash in destination {
+        let prefix = hash.prefix.clone();
+        groups.entry(prefix).or_insert((None, None)).1 = Some(hash);
+    }
+    groups
+        .into_iter()
+        .filter_map(|(prefix, (src, dst))| {
+            let count = |hash: &Option<request::PrefixHash>| hash.as_ref().map_or(0, |h| h.count);
+            let count = cmp::max(count(&src), count(&dst));
+            if src.map(|h| h.hash) == dst.map(|h| h.hash) {
+                None
+            } else {
+                Some((prefix, count))
+            }
+        })
+        .collect()
+}
+
+/// Make the entry with the given key within the `destination` tree equal to that within the
+/// `source` tree, producing `1` if the entry was set or removed or otherwise `0`.
+fn sync_key(
+    source: &Client,
+    destination: &Client,
+    key: Key,
+) -> impl Future<Item = u64, Error = Error> {
+    let dst = destination.clone();
+    source
+        .get(key.clone())
+        .join(destination.get(key.clone()))
+        .and_then(move |(src_value, dst_value)| {
+            if src_value == dst_value {
+                return Either::A(futures::future::ok(0));
+            }
+            match src_value {
+                Some(value) => Either::B(Either::A(dst.set(key, value).map(|()| 1))),
+                None => Either::B(Either::B(dst.del(key).map(|_| 1))),
+            }
+        })
+}
+
+/// Make the entries whose keys begin with `prefix` within the `destination` tree equal to those
+/// within the `source` tree, producing the number of entries set or removed.
+fn sync_prefix(
+    source: &Client,
+    destination: &Client,
+    prefix: Key,
+) -> impl Future<Item = u64, Error = Error> {
+    let dst = destination.clone();
+    source
+        .get_prefix(prefix.clone())
+        .join(destination.get_prefix(prefix))
+        .and_then(move |(src_entries, dst_entries)| {
+            let mut ops: Vec<_> = dst_entries
+                .keys()
+                .filter(|key| !src_entries.contains_key(*key))
+                .map(|key| request::BatchOp::Del { key: key.clone() })
+                .collect();
+            for (key, value) in src_entries {
+                if dst_entries.get(&key) != Some(&value) {
+                    ops.push(request::BatchOp::Set { key, value });
+                }
+            }
+            let count = ops.len() as u64;
+            if ops.is_empty() {
+                return Either::A(futures::future::ok(0));
+            }
+            Either::B(dst.batch(ops).map(move |()| count))
+        })
 }
 
 /// Concatenate and deserialize a single-chunk reponse.
+///
+/// An empty body, e.g. from a proxy or middleware rejecting the request, produces an
+/// `Error::Server` describing the response's status.
 fn concat_and_deserialize<T>(response: Response<Body>) -> impl Future<Item = T, Error = Error>
 where
     T: for<'de> Deserialize<'de>,
 {
     let status = response.status();
-    BodyToJsonChunks::from(response.into_body())
-        .and_then(move |value| {
-            if status == StatusCode::INTERNAL_SERVER_ERROR {
-                let s = serde_json::from_value(value).map_err(Error::SerdeJson)?;
-                return Err(Error::Server(s));
-            }
-            serde_json::from_value::<T>(value).map_err(Error::SerdeJson)
-        })
+    stream_and_deserialize(response)
         .into_future()
         .map_err(|(err, _)| err)
-        .and_then(|(opt, _stream)| opt.ok_or_else(|| unreachable!()))
+        .and_then(move |(opt, _stream)| {
+            opt.ok_or_else(|| Error::Server(format!("{} response with an empty body", status)))
+        })
 }
 
 /// Convert the given response body chunks into a stream of deserialized items.
+///
+/// If the server responded with an error status, the stream yields the described error. See
+/// `response_error`.
 fn stream_and_deserialize<T>(response: Response<Body>) -> impl Stream<Item = T, Error = Error>
 where
     T: for<'de> Deserialize<'de>,
 {
-    BodyToJsonChunks::from(response.into_body())
-        .and_then(|json| serde_json::from_value(json).map_err(Error::SerdeJson))
+    let status = response.status();
+    let base64 = match encoding::content_encoding(response.headers()) {
+        Some(Encoding::Bincode) => return Either::A(BodyToBincodeItems::from(response.into_body())),
+        Some(Encoding::Base64Json) => true,
+        _ => false,
+    };
+    let items = BodyToJsonChunks::from(response.into_body())
+        .and_then(move |json| {
+            if status.is_client_error() || status.is_server_error() {
+                return Err(response_error(json));
+            }
+            if base64 {
+                return base64_json::from_value(json).map_err(Error::SerdeJson);
+            }
+            serde_json::from_value(json).map_err(Error::SerdeJson)
+        });
+    Either::B(items)
+}
+
+/// The error described by the JSON body of an error response.
+///
+/// The body is a `request::ErrorBody`, or a `String` if the server speaks a wire format revision
+/// prior to 8.
+fn response_error(json: serde_json::Value) -> Error {
+    match serde_json::from_value::<request::ErrorBody>(json.clone()) {
+        Ok(body) => Error::Response(body),
+        Err(_) => match serde_json::from_value(json) {
+            Ok(msg) => Error::Server(msg),
+            Err(err) => Error::SerdeJson(err),
+        },
+    }
+}
+
+/// Produce a `Sec-WebSocket-Key` nonce for a `Subscribe` handshake.
+///
+/// The nonce only serves to prevent intermediaries from replaying cached handshakes, so it is
+/// derived from the current time and a counter rather than a source of randomness.
+fn websocket_key() -> String {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+    let time = now.as_secs()
+        .wrapping_mul(1_000_000_000)
+        .wrapping_add(u64::from(now.subsec_nanos()));
+    let count = COUNT.fetch_add(1, Ordering::Relaxed) as u64;
+    let mut nonce = [0u8; 16];
+    for i in 0..8 {
+        nonce[i] = (time >> (i * 8)) as u8;
+        nonce[i + 8] = (count >> (i * 8)) as u8;
+    }
+    base64::encode(&nonce)
+}
+
+/// Submit the given `Watch` request, resuming after `last_seq` if any or otherwise from the
+/// request's cursor if any.
+fn connect_watch(client: &Client, watch: &request::Watch, last_seq: Option<u64>) -> Submitted {
+    let request = request::watch_with(client.uri.clone(), watch, last_seq);
+    submit(client, request)
+}
+
+/// The data of a Server-Sent Events event block, or `None` if the block has no data, e.g. if it
+/// is a comment used to keep the connection alive.
+fn sse_data(block: &[u8]) -> Option<String> {
+    let block = String::from_utf8_lossy(block);
+    let data: Vec<&str> = block
+        .lines()
+        .filter_map(|line| if line.starts_with("data:") { Some(&line[5..]) } else { None })
+        .map(|data| if data.starts_with(' ') { &data[1..] } else { data })
+        .collect();
+    if data.is_empty() {
+        None
+    } else {
+        Some(data.join("\n"))
+    }
+}
+
+/// A response submitted via `submit`.
+type Submitted = Box<Future<Item = Response<Body>, Error = Error> + Send>;
+
+/// Prepare the given request via `prepare_request` and submit it via the client's transport,
+/// decoding the response body to JSON if necessary.
+///
+/// Yields `Error::TimedOut` if the response does not begin within the client's `timeout`.
+fn submit(client: &Client, request: Request<Body>) -> Submitted {
+    let request = prepare_request(client, request);
+    let response = client.transport.request(request);
+    let delay = client.timeout.map(|timeout| Delay::new(Instant::now() + timeout));
+    let response = ResponseTimeout { response, delay }
+        .map(compression::decompress_response)
+        .map(encoding::decode_response);
+    Box::new(response)
+}
+
+/// A response that fails with `Error::TimedOut` once its `delay` elapses.
+struct ResponseTimeout {
+    response: ResponseFuture,
+    /// `None` if there is no timeout, or if there is no timer with which to apply it.
+    delay: Option<Delay>,
+}
+
+impl Future for ResponseTimeout {
+    type Item = Response<Body>;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // The delay is polled first so that a response that is never ready does not starve it.
+        let elapsed = match self.delay {
+            None => false,
+            Some(ref mut delay) => match delay.poll() {
+                Ok(Async::NotReady) => false,
+                Ok(Async::Ready(())) => true,
+                Err(_) => {
+                    self.delay = None;
+                    false
+                }
+            },
+        };
+        if elapsed {
+            return Err(Error::TimedOut);
+        }
+        self.response.poll().map_err(Error::Hyper)
+    }
+}
+
+/// Apply the path prefix and headers configured via the `Client` to the given request.
+fn prepare_request(client: &Client, mut request: Request<Body>) -> Request<Body> {
+    request.headers_mut().insert(request::PROTOCOL, HeaderValue::from(client.protocol));
+    let base_path = client.base_path.as_ref().map(|path| &path[..]).unwrap_or("");
+    if !base_path.is_empty() || !client.path_prefix.is_empty() || client.tree_name.is_some() {
+        let prefix = format!("{}{}", base_path, client.path_prefix);
+        let path_and_query = {
+            let pq = request.uri().path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+            match client.tree_name {
+                Some(ref name) if pq.starts_with(request::TREE_PATH) => {
+                    let rest = &pq[request::TREE_PATH.len()..];
+                    format!("{}{}{}/{}", prefix, request::TREE_PATH, name, rest)
+                }
+                _ => format!("{}{}", prefix, pq),
+            }
+        };
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = Some(path_and_query
+            .parse()
+            .expect("failed to parse path and query for request URI"));
+        *request.uri_mut() = Uri::from_parts(parts)
+            .expect("failed to construct request URI from parts");
+    }
+    if let Some(deadline) = client.deadline {
+        let ms = deadline.as_secs() * 1_000 + u64::from(deadline.subsec_millis());
+        request.headers_mut().insert(request::DEADLINE, HeaderValue::from(ms));
+    }
+    if let Some(compression) = client.compression {
+        let token = HeaderValue::from_static(compression.token());
+        request.headers_mut().insert(header::ACCEPT_ENCODING, token);
+    }
+    if client.missing_not_found {
+        request.headers_mut().insert(request::MISSING, HeaderValue::from_static("404"));
+    }
+    encoding::encode_request(request, client.encoding)
 }
 
 /// Submit the given request, then concatenate and deserialize a single-chunk response.
@@ -278,11 +2763,40 @@ fn request_concat_and_deserialize<T>(
 where
     T: for<'de> Deserialize<'de>,
 {
-    client
-        .client
-        .request(request)
-        .map_err(Error::Hyper)
-        .and_then(concat_and_deserialize)
+    submit(client, request).and_then(concat_and_deserialize)
+}
+
+/// Submit the given request, then write each chunk of a successful response body to `writer`.
+///
+/// The body of an unsuccessful response is read as the `String` describing the error.
+fn request_and_write<W>(
+    client: &Client,
+    request: Request<Body>,
+    writer: W,
+) -> impl Future<Item = W, Error = Error>
+where
+    W: Write,
+{
+    submit(client, request).and_then(|response| {
+        let status = response.status();
+        let body = response.into_body();
+        if !status.is_success() {
+            let error = body.concat2().map_err(Error::Hyper).and_then(move |chunk| {
+                let error = serde_json::from_slice(&chunk)
+                    .map(response_error)
+                    .unwrap_or_else(|_| {
+                        Error::Server(format!("export failed with status {}", status))
+                    });
+                Err(error)
+            });
+            return Either::A(error);
+        }
+        let written = body.map_err(Error::Hyper).fold(writer, |mut writer, chunk| {
+            writer.write_all(&chunk).map_err(Error::Io)?;
+            Ok::<_, Error>(writer)
+        });
+        Either::B(written)
+    })
 }
 
 /// Submit the given request, then convert the response body chunks into a stream of deserialized
@@ -294,10 +2808,7 @@ fn request_stream_and_deserialize<T>(
 where
     T: for<'de> Deserialize<'de>,
 {
-    client
-        .client
-        .request(request)
-        .map_err(Error::Hyper)
+    submit(client, request)
         .map(stream_and_deserialize)
         .flatten_stream()
 }
@@ -0,0 +1,308 @@
+//! Compression of response bodies via the `Accept-Encoding` and `Content-Encoding` headers.
+//!
+//! The server compresses the body of each response with gzip or brotli when preferred by the
+//! request's `Accept-Encoding` header, e.g. the entries streamed in response to `Iter` or `Scan`
+//! or large values. Streamed bodies are compressed as they are produced, and the output is flushed
+//! whenever the stream stalls so that e.g. `Watch` events are not held back. Responses that are
+//! already encoded, protocol upgrades and bodies smaller than `MIN_LEN` are left as they are.
+//!
+//! A `Client` requests compressed responses via `Client::compression` and decompresses any
+//! response with a supported `Content-Encoding`.
+
+use brotli;
+use flate2;
+use flate2::write::{GzDecoder, GzEncoder};
+use futures::{Async, Poll};
+use hyper::{Body, Chunk, Response, StatusCode};
+use hyper::body::Payload;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::rt::Stream;
+use std::error::Error as StdError;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+/// The content coding token of gzip.
+pub const GZIP: &'static str = "gzip";
+
+/// The content coding token of brotli.
+pub const BROTLI: &'static str = "br";
+
+/// Bodies of a known length smaller than this are not compressed, as the saving is negligible.
+pub const MIN_LEN: u64 = 1024;
+
+/// The brotli quality level, trading some compression for speed as bodies are compressed on the
+/// fly.
+const BROTLI_QUALITY: u32 = 5;
+
+/// The base two logarithm of the brotli window size.
+const BROTLI_LG_WINDOW: u32 = 22;
+
+/// The size of the internal buffers of the brotli encoder and decoder.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// A compression of response bodies.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Brotli,
+}
+
+/// The output of a `Coder`, shared with the writer so that it may be taken after each write.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+/// Compresses or decompresses the bytes written to it into its `Output`.
+enum Coder {
+    GzipEncoder(GzEncoder<Output>),
+    GzipDecoder(GzDecoder<Output>),
+    BrotliEncoder(brotli::CompressorWriter<Output>),
+    BrotliDecoder(brotli::DecompressorWriter<Output>),
+}
+
+/// Compresses or decompresses a streamed body via a `Coder`.
+struct Coded<S> {
+    body: S,
+    /// `None` once the body has ended.
+    coder: Option<Coder>,
+    output: Output,
+    /// Whether bytes have been written to the coder since it was last flushed.
+    pending: bool,
+}
+
+impl Compression {
+    /// All supported compressions.
+    pub const ALL: &'static [Compression] = &[Compression::Gzip, Compression::Brotli];
+
+    /// The content coding token used to specify the compression within headers.
+    pub fn token(&self) -> &'static str {
+        match *self {
+            Compression::Gzip => GZIP,
+            Compression::Brotli => BROTLI,
+        }
+    }
+
+    /// The compression specified by the given content coding, ignoring any parameters.
+    pub fn from_token(token: &str) -> Option<Self> {
+        let token = token.split(';').next().unwrap_or("").trim();
+        Compression::ALL
+            .iter()
+            .cloned()
+            .find(|compression| token.eq_ignore_ascii_case(compression.token()))
+    }
+
+    fn encoder(&self, output: Output) -> Coder {
+        match *self {
+            Compression::Gzip => {
+                Coder::GzipEncoder(GzEncoder::new(output, flate2::Compression::default()))
+            }
+            Compression::Brotli => {
+                let encoder = brotli::CompressorWriter::new(
+                    output,
+                    BROTLI_BUFFER_SIZE,
+                    BROTLI_QUALITY,
+                    BROTLI_LG_WINDOW,
+                );
+                Coder::BrotliEncoder(encoder)
+            }
+        }
+    }
+
+    fn decoder(&self, output: Output) -> Coder {
+        match *self {
+            Compression::Gzip => Coder::GzipDecoder(GzDecoder::new(output)),
+            Compression::Brotli => {
+                Coder::BrotliDecoder(brotli::DecompressorWriter::new(output, BROTLI_BUFFER_SIZE))
+            }
+        }
+    }
+}
+
+impl Output {
+    /// Take all bytes written so far.
+    fn take(&self) -> Vec<u8> {
+        mem::replace(&mut *self.0.lock().expect("failed to acquire output lock"), vec![])
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("failed to acquire output lock").extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Coder {
+    /// Write any remaining output, completing the compressed stream.
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Coder::GzipEncoder(mut encoder) => encoder.try_finish(),
+            Coder::GzipDecoder(mut decoder) => decoder.try_finish(),
+            // Brotli streams are completed when the writer is dropped.
+            Coder::BrotliEncoder(encoder) => {
+                drop(encoder);
+                Ok(())
+            }
+            Coder::BrotliDecoder(decoder) => {
+                drop(decoder);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for Coder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Coder::GzipEncoder(ref mut encoder) => encoder.write(buf),
+            Coder::GzipDecoder(ref mut decoder) => decoder.write(buf),
+            Coder::BrotliEncoder(ref mut encoder) => encoder.write(buf),
+            Coder::BrotliDecoder(ref mut decoder) => decoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Coder::GzipEncoder(ref mut encoder) => encoder.flush(),
+            Coder::GzipDecoder(ref mut decoder) => decoder.flush(),
+            Coder::BrotliEncoder(ref mut encoder) => encoder.flush(),
+            Coder::BrotliDecoder(ref mut decoder) => decoder.flush(),
+        }
+    }
+}
+
+impl<S> Stream for Coded<S>
+where
+    S: Stream<Item = Chunk>,
+    S::Error: 'static + StdError + Send + Sync,
+{
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.coder.is_none() {
+                return Ok(Async::Ready(None));
+            }
+            match self.body.poll() {
+                Err(err) => return Err(Box::new(err)),
+                Ok(Async::NotReady) => {
+                    if !self.pending {
+                        return Ok(Async::NotReady);
+                    }
+                    // The body has stalled, so flush the output written so far.
+                    if let Some(ref mut coder) = self.coder {
+                        coder.flush()?;
+                    }
+                    self.pending = false;
+                }
+                Ok(Async::Ready(None)) => {
+                    if let Some(coder) = self.coder.take() {
+                        coder.finish()?;
+                    }
+                }
+                Ok(Async::Ready(Some(chunk))) => {
+                    if let Some(ref mut coder) = self.coder {
+                        coder.write_all(&chunk)?;
+                    }
+                    self.pending = true;
+                }
+            }
+            let output = self.output.take();
+            if !output.is_empty() {
+                return Ok(Async::Ready(Some(Chunk::from(output))));
+            }
+        }
+    }
+}
+
+/// The compression of the response body preferred via the request's `Accept-Encoding` header.
+///
+/// The first supported content coding listed is preferred, unless its quality value is zero.
+/// Produces `None` if no supported content coding is listed.
+pub fn accepted_compression(headers: &HeaderMap) -> Option<Compression> {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter(|coding| !is_refused(coding))
+        .filter_map(Compression::from_token)
+        .next()
+}
+
+/// Whether the given `Accept-Encoding` item has a quality value of zero, refusing the coding.
+fn is_refused(coding: &str) -> bool {
+    coding.split(';').skip(1).any(|param| {
+        let mut param = param.splitn(2, '=').map(str::trim);
+        match (param.next(), param.next()) {
+            (Some(name), Some(q)) if name.eq_ignore_ascii_case("q") => q.parse() == Ok(0.0f32),
+            _ => false,
+        }
+    })
+}
+
+/// The compression of the body as specified via the `Content-Encoding` header.
+///
+/// Produces `None` if the header is missing or names an unsupported content coding.
+pub fn content_compression(headers: &HeaderMap) -> Option<Compression> {
+    headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Compression::from_token)
+}
+
+/// Compress the body of the given response with the given compression.
+///
+/// Used by the server. Responses that already have a `Content-Encoding`, protocol upgrades and
+/// bodies that are empty or smaller than `MIN_LEN` are left as they are.
+pub fn compress_response(
+    response: Response<Body>,
+    compression: Option<Compression>,
+) -> Response<Body> {
+    let compression = match compression {
+        None => return response,
+        Some(compression) => compression,
+    };
+    match response.status() {
+        StatusCode::SWITCHING_PROTOCOLS | StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED => {
+            return response;
+        }
+        _ => (),
+    }
+    if response.headers().contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+    let body = response.body();
+    if body.is_end_stream() || body.content_length().map(|len| len < MIN_LEN).unwrap_or(false) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(compression.token()));
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let output = Output::default();
+    let coder = Some(compression.encoder(output.clone()));
+    let compressed = Coded { body, coder, output, pending: false };
+    Response::from_parts(parts, Body::from(Box::new(compressed) as Box<_>))
+}
+
+/// Decompress the body of the given response as specified via its `Content-Encoding` header.
+///
+/// Used by clients. Responses without a supported `Content-Encoding` are left as they are.
+pub fn decompress_response(response: Response<Body>) -> Response<Body> {
+    let compression = match content_compression(response.headers()) {
+        None => return response,
+        Some(compression) => compression,
+    };
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let output = Output::default();
+    let coder = Some(compression.decoder(output.clone()));
+    let decompressed = Coded { body, coder, output, pending: false };
+    Response::from_parts(parts, Body::from(Box::new(decompressed) as Box<_>))
+}
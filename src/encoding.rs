@@ -0,0 +1,534 @@
+//! Negotiation of the encoding of request and response bodies.
+//!
+//! Bodies are JSON by default, while CBOR (`application/cbor`) and MessagePack
+//! (`application/msgpack`) may be selected per request. A request whose `Content-Type` header
+//! names another encoding has its body decoded from that encoding, while a request whose `Accept`
+//! header prefers another encoding receives that encoding in place of JSON within the response
+//! body. Streamed responses, e.g. to `Iter`, are encoded as a sequence of items, one for each
+//! JSON value. Responses that are not JSON, e.g. to `Export` or `Watch`, are unaffected.
+//!
+//! Bodies are converted between encodings at the edge of the `server::Router`, so every endpoint
+//! supports every encoding. As such, keys and values are encoded as arrays of integers, taking one
+//! or two bytes per byte in CBOR and MessagePack rather than up to four in JSON. Byte strings are
+//! also accepted within requests wherever an array of bytes is expected.
+//!
+//! Bincode (`application/x-bincode`) is a fast path for Rust clients. As it is not
+//! self-describing, bincode bodies cannot be converted to or from JSON. Instead, request bodies
+//! are deserialized directly into their request type by `response::concat_and_then`, and the
+//! responses of the `/tree` endpoints handled by `response::response` are serialized directly
+//! into bincode, avoiding JSON entirely. Other endpoints respond in JSON as usual. A client's
+//! requests carry their unserialized body as a `TypedBody` extension so that `encode_request` may
+//! serialize it into bincode. Bincode request bodies are not supported by caching proxies or
+//! federated servers, which must inspect the request.
+//!
+//! JSON with keys and values as base64 strings (`application/x-base64+json`) is handled in the
+//! same manner as bincode, as only the type of a value determines whether a string within is
+//! base64. See the `base64_json` module. As browsers cannot easily set the `Accept` header, a
+//! `bytes=base64` query parameter may be given in its place, in which case any JSON request body
+//! is also expected to contain base64 strings.
+//!
+//! A `Client` may select an encoding for all of its requests via `Client::encoding`.
+
+use base64_json;
+use bincode;
+use futures::{Async, Poll};
+use hyper::{Body, Chunk, Request, Response, StatusCode};
+use hyper::body::Payload;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::rt::{Future, Stream};
+use rmp_serde;
+use serde::{Deserialize, Serialize};
+use serde::de::IgnoredAny;
+use serde_cbor;
+use serde_json;
+use serde_transcode;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// The media type of JSON bodies.
+pub const JSON: &'static str = "application/json";
+
+/// The media type of CBOR bodies.
+pub const CBOR: &'static str = "application/cbor";
+
+/// The media type of MessagePack bodies.
+pub const MSGPACK: &'static str = "application/msgpack";
+
+/// An alternative media type of MessagePack bodies that is also accepted.
+pub const X_MSGPACK: &'static str = "application/x-msgpack";
+
+/// The media type of bincode bodies.
+pub const BINCODE: &'static str = "application/x-bincode";
+
+/// The media type of JSON bodies in which keys and values are base64 strings.
+pub const BASE64_JSON: &'static str = "application/x-base64+json";
+
+/// The query parameter selecting `Encoding::Base64Json` for both the request and response.
+pub const BASE64_QUERY: &'static str = "bytes=base64";
+
+/// An encoding of request and response bodies.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Encoding {
+    Json,
+    Cbor,
+    MessagePack,
+    Bincode,
+    Base64Json,
+}
+
+/// The body of a request prior to its serialization.
+///
+/// Inserted into the extensions of each request produced via `request::IntoRequest`, allowing
+/// `encode_request` to serialize the body into an encoding that cannot be converted from JSON.
+#[derive(Clone)]
+pub struct TypedBody {
+    to_vec: Arc<Fn(Encoding) -> Result<Vec<u8>, Box<StdError + Send + Sync>> + Send + Sync>,
+}
+
+/// Converts each item within a streamed body in the given encoding into JSON.
+///
+/// A chunk may contain any number of items or parts thereof, e.g. once decompressed.
+struct ToJson<S> {
+    body: S,
+    encoding: Encoding,
+    buffer: Vec<u8>,
+}
+
+/// Converts each JSON value within a streamed body into the given encoding.
+///
+/// A JSON value may be split across several chunks.
+struct FromJson<S> {
+    body: S,
+    encoding: Encoding,
+    buffer: Vec<u8>,
+    /// Whether or not the body has ended.
+    ended: bool,
+}
+
+impl Encoding {
+    /// All supported encodings, in order of preference.
+    pub const ALL: &'static [Encoding] = &[
+        Encoding::Json,
+        Encoding::Cbor,
+        Encoding::MessagePack,
+        Encoding::Bincode,
+        Encoding::Base64Json,
+    ];
+
+    /// The media type of bodies in this encoding.
+    pub fn media_type(&self) -> &'static str {
+        match *self {
+            Encoding::Json => JSON,
+            Encoding::Cbor => CBOR,
+            Encoding::MessagePack => MSGPACK,
+            Encoding::Bincode => BINCODE,
+            Encoding::Base64Json => BASE64_JSON,
+        }
+    }
+
+    /// Whether or not bodies in this encoding may be converted to and from JSON without knowing
+    /// the type of the value within.
+    pub fn is_self_describing(&self) -> bool {
+        match *self {
+            Encoding::Bincode | Encoding::Base64Json => false,
+            _ => true,
+        }
+    }
+
+    /// Serialize the given value directly into this encoding.
+    pub fn to_vec<T>(&self, value: &T) -> Result<Vec<u8>, Box<StdError + Send + Sync>>
+    where
+        T: ?Sized + Serialize,
+    {
+        let bytes = match *self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            Encoding::Cbor => {
+                // `serde_cbor::to_vec` requires a sized value.
+                let mut bytes = vec![];
+                value.serialize(&mut serde_cbor::Serializer::new(&mut bytes))?;
+                bytes
+            }
+            Encoding::MessagePack => rmp_serde::to_vec(value)?,
+            Encoding::Bincode => bincode::serialize(value)?,
+            Encoding::Base64Json => base64_json::to_vec(value)?,
+        };
+        Ok(bytes)
+    }
+
+    /// Deserialize a single value of type `T` directly from this encoding.
+    pub fn from_slice<T>(&self, bytes: &[u8]) -> Result<T, Box<StdError + Send + Sync>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let value = match *self {
+            Encoding::Json => serde_json::from_slice(bytes)?,
+            Encoding::Cbor => serde_cbor::from_slice(bytes)?,
+            Encoding::MessagePack => rmp_serde::from_slice(bytes)?,
+            Encoding::Bincode => bincode::deserialize(bytes)?,
+            Encoding::Base64Json => base64_json::from_slice(bytes)?,
+        };
+        Ok(value)
+    }
+
+    /// The encoding with the given media type, ignoring any parameters, e.g. `; charset=utf-8`.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next().unwrap_or("").trim();
+        if essence.eq_ignore_ascii_case(X_MSGPACK) {
+            return Some(Encoding::MessagePack);
+        }
+        Encoding::ALL
+            .iter()
+            .cloned()
+            .find(|encoding| essence.eq_ignore_ascii_case(encoding.media_type()))
+    }
+
+    /// The length of the first value within the given bytes in this self-describing encoding, or
+    /// `None` if the bytes end part way through it.
+    fn value_len(&self, bytes: &[u8]) -> Result<Option<usize>, String> {
+        match *self {
+            Encoding::Json => {
+                let mut values = serde_json::Deserializer::from_slice(bytes)
+                    .into_iter::<IgnoredAny>();
+                match values.next() {
+                    None => Ok(None),
+                    Some(Ok(_)) => Ok(Some(values.byte_offset())),
+                    Some(Err(ref err)) if err.is_eof() => Ok(None),
+                    Some(Err(err)) => Err(format!("failed to decode JSON body: {}", err)),
+                }
+            }
+            Encoding::Cbor => {
+                let mut values = serde_cbor::Deserializer::from_slice(bytes)
+                    .into_iter::<IgnoredAny>();
+                match values.next() {
+                    None => Ok(None),
+                    Some(Ok(_)) => Ok(Some(values.byte_offset())),
+                    Some(Err(ref err)) if err.is_eof() => Ok(None),
+                    Some(Err(err)) => Err(format!("failed to decode CBOR body: {}", err)),
+                }
+            }
+            Encoding::MessagePack => {
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                let mut remaining = bytes;
+                let res = {
+                    let mut de = rmp_serde::Deserializer::new(&mut remaining);
+                    IgnoredAny::deserialize(&mut de)
+                };
+                match res {
+                    Ok(_) => Ok(Some(bytes.len() - remaining.len())),
+                    Err(rmp_serde::decode::Error::InvalidMarkerRead(ref err))
+                    | Err(rmp_serde::decode::Error::InvalidDataRead(ref err))
+                        if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(err) => Err(format!("failed to decode MessagePack body: {}", err)),
+                }
+            }
+            Encoding::Bincode | Encoding::Base64Json => {
+                Err(format!("{} cannot be converted to JSON", self.media_type()))
+            }
+        }
+    }
+
+    /// Convert a single value in this encoding to JSON.
+    pub fn to_json(&self, bytes: &[u8]) -> Result<Vec<u8>, String> {
+        match *self {
+            Encoding::Json => Ok(bytes.to_vec()),
+            Encoding::Cbor => {
+                let mut de = serde_cbor::Deserializer::from_slice(bytes);
+                let mut json = vec![];
+                serde_transcode::transcode(&mut de, &mut serde_json::Serializer::new(&mut json))
+                    .map_err(|err| format!("failed to decode CBOR body: {}", err))?;
+                de.end().map_err(|err| format!("failed to decode CBOR body: {}", err))?;
+                Ok(json)
+            }
+            Encoding::MessagePack => {
+                let mut remaining = bytes;
+                let mut json = vec![];
+                {
+                    let mut de = rmp_serde::Deserializer::new(&mut remaining);
+                    let mut ser = serde_json::Serializer::new(&mut json);
+                    serde_transcode::transcode(&mut de, &mut ser)
+                        .map_err(|err| format!("failed to decode MessagePack body: {}", err))?;
+                }
+                if !remaining.is_empty() {
+                    return Err("failed to decode MessagePack body: trailing bytes".to_string());
+                }
+                Ok(json)
+            }
+            Encoding::Bincode | Encoding::Base64Json => {
+                Err(format!("{} bodies cannot be converted to JSON", self.media_type()))
+            }
+        }
+    }
+
+    /// Convert a single JSON value to this encoding.
+    pub fn from_json(&self, json: &serde_json::Value) -> Result<Vec<u8>, String> {
+        match *self {
+            Encoding::Json => serde_json::to_vec(json).map_err(|err| format!("{}", err)),
+            Encoding::Cbor => serde_cbor::to_vec(json).map_err(|err| format!("{}", err)),
+            Encoding::MessagePack => rmp_serde::to_vec(json).map_err(|err| format!("{}", err)),
+            Encoding::Bincode | Encoding::Base64Json => {
+                Err(format!("JSON cannot be converted to {}", self.media_type()))
+            }
+        }
+    }
+}
+
+/// The encoding of the request's body as specified via its `Content-Type` header.
+///
+/// Produces `None` if the media type is not supported. A missing header implies JSON.
+pub fn content_encoding(headers: &HeaderMap) -> Option<Encoding> {
+    match headers.get(header::CONTENT_TYPE) {
+        None => Some(Encoding::Json),
+        Some(value) => value.to_str().ok().and_then(Encoding::from_media_type),
+    }
+}
+
+/// The encoding of the response body preferred via the request's `Accept` header.
+///
+/// The first supported media type listed is preferred, falling back to JSON. Quality values are
+/// not considered.
+pub fn accepted_encoding(headers: &HeaderMap) -> Encoding {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(Encoding::from_media_type)
+        .next()
+        .unwrap_or(Encoding::Json)
+}
+
+/// Select `Encoding::Base64Json` via the headers of the given request if its query contains the
+/// `BASE64_QUERY` parameter.
+///
+/// The `Accept` header is replaced, as is the `Content-Type` of a JSON body.
+pub fn apply_base64_query(mut request: Request<Body>) -> Request<Body> {
+    let requested = request
+        .uri()
+        .query()
+        .map(|query| query.split('&').any(|param| param == BASE64_QUERY))
+        .unwrap_or(false);
+    if !requested {
+        return request;
+    }
+    let media_type = HeaderValue::from_static(BASE64_JSON);
+    if content_encoding(request.headers()) == Some(Encoding::Json) {
+        request.headers_mut().insert(header::CONTENT_TYPE, media_type.clone());
+    }
+    request.headers_mut().insert(header::ACCEPT, media_type);
+    request
+}
+
+/// Convert the body of the given request to JSON from the encoding given via its `Content-Type`.
+///
+/// Requests whose body could not be decoded receive an empty body, which the endpoint then
+/// rejects as it would any malformed JSON body. Bincode and base64 JSON bodies are left as they
+/// are.
+pub fn decode_request(request: Request<Body>) -> Request<Body> {
+    let encoding = match content_encoding(request.headers()) {
+        Some(Encoding::Json) | None => return request,
+        Some(encoding) if !encoding.is_self_describing() => return request,
+        Some(encoding) => encoding,
+    };
+    let (mut parts, body) = request.into_parts();
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(JSON));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let json = body
+        .concat2()
+        .map(move |chunk| {
+            if chunk.is_empty() {
+                return chunk;
+            }
+            Chunk::from(encoding.to_json(&chunk).unwrap_or_default())
+        })
+        .map_err(|err| -> Box<StdError + Send + Sync> { Box::new(err) })
+        .into_stream();
+    Request::from_parts(parts, Body::from(Box::new(json) as Box<_>))
+}
+
+/// Convert the JSON body of the given response to the given encoding.
+///
+/// Responses with a `Content-Type` other than JSON and protocol upgrades are left as they are, as
+/// are all responses when bincode or base64 JSON is preferred.
+pub fn encode_response(response: Response<Body>, encoding: Encoding) -> Response<Body> {
+    if encoding == Encoding::Json || !encoding.is_self_describing() {
+        return response;
+    }
+    if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+        return response;
+    }
+    if content_encoding(response.headers()) != Some(Encoding::Json) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(encoding.media_type()));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let buffer = vec![];
+    let ended = false;
+    let encoded = FromJson { body, encoding, buffer, ended };
+    Response::from_parts(parts, Body::from(Box::new(encoded) as Box<_>))
+}
+
+/// Convert the JSON body of the given request to the given encoding, preferring a response in
+/// the same encoding via the `Accept` header.
+///
+/// Used by clients. Requests without a body or with a `Content-Type` other than JSON, e.g.
+/// `Import`, only have their `Accept` header set. Bincode and base64 JSON bodies are serialized
+/// from the request's `TypedBody`, and requests without one are left in JSON.
+pub fn encode_request(request: Request<Body>, encoding: Encoding) -> Request<Body> {
+    if encoding == Encoding::Json {
+        return request;
+    }
+    let (mut parts, body) = request.into_parts();
+    parts.headers.insert(header::ACCEPT, HeaderValue::from_static(encoding.media_type()));
+    if body.is_end_stream() || content_encoding(&parts.headers) != Some(Encoding::Json) {
+        return Request::from_parts(parts, body);
+    }
+    if !encoding.is_self_describing() {
+        let typed = parts.extensions.get::<TypedBody>().map(|typed| typed.to_vec(encoding));
+        let bytes = match typed {
+            Some(Ok(bytes)) => bytes,
+            _ => return Request::from_parts(parts, body),
+        };
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(encoding.media_type()));
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Request::from_parts(parts, Body::from(bytes));
+    }
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(encoding.media_type()));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let encoded = body
+        .concat2()
+        .map_err(|err| -> Box<StdError + Send + Sync> { Box::new(err) })
+        .and_then(move |chunk| -> Result<Chunk, Box<StdError + Send + Sync>> {
+            if chunk.is_empty() {
+                return Ok(chunk);
+            }
+            let value = serde_json::from_slice::<serde_json::Value>(&chunk)
+                .map_err(|err| -> Box<StdError + Send + Sync> { Box::new(err) })?;
+            Ok(Chunk::from(encoding.from_json(&value)?))
+        })
+        .into_stream();
+    Request::from_parts(parts, Body::from(Box::new(encoded) as Box<_>))
+}
+
+/// Convert the body of the given response to JSON from the encoding given via its
+/// `Content-Type`.
+///
+/// Used by clients. Responses that are JSON, bincode, base64 JSON or in no known encoding are left
+/// as they are.
+pub fn decode_response(response: Response<Body>) -> Response<Body> {
+    let encoding = match content_encoding(response.headers()) {
+        Some(Encoding::Json) | None => return response,
+        Some(encoding) if !encoding.is_self_describing() => return response,
+        Some(encoding) => encoding,
+    };
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(JSON));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let buffer = vec![];
+    let decoded = ToJson { body, encoding, buffer };
+    Response::from_parts(parts, Body::from(Box::new(decoded) as Box<_>))
+}
+
+impl TypedBody {
+    /// Keep the given request body for serialization via `to_vec`.
+    pub fn new<T>(body: T) -> Self
+    where
+        T: 'static + Serialize + Send + Sync,
+    {
+        let to_vec = Arc::new(move |encoding: Encoding| encoding.to_vec(&body));
+        TypedBody { to_vec }
+    }
+
+    /// Serialize the body into the given encoding.
+    pub fn to_vec(&self, encoding: Encoding) -> Result<Vec<u8>, Box<StdError + Send + Sync>> {
+        (self.to_vec)(encoding)
+    }
+}
+
+impl fmt::Debug for TypedBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedBody").finish()
+    }
+}
+
+impl<S> Stream for ToJson<S>
+where
+    S: Stream<Item = Chunk>,
+    S::Error: 'static + StdError + Send + Sync,
+{
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            // Convert every complete item, leaving the remainder to be completed by the following
+            // chunks.
+            let mut json = vec![];
+            let mut consumed = 0;
+            while let Some(len) = self.encoding.value_len(&self.buffer[consumed..])? {
+                json.extend(self.encoding.to_json(&self.buffer[consumed..consumed + len])?);
+                consumed += len;
+            }
+            self.buffer.drain(..consumed);
+            if !json.is_empty() {
+                return Ok(Async::Ready(Some(Chunk::from(json))));
+            }
+            match self.body.poll() {
+                Err(err) => return Err(Box::new(err)),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => {
+                    if self.buffer.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    let msg = format!("{} body ended within an item", self.encoding.media_type());
+                    return Err(msg.into());
+                }
+                Ok(Async::Ready(Some(chunk))) => self.buffer.extend(chunk),
+            }
+        }
+    }
+}
+
+impl<S> Stream for FromJson<S>
+where
+    S: Stream<Item = Chunk>,
+    S::Error: 'static + StdError + Send + Sync,
+{
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.ended {
+                return Ok(Async::Ready(None));
+            }
+            match self.body.poll() {
+                Err(err) => return Err(Box::new(err)),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => self.ended = true,
+                Ok(Async::Ready(Some(chunk))) => self.buffer.extend(chunk),
+            }
+            let ended = self.ended;
+            let mut encoded = vec![];
+            let consumed = {
+                let mut values = serde_json::Deserializer::from_slice(&self.buffer)
+                    .into_iter::<serde_json::Value>();
+                loop {
+                    let offset = values.byte_offset();
+                    match values.next() {
+                        None => break offset,
+                        Some(Ok(value)) => encoded.extend(self.encoding.from_json(&value)?),
+                        Some(Err(ref err)) if err.is_eof() && !ended => break offset,
+                        Some(Err(err)) => return Err(Box::new(err)),
+                    }
+                }
+            };
+            self.buffer.drain(..consumed);
+            if !encoded.is_empty() {
+                return Ok(Async::Ready(Some(Chunk::from(encoded))));
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Serving a single keyspace spread across several sled-web servers, configured via
+//! `server::ConfigBuilder::shard`.
+//!
+//! Each key belongs to the `Shard` with the longest prefix with which the key begins. Requests
+//! for a single key are forwarded to the server of the shard to which the key belongs, while
+//! `Iter`, `Scan` and `ScanRange` requests are made to every shard, merging the resulting
+//! streams in order of their keys via `client::merge_sorted`. These are deserialized in the same
+//! manner as by the server's other endpoints, e.g. from bincode or from the query of a `GET`.
+
+use client::Key;
+
+/// A range of keys served by another sled-web server.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Shard {
+    /// The keys beginning with this prefix belong to the shard, unless they also begin with the
+    /// longer prefix of another shard. An empty prefix matches every key.
+    pub prefix: Key,
+    /// The base URI of the server storing the shard's entries, e.g. `http://shard-a:3000`.
+    pub uri: String,
+}
+
+/// The index of the shard to which the given key belongs, or `None` if no shard's prefix matches.
+pub fn owner(shards: &[Shard], key: &[u8]) -> Option<usize> {
+    shards
+        .iter()
+        .enumerate()
+        .filter(|&(_, shard)| key.starts_with(&shard.prefix))
+        .max_by_key(|&(_, shard)| shard.prefix.len())
+        .map(|(ix, _)| ix)
+}
@@ -5,37 +5,225 @@
 //! | HTTP Request                      | Description
 //! |-----------------------------------|--------------------------------------
 //! | GET    /tree/entries/get          | Get a `Tree` entry by key.
+//! | GET    /tree/entries/get_many     | Get several `Tree` entries by key.
+//! | GET    /tree/entries/get_prefix   | Get all `Tree` entries with a key prefix as a map.
 //! | DELETE /tree/entries/del          | Delete a `Tree` entry by key.
+//! | DELETE /tree/entries/del_if_equal | Delete a `Tree` entry if it has the expected value.
+//! | DELETE /tree/entries/del_prefix   | Delete all `Tree` entries with the given key prefix.
 //! | POST   /tree/entries/set          | Set a new `Tree` entry by key/value pair.
+//! | POST   /tree/entries/replace      | Set a `Tree` entry, returning the previous value.
+//! | POST   /tree/entries/set_many     | Set several `Tree` entries by key/value pair.
 //! | PUT    `/tree/entries/cas`        | Perform a compare-and-swap.
+//! | PUT    `/tree/entries/batch_cas`  | Perform several independent compare-and-swaps.
 //! | POST   `/tree/entries/merge`      | Merge a value into an entry for a key.
+//! | POST   `/tree/entries/incr`       | Atomically add to an `i64` entry for a key.
+//! | POST   `/tree/entries/get_or_insert` | Get an entry, inserting a default if missing.
+//! | POST   `/tree/entries/pop_min`    | Remove and return the smallest `Tree` entry.
+//! | POST   `/tree/entries/pop_max`    | Remove and return the greatest `Tree` entry.
 //! | POST   `/tree/entries/flush`      | Flush and pending IO.
+//! | POST   `/tree/entries/batch`      | Apply a list of `Set`/`Del` operations in order.
+//! | POST   `/tree/transaction`        | Apply writes only if all conditions on entries hold.
+//! | POST   `/tree/entries/rename`     | Atomically move a `Tree` entry to a new key.
+//! | POST   `/tree/generate_id`        | Generate a unique, monotonically increasing ID.
+//! | GET    `/tree/len`                | Count the number of `Tree` entries.
+//! | GET    `/tree/stats`              | Summary statistics of `Tree` key and value sizes.
+//! | POST   `/tree/clear`              | Remove all `Tree` entries (requires confirmation).
+//! | GET    `/tree/export`             | Stream a consistent binary dump of all entries.
+//! | GET    `/tree/export_since`       | Stream the changes since a sequence number as a dump.
+//! | POST   `/tree/import`             | Load an export stream, merging or replacing entries.
+//! | GET    `/tree/raw/{key}`          | Get the raw bytes of a value by URL-safe base64 key.
+//! | HEAD   `/tree/raw/{key}`          | Check the presence and length of a value.
+//! | PUT    `/tree/raw/{key}`          | Set a value to the raw bytes of the request body.
+//! | GET    `/tree/entries/{key}`      | Get a `Tree` entry by URL-safe base64 key.
+//! | HEAD   `/tree/entries/{key}`      | Check the presence and length of a `Tree` entry.
+//! | PUT    `/tree/entries/{key}`      | Set a `Tree` entry by URL-safe base64 key.
+//! | DELETE `/tree/entries/{key}`      | Delete a `Tree` entry by URL-safe base64 key.
 //! | GET    /tree/entries/iter         | Iterate over all `Tree` entries.
 //! | GET    /tree/entries/scan         | Iterate over all `Tree` entries starting from a key.
 //! | GET    /tree/entries/scan_range   | Iterate over all `Tree` entries within a key range.
+//! | GET    /tree/entries/count_range  | Count the `Tree` entries within a key range.
+//! | GET    /tree/entries/hash_prefix  | Hash the entries under a prefix, grouped by next byte.
+//! | GET    /tree/stats/top_values     | Find the `Tree` entries with the largest values.
+//! | GET    /tree/entries/aggregate    | Sum, min, max or average numeric values in a range.
+//! | GET    /tree/entries/iter_page    | Retrieve a page of `Tree` entries from a cursor.
+//! | GET    /tree/entries/scan_suffix  | Iterate over `Tree` entries whose keys end with a suffix.
+//! | GET    /tree/keys/iter            | Iterate over all `Tree` keys, omitting values.
+//! | GET    /tree/keys/scan            | Iterate over all `Tree` keys starting from a key.
+//! | GET    /tree/entries/matching     | Iterate over `Tree` entries whose keys match a pattern.
+//! | GET    /tree/entries/iter_rev     | Iterate over all `Tree` entries in reverse order.
+//! | GET    /tree/entries/scan_range_rev | Iterate over a key range of `Tree` entries in reverse.
 //! | GET    /tree/entries/max          | Get the greatest `Tree` entry.
 //! | GET    /tree/entries/pred         | Get the `Tree` entry preceding a key.
 //! | GET    /tree/entries/pred_incl    | Get the `Tree` entry preceding or including a key.
 //! | GET    /tree/entries/succ         | Get the `Tree` entry succeeding a key.
 //! | GET    /tree/entries/succ_incl    | Get the `Tree` entry succeeding or including a key.
+//! | GET    /tree/subscribe            | Upgrade to a WebSocket streaming every `Tree` change.
+//! | GET    /tree/watch?prefix=...     | Stream changes under a key prefix as Server-Sent Events.
+//! | GET    /tree/changes?from_seq=... | Query or long-poll the changes from a sequence number.
+//! | POST   /tree/cursors/ack          | Acknowledge changes via a named subscription cursor.
+//! | GET    /tree/cursors/get          | Get the sequence number acknowledged via a cursor.
+//! | DELETE /tree/cursors/remove       | Remove a named subscription cursor.
+//! | GET    /version                   | Get the crate, wire format and `sled` versions.
+//! | GET    /db/info                   | Get the versions, `sled` config and server uptime.
+//! | POST   /admin/views/set           | Define a named view onto the `Tree`.
+//! | DELETE /admin/views/remove        | Remove a named view.
+//! | GET    /admin/views/list          | List all named views.
+//! | POST   /admin/webhooks/set        | Register a named webhook for changes.
+//! | DELETE /admin/webhooks/remove     | Remove a named webhook.
+//! | GET    /admin/webhooks/list       | List all named webhooks.
+//! | POST   /admin/migrations/pull     | Pull a key range from another server in the background.
+//! | GET    /admin/migrations/list     | Get the progress of all range migrations.
+//! | GET    /admin/streams/status      | Get the number of streamed responses in progress.
+//! | GET    /admin/backups/status      | Get the status of the scheduled backups.
+//! | POST   /admin/backups/restore     | Restore the tree to a point in time via the changelog.
+//! | POST   /admin/trees/open          | Open a named tree, creating it if necessary.
+//! | DELETE /admin/trees/drop          | Drop a named tree along with all of its entries.
+//! | GET    /admin/trees/list          | List the names of all open trees.
+//! | POST   /admin/trees/copy          | Copy a key range of entries between trees.
+//! | POST   /admin/trees/move          | Move a key range of entries between trees.
+//!
+//! Any of the `/tree` requests above may also be made via a named view by prefixing the path with
+//! `/views/{name}`, e.g. `/views/public/tree/entries/get`. See `request::View` for details.
+//!
+//! Requests may be made to a named tree by including its name after `/tree`, e.g.
+//! `/tree/users/entries/get`. Named trees are opened via a `backend::Store` given to the
+//! `server::Router`.
+//!
+//! The server may serve any `backend::KvBackend`, e.g. a `sled::Tree` or the in-memory
+//! `backend::MemoryBackend`, via the same API.
+//!
+//! A server may run as a read-only replica of another via `server::ConfigBuilder::replicate_from`,
+//! following the upstream's changes after an initial snapshot while serving reads locally. A
+//! `Client` may spread its reads across replicas via `Client::replicas`.
+//!
+//! A server may also front another as a caching proxy via `server::ConfigBuilder::cache_for`,
+//! serving `Get` requests from a local cache invalidated via the origin's changes while forwarding
+//! all other requests to the origin.
+//!
+//! Redis client libraries may also read and write the tree via a listener speaking a subset of the
+//! Redis protocol, configured via `server::ConfigBuilder::resp_addr`. See the `resp` module.
+//!
+//! A keyspace may be spread across several servers behind a single endpoint by mapping key
+//! prefixes to servers via `server::ConfigBuilder::shard`. See the `federation` module.
+//!
+//! Scheduled backups and client exports may be written to any `backup::BackupTarget`, e.g. a local
+//! directory via `backup::DirTarget` or an object store implemented by the user.
+//!
+//! Every change to the served tree may be forwarded to an external event bus by configuring the
+//! `tree::Tree` with a `tree::ChangeSink` before serving it.
+//!
+//! The API may also be mounted within existing `warp` or `actix-web` servers via the `adapter`
+//! module by enabling the `warp` or `actix-web` features, or within existing `hyper` servers via
+//! the `adapter::service::TreeService`.
+//!
+//! All routes may be served beneath a base path, e.g. `/kv/tree/entries/get`, via
+//! `server::ConfigBuilder::base_path` and `client::Client::base_path`.
+//!
+//! A server may be shut down gracefully via `server::Server::with_graceful_shutdown` or
+//! `server::run_until`, draining in-flight requests and flushing all trees before stopping.
+//!
+//! The server speaks both HTTP/1.1 and HTTP/2, allowing many concurrent requests and long-running
+//! streams to be multiplexed over a single connection. A `Client` speaking HTTP/2 without TLS
+//! (h2c) may be created via `client::Client::http2`.
+//!
+//! Enabling the `tls` feature allows the server to listen over HTTPS directly, given a certificate
+//! chain and private key via `server::ConfigBuilder::tls`. See the `tls` module.
+//!
+//! Clients may also be required to present a certificate signed by a given authority via
+//! `server::ConfigBuilder::tls_client_ca`. Each client's verified certificates are available to
+//! the `server::Authorizer` given via `server::Router::set_authorizer`, allowing for
+//! certificate-based access control.
+//!
+//! Request and response bodies may be encoded as CBOR or MessagePack in place of JSON via the
+//! `Content-Type` and `Accept` headers, which the `Client` selects via `Client::encoding`. Rust
+//! clients may instead select bincode, avoiding JSON entirely on the `/tree` endpoints. Keys and
+//! values may be given as base64 strings within JSON bodies in place of arrays of numbers via the
+//! `application/x-base64+json` media type or the `?bytes=base64` query parameter. See the
+//! `encoding` module.
+//!
+//! Response bodies are compressed with gzip or brotli when requested via the `Accept-Encoding`
+//! header, which the `Client` sets via `Client::compression`. See the `compression` module.
+//!
+//! Clients may pin the wire format revision used for each request via the `request::PROTOCOL`
+//! header, while the server advertises the range of revisions it supports via the
+//! `request::PROTOCOL_SUPPORTED` response header. For example, since revision 7 a failed `Cas`
+//! responds with `409 Conflict` and the current value rather than `200 OK` and an `Err`.
+//!
+//! Since revision 8, error responses carry a `request::ErrorBody` describing the error via a
+//! `request::ErrorCode`, a message, the related key if any and whether the request may be retried.
+//! The `Client` surfaces these as `client::Error::Response`.
+//!
+//! Since revision 9, a `BatchCas` reports any error of each swap as a `request::CasError` and
+//! performs the swaps that follow it, rather than failing the whole request.
 //!
 //! See the `request` module for the expected request types. The server expects the corresponding
-//! request type serialized to JSON within the `Body` of the received `Request`.
+//! request type serialized to JSON within the `Body` of the received `Request`. `GET` requests
+//! may instead give their fields as query parameters, e.g. `/tree/entries/get?key=aGVsbG8`. See
+//! the `query` module.
+//!
+//! `HEAD` requests to `/tree/entries/get`, `/tree/entries/{key}` and `/tree/raw/{key}` cheaply
+//! check the presence of an entry and the length of its value, responding with `404 Not Found` if
+//! there is no entry and otherwise with the length via the `request::VALUE_LENGTH` header, but
+//! without a body.
+//!
+//! Responses to `GET` and `HEAD` requests for an existing entry include an `ETag` hashing its
+//! value. Requests listing that tag via `If-None-Match` receive `304 Not Modified` without a body
+//! while the value is unchanged, saving bandwidth for clients polling hot keys via
+//! `Client::get_cached`.
+//!
+//! Conversely, `Set` and `Del` requests, the `PUT` and `DELETE` of `/tree/entries/{key}` and the
+//! `PUT` of `/tree/raw/{key}` may be made conditional via `If-Match`. The write is then performed
+//! as a compare-and-swap against the value with the listed tag, responding with `412 Precondition
+//! Failed` if the entry has since changed.
+//!
+//! A `Get` of a missing key responds with `200 OK` and `null` by default, or with `404 Not Found`
+//! if requested via the `request::MISSING` header, e.g. by a client configured via
+//! `Client::missing_not_found`.
 //!
 //! See the `response::response` function for the associated responses, their status and layout.
 
 #[macro_use] extern crate serde_derive;
+#[cfg(feature = "actix-web")] extern crate actix_web;
+extern crate base64;
+extern crate bincode;
+extern crate brotli;
+extern crate bytes;
+extern crate flate2;
 extern crate futures;
 extern crate http;
-extern crate serde;
+extern crate regex;
+extern crate rmp_serde;
+#[cfg(feature = "tls")] extern crate rustls;
+#[macro_use] extern crate serde;
+extern crate serde_cbor;
 extern crate serde_json;
+extern crate serde_transcode;
+extern crate sha1;
+extern crate tokio;
+#[cfg(feature = "tls")] extern crate tokio_rustls;
+extern crate tokio_threadpool;
+extern crate tokio_timer;
+extern crate tokio_tungstenite;
 pub extern crate hyper;
 pub extern crate sled_search;
+#[cfg(feature = "warp")] extern crate warp;
 
 pub use client::Client;
 pub use sled_search::sled;
 
+pub mod adapter;
+pub mod backend;
+pub mod backup;
+pub mod base64_json;
 pub mod client;
+pub mod compression;
+pub mod encoding;
+pub mod federation;
+pub mod query;
 pub mod request;
+pub mod resp;
 pub mod response;
 pub mod server;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod tree;
@@ -0,0 +1,266 @@
+//! Deserialization of requests from URI query parameters, e.g.
+//! `/tree/entries/scan?key=aGVsbG8&limit=10`.
+//!
+//! This allows the read endpoints to be requested without a body, making them cacheable,
+//! loggable and usable from a browser's address bar. Each parameter corresponds to the field of
+//! the same name. Keys and other byte arrays are given as base64, either standard or URL-safe and
+//! with or without padding, or as hexadecimal byte pairs if the query also contains
+//! `BYTES_HEX`. Integers, floats and booleans are parsed from their usual textual form, while
+//! unit enum variants are given by name. Nested structures, e.g. a `ValueFilter`, and sequences of
+//! keys are not supported.
+
+use request;
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+
+/// The query parameter specifying that byte arrays are given as hexadecimal byte pairs rather
+/// than as base64.
+pub const BYTES_HEX: &'static str = "bytes=hex";
+
+/// The representation of byte arrays within a query.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+enum Bytes {
+    Base64,
+    Hex,
+}
+
+/// Deserializes a request from the parameters of a query.
+struct QueryDeserializer {
+    params: Vec<(String, String)>,
+    bytes: Bytes,
+}
+
+/// Deserializes a field from the value of a single query parameter.
+struct ParamDeserializer {
+    value: String,
+    bytes: Bytes,
+}
+
+/// Deserialize a `T` from the given URI query, excluding the leading `?`.
+pub fn from_query<T>(query: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let bytes = if query.split('&').any(|param| param == BYTES_HEX) {
+        Bytes::Hex
+    } else {
+        Bytes::Base64
+    };
+    let params = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| -> Result<(String, String), Error> {
+            let mut parts = param.splitn(2, '=');
+            let name = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+            let value = request::percent_decode_key(value)
+                .and_then(|value| String::from_utf8(value).ok())
+                .ok_or_else(|| de::Error::custom(format!("invalid query parameter `{}`", name)))?;
+            Ok((name.to_string(), value))
+        })
+        .collect::<Result<_, Error>>()?;
+    T::deserialize(QueryDeserializer { params, bytes })
+}
+
+/// Decode a byte array given within a query in the given representation.
+fn decode_bytes(value: &str, bytes: Bytes) -> Result<Vec<u8>, Error> {
+    let decoded = match bytes {
+        Bytes::Hex => decode_hex(value),
+        Bytes::Base64 => {
+            // A `+` left unescaped within a query is decoded as a space.
+            let url_safe: String = value
+                .chars()
+                .map(|c| match c {
+                    '+' | ' ' => '-',
+                    '/' => '_',
+                    c => c,
+                })
+                .collect();
+            request::decode_raw_key(&url_safe)
+        }
+    };
+    decoded.ok_or_else(|| {
+        let expected: &de::Expected = match bytes {
+            Bytes::Hex => &"hexadecimal byte pairs",
+            Bytes::Base64 => &"a base64 string",
+        };
+        de::Error::invalid_value(de::Unexpected::Str(value), expected)
+    })
+}
+
+/// Decode a string of hexadecimal byte pairs.
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| value.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+        .collect()
+}
+
+impl<'de> de::Deserializer<'de> for QueryDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let bytes = self.bytes;
+        let params = self.params
+            .into_iter()
+            .map(move |(name, value)| (name, ParamDeserializer { value, bytes }));
+        let mut map: MapDeserializer<_, Error> = MapDeserializer::new(params);
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self.value.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => de::Deserializer::deserialize_any(self, visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ParamDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.value)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let bytes = decode_bytes(&self.value, self.bytes)?;
+        let mut seq: SeqDeserializer<_, Error> = SeqDeserializer::new(bytes.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_byte_buf(self, visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(decode_bytes(&self.value, self.bytes)?)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.value.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        char str string unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ParamDeserializer {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
@@ -1,10 +1,19 @@
 //! Functions to simplify the construction of requests along with request types that can be
 //! serialized to and from the JSON body.
 
+use base64;
+use encoding::TypedBody;
+use http::header;
 use http::uri::PathAndQuery;
-use hyper::{Body, Method, Request, Uri};
-use serde::Serialize;
+use hyper::{Body, Method, Request, StatusCode, Uri};
+use regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+use serde::ser::SerializeMap;
 use serde_json;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
 
 /// Types that represent a request being made to the server.
 pub trait RequestType {
@@ -29,23 +38,275 @@ pub trait IntoRequest: RequestType + IntoBody {
     fn into_request(self, base_uri: Uri) -> Request<Body>;
 }
 
+/// The header with which a client may specify how long it is willing to wait for a response.
+///
+/// The value is a number of milliseconds measured from the moment the server receives the request.
+/// If the deadline passes before the response begins, the server abandons the request and
+/// responds with `504 Gateway Timeout` instead. Streamed bodies still in progress at the deadline,
+/// e.g. of `Iter` or `Watch`, are ended with an error.
+pub const DEADLINE: &'static str = "x-request-deadline";
+
+/// The header with which a client resumes a `Watch` from the sequence number of the last event it
+/// received, as specified for Server-Sent Events.
+pub const LAST_EVENT_ID: &'static str = "last-event-id";
+
+/// The number of milliseconds for which a `ChangesSince` request waits for a change by default.
+///
+/// Comfortably within the idle timeouts of common proxies.
+pub const DEFAULT_CHANGES_TIMEOUT_MS: u64 = 30_000;
+
+/// The bytes with which every `Export` stream begins, identifying the format and its revision.
+pub const EXPORT_MAGIC: &'static [u8] = b"sledweb\x01";
+
+/// The key length with which the end of an `Export` stream is marked.
+///
+/// The marker is followed by the number of exported entries as a big-endian `u64`, allowing a
+/// truncated stream to be detected.
+pub const EXPORT_END: u32 = ::std::u32::MAX;
+
+/// The bytes with which every `ExportSince` stream begins in place of `EXPORT_MAGIC`.
+pub const DELTA_MAGIC: &'static [u8] = b"sleddlt\x01";
+
+/// The value length with which the removal of an entry is marked within an `ExportSince` stream.
+///
+/// The marker is not followed by a value.
+pub const DELTA_REMOVED: u32 = ::std::u32::MAX - 1;
+
+/// The number of times a failed `Webhook` delivery is retried by default.
+pub const DEFAULT_WEBHOOK_MAX_RETRIES: u32 = 5;
+
 /// The vector of bytes used as a key into a `sled::Tree`.
 type Key = Vec<u8>;
 /// The vector of bytes representing a value within a `sled::Tree`.
 type Value = Vec<u8>;
 
+/// The revision of the wire format spoken by this version of the crate.
+///
+/// This is incremented whenever a change is made to the layout of requests or responses that would
+/// cause clients and servers of differing revisions to misunderstand one another.
+///
+/// - `1`: The initial revision.
+/// - `2`: `Iter`, `Scan` and `ScanRange` carry an optional `limit`.
+/// - `3`: `ScanRange` bounds are optional and may be inclusive or exclusive.
+/// - `4`: `Iter` and `Scan` carry an optional value `filter`.
+/// - `5`: `Subscribe`, `Watch` and `ChangesSince` carry an optional event `filter`.
+/// - `6`: `Subscribe` and `Watch` carry an optional `coalesce_ms` window.
+/// - `7`: A failed `Cas` responds with `409 Conflict` and the current value.
+/// - `8`: Error responses carry an `ErrorBody` rather than a `String`.
+/// - `9`: `BatchCas` reports each failed operation as a `CasError` and performs those following.
+pub const WIRE_FORMAT: u32 = 9;
+
+/// The oldest revision of the wire format that this version of the crate is able to speak.
+pub const MIN_WIRE_FORMAT: u32 = 1;
+
+/// The header with which a client pins the revision of the wire format used for a request.
+///
+/// Requests without this header are assumed to use `MIN_WIRE_FORMAT`. Servers respond to requests
+/// pinning an unsupported revision with `400 Bad Request`.
+pub const PROTOCOL: &'static str = "x-sled-web-protocol";
+
+/// The header with which the server advertises the range of supported wire format revisions.
+///
+/// The value is of the form `{min}-{max}`, e.g. `1-3`, and is included with every response.
+pub const PROTOCOL_SUPPORTED: &'static str = "x-sled-web-protocol-supported";
+
+/// The header with which the server reports the length in bytes of a value in response to a
+/// `HEAD` request, e.g. via `head_entry`.
+///
+/// `HEAD` requests are supported by the `Get` endpoint, given its key as a query parameter, and by
+/// the `ENTRIES_PATH` and `RAW_PATH` routes. They respond with `404 Not Found` if there is no
+/// entry, and otherwise with the headers of the equivalent `GET` response, including its
+/// `Content-Length`, but without a body.
+pub const VALUE_LENGTH: &'static str = "x-sled-web-value-length";
+
+/// The header with which a client specifies how the server responds to a `Get` for a missing key.
+///
+/// Given `404`, the server responds with `404 Not Found` and an `ErrorBody` rather than with
+/// `200 OK` and `null`, allowing caches, monitors and other HTTP tooling to distinguish hits from
+/// misses by status alone. Any other value, or none, retains the default.
+pub const MISSING: &'static str = "x-sled-web-missing";
+
+/// The version requirement of the `sled` dependency with which the server was built.
+pub const SLED_VERSION: &'static str = "0.15";
+
+/// The key at which the counter for `GenerateId` requests is stored within the ID store, following
+/// the prefix of any view. See `Tree::with_id_store`.
+///
+/// The counter is a little-endian `i64`. Earlier versions stored it within the tree itself.
+pub const ID_KEY: &'static [u8] = b"__sled_web_next_id";
+
+/// The greatest number of entries within a single `Page`. Greater `page_size`s are clamped to it.
+pub const MAX_PAGE_SIZE: u64 = 10_000;
+
+/// The confirmation that must be given with a `Clear` request.
+pub const CLEAR_CONFIRMATION: &'static str = "clear all entries";
+
+/// The path prefix under which requests may be made via a named view, e.g.
+/// `/views/public/tree/entries/get`.
+pub const VIEWS_PATH: &'static str = "/views/";
+
+/// The path prefix under which requests may be made to a named tree, e.g.
+/// `/tree/users/entries/get`.
+///
+/// Requests to the default tree omit the name, e.g. `/tree/entries/get`.
+pub const TREE_PATH: &'static str = "/tree/";
+
+/// The path prefix of the raw value endpoints, followed by a key encoded via `encode_raw_key`,
+/// e.g. `/tree/raw/aGVsbG8`.
+///
+/// A `GET` responds with the value of the entry as the raw bytes of an `application/octet-stream`
+/// body, or with `404 Not Found` if there is no such entry. A `PUT` sets the value of the entry
+/// to the raw bytes of the request body. This allows for storing blobs via `curl`, browsers and
+/// other languages without the JSON byte array convention.
+pub const RAW_PATH: &'static str = "/tree/raw/";
+
+/// The path prefix of the resource-style entry routes, followed by a key encoded via
+/// `encode_entry_key`, e.g. `/tree/entries/aGVsbG8`.
+///
+/// A `GET` responds with the `Value` of the entry, or with `404 Not Found` if there is no such
+/// entry. A `PUT` sets the value of the entry to the `Value` within the request body, while a
+/// `DELETE` removes the entry, responding as to `Del`. Unlike the endpoints taking the key within
+/// the body, these requests may be cached by HTTP caches and survive proxies that drop the bodies
+/// of `GET` and `DELETE` requests. Paths naming an endpoint, e.g. `/tree/entries/get`, are
+/// handled by that endpoint.
+pub const ENTRIES_PATH: &'static str = "/tree/entries/";
+
+/// A window onto the entries of the `Tree` whose keys begin with `prefix`.
+///
+/// Requests made via a view have the prefix prepended to their keys, while entries produced in
+/// response have the prefix stripped from their keys. If `read_only` is `true`, only `GET`
+/// requests may be made via the view.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct View {
+    pub prefix: Key,
+    pub read_only: bool,
+}
+
+/// A URL to which the server `POST`s each `Event` for a change to the `Tree`.
+///
+/// Only events whose keys begin with `prefix` and whose kind is listed within `kinds` are
+/// delivered. An empty `kinds` list matches all kinds. Failed deliveries are retried with
+/// exponential backoff up to `max_retries` times.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub prefix: Key,
+    #[serde(default)]
+    pub kinds: Vec<EventKind>,
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32,
+}
+
+/// The progress of a migration started via `PullRange`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MigrationStatus {
+    /// The base URI of the server from which entries are pulled.
+    pub source: String,
+    pub start: Key,
+    pub end: Option<Key>,
+    /// The number of entries copied so far.
+    pub copied: u64,
+    /// The key of the last entry copied, if any.
+    pub last_key: Option<Key>,
+    pub state: MigrationState,
+}
+
+/// Whether a migration is running, has completed or has failed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MigrationState {
+    Running,
+    Complete,
+    /// The migration ended early due to the given error. Entries copied before the error remain.
+    Failed { error: String },
+}
+
 /// Get a single entry from the DB, identified by the given unique key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Get {
     pub key: Key,
 }
 
+/// Remove all entries from the `Tree`.
+///
+/// The request is rejected unless `confirm` is equal to `CLEAR_CONFIRMATION`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Clear {
+    pub confirm: String,
+}
+
+/// Generate a unique, monotonically increasing ID.
+///
+/// IDs begin at `0` and are backed by a counter kept outside of the tree's entries, so it is never
+/// iterated, counted, exported or removed. IDs remain monotonic across restarts only if the
+/// server's `Tree` persists the counter via `Tree::with_id_store`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GenerateId;
+
+/// Count the number of entries within the `Tree`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Len;
+
+/// Compute summary statistics over all entries within the `Tree`, responding with `TreeStats`.
+///
+/// The server must iterate over every entry. If `max_age_ms` is `Some`, the server may instead
+/// respond with statistics computed within that many milliseconds of the request.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Stats {
+    #[serde(default)]
+    pub max_age_ms: Option<u64>,
+}
+
+/// Summary statistics over the entries within a `Tree`.
+///
+/// The value sizes are `None` if there are no entries.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct TreeStats {
+    pub entries: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+    pub min_value_size: Option<u64>,
+    pub max_value_size: Option<u64>,
+    pub avg_value_size: Option<f64>,
+}
+
+/// Get the values associated with each of the given keys.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GetMany {
+    pub keys: Vec<Key>,
+}
+
+/// Get all entries whose keys begin with the given prefix within a single `EntryMap`.
+///
+/// Unlike `Scan`, the response is not streamed, so this is best suited to small prefixes.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GetPrefix {
+    pub prefix: Key,
+}
+
+/// A map from keys to values, serialized as a JSON object with each key encoded via `encode_key`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct EntryMap(pub BTreeMap<Key, Value>);
+
 /// Delete the entry at the given key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Del {
     pub key: Key,
 }
 
+/// Delete the entry at the given key only if its current value is equal to `expected`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DelIfEqual {
+    pub key: Key,
+    pub expected: Value,
+}
+
+/// Delete all entries whose keys begin with the given prefix.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DelPrefix {
+    pub prefix: Key,
+}
+
 /// Set the entry with the given key and value, replacing the original if one exists.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Set {
@@ -53,6 +314,21 @@ pub struct Set {
     pub value: Value,
 }
 
+/// The same as `Set`, but responds with the value that was replaced, if any.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Replace {
+    pub key: Key,
+    pub value: Value,
+}
+
+/// Set each of the given entries, replacing the originals where they exist.
+///
+/// Unlike a `Batch`, each entry is set independently and the result of each is reported.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SetMany {
+    pub entries: Vec<(Key, Value)>,
+}
+
 /// Compare and swap. Capable of unique creation, conditional modification, or deletion.
 ///
 /// If old is None, this will only set the value if it doesn't exist yet. If new is None, will
@@ -67,6 +343,29 @@ pub struct Cas {
     pub new: Option<Value>,
 }
 
+/// Perform each of the given compare and swap operations independently within a single request.
+///
+/// The result of each operation is reported in the same order as the operations, as a
+/// `Result<(), CasError>`. An operation that fails for any reason does not prevent those following
+/// it from being performed.
+///
+/// Prior to wire format revision 9, each result is a `Result<(), Option<Value>>` holding the
+/// current value of a failed comparison, and any other error responds with `500 Internal Server
+/// Error` and leaves the remaining operations unperformed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BatchCas {
+    pub ops: Vec<Cas>,
+}
+
+/// The failure of a single operation within a `BatchCas`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CasError {
+    /// The entry holds some other value, given here, or no value if `None`.
+    Conflict(Option<Value>),
+    /// The operation could not be performed, e.g. due to a storage error.
+    Failed(String),
+}
+
 /// Merge a new value into the total state for a key.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Merge {
@@ -74,27 +373,357 @@ pub struct Merge {
     pub value: Value,
 }
 
+/// Atomically add `delta` to the integer value associated with the given key.
+///
+/// The value is interpreted as a little-endian `i64`, where a missing entry is treated as `0`. If
+/// the addition would overflow, the entry is left unchanged and the server responds with
+/// `409 Conflict`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Incr {
+    pub key: Key,
+    pub delta: i64,
+}
+
+/// Atomically retrieve the value associated with the given key, inserting `default` if there is
+/// no entry.
+///
+/// Responds with the existing value or with `default` if it was inserted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GetOrInsert {
+    pub key: Key,
+    pub default: Value,
+}
+
+/// Atomically remove and return the entry with the smallest key.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PopMin;
+
+/// Atomically remove and return the entry with the greatest key.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PopMax;
+
 /// Flushes any pending IO buffers to disk to ensure durability.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Flush;
 
-/// Iterate over all entries within the `Tree`.
+/// Apply a list of operations in order within a single request.
+///
+/// No other mutations are applied to the `Tree` via the server while the batch is applied.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Batch {
+    pub ops: Vec<BatchOp>,
+}
+
+/// A single operation within a `Batch`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BatchOp {
+    Set { key: Key, value: Value },
+    Del { key: Key },
+}
+
+/// Apply the `writes` only if every entry named within `conditions` has its expected value.
+///
+/// The conditions are checked and the writes applied while no other mutations are applied to the
+/// `Tree` via the server. Should a write fail, the writes preceding it are undone by restoring the
+/// previous values of their entries, so either all writes commit or none do. See
+/// `Tree::transaction`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Transaction {
+    pub conditions: Vec<Condition>,
+    pub writes: Vec<BatchOp>,
+}
+
+/// Requires that the entry at `key` has the value `expected`, where `None` requires that there
+/// is no entry.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Condition {
+    pub key: Key,
+    pub expected: Option<Value>,
+}
+
+/// The outcome of a `Transaction`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TransactionOutcome {
+    /// All conditions held and all writes were applied.
+    Committed,
+    /// At least one condition failed and no writes were applied.
+    ///
+    /// Contains the current value of each entry whose condition failed.
+    Aborted { conflicts: Vec<(Key, Option<Value>)> },
+}
+
+/// Move the value at `from` to the key `to`, removing the entry at `from`.
+///
+/// Unless `overwrite` is `true`, the rename is refused if an entry already exists at `to`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: Key,
+    pub to: Key,
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// The outcome of a `Rename`.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
-pub struct Iter;
+pub enum RenameOutcome {
+    /// The value was moved to the destination key.
+    Renamed,
+    /// There was no entry at the source key, so nothing was changed.
+    SourceMissing,
+    /// An entry already exists at the destination key and `overwrite` was `false`, so nothing
+    /// was changed.
+    DestinationExists,
+}
+
+/// Iterate over all entries within the `Tree`.
+///
+/// If `filter` is `Some`, only entries whose value matches the filter are streamed back. If
+/// `limit` is `Some`, the stream ends after at most `limit` matching entries.
+///
+/// Deserializes from `null` for compatibility with the first revision of the wire format, though
+/// only in human-readable encodings such as JSON.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
+pub struct Iter {
+    pub limit: Option<u64>,
+    pub filter: Option<ValueFilter>,
+}
 
 /// Iterate over all entries within the `Tree` that start at or follow the given key.
+///
+/// If `filter` is `Some`, only entries whose value matches the filter are streamed back. If
+/// `limit` is `Some`, the stream ends after at most `limit` matching entries.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Scan {
     pub key: Key,
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub filter: Option<ValueFilter>,
+}
+
+/// A declarative predicate on the value of an entry, evaluated by the server.
+///
+/// A value matches only if it satisfies every field that is `Some`. The default filter matches
+/// every value.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ValueFilter {
+    /// The value must start with these bytes.
+    #[serde(default)]
+    pub prefix: Option<Value>,
+    /// The value must be exactly these bytes.
+    #[serde(default)]
+    pub equals: Option<Value>,
+    /// The value must be at least this many bytes long.
+    #[serde(default)]
+    pub min_len: Option<u64>,
+    /// The value must be at most this many bytes long.
+    #[serde(default)]
+    pub max_len: Option<u64>,
 }
 
 /// Iterate over all entries within the `Tree` within the given key range.
 ///
-/// The given range is non-inclusive of the `end` key.
+/// A `start` or `end` of `None` leaves that side of the range unbounded. By default the range is
+/// inclusive of the `start` key and non-inclusive of the `end` key, as with `start..end`. If
+/// `limit` is `Some`, the stream ends after at most `limit` entries.
+///
+/// The omitted fields default such that a request from an earlier revision of the wire format
+/// describes the same half-open range.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ScanRange {
+    pub start: Option<Key>,
+    pub end: Option<Key>,
+    #[serde(default = "default_start_inclusive")]
+    pub start_inclusive: bool,
+    #[serde(default)]
+    pub end_inclusive: bool,
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// Count the entries within the `Tree` within the given key range.
+///
+/// The given range is non-inclusive of the `end` key.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CountRange {
+    pub start: Key,
+    pub end: Key,
+}
+
+/// Hash the entries whose keys begin with `prefix`, grouped by the byte following the prefix.
+///
+/// Produces a `PrefixHash` for each non-empty group in ascending order, allowing two servers to
+/// be compared one level of the key space at a time, descending only into the groups that differ.
+/// The entry whose key is the `prefix` itself belongs to no group.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct HashPrefix {
+    pub prefix: Key,
+}
+
+/// The hash of the entries within a group produced by `HashPrefix`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PrefixHash {
+    /// The prefix shared by all entries of the group, i.e. the requested prefix and one byte.
+    pub prefix: Key,
+    /// The number of entries within the group.
+    pub count: u64,
+    /// The hexadecimal SHA-1 of the entries in ascending order of their keys, each encoded via
+    /// `encode_export_entry`.
+    pub hash: String,
+}
+
+/// Find the `count` entries within the `Tree` with the largest values.
+///
+/// Produces the key and value length of each entry in descending order of value length.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TopValues {
+    pub count: u64,
+}
+
+/// Aggregate the numeric values of all entries within the key range `start..end` via `op`.
+///
+/// Each value must be an 8-byte little-endian number of the given `value_type`. Responds with an
+/// `AggregateResult`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub start: Key,
+    pub end: Key,
+    pub op: AggregateOp,
+    pub value_type: ValueType,
+}
+
+/// The operation performed by an `Aggregate` request.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AggregateOp {
+    Sum,
+    Min,
+    Max,
+    /// Always produces an `F64`.
+    Avg,
+}
+
+/// The interpretation of values aggregated via an `Aggregate` request.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ValueType {
+    /// A little-endian `u64`.
+    U64,
+    /// A little-endian `f64`.
+    F64,
+}
+
+/// A number produced by an `Aggregate` request.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum Number {
+    U64(u64),
+    F64(f64),
+}
+
+/// The response to an `Aggregate` request.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct AggregateResult {
+    /// The number of entries aggregated.
+    pub count: u64,
+    /// The result of the operation, or `None` if there were no entries within the range.
+    pub value: Option<Number>,
+}
+
+/// Retrieve a single page of at most `page_size` entries in order of their keys.
+///
+/// The `page_size` must be non-zero and is clamped to `MAX_PAGE_SIZE`. The first page is retrieved
+/// with a `cursor` of `None`. Each following page is retrieved using the `next_cursor` of the
+/// preceding `Page`. Cursors are opaque to clients and remain valid across server restarts, as they
+/// only describe the position within the tree.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct IterPage {
+    pub cursor: Option<String>,
+    pub page_size: u64,
+}
+
+/// The response to an `IterPage` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Page {
+    pub entries: Vec<(Key, Value)>,
+    /// The cursor from which the following page may be retrieved, or `None` if this is the last.
+    pub next_cursor: Option<String>,
+}
+
+/// Iterate over all entries within the `Tree` whose keys end with the given suffix.
+///
+/// Requires that the server maintains a suffix index for the `Tree`. Entries are produced in order
+/// of their reversed keys. If `limit` is `Some`, the stream ends after at most `limit` entries.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScanSuffix {
+    pub suffix: Key,
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// Iterate over the keys of all entries within the `Tree`, omitting their values.
+///
+/// If `limit` is `Some`, the stream ends after at most `limit` keys.
+///
+/// Deserializes from `null` for compatibility with revisions of the wire format prior to the
+/// `limit`, though only in human-readable encodings such as JSON.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
+pub struct IterKeys {
+    pub limit: Option<u64>,
+}
+
+/// Iterate over the keys of all entries that start at or follow the given key, omitting their
+/// values.
+///
+/// If `limit` is `Some`, the stream ends after at most `limit` keys.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScanKeys {
+    pub key: Key,
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// Iterate over all entries within the `Tree` whose keys match the given pattern.
+///
+/// Patterns are matched against the UTF-8 interpretation of each key, and keys that are not valid
+/// UTF-8 never match. If `limit` is `Some`, the stream ends after at most `limit` entries.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScanMatching {
+    pub pattern: KeyPattern,
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// A pattern with which keys are matched by a `ScanMatching` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum KeyPattern {
+    /// A glob where `*` matches any sequence of characters and `?` matches any single character.
+    Glob(String),
+    /// A regular expression that must match the whole key.
+    Regex(String),
+}
+
+/// Iterate over all entries within the `Tree` in descending order of their keys.
+///
+/// If `limit` is `Some`, the stream ends after at most `limit` entries, i.e. the last `limit`
+/// entries of the `Tree`.
+///
+/// Deserializes from `null` for compatibility with revisions of the wire format prior to the
+/// `limit`, though only in human-readable encodings such as JSON.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
+pub struct IterRev {
+    pub limit: Option<u64>,
+}
+
+/// Iterate over all entries within the `Tree` within the given key range in descending order of
+/// their keys.
+///
+/// The given range is non-inclusive of the `end` key. The entries are produced starting from the
+/// `end` of the range, making this an efficient way of retrieving the last entries of a range.
+/// If `limit` is `Some`, the stream ends after at most `limit` entries.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScanRangeRev {
     pub start: Key,
     pub end: Key,
+    #[serde(default)]
+    pub limit: Option<u64>,
 }
 
 /// Retrieve the entry with the greatest `Key` in the `Tree`.
@@ -125,174 +754,1897 @@ pub struct SuccIncl {
     pub key: Key,
 }
 
-impl RequestType for Get {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/get";
+/// Upgrade the connection to a WebSocket over which every subsequent change to the `Tree` is
+/// pushed as an `Event`.
+///
+/// Unlike other requests, `Subscribe` is sent with an empty body along with the headers of a
+/// WebSocket handshake. See the `subscribe` function. Each `Event` is sent as a JSON text
+/// message. The subscription ends when either side closes the WebSocket.
+///
+/// Only changes to the entries beginning with `prefix` that satisfy the `filter` are sent. These
+/// and the `coalesce_ms` window are given as query parameters in the same manner as for `Watch`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Subscribe {
+    #[serde(default)]
+    pub prefix: Key,
+    #[serde(default)]
+    pub filter: EventFilter,
+    #[serde(default)]
+    pub coalesce_ms: Option<u64>,
 }
 
-impl RequestType for Del {
-    const METHOD: Method = Method::DELETE;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/delete";
+/// Stream every subsequent change to the entries whose keys begin with `prefix` as
+/// Server-Sent Events.
+///
+/// Unlike other requests, `Watch` has no body. The `prefix` is instead given as a percent-encoded
+/// query parameter, e.g. `/tree/watch?prefix=users%2F`, allowing the stream to be consumed via a
+/// browser `EventSource` or `curl`. See the `watch` function.
+///
+/// Each change is sent as an event whose `id` is its sequence number and whose `data` is the
+/// `Event` serialized to JSON. Upon reconnecting with a `Last-Event-ID` header, any changes since
+/// that event that are still retained by the server are sent first.
+///
+/// If a `cursor` name is given, e.g. `/tree/watch?prefix=users%2F&cursor=mailer`, and there is no
+/// `Last-Event-ID` header, the stream begins with any retained changes following the sequence
+/// number last acknowledged via `AckCursor`. A new cursor begins at the most recent change.
+///
+/// The `filter` is given via the `start`, `end` and `kinds` query parameters, see
+/// `EventFilter::to_query`.
+///
+/// If `coalesce_ms` is given, e.g. `/tree/watch?coalesce_ms=250`, changes to the same key made
+/// within that many milliseconds of the first are coalesced into a single event describing the
+/// entry's value before the first change and after the last. Events are still sent in order of
+/// their sequence numbers, so each may be delayed by up to roughly twice the window.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Watch {
+    pub prefix: Key,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub filter: EventFilter,
+    #[serde(default)]
+    pub coalesce_ms: Option<u64>,
 }
 
-impl RequestType for Set {
-    const METHOD: Method = Method::POST;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/set";
+/// Conditions evaluated by the server to decide which changes are sent to a subscriber, in
+/// addition to the subscription's key prefix.
+///
+/// A change is sent only if its key is within `start..end` and its kind is listed within `kinds`.
+/// A missing bound leaves the range unbounded on that side, while an empty `kinds` list matches
+/// all kinds. Keys are compared after any view prefix has been stripped.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// The inclusive lower bound of keys.
+    #[serde(default)]
+    pub start: Option<Key>,
+    /// The exclusive upper bound of keys.
+    #[serde(default)]
+    pub end: Option<Key>,
+    #[serde(default)]
+    pub kinds: Vec<EventKind>,
 }
 
-impl RequestType for Cas {
-    const METHOD: Method = Method::PUT;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/cas";
+/// Acknowledge every change up to and including `seq` via the named subscription cursor.
+///
+/// Cursor names follow the same rules as view names. Cursors never move backwards.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct AckCursor {
+    pub name: String,
+    pub seq: u64,
 }
 
-impl RequestType for Merge {
-    const METHOD: Method = Method::POST;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/merge";
+/// Retrieve the sequence number last acknowledged via the named subscription cursor.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GetCursor {
+    pub name: String,
 }
 
-impl RequestType for Flush {
-    const METHOD: Method = Method::PUT;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/flush";
+/// Remove the named subscription cursor.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RemoveCursor {
+    pub name: String,
+}
+
+/// Retrieve the changes to the entries beginning with `prefix` that follow the sequence number
+/// `since`, waiting up to `timeout_ms` milliseconds for a change if there are none yet.
+///
+/// This allows for following changes via repeated requests where WebSockets are unavailable. Each
+/// request should pass the `seq` of the previous `ChangeBatch` as `since`. Only changes retained
+/// by the server, either in memory or within a persistent changelog, may be retrieved once they
+/// have passed. If `limit` is `Some`, at most `limit` changes are returned.
+///
+/// The request may also be made without a body via query parameters, e.g.
+/// `/tree/changes?from_seq=42`, where `from_seq` is `since + 1`. Only changes satisfying the
+/// `filter` are returned.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChangesSince {
+    pub since: u64,
+    #[serde(default)]
+    pub prefix: Key,
+    #[serde(default = "default_changes_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub limit: Option<u64>,
+    #[serde(default)]
+    pub filter: EventFilter,
+}
+
+/// The response to a `ChangesSince` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ChangeBatch {
+    /// The changes following `since` in order of their sequence numbers.
+    pub events: Vec<Event>,
+    /// The sequence number to pass as `since` when requesting the following changes.
+    pub seq: u64,
+    /// Whether or not `events` contains every change between `since` and `seq`.
+    ///
+    /// `false` if some of the changes are no longer retained by the server, or if the server has
+    /// restarted since `since` was produced. Either way, the client should re-read any state
+    /// derived from the changes.
+    pub complete: bool,
+}
+
+/// A change to an entry of the `Tree`, pushed to subscribers via `Subscribe` or `Watch`.
+///
+/// Events are delivered in the order in which their changes were applied, so replaying them in
+/// order of `seq` reproduces the final value of every entry.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Event {
+    /// The sequence number of the change, incremented with each change made to the `Tree`.
+    pub seq: u64,
+    pub kind: EventKind,
+    pub key: Key,
+    /// The value of the entry before the change, or `None` if there was no entry.
+    pub old: Option<Value>,
+    /// The value of the entry after the change, or `None` if the entry was removed.
+    pub new: Option<Value>,
+    /// The number of milliseconds since the UNIX epoch at which the change was made, or `0` if
+    /// the change was recorded by a version of the server that did not record the time.
+    #[serde(default)]
+    pub time_ms: u64,
+}
+
+/// The kind of change described by an `Event`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventKind {
+    /// The entry was set, either directly or via a compare-and-swap.
+    Set,
+    /// The entry was removed.
+    Del,
+    /// A value was merged into the entry.
+    Merge,
+}
+
+/// Stream a dump of all entries in the `Tree` in a compact binary format.
+///
+/// The stream begins with `EXPORT_MAGIC` followed by the sequence number of the most recent
+/// change at the moment the dump began as a big-endian `u64`. Mutations are not excluded while
+/// the dump is streamed, so the dump reflects every change up to that sequence number and possibly
+/// some that follow it. Replaying the changes following the sequence number, e.g. via
+/// `ChangesSince`, onto the dump reproduces the `Tree`. Each entry follows in ascending order
+/// of its key as the key length as a big-endian `u32`, the key, the value length as a big-endian
+/// `u32` and the value. The stream ends with `EXPORT_END` in place of a key length followed by the
+/// number of entries as a big-endian `u64`. See `encode_export_header`, `encode_export_entry` and
+/// `encode_export_end`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Export;
+
+/// Stream the net effect of all changes following the sequence number `since` in the format of
+/// an `Export`, allowing for incremental backups.
+///
+/// The stream begins with `DELTA_MAGIC` followed by `since` and the sequence number of the most
+/// recent change reflected within the stream, each as a big-endian `u64`. Each changed entry
+/// follows as within an `Export`, except that a removed entry has `DELTA_REMOVED` in place of its
+/// value length and no value. See `encode_delta_header` and `encode_delta_removed`.
+///
+/// Applying the stream via `Import` to a restored `Export` whose sequence number is `since`
+/// reproduces the entries. The server responds with `410 Gone` if some of the changes are no
+/// longer retained, in which case a full `Export` is required.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ExportSince {
+    pub since: u64,
+}
+
+/// Load the entries of an `Export` stream into the `Tree`, or apply an `ExportSince` stream.
+///
+/// Unlike other requests, the body of an `Import` is the binary `Export` stream itself, while the
+/// `mode` is given as a query parameter, e.g. `/tree/import?mode=replace`. See the `import`
+/// function. Entries are applied as they are received, so an import that fails part way through
+/// leaves all preceding entries applied. The response is the number of entries imported. An
+/// `ExportSince` stream may only be applied via `ImportMode::Merge`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Import {
+    pub mode: ImportMode,
+}
+
+/// How the entries of an `Import` are combined with the existing entries of the `Tree`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ImportMode {
+    /// Set each imported entry, retaining existing entries with other keys.
+    Merge,
+    /// Remove all existing entries once the stream's header is received, then set each imported
+    /// entry.
+    Replace,
+}
+
+/// An item decoded from an `Export` stream by an `ExportDecoder`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub enum ExportItem {
+    /// The beginning of the stream along with the sequence number of the most recent change
+    /// reflected within the entries.
+    Header { seq: u64 },
+    /// The beginning of an `ExportSince` stream along with the sequence numbers between which the
+    /// changes are reflected.
+    DeltaHeader { since: u64, seq: u64 },
+    Entry(Key, Value),
+    /// The removal of the entry with the given key within an `ExportSince` stream.
+    Removed(Key),
+    /// The end of the stream along with the number of entries it contained.
+    End { count: u64 },
+}
+
+/// Incrementally decodes an `Export` or `ExportSince` stream from chunks of bytes as they are
+/// received.
+#[derive(Clone, Debug, Default)]
+pub struct ExportDecoder {
+    buffer: Vec<u8>,
+    /// The position within `buffer` of the first undecoded byte.
+    pos: usize,
+    header_decoded: bool,
+    /// Whether or not the stream is an `ExportSince` stream.
+    delta: bool,
+    end_decoded: bool,
+    entries: u64,
+}
+
+/// Retrieve the versions of the crate, wire format and `sled` with which the server was built.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Version;
+
+/// The response to a `Version` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// The version of the `sled-web` crate.
+    pub crate_version: String,
+    /// The latest supported revision of the wire format. See `WIRE_FORMAT`.
+    pub wire_format: u32,
+    /// The oldest supported revision of the wire format. See `MIN_WIRE_FORMAT`.
+    #[serde(default = "default_min_wire_format")]
+    pub min_wire_format: u32,
+    /// The version requirement of the `sled` dependency. See `SLED_VERSION`.
+    pub sled_version: String,
+}
+
+/// The kind of error described by an `ErrorBody`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request was malformed or cannot be satisfied as described.
+    BadRequest,
+    /// The request was refused, e.g. a write via a read-only view or to a replica.
+    Forbidden,
+    /// The requested resource does not exist.
+    NotFound,
+    /// The entry holds some other value than expected.
+    Conflict,
+    /// The requested changes are no longer retained.
+    Gone,
+    /// The entry does not match the request's `If-Match` header.
+    PreconditionFailed,
+    /// The request's body is larger than the server permits.
+    PayloadTooLarge,
+    /// The request's deadline passed before it could be processed. See `DEADLINE`.
+    DeadlineExceeded,
+    /// The server is handling as many concurrent requests or streams as permitted.
+    TooManyRequests,
+    /// A server on which the request depends could not be reached.
+    Unavailable,
+    /// The server does not support the request, e.g. named trees without a `Store`.
+    NotImplemented,
+    /// The database failed to perform the operation.
+    Database,
+    /// Any other error.
+    Internal,
+}
+
+/// The body of every error response since wire format revision 8.
+///
+/// Earlier revisions respond with the `message` alone as a `String`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ErrorBody {
+    /// The kind of error, allowing callers to branch on it programmatically.
+    pub code: ErrorCode,
+    /// A human readable description of the error.
+    pub message: String,
+    /// The key of the entry to which the error relates, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Key>,
+    /// Whether the same request may succeed if retried later.
+    pub retryable: bool,
+}
+
+/// Retrieve the versions, `sled` configuration and uptime of the server.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Info;
+
+/// The response to an `Info` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DbInfo {
+    /// The versions with which the server was built.
+    pub version: VersionInfo,
+    /// The `sled` configuration in effect, or `None` if the server was not given one.
+    pub sled_config: Option<SledConfig>,
+    /// The number of milliseconds for which the server has been running.
+    pub uptime_ms: u64,
+}
+
+/// The subset of a `sled` configuration reported via `DbInfo`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SledConfig {
+    /// The path at which the database is stored.
+    pub path: String,
+    /// The maximum size of the page cache in bytes.
+    pub cache_capacity: u64,
+    /// The size of each log segment in bytes.
+    pub segment_size: u64,
+    /// The interval between flushes in milliseconds, or `None` if flushes are manual.
+    pub flush_every_ms: Option<u64>,
+}
+
+/// Define a view with the given name, replacing any existing view with the same name.
+///
+/// Names may only contain ASCII alphanumeric characters, `-` and `_`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SetView {
+    pub name: String,
+    pub view: View,
+}
+
+/// Remove the view with the given name.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RemoveView {
+    pub name: String,
+}
+
+/// Retrieve all views along with their names.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Views;
+
+/// Register a webhook with the given name, replacing any existing webhook with the same name.
+///
+/// Names follow the same rules as view names.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SetWebhook {
+    pub name: String,
+    pub webhook: Webhook,
+}
+
+/// Remove the webhook with the given name.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RemoveWebhook {
+    pub name: String,
+}
+
+/// Retrieve all webhooks along with their names.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Webhooks;
+
+/// Retrieve the status of the server's scheduled backups. See `server::ConfigBuilder::backup`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Backups;
+
+/// The response to a `Backups` request.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BackupStatus {
+    /// The file names of the retained backups, oldest first.
+    pub backups: Vec<String>,
+    /// The sequence number of the most recent change reflected within the last backup taken since
+    /// the server started, if any.
+    pub last_seq: Option<u64>,
+    /// The number of milliseconds since the UNIX epoch at which the last successful backup since
+    /// the server started was begun, if any.
+    pub last_backup_ms: Option<u64>,
+    /// The error with which the most recent backup failed, or `None` if it succeeded.
+    pub last_error: Option<String>,
+}
+
+/// Restore the `Tree` to its state at the given point in time from the scheduled backups and the
+/// `Tree`'s changelog.
+///
+/// The most recent backup preceding the point is loaded and the changes that followed it up to
+/// and including the point are replayed, after which all entries of the `Tree` are replaced with
+/// the result. A point preceding all backups is replayed from an empty `Tree`. The restore itself
+/// is recorded as a series of new changes, so it may in turn be undone by restoring to an earlier
+/// point.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Restore {
+    pub to: RestorePoint,
+}
+
+/// The point in time to which a `Restore` request restores the `Tree`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RestorePoint {
+    /// The state following the change with the given sequence number.
+    Seq(u64),
+    /// The state at the given number of milliseconds since the UNIX epoch.
+    ///
+    /// Backups are chosen by the moment at which they were begun, while changes are chosen by
+    /// the moment at which they were made.
+    TimeMs(u64),
+}
+
+/// The response to a `Restore` request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Restored {
+    /// The name of the backup from which the `Tree` was restored, or `None` if the changes were
+    /// replayed from an empty `Tree`.
+    pub backup: Option<String>,
+    /// The sequence number of the last change reflected within the restored state.
+    pub seq: u64,
+    /// The number of changes replayed following the backup.
+    pub replayed: u64,
+    /// The number of entries within the restored `Tree`.
+    pub entries: u64,
+}
+
+/// Start copying the entries within `start..end` from the sled-web server at the `source` base
+/// URI into the `Tree`, e.g. in order to reshard a tree across servers.
+///
+/// The entries are copied in the background a page at a time, setting each entry within the
+/// `Tree`. Progress is reported via `Migrations` under the given `name`, which follows the same
+/// rules as view names. A migration may not replace a running migration of the same name. The
+/// source is not modified, so the entries may be removed from it via `DelPrefix` or `Batch` once
+/// the migration is complete.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PullRange {
+    pub name: String,
+    pub source: String,
+    #[serde(default)]
+    pub start: Key,
+    #[serde(default)]
+    pub end: Option<Key>,
+}
+
+/// Retrieve the status of all migrations started via `PullRange` along with their names.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Migrations;
+
+/// Retrieve the number of streamed responses in progress, e.g. to `Iter` or `Scan`, along with
+/// the maximum permitted. See `server::ConfigBuilder::max_concurrent_streams`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Streams;
+
+/// The response to a `Streams` request.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StreamStatus {
+    /// The number of streamed responses currently in progress.
+    pub in_progress: u64,
+    /// The greatest number of streamed responses permitted at once, or `None` if unlimited.
+    pub max: Option<u64>,
+}
+
+/// Open the tree with the given name, creating it if it does not yet exist.
+///
+/// Names follow the same rules as view names, though `entries`, `keys`, `stats` and `cursors`
+/// are reserved.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OpenTree {
+    pub name: String,
+}
+
+/// Close the tree with the given name and permanently remove all of its entries.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DropTree {
+    pub name: String,
+}
+
+/// Retrieve the names of all open trees.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TreeNames;
+
+/// Copy the entries within the key range `start..end` from one tree to another.
+///
+/// A tree name of `None` refers to the default tree, while an `end` of `None` leaves the range
+/// unbounded. All other mutations via both trees are excluded during the copy. Responds with the
+/// number of entries copied.
+///
+/// The copy is not atomic. Entries are copied one at a time in order of their keys, so if the copy
+/// fails part way through, the `ErrorBody` message states how many entries were copied and its
+/// `key` is the key from which the copy may be resumed by a subsequent request with that `start`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CopyEntries {
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub start: Key,
+    pub end: Option<Key>,
+}
+
+/// The same as `CopyEntries`, but the entries are removed from the source tree once copied.
+///
+/// Entries are written to the destination before being removed from the source, so a failure part
+/// way through may leave the entry at the `ErrorBody` `key` within both trees but never within
+/// neither. Resuming the move from that key completes it.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MoveEntries {
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub start: Key,
+    pub end: Option<Key>,
+}
+
+impl RequestType for Get {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/get";
+}
+
+impl RequestType for GetMany {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/get_many";
+}
+
+impl RequestType for GetPrefix {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/get_prefix";
+}
+
+impl RequestType for Del {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/delete";
+}
+
+impl RequestType for DelIfEqual {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/del_if_equal";
+}
+
+impl RequestType for DelPrefix {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/del_prefix";
+}
+
+impl RequestType for Set {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/set";
+}
+
+impl RequestType for Replace {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/replace";
+}
+
+impl RequestType for SetMany {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/set_many";
+}
+
+impl RequestType for Cas {
+    const METHOD: Method = Method::PUT;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/cas";
+}
+
+impl RequestType for BatchCas {
+    const METHOD: Method = Method::PUT;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/batch_cas";
+}
+
+impl RequestType for Merge {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/merge";
+}
+
+impl RequestType for Incr {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/incr";
+}
+
+impl RequestType for GetOrInsert {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/get_or_insert";
+}
+
+impl RequestType for PopMin {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/pop_min";
+}
+
+impl RequestType for PopMax {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/pop_max";
+}
+
+impl RequestType for Flush {
+    const METHOD: Method = Method::PUT;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/flush";
+}
+
+impl RequestType for Batch {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/batch";
+}
+
+impl RequestType for GenerateId {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/generate_id";
+}
+
+impl RequestType for Len {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/len";
+}
+
+impl RequestType for Stats {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/stats";
+}
+
+impl RequestType for Export {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/export";
+}
+
+impl RequestType for ExportSince {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/export_since";
+}
+
+impl RequestType for Import {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/import";
+}
+
+impl RequestType for Clear {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/clear";
+}
+
+impl RequestType for Transaction {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/transaction";
+}
+
+impl RequestType for Rename {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/rename";
+}
+
+impl RequestType for Iter {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/iter";
+}
+
+impl RequestType for Scan {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/scan";
+}
+
+impl RequestType for ScanRange {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/scan_range";
+}
+
+impl RequestType for HashPrefix {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/hash_prefix";
+}
+
+impl RequestType for CountRange {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/count_range";
+}
+
+impl RequestType for TopValues {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/stats/top_values";
+}
+
+impl RequestType for Aggregate {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/aggregate";
+}
+
+impl RequestType for IterPage {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/iter_page";
+}
+
+impl RequestType for ScanSuffix {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/scan_suffix";
+}
+
+impl RequestType for IterKeys {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/keys/iter";
+}
+
+impl RequestType for ScanKeys {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/keys/scan";
+}
+
+impl RequestType for ScanMatching {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/matching";
+}
+
+impl RequestType for IterRev {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/iter_rev";
+}
+
+impl RequestType for ScanRangeRev {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/scan_range_rev";
+}
+
+impl RequestType for Max {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/max";
+}
+
+impl RequestType for Pred {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/pred";
+}
+
+impl RequestType for PredIncl {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/pred_incl";
+}
+
+impl RequestType for Succ {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/succ";
+}
+
+impl RequestType for SuccIncl {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/entries/succ_incl";
+}
+
+impl RequestType for Subscribe {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/subscribe";
+}
+
+impl RequestType for Watch {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/watch";
+}
+
+impl RequestType for ChangesSince {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/changes";
+}
+
+impl RequestType for AckCursor {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/tree/cursors/ack";
+}
+
+impl RequestType for GetCursor {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/tree/cursors/get";
+}
+
+impl RequestType for RemoveCursor {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/tree/cursors/remove";
+}
+
+impl RequestType for Version {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/version";
+}
+
+impl RequestType for Info {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/db/info";
+}
+
+impl RequestType for SetView {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/views/set";
+}
+
+impl RequestType for RemoveView {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/admin/views/remove";
+}
+
+impl RequestType for Views {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/views/list";
+}
+
+impl RequestType for SetWebhook {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/webhooks/set";
+}
+
+impl RequestType for RemoveWebhook {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/admin/webhooks/remove";
+}
+
+impl RequestType for Webhooks {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/webhooks/list";
+}
+
+impl RequestType for Backups {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/backups/status";
+}
+
+impl RequestType for Restore {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/backups/restore";
+}
+
+impl RequestType for PullRange {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/migrations/pull";
+}
+
+impl RequestType for Migrations {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/migrations/list";
+}
+
+impl RequestType for Streams {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/streams/status";
+}
+
+impl RequestType for OpenTree {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/trees/open";
+}
+
+impl RequestType for DropTree {
+    const METHOD: Method = Method::DELETE;
+    const PATH_AND_QUERY: &'static str = "/admin/trees/drop";
+}
+
+impl RequestType for TreeNames {
+    const METHOD: Method = Method::GET;
+    const PATH_AND_QUERY: &'static str = "/admin/trees/list";
+}
+
+impl RequestType for CopyEntries {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/trees/copy";
+}
+
+impl RequestType for MoveEntries {
+    const METHOD: Method = Method::POST;
+    const PATH_AND_QUERY: &'static str = "/admin/trees/move";
+}
+
+impl IntoBody for Get {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for GetMany {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for GetPrefix {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Del {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for DelIfEqual {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for DelPrefix {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Set {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Replace {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for SetMany {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Cas {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for BatchCas {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Merge {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Incr {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for GetOrInsert {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for PopMin {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for PopMax {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Flush {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Batch {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for GenerateId {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Len {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Stats {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Clear {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Rename {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Transaction {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Iter {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Scan {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ScanRange {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for HashPrefix {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for CountRange {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for TopValues {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Aggregate {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for IterPage {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ScanSuffix {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for IterKeys {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ScanKeys {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ScanMatching {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for IterRev {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ScanRangeRev {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Max {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Pred {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for PredIncl {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Succ {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for SuccIncl {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Version {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ChangesSince {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for AckCursor {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for GetCursor {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for RemoveCursor {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Info {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for SetView {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for RemoveView {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Views {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Export {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for ExportSince {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for SetWebhook {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for RemoveWebhook {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Webhooks {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Backups {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Restore {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for PullRange {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Migrations {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for Streams {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for OpenTree {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for DropTree {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for TreeNames {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for CopyEntries {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl IntoBody for MoveEntries {
+    type Body = Self;
+    fn into_body(self) -> Self::Body { self }
+}
+
+impl<T> IntoRequest for T
+where
+    T: RequestType + IntoBody,
+    T::Body: 'static + Send + Sync,
+{
+    fn into_request(self, base_uri: Uri) -> Request<Body> {
+        let method = T::METHOD;
+        let uri = uri_with_path(base_uri, T::PATH_AND_QUERY);
+        let body = self.into_body();
+        let body_json = serde_json::to_vec(&body).expect("failed to serialize request body");
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(body_json.into())
+            .expect("attempted to construct invalid request");
+        request.extensions_mut().insert(TypedBody::new(body));
+        request
+    }
+}
+
+/// Append the given path to the given `Uri`.
+///
+/// Assumes the `Uri` already contains the scheme and authority parts.
+fn uri_with_path(uri: Uri, path: &str) -> Uri {
+    let mut parts = uri.into_parts();
+    let path_and_query = path
+        .parse::<PathAndQuery>()
+        .expect("failed to parse path and query for request URI");
+    parts.path_and_query = Some(path_and_query);
+    Uri::from_parts(parts)
+        .expect("failed to construct request URI from parts")
+}
+
+impl Serialize for EntryMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(&encode_key(key), value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        BTreeMap::<String, Value>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| match decode_key(&key) {
+                Some(key) => Ok((key, value)),
+                None => Err(D::Error::custom(format!("invalid hexadecimal key `{}`", key))),
+            })
+            .collect::<Result<_, _>>()
+            .map(EntryMap)
+    }
+}
+
+impl<'de> Deserialize<'de> for Iter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(default)]
+            limit: Option<u64>,
+            #[serde(default)]
+            filter: Option<ValueFilter>,
+        }
+        let fields = if deserializer.is_human_readable() {
+            Option::<Fields>::deserialize(deserializer)?
+        } else {
+            Some(Fields::deserialize(deserializer)?)
+        };
+        Ok(match fields {
+            None => Iter::default(),
+            Some(Fields { limit, filter }) => Iter { limit, filter },
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for IterKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(default)]
+            limit: Option<u64>,
+        }
+        let fields = if deserializer.is_human_readable() {
+            Option::<Fields>::deserialize(deserializer)?
+        } else {
+            Some(Fields::deserialize(deserializer)?)
+        };
+        Ok(match fields {
+            None => IterKeys::default(),
+            Some(Fields { limit }) => IterKeys { limit },
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for IterRev {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(default)]
+            limit: Option<u64>,
+        }
+        let fields = if deserializer.is_human_readable() {
+            Option::<Fields>::deserialize(deserializer)?
+        } else {
+            Some(Fields::deserialize(deserializer)?)
+        };
+        Ok(match fields {
+            None => IterRev::default(),
+            Some(Fields { limit }) => IterRev { limit },
+        })
+    }
+}
+
+impl ValueFilter {
+    /// Whether or not the given value satisfies every condition of the filter.
+    pub fn matches(&self, value: &[u8]) -> bool {
+        let len = value.len() as u64;
+        self.prefix.as_ref().map_or(true, |prefix| value.starts_with(prefix))
+            && self.equals.as_ref().map_or(true, |equals| &equals[..] == value)
+            && self.min_len.map_or(true, |min| len >= min)
+            && self.max_len.map_or(true, |max| len <= max)
+    }
+}
+
+impl EventKind {
+    /// The lowercase name of the kind used within query parameters, e.g. `"set"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EventKind::Set => "set",
+            EventKind::Del => "del",
+            EventKind::Merge => "merge",
+        }
+    }
+
+    /// The kind with the given name as produced by `EventKind::name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [EventKind::Set, EventKind::Del, EventKind::Merge]
+            .iter()
+            .cloned()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl EventFilter {
+    /// Whether or not the given event satisfies every condition of the filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        self.start.as_ref().map_or(true, |start| &event.key >= start)
+            && self.end.as_ref().map_or(true, |end| &event.key < end)
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+    }
+
+    /// Encode the filter as query parameters, each preceded by `&`.
+    ///
+    /// Bounds are percent-encoded as `start` and `end`, while `kinds` is a comma-separated list
+    /// of names, e.g. `&start=a&end=b&kinds=set,del`. Empty conditions are omitted.
+    pub fn to_query(&self) -> String {
+        let mut query = String::new();
+        if let Some(ref start) = self.start {
+            query.push_str(&format!("&start={}", percent_encode_key(start)));
+        }
+        if let Some(ref end) = self.end {
+            query.push_str(&format!("&end={}", percent_encode_key(end)));
+        }
+        if !self.kinds.is_empty() {
+            let kinds: Vec<_> = self.kinds.iter().map(|kind| kind.name()).collect();
+            query.push_str(&format!("&kinds={}", kinds.join(",")));
+        }
+        query
+    }
+}
+
+impl ImportMode {
+    /// The lowercase name of the mode used within query parameters, e.g. `"merge"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ImportMode::Merge => "merge",
+            ImportMode::Replace => "replace",
+        }
+    }
+
+    /// The mode with the given name as produced by `ImportMode::name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [ImportMode::Merge, ImportMode::Replace]
+            .iter()
+            .cloned()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for ImportMode {
+    fn default() -> Self {
+        ImportMode::Merge
+    }
+}
+
+impl ExportDecoder {
+    /// A decoder awaiting the beginning of a stream.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Append the given bytes received from the stream.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Whether or not the end of the stream has been decoded.
+    pub fn is_complete(&self) -> bool {
+        self.end_decoded
+    }
+
+    /// Decode the next item from the bytes received so far.
+    ///
+    /// Returns `Ok(None)` if more bytes are required, or `Err` describing why the stream is
+    /// malformed.
+    pub fn next_item(&mut self) -> Result<Option<ExportItem>, String> {
+        let bytes = &self.buffer[self.pos..];
+        if self.end_decoded {
+            if !bytes.is_empty() {
+                return Err("unexpected bytes following the end of the export".to_string());
+            }
+            return Ok(None);
+        }
+        if !self.header_decoded {
+            let magic_len = cmp::min(bytes.len(), EXPORT_MAGIC.len());
+            let delta = bytes[..magic_len] == DELTA_MAGIC[..magic_len];
+            if !delta && bytes[..magic_len] != EXPORT_MAGIC[..magic_len] {
+                return Err("the stream is not an export".to_string());
+            }
+            let len = if delta { DELTA_MAGIC.len() + 16 } else { EXPORT_MAGIC.len() + 8 };
+            if bytes.len() < len {
+                return Ok(None);
+            }
+            let seq = decode_u64(&bytes[len - 8..len]);
+            let item = if delta {
+                let since = decode_u64(&bytes[DELTA_MAGIC.len()..len - 8]);
+                ExportItem::DeltaHeader { since, seq }
+            } else {
+                ExportItem::Header { seq }
+            };
+            self.pos += len;
+            self.header_decoded = true;
+            self.delta = delta;
+            return Ok(Some(item));
+        }
+        if bytes.len() < 4 {
+            return Ok(None);
+        }
+        let key_len = decode_u64(&bytes[..4]) as u32;
+        if key_len == EXPORT_END {
+            if bytes.len() < 12 {
+                return Ok(None);
+            }
+            let count = decode_u64(&bytes[4..12]);
+            if count != self.entries {
+                return Err(format!(
+                    "the export claims {} entries but contained {}",
+                    count, self.entries,
+                ));
+            }
+            self.pos += 12;
+            self.end_decoded = true;
+            return Ok(Some(ExportItem::End { count }));
+        }
+        let key_end = 4 + key_len as usize;
+        if bytes.len() < key_end + 4 {
+            return Ok(None);
+        }
+        let value_len = decode_u64(&bytes[key_end..key_end + 4]) as u32;
+        if value_len == DELTA_REMOVED && self.delta {
+            let key = bytes[4..key_end].to_vec();
+            self.pos += key_end + 4;
+            self.entries += 1;
+            return Ok(Some(ExportItem::Removed(key)));
+        }
+        let value_len = value_len as usize;
+        let value_end = key_end + 4 + value_len;
+        if bytes.len() < value_end {
+            return Ok(None);
+        }
+        let key = bytes[4..key_end].to_vec();
+        let value = bytes[key_end + 4..value_end].to_vec();
+        self.pos += value_end;
+        self.entries += 1;
+        Ok(Some(ExportItem::Entry(key, value)))
+    }
+}
+
+impl Webhook {
+    /// A `Webhook` delivering all events to the given URL.
+    pub fn new(url: String) -> Self {
+        Webhook {
+            url,
+            prefix: vec![],
+            kinds: vec![],
+            max_retries: DEFAULT_WEBHOOK_MAX_RETRIES,
+        }
+    }
+
+    /// Whether or not the given event should be delivered to the webhook.
+    pub fn matches(&self, event: &Event) -> bool {
+        event.key.starts_with(&self.prefix)
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+    }
+}
+
+impl ScanRange {
+    /// A `ScanRange` over the given range of keys without a `limit`.
+    pub fn new<R>(range: R) -> Self
+    where
+        R: RangeBounds<Key>,
+    {
+        let (start, start_inclusive) = match range.start_bound() {
+            Bound::Included(key) => (Some(key.clone()), true),
+            Bound::Excluded(key) => (Some(key.clone()), false),
+            Bound::Unbounded => (None, true),
+        };
+        let (end, end_inclusive) = match range.end_bound() {
+            Bound::Included(key) => (Some(key.clone()), true),
+            Bound::Excluded(key) => (Some(key.clone()), false),
+            Bound::Unbounded => (None, false),
+        };
+        ScanRange { start, end, start_inclusive, end_inclusive, limit: None }
+    }
+
+    /// The lower bound of the range.
+    pub fn start_bound(&self) -> Bound<&[u8]> {
+        match self.start {
+            None => Bound::Unbounded,
+            Some(ref key) if self.start_inclusive => Bound::Included(&key[..]),
+            Some(ref key) => Bound::Excluded(&key[..]),
+        }
+    }
+
+    /// The upper bound of the range.
+    pub fn end_bound(&self) -> Bound<&[u8]> {
+        match self.end {
+            None => Bound::Unbounded,
+            Some(ref key) if self.end_inclusive => Bound::Included(&key[..]),
+            Some(ref key) => Bound::Excluded(&key[..]),
+        }
+    }
+}
+
+impl KeyPattern {
+    /// The anchored regular expression equivalent to this pattern.
+    pub fn to_regex(&self) -> String {
+        match *self {
+            KeyPattern::Regex(ref pattern) => format!("^(?:{})$", pattern),
+            KeyPattern::Glob(ref glob) => {
+                // `(?s)` allows wildcards to match newlines.
+                let mut pattern = "(?s)^".to_string();
+                for c in glob.chars() {
+                    match c {
+                        '*' => pattern.push_str(".*"),
+                        '?' => pattern.push('.'),
+                        c => pattern.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                pattern.push('$');
+                pattern
+            }
+        }
+    }
+}
+
+impl Number {
+    /// The number as an `f64`, which may lose precision for large `U64`s.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Number::U64(n) => n as f64,
+            Number::F64(n) => n,
+        }
+    }
+}
+
+impl VersionInfo {
+    /// The versions with which this build of the crate was compiled.
+    pub fn current() -> Self {
+        VersionInfo {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            wire_format: WIRE_FORMAT,
+            min_wire_format: MIN_WIRE_FORMAT,
+            sled_version: SLED_VERSION.to_string(),
+        }
+    }
+
+    /// Whether or not a peer with these versions supports the given wire format revision.
+    pub fn supports(&self, wire_format: u32) -> bool {
+        self.min_wire_format <= wire_format && wire_format <= self.wire_format
+    }
+
+    /// Whether or not a peer with these versions can communicate with this build of the crate.
+    pub fn is_compatible(&self) -> bool {
+        self.supports(WIRE_FORMAT)
+    }
+}
+
+impl ErrorCode {
+    /// The code describing an error response with the given status.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => ErrorCode::BadRequest,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::GONE => ErrorCode::Gone,
+            StatusCode::PRECONDITION_FAILED => ErrorCode::PreconditionFailed,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::GATEWAY_TIMEOUT => ErrorCode::DeadlineExceeded,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::TooManyRequests,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Unavailable,
+            StatusCode::NOT_IMPLEMENTED => ErrorCode::NotImplemented,
+            _ => ErrorCode::Internal,
+        }
+    }
+
+    /// Whether errors of this kind may be resolved by retrying the same request later.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            ErrorCode::DeadlineExceeded | ErrorCode::TooManyRequests => true,
+            ErrorCode::Unavailable => true,
+            _ => false,
+        }
+    }
 }
 
-impl RequestType for Iter {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/iter";
+impl ErrorBody {
+    /// Describe an error response with the given status, deriving its `code` and `retryable`.
+    pub fn new<S>(status: StatusCode, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        ErrorBody::with_code(ErrorCode::from_status(status), message)
+    }
+
+    /// Describe an error of the given kind.
+    pub fn with_code<S>(code: ErrorCode, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let message = message.into();
+        let retryable = code.is_retryable();
+        ErrorBody { code, message, key: None, retryable }
+    }
+
+    /// Relate the error to the entry with the given key.
+    pub fn key(mut self, key: Key) -> Self {
+        self.key = Some(key);
+        self
+    }
 }
 
-impl RequestType for Scan {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/scan";
+/// The wire format revision pinned by the given request, or `MIN_WIRE_FORMAT` if it pins none.
+///
+/// See `PROTOCOL`.
+pub fn pinned_protocol(request: &Request<Body>) -> u32 {
+    request
+        .headers()
+        .get(PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(MIN_WIRE_FORMAT)
 }
 
-impl RequestType for ScanRange {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/scan_range";
+/// Servers predating wire format negotiation only ever spoke the first revision.
+fn default_min_wire_format() -> u32 {
+    MIN_WIRE_FORMAT
 }
 
-impl RequestType for Max {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/max";
+/// Earlier revisions of `ScanRange` were always inclusive of the `start` key.
+fn default_start_inclusive() -> bool {
+    true
 }
 
-impl RequestType for Pred {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/pred";
+fn default_changes_timeout_ms() -> u64 {
+    DEFAULT_CHANGES_TIMEOUT_MS
 }
 
-impl RequestType for PredIncl {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/pred_incl";
+fn default_webhook_max_retries() -> u32 {
+    DEFAULT_WEBHOOK_MAX_RETRIES
 }
 
-impl RequestType for Succ {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/succ";
+/// Whether or not the given name is valid for a view.
+pub fn is_valid_view_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
-impl RequestType for SuccIncl {
-    const METHOD: Method = Method::GET;
-    const PATH_AND_QUERY: &'static str = "/tree/entries/succ_incl";
+/// Whether or not the given name is valid for a named tree.
+///
+/// Names that would be ambiguous with the paths of the default tree are reserved.
+pub fn is_valid_tree_name(name: &str) -> bool {
+    is_valid_view_name(name) && !["entries", "keys", "stats", "cursors", "raw"].contains(&name)
 }
 
-impl IntoBody for Get {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Normalize the path beneath which the API is mounted, e.g. `kv/` becomes `/kv`.
+///
+/// Returns `None` if the path is empty or `/`, in which case the API is mounted at the root.
+pub fn normalize_base_path(path: &str) -> Option<String> {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return None;
+    }
+    Some(format!("/{}", path))
 }
 
-impl IntoBody for Del {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Strip the given normalized base path from the beginning of the path of some request.
+///
+/// Returns `None` if the path is not beneath the base path, e.g. `/kvx` is not beneath `/kv`.
+pub fn strip_base_path<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    if !path.starts_with(base_path) {
+        return None;
+    }
+    match &path[base_path.len()..] {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
 }
 
-impl IntoBody for Set {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// The beginning of an `Export` stream whose entries reflect all changes up to `seq`.
+pub fn encode_export_header(seq: u64) -> Vec<u8> {
+    let mut bytes = EXPORT_MAGIC.to_vec();
+    bytes.extend((0..8).rev().map(|i| (seq >> (i * 8)) as u8));
+    bytes
 }
 
-impl IntoBody for Cas {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// The beginning of an `ExportSince` stream reflecting all changes following `since` up to `seq`.
+pub fn encode_delta_header(since: u64, seq: u64) -> Vec<u8> {
+    let mut bytes = DELTA_MAGIC.to_vec();
+    for n in &[since, seq] {
+        bytes.extend((0..8).rev().map(|i| (n >> (i * 8)) as u8));
+    }
+    bytes
 }
 
-impl IntoBody for Merge {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// An entry within an `Export` or `ExportSince` stream.
+///
+/// Panics if the key is `EXPORT_END` bytes or longer or the value is `DELTA_REMOVED` bytes or
+/// longer.
+pub fn encode_export_entry(key: &[u8], value: &[u8]) -> Vec<u8> {
+    assert!(key.len() < EXPORT_END as usize && value.len() < DELTA_REMOVED as usize);
+    let mut bytes = Vec::with_capacity(8 + key.len() + value.len());
+    for field in &[key, value] {
+        let len = field.len() as u32;
+        bytes.extend((0..4).rev().map(|i| (len >> (i * 8)) as u8));
+        bytes.extend_from_slice(field);
+    }
+    bytes
 }
 
-impl IntoBody for Flush {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// The removal of the entry with the given key within an `ExportSince` stream.
+///
+/// Panics if the key is `EXPORT_END` bytes or longer.
+pub fn encode_delta_removed(key: &[u8]) -> Vec<u8> {
+    assert!(key.len() < EXPORT_END as usize);
+    let len = key.len() as u32;
+    let mut bytes: Vec<u8> = (0..4).rev().map(|i| (len >> (i * 8)) as u8).collect();
+    bytes.extend_from_slice(key);
+    bytes.extend((0..4).rev().map(|i| (DELTA_REMOVED >> (i * 8)) as u8));
+    bytes
 }
 
-impl IntoBody for Iter {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// The end of an `Export` or `ExportSince` stream containing `count` entries.
+pub fn encode_export_end(count: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = (0..4).rev().map(|i| (EXPORT_END >> (i * 8)) as u8).collect();
+    bytes.extend((0..8).rev().map(|i| (count >> (i * 8)) as u8));
+    bytes
 }
 
-impl IntoBody for Scan {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Decode the given big-endian bytes of an `Export` stream as an unsigned integer.
+fn decode_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |n, &b| (n << 8) | u64::from(b))
 }
 
-impl IntoBody for ScanRange {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Encode the given key as a string of lowercase hexadecimal byte pairs.
+pub fn encode_key(key: &[u8]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-impl IntoBody for Max {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Encode the given key as unpadded URL-safe base64 for use within a `RAW_PATH`.
+pub fn encode_raw_key(key: &[u8]) -> String {
+    base64::encode_config(key, base64::URL_SAFE_NO_PAD)
 }
 
-impl IntoBody for Pred {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Decode a key produced by `encode_raw_key`, or return `None` if it is invalid.
+///
+/// Trailing padding is accepted, as produced by many base64 encoders.
+pub fn decode_raw_key(s: &str) -> Option<Key> {
+    base64::decode_config(s.trim_right_matches('='), base64::URL_SAFE_NO_PAD).ok()
 }
 
-impl IntoBody for PredIncl {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Encode the given key as URL-safe base64 for use within an `ENTRIES_PATH`.
+///
+/// Padding is appended to encodings consisting solely of lowercase letters and underscores, so
+/// that a key never names an endpoint, e.g. the encoding `iter=` rather than `iter`.
+pub fn encode_entry_key(key: &[u8]) -> String {
+    let mut encoded = encode_raw_key(key);
+    if encoded.bytes().all(|b| b.is_ascii_lowercase() || b == b'_') {
+        encoded.push('=');
+    }
+    encoded
 }
 
-impl IntoBody for Succ {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Decode the key from the path of a resource-style entry route, or return `None` if the path is
+/// not within `ENTRIES_PATH` or the key is invalid.
+pub fn entry_path_key(path: &str) -> Option<Key> {
+    if !path.starts_with(ENTRIES_PATH) {
+        return None;
+    }
+    let encoded = &path[ENTRIES_PATH.len()..];
+    if encoded.contains('/') {
+        return None;
+    }
+    decode_raw_key(encoded)
 }
 
-impl IntoBody for SuccIncl {
-    type Body = Self;
-    fn into_body(self) -> Self::Body { self }
+/// Percent-encode the given key for use within a URI query, leaving only unreserved characters
+/// as-is.
+pub fn percent_encode_key(key: &[u8]) -> String {
+    key.iter()
+        .map(|&b| match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
 }
 
-impl<T> IntoRequest for T
-where
-    T: RequestType + IntoBody,
-{
-    fn into_request(self, base_uri: Uri) -> Request<Body> {
-        let method = T::METHOD;
-        let uri = uri_with_path(base_uri, T::PATH_AND_QUERY);
-        let body = self.into_body();
-        let body_json = serde_json::to_vec(&body).expect("failed to serialize request body");
-        Request::builder()
-            .method(method)
-            .uri(uri)
-            .body(body_json.into())
-            .expect("attempted to construct invalid request")
+/// Decode a percent-encoded key from a URI query, or return `None` if it is invalid.
+///
+/// As within form-encoded queries, `+` is decoded as a space.
+pub fn percent_decode_key(s: &str) -> Option<Key> {
+    let bytes = s.as_bytes();
+    let mut key = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3)?;
+                if !hex.chars().all(|c| c.is_digit(16)) {
+                    return None;
+                }
+                key.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                key.push(b' ');
+                i += 1;
+            }
+            b => {
+                key.push(b);
+                i += 1;
+            }
+        }
     }
+    Some(key)
 }
 
-/// Append the given path to the given `Uri`.
-///
-/// Assumes the `Uri` already contains the scheme and authority parts.
-fn uri_with_path(uri: Uri, path: &str) -> Uri {
-    let mut parts = uri.into_parts();
-    let path_and_query = path
-        .parse::<PathAndQuery>()
-        .expect("failed to parse path and query for request URI");
-    parts.path_and_query = Some(path_and_query);
-    Uri::from_parts(parts)
-        .expect("failed to construct request URI from parts")
+/// Decode a key produced by `encode_key`, or return `None` if it is invalid.
+pub fn decode_key(s: &str) -> Option<Key> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
 }
 
 /// A request to download the entire tree.
@@ -310,29 +2662,118 @@ pub fn get(base_uri: Uri, key: Key) -> Request<Body> {
     from(base_uri, Get { key })
 }
 
+/// Shorthand for `from(base_uri, GetMany { keys })`.
+pub fn get_many(base_uri: Uri, keys: Vec<Key>) -> Request<Body> {
+    from(base_uri, GetMany { keys })
+}
+
+/// Shorthand for `from(base_uri, GetPrefix { prefix })`.
+pub fn get_prefix(base_uri: Uri, prefix: Key) -> Request<Body> {
+    from(base_uri, GetPrefix { prefix })
+}
+
 /// Shorthand for `from(base_uri, Del { key })`.
 pub fn del(base_uri: Uri, key: Key) -> Request<Body> {
     from(base_uri, Del { key })
 }
 
+/// Shorthand for `from(base_uri, DelIfEqual { key, expected })`.
+pub fn del_if_equal(base_uri: Uri, key: Key, expected: Value) -> Request<Body> {
+    from(base_uri, DelIfEqual { key, expected })
+}
+
+/// Shorthand for `from(base_uri, DelPrefix { prefix })`.
+pub fn del_prefix(base_uri: Uri, prefix: Key) -> Request<Body> {
+    from(base_uri, DelPrefix { prefix })
+}
+
 /// Shorthand for `from(base_uri, Set { key, value })`.
 pub fn set(base_uri: Uri, key: Key, value: Value) -> Request<Body> {
     from(base_uri, Set { key, value })
 }
 
-/// Shorthand for `from(base_uri, Iter)`.
+/// Shorthand for `from(base_uri, Replace { key, value })`.
+pub fn replace(base_uri: Uri, key: Key, value: Value) -> Request<Body> {
+    from(base_uri, Replace { key, value })
+}
+
+/// Shorthand for `from(base_uri, Iter::default())`.
 pub fn iter(base_uri: Uri) -> Request<Body> {
-    from(base_uri, Iter)
+    from(base_uri, Iter::default())
 }
 
-/// Shorthand for `from(base_uri, Scan { key })`.
+/// Shorthand for `from(base_uri, Scan { key, limit: None, filter: None })`.
 pub fn scan(base_uri: Uri, key: Key) -> Request<Body> {
-    from(base_uri, Scan { key })
+    from(base_uri, Scan { key, limit: None, filter: None })
+}
+
+/// Shorthand for `from(base_uri, ScanRange::new(range))`.
+pub fn scan_range<R>(base_uri: Uri, range: R) -> Request<Body>
+where
+    R: RangeBounds<Key>,
+{
+    from(base_uri, ScanRange::new(range))
+}
+
+/// Shorthand for `from(base_uri, HashPrefix { prefix })`.
+pub fn hash_prefix(base_uri: Uri, prefix: Key) -> Request<Body> {
+    from(base_uri, HashPrefix { prefix })
 }
 
-/// Shorthand for `from(base_uri, ScanRange { start, end })`.
-pub fn scan_range(base_uri: Uri, start: Key, end: Key) -> Request<Body> {
-    from(base_uri, ScanRange { start, end })
+/// Shorthand for `from(base_uri, CountRange { start, end })`.
+pub fn count_range(base_uri: Uri, start: Key, end: Key) -> Request<Body> {
+    from(base_uri, CountRange { start, end })
+}
+
+/// Shorthand for `from(base_uri, TopValues { count })`.
+pub fn top_values(base_uri: Uri, count: u64) -> Request<Body> {
+    from(base_uri, TopValues { count })
+}
+
+/// Shorthand for `from(base_uri, Aggregate { start, end, op, value_type })`.
+pub fn aggregate(
+    base_uri: Uri,
+    start: Key,
+    end: Key,
+    op: AggregateOp,
+    value_type: ValueType,
+) -> Request<Body> {
+    from(base_uri, Aggregate { start, end, op, value_type })
+}
+
+/// Shorthand for `from(base_uri, IterPage { cursor, page_size })`.
+pub fn iter_page(base_uri: Uri, cursor: Option<String>, page_size: u64) -> Request<Body> {
+    from(base_uri, IterPage { cursor, page_size })
+}
+
+/// Shorthand for `from(base_uri, ScanSuffix { suffix, limit: None })`.
+pub fn scan_suffix(base_uri: Uri, suffix: Key) -> Request<Body> {
+    from(base_uri, ScanSuffix { suffix, limit: None })
+}
+
+/// Shorthand for `from(base_uri, IterKeys { limit: None })`.
+pub fn iter_keys(base_uri: Uri) -> Request<Body> {
+    from(base_uri, IterKeys { limit: None })
+}
+
+/// Shorthand for `from(base_uri, ScanKeys { key, limit: None })`.
+pub fn scan_keys(base_uri: Uri, key: Key) -> Request<Body> {
+    from(base_uri, ScanKeys { key, limit: None })
+}
+
+/// Shorthand for `from(base_uri, ScanMatching { pattern, limit: None })`.
+pub fn scan_matching(base_uri: Uri, pattern: KeyPattern) -> Request<Body> {
+    from(base_uri, ScanMatching { pattern, limit: None })
+}
+
+/// Shorthand for `from(base_uri, IterRev { limit: None })`.
+pub fn iter_rev(base_uri: Uri) -> Request<Body> {
+    from(base_uri, IterRev { limit: None })
+}
+
+/// Shorthand for `from(base_uri, ScanRangeRev { start, end, limit: None })`.
+pub fn scan_range_rev(base_uri: Uri, start: Key, end: Key) -> Request<Body> {
+    from(base_uri, ScanRangeRev { start, end, limit: None })
 }
 
 /// Shorthand for `from(base_uri, Max)`.
@@ -360,17 +2801,377 @@ pub fn succ_incl(base_uri: Uri, key: Key) -> Request<Body> {
     from(base_uri, SuccIncl { key })
 }
 
+/// Construct the WebSocket handshake for a `Subscribe` request.
+///
+/// The `websocket_key` is sent as the `Sec-WebSocket-Key` header and should be a base64-encoded
+/// 16-byte nonce.
+pub fn subscribe(base_uri: Uri, websocket_key: &str) -> Request<Body> {
+    subscribe_with(base_uri, &Subscribe::default(), websocket_key)
+}
+
+/// The same as `subscribe`, but with the `prefix` and `filter` of the given `Subscribe` encoded as
+/// query parameters.
+pub fn subscribe_with(base_uri: Uri, subscribe: &Subscribe, websocket_key: &str) -> Request<Body> {
+    let mut path = format!(
+        "{}?prefix={}{}",
+        Subscribe::PATH_AND_QUERY,
+        percent_encode_key(&subscribe.prefix),
+        subscribe.filter.to_query(),
+    );
+    if let Some(ms) = subscribe.coalesce_ms {
+        path.push_str(&format!("&coalesce_ms={}", ms));
+    }
+    let uri = uri_with_path(base_uri, &path);
+    Request::builder()
+        .method(Subscribe::METHOD)
+        .uri(uri)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_VERSION, "13")
+        .header(header::SEC_WEBSOCKET_KEY, websocket_key)
+        .body(Body::empty())
+        .expect("attempted to construct invalid request")
+}
+
+/// Shorthand for `from(base_uri, ChangesSince { since, prefix, timeout_ms, .. })` without a
+/// `limit` or `filter`.
+pub fn changes_since(base_uri: Uri, since: u64, prefix: Key, timeout_ms: u64) -> Request<Body> {
+    let filter = EventFilter::default();
+    from(base_uri, ChangesSince { since, prefix, timeout_ms, limit: None, filter })
+}
+
+/// Construct a `Watch` request for the changes to the entries beginning with `prefix`.
+///
+/// If `last_event_id` is `Some`, it is sent as the `Last-Event-ID` header so that retained
+/// changes since that event are sent first.
+pub fn watch(base_uri: Uri, prefix: &[u8], last_event_id: Option<u64>) -> Request<Body> {
+    let watch = Watch { prefix: prefix.to_vec(), ..Default::default() };
+    watch_with(base_uri, &watch, last_event_id)
+}
+
+/// The same as `watch`, but resuming from the sequence number acknowledged via the named cursor
+/// if `last_event_id` is `None`.
+pub fn watch_cursor(
+    base_uri: Uri,
+    prefix: &[u8],
+    cursor: &str,
+    last_event_id: Option<u64>,
+) -> Request<Body> {
+    let cursor = Some(cursor.to_string());
+    let watch = Watch { prefix: prefix.to_vec(), cursor, ..Default::default() };
+    watch_with(base_uri, &watch, last_event_id)
+}
+
+/// Construct a `Watch` request with the fields of the given `Watch` encoded as query parameters.
+///
+/// If `last_event_id` is `Some`, it is sent as the `Last-Event-ID` header.
+pub fn watch_with(base_uri: Uri, watch: &Watch, last_event_id: Option<u64>) -> Request<Body> {
+    let prefix = percent_encode_key(&watch.prefix);
+    let mut path = format!("{}?prefix={}", Watch::PATH_AND_QUERY, prefix);
+    if let Some(ref cursor) = watch.cursor {
+        path.push_str(&format!("&cursor={}", percent_encode_key(cursor.as_bytes())));
+    }
+    path.push_str(&watch.filter.to_query());
+    if let Some(ms) = watch.coalesce_ms {
+        path.push_str(&format!("&coalesce_ms={}", ms));
+    }
+    let uri = uri_with_path(base_uri, &path);
+    let mut builder = Request::builder();
+    builder.method(Watch::METHOD).uri(uri);
+    if let Some(id) = last_event_id {
+        builder.header(LAST_EVENT_ID, header::HeaderValue::from(id));
+    }
+    builder.body(Body::empty()).expect("attempted to construct invalid request")
+}
+
+/// A `GET` request for the raw value of the entry with the given key. See `RAW_PATH`.
+pub fn get_raw(base_uri: Uri, key: &[u8]) -> Request<Body> {
+    let uri = uri_with_path(base_uri, &format!("{}{}", RAW_PATH, encode_raw_key(key)));
+    Request::builder()
+        .method(Method::GET)
+        .uri(uri)
+        .body(Body::empty())
+        .expect("attempted to construct invalid request")
+}
+
+/// A `PUT` request setting the raw value of the entry with the given key. See `RAW_PATH`.
+pub fn set_raw(base_uri: Uri, key: &[u8], value: Vec<u8>) -> Request<Body> {
+    let uri = uri_with_path(base_uri, &format!("{}{}", RAW_PATH, encode_raw_key(key)));
+    Request::builder()
+        .method(Method::PUT)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(value.into())
+        .expect("attempted to construct invalid request")
+}
+
+/// A `GET` request for the value of the entry with the given key. See `ENTRIES_PATH`.
+pub fn get_entry(base_uri: Uri, key: &[u8]) -> Request<Body> {
+    entry_request(base_uri, Method::GET, key, Body::empty())
+}
+
+/// A `HEAD` request checking the presence and length of the entry with the given key. See
+/// `VALUE_LENGTH`.
+pub fn head_entry(base_uri: Uri, key: &[u8]) -> Request<Body> {
+    entry_request(base_uri, Method::HEAD, key, Body::empty())
+}
+
+/// A `PUT` request setting the value of the entry with the given key. See `ENTRIES_PATH`.
+pub fn put_entry(base_uri: Uri, key: &[u8], value: Value) -> Request<Body> {
+    let body = serde_json::to_vec(&value).expect("failed to serialize value to JSON");
+    let mut request = entry_request(base_uri, Method::PUT, key, body.into());
+    request.extensions_mut().insert(TypedBody::new(value));
+    request
+}
+
+/// A `DELETE` request removing the entry with the given key. See `ENTRIES_PATH`.
+pub fn delete_entry(base_uri: Uri, key: &[u8]) -> Request<Body> {
+    entry_request(base_uri, Method::DELETE, key, Body::empty())
+}
+
+fn entry_request(base_uri: Uri, method: Method, key: &[u8], body: Body) -> Request<Body> {
+    let uri = uri_with_path(base_uri, &format!("{}{}", ENTRIES_PATH, encode_entry_key(key)));
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(body)
+        .expect("attempted to construct invalid request")
+}
+
+/// Shorthand for `from(base_uri, AckCursor { name, seq })`.
+pub fn ack_cursor(base_uri: Uri, name: String, seq: u64) -> Request<Body> {
+    from(base_uri, AckCursor { name, seq })
+}
+
+/// Shorthand for `from(base_uri, GetCursor { name })`.
+pub fn get_cursor(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, GetCursor { name })
+}
+
+/// Shorthand for `from(base_uri, RemoveCursor { name })`.
+pub fn remove_cursor(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, RemoveCursor { name })
+}
+
+/// Shorthand for `from(base_uri, SetMany { entries })`.
+pub fn set_many(base_uri: Uri, entries: Vec<(Key, Value)>) -> Request<Body> {
+    from(base_uri, SetMany { entries })
+}
+
 /// Shorthand for `from(base_uri, Cas { key, old, new })`.
 pub fn cas(base_uri: Uri, key: Key, old: Option<Value>, new: Option<Value>) -> Request<Body> {
     from(base_uri, Cas { key, old, new })
 }
 
+/// Shorthand for `from(base_uri, BatchCas { ops })`.
+pub fn batch_cas(base_uri: Uri, ops: Vec<Cas>) -> Request<Body> {
+    from(base_uri, BatchCas { ops })
+}
+
 /// Shorthand for `from(base_uri, Merge { key, value })`.
 pub fn merge(base_uri: Uri, key: Key, value: Value) -> Request<Body> {
     from(base_uri, Merge { key, value })
 }
 
+/// Shorthand for `from(base_uri, Incr { key, delta })`.
+pub fn incr(base_uri: Uri, key: Key, delta: i64) -> Request<Body> {
+    from(base_uri, Incr { key, delta })
+}
+
+/// Shorthand for `from(base_uri, GetOrInsert { key, default })`.
+pub fn get_or_insert(base_uri: Uri, key: Key, default: Value) -> Request<Body> {
+    from(base_uri, GetOrInsert { key, default })
+}
+
+/// Shorthand for `from(base_uri, PopMin)`.
+pub fn pop_min(base_uri: Uri) -> Request<Body> {
+    from(base_uri, PopMin)
+}
+
+/// Shorthand for `from(base_uri, PopMax)`.
+pub fn pop_max(base_uri: Uri) -> Request<Body> {
+    from(base_uri, PopMax)
+}
+
 /// Shorthand for `from(base_uri, Flush)`.
 pub fn flush(base_uri: Uri) -> Request<Body> {
     from(base_uri, Flush)
 }
+
+/// Shorthand for `from(base_uri, Batch { ops })`.
+pub fn batch(base_uri: Uri, ops: Vec<BatchOp>) -> Request<Body> {
+    from(base_uri, Batch { ops })
+}
+
+/// Shorthand for `from(base_uri, GenerateId)`.
+pub fn generate_id(base_uri: Uri) -> Request<Body> {
+    from(base_uri, GenerateId)
+}
+
+/// Shorthand for `from(base_uri, Len)`.
+pub fn len(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Len)
+}
+
+/// Shorthand for `from(base_uri, Stats { max_age_ms: None })`.
+pub fn stats(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Stats { max_age_ms: None })
+}
+
+/// Shorthand for `from(base_uri, Export)`.
+pub fn export(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Export)
+}
+
+/// Shorthand for `from(base_uri, ExportSince { since })`.
+pub fn export_since(base_uri: Uri, since: u64) -> Request<Body> {
+    from(base_uri, ExportSince { since })
+}
+
+/// Construct an `Import` request whose body is the given `Export` stream.
+pub fn import(base_uri: Uri, mode: ImportMode, body: Body) -> Request<Body> {
+    let path = format!("{}?mode={}", Import::PATH_AND_QUERY, mode.name());
+    let uri = uri_with_path(base_uri, &path);
+    Request::builder()
+        .method(Import::METHOD)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .body(body)
+        .expect("attempted to construct invalid request")
+}
+
+/// A `Clear` request carrying the `CLEAR_CONFIRMATION`.
+pub fn clear(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Clear { confirm: CLEAR_CONFIRMATION.to_string() })
+}
+
+/// Shorthand for `from(base_uri, Transaction { conditions, writes })`.
+pub fn transaction(
+    base_uri: Uri,
+    conditions: Vec<Condition>,
+    writes: Vec<BatchOp>,
+) -> Request<Body> {
+    from(base_uri, Transaction { conditions, writes })
+}
+
+/// Shorthand for `from(base_uri, Rename { from, to, overwrite })`.
+pub fn rename(base_uri: Uri, from_key: Key, to: Key, overwrite: bool) -> Request<Body> {
+    from(base_uri, Rename { from: from_key, to, overwrite })
+}
+
+/// Shorthand for `from(base_uri, Version)`.
+pub fn version(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Version)
+}
+
+/// Shorthand for `from(base_uri, Info)`.
+pub fn info(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Info)
+}
+
+/// Shorthand for `from(base_uri, SetView { name, view })`.
+pub fn set_view(base_uri: Uri, name: String, view: View) -> Request<Body> {
+    from(base_uri, SetView { name, view })
+}
+
+/// Shorthand for `from(base_uri, RemoveView { name })`.
+pub fn remove_view(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, RemoveView { name })
+}
+
+/// Shorthand for `from(base_uri, Views)`.
+pub fn views(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Views)
+}
+
+/// Shorthand for `from(base_uri, SetWebhook { name, webhook })`.
+pub fn set_webhook(base_uri: Uri, name: String, webhook: Webhook) -> Request<Body> {
+    from(base_uri, SetWebhook { name, webhook })
+}
+
+/// Shorthand for `from(base_uri, RemoveWebhook { name })`.
+pub fn remove_webhook(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, RemoveWebhook { name })
+}
+
+/// Shorthand for `from(base_uri, Webhooks)`.
+pub fn webhooks(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Webhooks)
+}
+
+/// Shorthand for `from(base_uri, Backups)`.
+pub fn backups(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Backups)
+}
+
+/// Shorthand for `from(base_uri, Restore { to })`.
+pub fn restore(base_uri: Uri, to: RestorePoint) -> Request<Body> {
+    from(base_uri, Restore { to })
+}
+
+/// Shorthand for `from(base_uri, PullRange { name, source, start, end })`.
+pub fn pull_range(
+    base_uri: Uri,
+    name: String,
+    source: String,
+    start: Key,
+    end: Option<Key>,
+) -> Request<Body> {
+    from(base_uri, PullRange { name, source, start, end })
+}
+
+/// Shorthand for `from(base_uri, Migrations)`.
+pub fn migrations(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Migrations)
+}
+
+/// Shorthand for `from(base_uri, Streams)`.
+pub fn streams(base_uri: Uri) -> Request<Body> {
+    from(base_uri, Streams)
+}
+
+/// Shorthand for `from(base_uri, OpenTree { name })`.
+pub fn open_tree(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, OpenTree { name })
+}
+
+/// Shorthand for `from(base_uri, DropTree { name })`.
+pub fn drop_tree(base_uri: Uri, name: String) -> Request<Body> {
+    from(base_uri, DropTree { name })
+}
+
+/// Shorthand for `from(base_uri, TreeNames)`.
+pub fn tree_names(base_uri: Uri) -> Request<Body> {
+    from(base_uri, TreeNames)
+}
+
+/// Shorthand for `from(base_uri, CopyEntries { source, destination, start, end })`.
+pub fn copy_entries(
+    base_uri: Uri,
+    source: Option<String>,
+    destination: Option<String>,
+    start: Key,
+    end: Option<Key>,
+) -> Request<Body> {
+    from(base_uri, CopyEntries { source, destination, start, end })
+}
+
+/// Shorthand for `from(base_uri, MoveEntries { source, destination, start, end })`.
+pub fn move_entries(
+    base_uri: Uri,
+    source: Option<String>,
+    destination: Option<String>,
+    start: Key,
+    end: Option<Key>,
+) -> Request<Body> {
+    from(base_uri, MoveEntries { source, destination, start, end })
+}
+
+/// The exclusive end of the range containing only the given key.
+///
+/// Useful for copying or moving a single entry via `CopyEntries` or `MoveEntries`.
+pub fn single_key_end(key: &[u8]) -> Key {
+    let mut end = key.to_vec();
+    end.push(0);
+    end
+}
@@ -0,0 +1,439 @@
+//! A listener speaking a subset of the Redis protocol (RESP), configured via
+//! `server::ConfigBuilder::resp_addr`.
+//!
+//! This allows existing Redis client libraries to read and write the default tree without an HTTP
+//! shim. Each command is made via a `Client` submitting requests directly to the server's
+//! `Router`, so views, replicas and caching proxies behave as they do over HTTP.
+//!
+//! The supported commands are:
+//!
+//! - `PING [message]`
+//! - `GET key`
+//! - `SET key value`, without any of the `EX`, `PX`, `NX` or `XX` options.
+//! - `DEL key [key ...]`
+//! - `INCR key`, interpreting the value as a decimal integer as Redis does, via a
+//!   compare-and-swap loop. This differs from the `Incr` request, which uses little-endian `i64`s.
+//! - `SCAN cursor [MATCH pattern] [COUNT count]`, where the cursor is the number of keys skipped.
+//!   Keys added or removed before the cursor while scanning may cause keys to be repeated or
+//!   missed. Patterns are globs matched as by the `ScanMatching` request, and the `TYPE` option is
+//!   not supported.
+//!
+//! Commands may be pipelined, and replies are written in the order in which commands are received.
+//! Inline commands, e.g. `PING` typed into a telnet session, are also accepted.
+//!
+//! Commands larger than `server::ConfigBuilder::max_body_size` are rejected with a protocol error
+//! that closes the connection, rather than being buffered.
+
+use bytes::{BufMut, BytesMut};
+use client::{self, Client, Key, Value};
+use futures::Sink;
+use futures::future::{self, Either, Loop};
+use hyper;
+use hyper::rt::{Future, Stream};
+use regex::Regex;
+use request::KeyPattern;
+use server::Router;
+use std::io;
+use std::net::SocketAddr;
+use std::str;
+use tokio::codec::{Decoder, Encoder, Framed};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The number of keys returned by `SCAN` if no `COUNT` is given, as in Redis.
+pub const DEFAULT_SCAN_COUNT: u64 = 10;
+
+/// A reply to a command.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Reply {
+    /// A simple string, e.g. `OK`.
+    Status(String),
+    /// An error message, beginning with the kind of error, e.g. `ERR`.
+    Error(String),
+    Integer(i64),
+    /// A bulk string, or the null bulk string if `None`.
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+/// Decodes commands as arrays of bulk strings and encodes `Reply`s.
+#[derive(Copy, Clone, Debug)]
+struct Codec {
+    /// The greatest size of a command in bytes.
+    max_len: usize,
+}
+
+/// A future producing the reply to a single command.
+type ReplyFuture = Box<Future<Item = Reply, Error = io::Error> + Send>;
+
+impl Reply {
+    fn ok() -> Self {
+        Reply::Status("OK".to_string())
+    }
+
+    fn syntax_error() -> Self {
+        Reply::Error("ERR syntax error".to_string())
+    }
+
+    fn not_an_integer() -> Self {
+        Reply::Error("ERR value is not an integer or out of range".to_string())
+    }
+
+    fn wrong_arity(command: &str) -> Self {
+        let command = command.to_lowercase();
+        Reply::Error(format!("ERR wrong number of arguments for '{}' command", command))
+    }
+
+    fn client_error(err: client::Error) -> Self {
+        Reply::Error(format!("ERR {}", err))
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Vec<Vec<u8>>;
+    type Error = io::Error;
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let (args, len) = match parse_command(&buf[..], self.max_len)? {
+            None => return Ok(None),
+            Some(parsed) => parsed,
+        };
+        buf.advance(len);
+        Ok(Some(args))
+    }
+}
+
+impl Encoder for Codec {
+    type Item = Reply;
+    type Error = io::Error;
+    fn encode(&mut self, reply: Reply, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        write_reply(&reply, buf);
+        Ok(())
+    }
+}
+
+/// Listen for RESP connections on the given address, serving each via the `Router`.
+///
+/// Connections sending a command larger than `max_len` bytes are closed.
+///
+/// Returns a `Future` that completes only if the listener fails. Each connection is spawned onto
+/// the default executor.
+pub fn serve(
+    addr: &SocketAddr,
+    router: Router,
+    max_len: usize,
+) -> impl Future<Item = (), Error = io::Error> {
+    let client = Client::local(router);
+    future::result(TcpListener::bind(addr)).and_then(move |listener| {
+        listener.incoming().for_each(move |socket| {
+            hyper::rt::spawn(connection(socket, client.clone(), max_len));
+            Ok(())
+        })
+    })
+}
+
+/// Execute each command received via the socket in turn, writing the replies in order.
+fn connection(
+    socket: TcpStream,
+    client: Client,
+    max_len: usize,
+) -> impl Future<Item = (), Error = ()> {
+    let (replies, commands) = Framed::new(socket, Codec { max_len }).split();
+    let replies_to_commands = commands.and_then(move |args| execute(&client, args));
+    replies.send_all(replies_to_commands).map(|_| ()).map_err(|_| ())
+}
+
+/// Execute the given command, producing its reply.
+///
+/// Errors encountered while executing the command are produced as `Reply::Error`s.
+fn execute(client: &Client, mut args: Vec<Vec<u8>>) -> ReplyFuture {
+    if args.is_empty() {
+        return ready(Reply::Error("ERR empty command".to_string()));
+    }
+    let name = String::from_utf8_lossy(&args.remove(0)).to_uppercase();
+    match (&name[..], args.len()) {
+        ("PING", 0) => ready(Reply::Status("PONG".to_string())),
+        ("PING", 1) => ready(Reply::Bulk(args.pop())),
+        ("GET", 1) => {
+            let key = args.remove(0);
+            or_error_reply(client.get(key).map(Reply::Bulk))
+        }
+        ("SET", 2) => {
+            let value = args.remove(1);
+            let key = args.remove(0);
+            or_error_reply(client.set(key, value).map(|()| Reply::ok()))
+        }
+        ("SET", n) if n > 2 => ready(Reply::syntax_error()),
+        ("DEL", n) if n > 0 => {
+            let dels: Vec<_> = args.into_iter().map(|key| client.del(key)).collect();
+            let reply = future::join_all(dels).map(|removed| {
+                Reply::Integer(removed.iter().filter(|value| value.is_some()).count() as i64)
+            });
+            or_error_reply(reply)
+        }
+        ("INCR", 1) => or_error_reply(incr(client.clone(), args.remove(0))),
+        ("SCAN", n) if n % 2 == 1 => match parse_scan(&args) {
+            Ok((cursor, count, pattern)) => or_error_reply(scan(client, cursor, count, pattern)),
+            Err(reply) => ready(reply),
+        },
+        ("PING", _) | ("GET", _) | ("SET", _) | ("DEL", _) | ("INCR", _) | ("SCAN", _) => {
+            ready(Reply::wrong_arity(&name))
+        }
+        _ => ready(Reply::Error(format!("ERR unknown command '{}'", name.to_lowercase()))),
+    }
+}
+
+/// A reply that is known immediately.
+fn ready(reply: Reply) -> ReplyFuture {
+    Box::new(future::ok(reply))
+}
+
+/// Produce a reply to the client's request, replying with an error if the request fails.
+fn or_error_reply<F>(future: F) -> ReplyFuture
+where
+    F: 'static + Future<Item = Reply, Error = client::Error> + Send,
+{
+    Box::new(future.or_else(|err| -> Result<Reply, io::Error> { Ok(Reply::client_error(err)) }))
+}
+
+/// Increment the decimal integer value at the given key, retrying if the value is changed
+/// concurrently.
+fn incr(client: Client, key: Key) -> impl Future<Item = Reply, Error = client::Error> {
+    future::loop_fn((), move |()| {
+        let client = client.clone();
+        let key = key.clone();
+        client.get(key.clone()).and_then(move |old: Option<Value>| {
+            let new = match old {
+                None => Some(1),
+                Some(ref value) => parse_integer(value).and_then(|n| n.checked_add(1)),
+            };
+            let new = match new {
+                None => return Either::A(future::ok(Loop::Break(Reply::not_an_integer()))),
+                Some(new) => new,
+            };
+            let swapped = client
+                .cas(key, old, Some(new.to_string().into_bytes()))
+                .map(move |result| match result {
+                    Ok(()) => Loop::Break(Reply::Integer(new)),
+                    Err(_current) => Loop::Continue(()),
+                });
+            Either::B(swapped)
+        })
+    })
+}
+
+/// Produce the page of keys following the given cursor, along with the next cursor.
+fn scan(
+    client: &Client,
+    cursor: u64,
+    count: u64,
+    pattern: Option<Regex>,
+) -> impl Future<Item = Reply, Error = client::Error> {
+    client
+        .iter_keys()
+        .skip(cursor)
+        .take(count)
+        .collect()
+        .map(move |keys: Vec<Key>| {
+            let next = if (keys.len() as u64) < count { 0 } else { cursor + count };
+            let keys = keys
+                .into_iter()
+                .filter(|key| match pattern {
+                    None => true,
+                    Some(ref re) => str::from_utf8(key).map(|s| re.is_match(s)).unwrap_or(false),
+                })
+                .map(|key| Reply::Bulk(Some(key)))
+                .collect();
+            let next = Reply::Bulk(Some(next.to_string().into_bytes()));
+            Reply::Array(vec![next, Reply::Array(keys)])
+        })
+}
+
+/// Parse the cursor and options of a `SCAN` command.
+fn parse_scan(args: &[Vec<u8>]) -> Result<(u64, u64, Option<Regex>), Reply> {
+    let cursor = str::from_utf8(&args[0])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Reply::Error("ERR invalid cursor".to_string()))?;
+    let mut count = DEFAULT_SCAN_COUNT;
+    let mut pattern = None;
+    for option in args[1..].chunks(2) {
+        let value = str::from_utf8(&option[1]).map_err(|_| Reply::syntax_error())?;
+        match &String::from_utf8_lossy(&option[0]).to_uppercase()[..] {
+            "COUNT" => {
+                count = match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    Ok(_) => return Err(Reply::syntax_error()),
+                    Err(_) => return Err(Reply::not_an_integer()),
+                };
+            }
+            "MATCH" => {
+                let regex = KeyPattern::Glob(value.to_string()).to_regex();
+                pattern = Some(Regex::new(&regex).map_err(|_| Reply::syntax_error())?);
+            }
+            _ => return Err(Reply::syntax_error()),
+        }
+    }
+    Ok((cursor, count, pattern))
+}
+
+/// Parse a decimal integer value as Redis does, rejecting whitespace and leading `+` signs.
+fn parse_integer(value: &[u8]) -> Option<i64> {
+    let s = str::from_utf8(value).ok()?;
+    if s.starts_with('+') || s.trim() != s {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Parse a single command of at most `max_len` bytes from the beginning of the given bytes.
+///
+/// Produces the arguments of the command along with the number of bytes consumed, or `None` if
+/// the command is incomplete. Commands that are, or declare arguments, larger than `max_len`
+/// produce an error rather than waiting for more bytes.
+fn parse_command(bytes: &[u8], max_len: usize) -> io::Result<Option<(Vec<Vec<u8>>, usize)>> {
+    match parse_command_within(bytes, max_len)? {
+        None if bytes.len() > max_len => Err(invalid_data("command too large")),
+        Some((_, len)) if len > max_len => Err(invalid_data("command too large")),
+        parsed => Ok(parsed),
+    }
+}
+
+/// Parse a single command as described by `parse_command`, without limiting the size of
+/// incomplete commands.
+fn parse_command_within(
+    bytes: &[u8],
+    max_len: usize,
+) -> io::Result<Option<(Vec<Vec<u8>>, usize)>> {
+    let (line, mut pos) = match read_line(bytes, 0) {
+        None => return Ok(None),
+        Some(read) => read,
+    };
+    if !line.starts_with(b"*") {
+        let args = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.to_vec())
+            .collect();
+        return Ok(Some((args, pos)));
+    }
+    let len = parse_len(&line[1..], max_len)?;
+    let mut args = vec![];
+    for _ in 0..len {
+        let (line, next) = match read_line(bytes, pos) {
+            None => return Ok(None),
+            Some(read) => read,
+        };
+        if !line.starts_with(b"$") {
+            return Err(invalid_data("expected a bulk string"));
+        }
+        let arg_len = parse_len(&line[1..], max_len)?;
+        let end = next
+            .checked_add(arg_len)
+            .filter(|end| end.checked_add(2).map_or(false, |end| end <= max_len))
+            .ok_or_else(|| invalid_data("command too large"))?;
+        if bytes.len() < end + 2 {
+            return Ok(None);
+        }
+        if &bytes[end..end + 2] != b"\r\n" {
+            return Err(invalid_data("expected CRLF following a bulk string"));
+        }
+        args.push(bytes[next..end].to_vec());
+        pos = end + 2;
+    }
+    Ok(Some((args, pos)))
+}
+
+/// The line beginning at `start`, excluding its CRLF, along with the position following it.
+fn read_line(bytes: &[u8], start: usize) -> Option<(&[u8], usize)> {
+    bytes[start..]
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .map(|ix| (&bytes[start..start + ix], start + ix + 2))
+}
+
+/// Parse the length of an array or bulk string, which may be no greater than `max_len`.
+///
+/// Negative lengths, e.g. of null bulk strings, are invalid within commands.
+fn parse_len(bytes: &[u8], max_len: usize) -> io::Result<usize> {
+    let len = str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid_data("invalid length"))?;
+    if len > max_len {
+        return Err(invalid_data("command too large"));
+    }
+    Ok(len)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write the given reply to the buffer.
+fn write_reply(reply: &Reply, buf: &mut BytesMut) {
+    match *reply {
+        Reply::Status(ref s) => write_line(buf, b'+', s.as_bytes()),
+        Reply::Error(ref s) => write_line(buf, b'-', s.as_bytes()),
+        Reply::Integer(n) => write_line(buf, b':', n.to_string().as_bytes()),
+        Reply::Bulk(None) => write_line(buf, b'$', b"-1"),
+        Reply::Bulk(Some(ref bytes)) => {
+            write_line(buf, b'$', bytes.len().to_string().as_bytes());
+            buf.reserve(bytes.len() + 2);
+            buf.put_slice(bytes);
+            buf.put_slice(b"\r\n");
+        }
+        Reply::Array(ref replies) => {
+            write_line(buf, b'*', replies.len().to_string().as_bytes());
+            for reply in replies {
+                write_reply(reply, buf);
+            }
+        }
+    }
+}
+
+fn write_line(buf: &mut BytesMut, prefix: u8, line: &[u8]) {
+    buf.reserve(line.len() + 3);
+    buf.put_u8(prefix);
+    buf.put_slice(line);
+    buf.put_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_command;
+
+    const MAX_LEN: usize = 64;
+
+    #[test]
+    fn complete_command() {
+        let bytes = b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n*1\r\n";
+        let (args, len) = parse_command(bytes, MAX_LEN).unwrap().unwrap();
+        assert_eq!(args, vec![b"GET".to_vec(), b"key".to_vec()]);
+        assert_eq!(len, bytes.len() - 4);
+    }
+
+    #[test]
+    fn truncated_command() {
+        for bytes in &[&b"*2\r\n$3\r\nGET\r\n$3\r\nke"[..], b"*2\r\n$3\r\nGET\r\n", b"*2\r"] {
+            assert!(parse_command(bytes, MAX_LEN).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn oversized_lengths() {
+        let overflowing = format!("*1\r\n${}\r\n", ::std::usize::MAX);
+        assert!(parse_command(overflowing.as_bytes(), MAX_LEN).is_err());
+        assert!(parse_command(b"*1\r\n$65\r\n", MAX_LEN).is_err());
+        assert!(parse_command(b"*100000\r\n", MAX_LEN).is_err());
+        // Inline commands, complete or not, may not grow beyond the limit either.
+        assert!(parse_command(&[b'a'; MAX_LEN + 1], MAX_LEN).is_err());
+        let mut inline = vec![b'a'; MAX_LEN];
+        inline.extend_from_slice(b"\r\n");
+        assert!(parse_command(&inline, MAX_LEN).is_err());
+    }
+
+    #[test]
+    fn negative_lengths() {
+        assert!(parse_command(b"*-1\r\n", MAX_LEN).is_err());
+        assert!(parse_command(b"*1\r\n$-1\r\n", MAX_LEN).is_err());
+    }
+}
@@ -1,20 +1,56 @@
-use futures;
-use hyper::{self, Body, Chunk, Request, Response, StatusCode};
+use base64;
+use encoding::{self, Encoding};
+use futures::{self, Async, Poll, Sink};
+use futures::future::Either;
+use futures::sync::mpsc;
+use hyper::{self, Body, Chunk, HeaderMap, Method, Request, Response, StatusCode, Uri};
+use hyper::header;
 use hyper::rt::{Future, Stream};
+use query;
+use regex::Regex;
 use request::{self, RequestType};
 use serde::Deserialize;
 use serde_json;
+use sha1::Sha1;
 use sled;
-use sled_search;
+use tokio::executor::{DefaultExecutor, Executor};
+use tokio_threadpool;
+use tokio_timer::Delay;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::protocol::{Message, Role};
+use tree::{self, Tree};
+use std::collections::{BTreeMap, VecDeque};
+use std::cmp;
 use std::error::Error as StdError;
 use std::fmt;
 use std::mem;
+use std::ops::Bound;
+use std::str;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// The number of chunks of a streamed response, e.g. entries of an `Iter`, produced ahead of the
+/// client consuming them.
+pub const STREAM_BUFFER: usize = 16;
+
+/// How the body of a request is deserialized, determined by the request's head via
+/// `request_decoding`.
+#[derive(Clone, Debug)]
+pub struct RequestDecoding {
+    encoding: Encoding,
+    /// The query of a `GET` or `HEAD` request, from which the request is deserialized if its body
+    /// is empty.
+    query: Option<String>,
+}
 
 /// Types that may be produced in response to some request.
 pub trait IntoResponse {
     /// Respond to the given request body, updating the `sled::Tree` as necessary.
-    fn into_response(self, Arc<sled::Tree>) -> Response<Body>;
+    ///
+    /// Successful response bodies are serialized in the given encoding, while errors are always
+    /// described by a JSON string.
+    fn into_response(self, Arc<Tree>, Encoding) -> Response<Body>;
 }
 
 /// A response to some request wrapped in a `Future`.
@@ -24,35 +60,250 @@ pub type ResponseFuture = Box<Future<Item = Response<Body>, Error = hyper::Error
 #[derive(Debug)]
 pub struct UnknownRequest;
 
-/// A wrapper around the `sled::Tree` iterator which is `'static`.
+/// A wrapper around the `Tree` iterator which is `'static`.
 ///
 /// This is achieved by unsafely transmuting the lifetime of the iterator to `'static`. We can do
 /// so safely by ensuring that the `Arc<Tree>` to which the original lifetime was bound is stored
 /// alongside the iterator itself, guaranteeing that the `Tree` live at least as long as this
 /// `Iter`.
 struct Iter {
-    _tree: Arc<sled::Tree>,
-    iter: sled::Iter<'static>,
+    _tree: Arc<Tree>,
+    iter: tree::Iter<'static>,
+}
+
+/// A wrapper around the `Tree::scan_suffix` iterator which is `'static`.
+///
+/// See `Iter` for details on how the `'static` lifetime is achieved safely.
+struct ScanSuffix {
+    _tree: Arc<Tree>,
+    iter: tree::ScanSuffix<'static>,
+}
+
+/// An iterator yielding the entries of a `sled::Tree` in descending order of their keys.
+///
+/// Each step performs a `Tree::pred` search from the previously yielded key, so only as
+/// many entries as are consumed are ever visited. The iterator is double-ended, with each step
+/// from the back performing a `Tree::scan` search from the lowest key not yet yielded, and so
+/// may be consumed from both ends of the range until they meet.
+struct RevIter {
+    tree: Arc<Tree>,
+    /// The inclusive lower bound of the range, advanced past each entry yielded from the back.
+    start: Vec<u8>,
+    /// The exclusive upper bound of the range. `None` indicates the iterator should begin from the
+    /// greatest entry in the tree.
+    end: Option<Vec<u8>>,
+    done: bool,
+}
+
+/// Ends the iterators that it wraps once cancelled, e.g. as the client consuming the entries has
+/// gone away. See `bounded_body`.
+#[derive(Clone, Default)]
+struct Cancel {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// An iterator that ends early once its `Cancel` is cancelled.
+///
+/// The wrapped iterator is checked for cancellation before every step, and so iterators that
+/// skip many entries, e.g. those filtered by value, stop promptly.
+struct Cancellable<I> {
+    iter: I,
+    cancel: Cancel,
+}
+
+/// A stream yielding the items of an iterator, each produced within a blocking section.
+///
+/// See `blocking`.
+struct BlockingIter<I> {
+    iter: I,
+}
+
+/// A streamed response body fed by some `feed` via a bounded channel. See `bounded_body`.
+///
+/// Cancels its `Cancel` once dropped.
+struct BoundedBody<F> {
+    /// Feeds the channel until spawned upon the first poll of the body. Remains here, driven
+    /// along with the body, if there is no executor on which to spawn it.
+    feed: Option<F>,
+    chunks: mpsc::Receiver<Result<Chunk, Box<StdError + Send + Sync>>>,
+    cancel: Cancel,
+}
+
+/// An iterator yielding the chunks of an `Export` stream of the given entries.
+///
+/// The end of the stream is only produced once all entries have been read without error.
+struct ExportChunks<I> {
+    entries: I,
+    /// The sequence number of the header, until the header is produced.
+    header: Option<u64>,
+    /// The number of entries produced so far.
+    count: u64,
+    done: bool,
+}
+
+/// A stream coalescing the changes to each key made within some window of the first.
+///
+/// Pending events are kept in order of sequence number, so that events are produced in that
+/// order. See `request::Watch` for details.
+struct Coalesce<S> {
+    events: S,
+    window: Duration,
+    /// Pending events along with the moment each is due to be produced.
+    pending: VecDeque<(Instant, request::Event)>,
+    delay: Option<Delay>,
+    done: bool,
 }
 
 impl IntoResponse for request::Get {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         tree.get(&self.key)
-            .map(|value| {
-                let bytes = serde_json::to_vec(&value)
-                    .expect("failed to serialize value to JSON");
+            .map(|value| get_response(value, encoding))
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::GetMany {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        self.keys
+            .iter()
+            .map(|key| tree.get(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|values| {
+                let bytes = encoding.to_vec(&values)
+                    .expect("failed to serialize values");
                 Response::new(bytes.into())
             })
             .unwrap_or_else(|err| db_err_response(&err))
     }
 }
 
+impl IntoResponse for request::GetPrefix {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let mut map = BTreeMap::new();
+        for res in tree.scan(&self.prefix) {
+            let (k, v) = match res {
+                Err(err) => return db_err_response(&err),
+                Ok(kv) => kv,
+            };
+            if !k.starts_with(&self.prefix) {
+                break;
+            }
+            map.insert(k, v);
+        }
+        let bytes = encoding.to_vec(&request::EntryMap(map))
+            .expect("failed to serialize entries");
+        Response::new(bytes.into())
+    }
+}
+
 impl IntoResponse for request::Del {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         tree.del(&self.key)
             .map(|value| {
-                let bytes = serde_json::to_vec(&value)
-                    .expect("failed to serialize value to JSON");
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Export {
+    fn into_response(self, tree: Arc<Tree>, _encoding: Encoding) -> Response<Body> {
+        // See `Tree::snapshot`.
+        let seq = tree.change_seq();
+        let cancel = Cancel::default();
+        let entries = cancel.wrap(tree_iter(tree));
+        let chunks = ExportChunks { entries, header: Some(seq), count: 0, done: false };
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(bounded_body(chunks, cancel))
+            .expect("failed to construct `Export` response")
+    }
+}
+
+impl IntoResponse for request::ExportSince {
+    fn into_response(self, tree: Arc<Tree>, _encoding: Encoding) -> Response<Body> {
+        let tree::Delta { since, seq, entries, complete } = match tree.delta(self.since) {
+            Ok(delta) => delta,
+            Err(err) => return db_err_response(&err),
+        };
+        if !complete {
+            return changes_not_retained_response(since);
+        }
+        let count = entries.len() as u64;
+        let start = Chunk::from(request::encode_delta_header(since, seq));
+        let chunks = entries.into_iter().map(|(k, v)| match v {
+            Some(v) => Chunk::from(request::encode_export_entry(&k, &v)),
+            None => Chunk::from(request::encode_delta_removed(&k)),
+        });
+        let end = Chunk::from(request::encode_export_end(count));
+        let chunks = Some(start).into_iter().chain(chunks).chain(Some(end));
+        let chunks = futures::stream::iter_ok::<_, Box<StdError + Send + Sync>>(chunks);
+        let stream = Box::new(chunks) as Box<_>;
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(stream))
+            .expect("failed to construct `ExportSince` response")
+    }
+}
+
+impl IntoResponse for request::AckCursor {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        if !request::is_valid_view_name(&self.name) {
+            return bad_request_response("invalid cursor name");
+        }
+        tree.ack_cursor(&self.name, self.seq)
+            .map(|acked| {
+                let bytes = encoding.to_vec(&acked).expect("failed to serialize seq");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::GetCursor {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.cursor(&self.name)
+            .map(|acked| {
+                let bytes = encoding.to_vec(&acked).expect("failed to serialize seq");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::RemoveCursor {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.remove_cursor(&self.name)
+            .map(|acked| {
+                let bytes = encoding.to_vec(&acked).expect("failed to serialize seq");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::DelIfEqual {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::DelIfEqual { key, expected } = self;
+        let deleted = match tree.cas(key, Some(expected), None) {
+            Ok(()) => true,
+            Err(sled::Error::CasFailed(_)) => false,
+            Err(err) => return db_err_response(&err),
+        };
+        let bytes = encoding.to_vec(&deleted)
+            .expect("failed to serialize result");
+        Response::new(bytes.into())
+    }
+}
+
+impl IntoResponse for request::DelPrefix {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.del_prefix(&self.prefix)
+            .map(|count| {
+                let bytes = encoding.to_vec(&count)
+                    .expect("failed to serialize count");
                 Response::new(bytes.into())
             })
             .unwrap_or_else(|err| db_err_response(&err))
@@ -60,12 +311,12 @@ impl IntoResponse for request::Del {
 }
 
 impl IntoResponse for request::Set {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         let request::Set { key, value } = self;
         tree.set(key, value)
             .map(|value| {
-                let bytes = serde_json::to_vec(&value)
-                    .expect("failed to serialize value to JSON");
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
                 Response::builder()
                     .status(StatusCode::CREATED)
                     .body(bytes.into())
@@ -75,20 +326,48 @@ impl IntoResponse for request::Set {
     }
 }
 
+impl IntoResponse for request::Replace {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::Replace { key, value } = self;
+        tree.replace(key, value)
+            .map(|previous| {
+                let bytes = encoding.to_vec(&previous)
+                    .expect("failed to serialize value");
+                Response::builder()
+                    .status(StatusCode::CREATED)
+                    .body(bytes.into())
+                    .expect("failed to construct `Replace` response")
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::SetMany {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let results: Vec<Result<(), String>> = self.entries
+            .into_iter()
+            .map(|(key, value)| tree.set(key, value).map_err(|err| format!("{}", err)))
+            .collect();
+        let bytes = encoding.to_vec(&results)
+            .expect("failed to serialize results");
+        Response::new(bytes.into())
+    }
+}
+
 impl IntoResponse for request::Cas {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         let request::Cas { key, old, new } = self;
         match tree.cas(key, old, new) {
             Ok(()) => {
                 let res: Result<(), Option<Vec<u8>>> = Ok(());
-                let bytes = serde_json::to_vec(&res)
-                    .expect("failed to serialize result to JSON");
+                let bytes = encoding.to_vec(&res)
+                    .expect("failed to serialize result");
                 Response::new(bytes.into())
             }
             Err(sled::Error::CasFailed(opt_bytes)) => {
                 let res: Result<(), Option<Vec<u8>>> = Err(opt_bytes);
-                let bytes = serde_json::to_vec(&res)
-                    .expect("failed to serialize result to JSON");
+                let bytes = encoding.to_vec(&res)
+                    .expect("failed to serialize result");
                 Response::new(bytes.into())
             }
             Err(err) => db_err_response(&err),
@@ -96,13 +375,51 @@ impl IntoResponse for request::Cas {
     }
 }
 
+impl IntoResponse for request::BatchCas {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let results: Vec<Result<(), request::CasError>> = self.ops
+            .into_iter()
+            .map(|request::Cas { key, old, new }| match tree.cas(key, old, new) {
+                Ok(()) => Ok(()),
+                Err(sled::Error::CasFailed(current)) => Err(request::CasError::Conflict(current)),
+                Err(err) => Err(request::CasError::Failed(format!("{}", err))),
+            })
+            .collect();
+        let bytes = encoding.to_vec(&results)
+            .expect("failed to serialize results");
+        Response::new(bytes.into())
+    }
+}
+
+/// Respond to a `BatchCas` for a client pinned to a wire format revision prior to 9.
+///
+/// Any error other than a failed comparison responds with `500 Internal Server Error`, leaving the
+/// remaining operations unperformed.
+fn legacy_batch_cas_response(
+    req: request::BatchCas,
+    tree: Arc<Tree>,
+    encoding: Encoding,
+) -> Response<Body> {
+    let mut results: Vec<Result<(), Option<Vec<u8>>>> = Vec::with_capacity(req.ops.len());
+    for request::Cas { key, old, new } in req.ops {
+        match tree.cas(key, old, new) {
+            Ok(()) => results.push(Ok(())),
+            Err(sled::Error::CasFailed(opt_bytes)) => results.push(Err(opt_bytes)),
+            Err(err) => return db_err_response(&err),
+        }
+    }
+    let bytes = encoding.to_vec(&results)
+        .expect("failed to serialize results");
+    Response::new(bytes.into())
+}
+
 impl IntoResponse for request::Merge {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         let request::Merge { key, value } = self;
         tree.merge(key, value)
             .map(|value| {
-                let bytes = serde_json::to_vec(&value)
-                    .expect("failed to serialize value to JSON");
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
                 Response::builder()
                     .status(StatusCode::CREATED)
                     .body(bytes.into())
@@ -112,12 +429,153 @@ impl IntoResponse for request::Merge {
     }
 }
 
+impl IntoResponse for request::Incr {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::Incr { key, delta } = self;
+        match tree.incr(key.clone(), delta) {
+            Err(err) => db_err_response(&err),
+            Ok(None) => incr_overflow_response(&key),
+            Ok(Some(value)) => {
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
+                Response::new(bytes.into())
+            }
+        }
+    }
+}
+
+impl IntoResponse for request::GetOrInsert {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::GetOrInsert { key, default } = self;
+        tree.get_or_insert(key, default)
+            .map(|value| {
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::PopMin {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.pop_min()
+            .map(|entry| {
+                let bytes = encoding.to_vec(&entry)
+                    .expect("failed to serialize entry");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::PopMax {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.pop_max()
+            .map(|entry| {
+                let bytes = encoding.to_vec(&entry)
+                    .expect("failed to serialize entry");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
 impl IntoResponse for request::Flush {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         tree.flush()
             .map(|value| {
-                let bytes = serde_json::to_vec(&value)
-                    .expect("failed to serialize value to JSON");
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Batch {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.batch(self.ops)
+            .map(|value| {
+                let bytes = encoding.to_vec(&value)
+                    .expect("failed to serialize value");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::GenerateId {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.generate_id()
+            .map(|id| {
+                let bytes = encoding.to_vec(&id)
+                    .expect("failed to serialize id");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Len {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.len()
+            .map(|len| {
+                let bytes = encoding.to_vec(&len)
+                    .expect("failed to serialize len");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Stats {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.stats(self.max_age_ms.map(Duration::from_millis))
+            .map(|stats| {
+                let bytes = encoding.to_vec(&stats)
+                    .expect("failed to serialize stats");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Clear {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        if self.confirm != request::CLEAR_CONFIRMATION {
+            return bad_request_response("`confirm` must equal `request::CLEAR_CONFIRMATION`");
+        }
+        tree.clear()
+            .map(|count| {
+                let bytes = encoding.to_vec(&count)
+                    .expect("failed to serialize count");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Transaction {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::Transaction { conditions, writes } = self;
+        tree.transaction(conditions, writes)
+            .map(|outcome| {
+                let bytes = encoding.to_vec(&outcome)
+                    .expect("failed to serialize outcome");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Rename {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::Rename { from, to, overwrite } = self;
+        tree.rename(from, to, overwrite)
+            .map(|outcome| {
+                let bytes = encoding.to_vec(&outcome)
+                    .expect("failed to serialize outcome");
                 Response::new(bytes.into())
             })
             .unwrap_or_else(|err| db_err_response(&err))
@@ -125,66 +583,255 @@ impl IntoResponse for request::Flush {
 }
 
 impl IntoResponse for request::Iter {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        let iter = tree_iter(tree)
-            .map(|res| {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let filter = self.filter;
+        let cancel = Cancel::default();
+        let iter = cancel.wrap(tree_iter(tree))
+            .filter(move |res| filter_matches(filter.as_ref(), res))
+            .take(limit(self.limit))
+            .map(move |res| {
                 let kv = res.map_err(|err| Box::new(err))?;
-                let bytes = serde_json::to_vec(&kv).map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
                 Ok(Chunk::from(bytes))
             });
-        let stream = Box::new(futures::stream::iter_result(iter)) as Box<_>;
         Response::builder()
-            .body(Body::from(stream))
+            .body(bounded_body(iter, cancel))
             .expect("failed to construct `Iter` response")
     }
 }
 
 impl IntoResponse for request::Scan {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        let scan = tree_scan(tree, &self.key)
-            .map(|res| {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let filter = self.filter;
+        let cancel = Cancel::default();
+        let scan = cancel.wrap(tree_scan(tree, &self.key))
+            .filter(move |res| filter_matches(filter.as_ref(), res))
+            .take(limit(self.limit))
+            .map(move |res| {
                 let kv = res.map_err(|err| Box::new(err))?;
-                let bytes = serde_json::to_vec(&kv).map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
                 Ok(Chunk::from(bytes))
             });
-        let stream = Box::new(futures::stream::iter_result(scan)) as Box<_>;
         Response::builder()
-            .body(Body::from(stream))
+            .body(bounded_body(scan, cancel))
             .expect("failed to construct `Iter` response")
     }
 }
 
 impl IntoResponse for request::ScanRange {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        let request::ScanRange { start, end } = self;
-        let scan = tree_scan(tree, &start)
-            .filter_map(move |res| {
-                let (k, v) = match res {
-                    Err(err) => return Some(Err(Box::new(err) as Box<StdError + Send + Sync>)),
-                    Ok(kv) => kv,
-                };
-                if k >= end {
-                    return None;
-                }
-                let bytes = match serde_json::to_vec(&(k, v)) {
-                    Err(err) => return Some(Err(Box::new(err))),
-                    Ok(bytes) => bytes,
-                };
-                Some(Ok(Chunk::from(bytes)))
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let (start, skip_start) = match self.start_bound() {
+            Bound::Included(key) => (key.to_vec(), false),
+            Bound::Excluded(key) => (key.to_vec(), true),
+            Bound::Unbounded => (vec![], false),
+        };
+        let end = match self.end_bound() {
+            Bound::Included(key) => Bound::Included(key.to_vec()),
+            Bound::Excluded(key) => Bound::Excluded(key.to_vec()),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let cancel = Cancel::default();
+        let scan = cancel.wrap(tree_scan(tree, &start));
+        let scan = scan
+            .skip_while(move |res| match *res {
+                Ok((ref k, _)) => skip_start && *k == start,
+                Err(_) => false,
+            })
+            .take_while(move |res| match *res {
+                Ok((ref k, _)) => match end {
+                    Bound::Included(ref end) => k <= end,
+                    Bound::Excluded(ref end) => k < end,
+                    Bound::Unbounded => true,
+                },
+                Err(_) => true,
+            })
+            .take(limit(self.limit))
+            .map(move |res| {
+                let kv = res.map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
+                Ok(Chunk::from(bytes))
             });
-        let stream = Box::new(futures::stream::iter_result(scan)) as Box<_>;
         Response::builder()
-            .body(Body::from(stream))
+            .body(bounded_body(scan, cancel))
             .expect("failed to construct `Iter` response")
     }
 }
 
+impl IntoResponse for request::HashPrefix {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.hash_prefix(&self.prefix)
+            .map(|hashes| {
+                let bytes = encoding.to_vec(&hashes)
+                    .expect("failed to serialize hashes");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::CountRange {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.count_range(&self.start, &self.end)
+            .map(|count| {
+                let bytes = encoding.to_vec(&count)
+                    .expect("failed to serialize count");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::TopValues {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.top_values(limit(Some(self.count)))
+            .map(|top| {
+                let bytes = encoding.to_vec(&top)
+                    .expect("failed to serialize entry sizes");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::Aggregate {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::Aggregate { start, end, op, value_type } = self;
+        tree.aggregate(&start, &end, op, value_type)
+            .map(|result| {
+                let bytes = encoding.to_vec(&result)
+                    .expect("failed to serialize result");
+                Response::new(bytes.into())
+            })
+            .unwrap_or_else(|err| db_err_response(&err))
+    }
+}
+
+impl IntoResponse for request::IterPage {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::IterPage { cursor, page_size } = self;
+        if page_size == 0 {
+            return bad_request_response("the page size must be non-zero");
+        }
+        let start = match cursor {
+            None => vec![],
+            Some(cursor) => match decode_cursor(&cursor) {
+                Some(start) => start,
+                None => return bad_request_response("invalid cursor"),
+            },
+        };
+        // Retrieve one extra entry to determine where the following page begins.
+        let page_size = cmp::min(page_size, request::MAX_PAGE_SIZE);
+        let take = limit(Some(page_size)).saturating_add(1);
+        let mut entries = match tree.scan(&start).take(take).collect::<Result<Vec<_>, _>>() {
+            Err(err) => return db_err_response(&err),
+            Ok(entries) => entries,
+        };
+        let next_cursor = if entries.len() == take {
+            entries.pop().map(|(k, _)| encode_cursor(&k))
+        } else {
+            None
+        };
+        let page = request::Page { entries, next_cursor };
+        let bytes = encoding.to_vec(&page).expect("failed to serialize page");
+        Response::new(bytes.into())
+    }
+}
+
+impl IntoResponse for request::ScanSuffix {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let scan = match tree_scan_suffix(tree, &self.suffix) {
+            None => return no_suffix_index_response(),
+            Some(scan) => scan,
+        };
+        let cancel = Cancel::default();
+        let scan = cancel.wrap(scan)
+            .take(limit(self.limit))
+            .map(move |res| {
+                let kv = res.map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
+                Ok(Chunk::from(bytes))
+            });
+        Response::builder()
+            .body(bounded_body(scan, cancel))
+            .expect("failed to construct `ScanSuffix` response")
+    }
+}
+
+impl IntoResponse for request::IterKeys {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        keys_response(tree_iter(tree), self.limit, encoding)
+    }
+}
+
+impl IntoResponse for request::ScanKeys {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        keys_response(tree_scan(tree, &self.key), self.limit, encoding)
+    }
+}
+
+impl IntoResponse for request::ScanMatching {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let regex = match Regex::new(&self.pattern.to_regex()) {
+            Ok(regex) => regex,
+            Err(err) => return bad_request_response(&format!("invalid pattern: {}", err)),
+        };
+        let cancel = Cancel::default();
+        let iter = cancel.wrap(tree_iter(tree))
+            .filter(move |res| match *res {
+                Ok((ref k, _)) => str::from_utf8(k).map(|k| regex.is_match(k)).unwrap_or(false),
+                Err(_) => true,
+            })
+            .take(limit(self.limit))
+            .map(move |res| {
+                let kv = res.map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
+                Ok(Chunk::from(bytes))
+            });
+        Response::builder()
+            .body(bounded_body(iter, cancel))
+            .expect("failed to construct `ScanMatching` response")
+    }
+}
+
+impl IntoResponse for request::IterRev {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let cancel = Cancel::default();
+        let iter = cancel.wrap(RevIter { tree, start: vec![], end: None, done: false })
+            .take(limit(self.limit))
+            .map(move |res| {
+                let kv = res.map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
+                Ok(Chunk::from(bytes))
+            });
+        Response::builder()
+            .body(bounded_body(iter, cancel))
+            .expect("failed to construct `IterRev` response")
+    }
+}
+
+impl IntoResponse for request::ScanRangeRev {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        let request::ScanRangeRev { start, end, limit: max } = self;
+        let cancel = Cancel::default();
+        let iter = cancel.wrap(RevIter { tree, start, end: Some(end), done: false })
+            .take(limit(max))
+            .map(move |res| {
+                let kv = res.map_err(|err| Box::new(err))?;
+                let bytes = encoding.to_vec(&kv)?;
+                Ok(Chunk::from(bytes))
+            });
+        Response::builder()
+            .body(bounded_body(iter, cancel))
+            .expect("failed to construct `ScanRangeRev` response")
+    }
+}
+
 impl IntoResponse for request::Max {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        sled_search::max(&tree)
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.max()
             .map(|entry| {
-                let bytes = serde_json::to_vec(&entry)
-                    .expect("failed to serialize entry to JSON");
+                let bytes = encoding.to_vec(&entry)
+                    .expect("failed to serialize entry");
                 Response::builder()
                     .body(bytes.into())
                     .expect("failed to construct `Max` response")
@@ -194,11 +841,11 @@ impl IntoResponse for request::Max {
 }
 
 impl IntoResponse for request::Pred {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        sled_search::pred(&tree, &self.key)
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.pred(&self.key)
             .map(|entry| {
-                let bytes = serde_json::to_vec(&entry)
-                    .expect("failed to serialize entry to JSON");
+                let bytes = encoding.to_vec(&entry)
+                    .expect("failed to serialize entry");
                 Response::new(bytes.into())
             })
             .unwrap_or_else(|err| db_err_response(&err))
@@ -206,11 +853,11 @@ impl IntoResponse for request::Pred {
 }
 
 impl IntoResponse for request::PredIncl {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
-        sled_search::pred_incl(&tree, &self.key)
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
+        tree.pred_incl(&self.key)
             .map(|entry| {
-                let bytes = serde_json::to_vec(&entry)
-                    .expect("failed to serialize entry to JSON");
+                let bytes = encoding.to_vec(&entry)
+                    .expect("failed to serialize entry");
                 Response::new(bytes.into())
             })
             .unwrap_or_else(|err| db_err_response(&err))
@@ -218,39 +865,271 @@ impl IntoResponse for request::PredIncl {
 }
 
 impl IntoResponse for request::Succ {
-    fn into_response(mut self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(mut self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         self.key.push(0);
         let entry = match tree.scan(&self.key).next() {
             Some(Err(err)) => return db_err_response(&err),
             Some(Ok(entry)) => Some(entry),
             None => None,
         };
-        let bytes = serde_json::to_vec(&entry)
-            .expect("failed to serialize entry to JSON");
+        let bytes = encoding.to_vec(&entry)
+            .expect("failed to serialize entry");
         Response::new(bytes.into())
     }
 }
 
 impl IntoResponse for request::SuccIncl {
-    fn into_response(self, tree: Arc<sled::Tree>) -> Response<Body> {
+    fn into_response(self, tree: Arc<Tree>, encoding: Encoding) -> Response<Body> {
         let entry = match tree.scan(&self.key).next() {
             Some(Err(err)) => return db_err_response(&err),
             Some(Ok(entry)) => Some(entry),
             None => None,
         };
-        let bytes = serde_json::to_vec(&entry)
-            .expect("failed to serialize entry to JSON");
+        let bytes = encoding.to_vec(&entry)
+            .expect("failed to serialize entry");
         Response::new(bytes.into())
     }
 }
 
-impl Iterator for Iter {
+impl RequestDecoding {
+    /// Deserialize a request of type `T` from the given body.
+    pub fn decode<T>(&self, body: &[u8]) -> Result<T, Box<StdError + Send + Sync>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        match self.query {
+            Some(ref query) if body.is_empty() => Ok(query::from_query(query)?),
+            _ => self.encoding.from_slice(body),
+        }
+    }
+}
+
+impl Iterator for Iter {
     type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next()
     }
 }
 
+impl<I> Iterator for ExportChunks<I>
+where
+    I: Iterator<Item = sled::Result<(Vec<u8>, Vec<u8>), ()>>,
+{
+    type Item = Result<Chunk, Box<StdError + Send + Sync>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(seq) = self.header.take() {
+            return Some(Ok(Chunk::from(request::encode_export_header(seq))));
+        }
+        if self.done {
+            return None;
+        }
+        match self.entries.next() {
+            Some(Ok((k, v))) => {
+                self.count += 1;
+                Some(Ok(Chunk::from(request::encode_export_entry(&k, &v))))
+            }
+            Some(Err(err)) => {
+                self.done = true;
+                Some(Err(Box::new(err)))
+            }
+            None => {
+                self.done = true;
+                Some(Ok(Chunk::from(request::encode_export_end(self.count))))
+            }
+        }
+    }
+}
+
+impl Iterator for ScanSuffix {
+    type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+impl Iterator for RevIter {
+    type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = match self.end {
+            None => self.tree.max(),
+            Some(ref end) => self.tree.pred(end),
+        };
+        match res {
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+            Ok(Some((k, v))) => {
+                if k < self.start {
+                    self.done = true;
+                    return None;
+                }
+                self.end = Some(k.clone());
+                Some(Ok((k, v)))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl DoubleEndedIterator for RevIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.tree.scan(&self.start).next() {
+            Some(Err(err)) => {
+                self.done = true;
+                Some(Err(err))
+            }
+            Some(Ok((k, v))) => {
+                if self.end.as_ref().map_or(false, |end| k >= *end) {
+                    self.done = true;
+                    return None;
+                }
+                // The least key following `k`.
+                self.start = k.clone();
+                self.start.push(0);
+                Some(Ok((k, v)))
+            }
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl Cancel {
+    /// Wrap the given iterator so that it ends once this is cancelled.
+    fn wrap<I>(&self, iter: I) -> Cancellable<I> {
+        let cancel = self.clone();
+        Cancellable { iter, cancel }
+    }
+
+    /// End all iterators wrapped via this `Cancel`.
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether or not `cancel` has been called.
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl<I> Iterator for Cancellable<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cancel.is_cancelled() {
+            return None;
+        }
+        self.iter.next()
+    }
+}
+
+impl<I> Stream for BlockingIter<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+    type Error = ();
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let iter = &mut self.iter;
+        match tokio_threadpool::blocking(|| iter.next()) {
+            Ok(next) => Ok(next),
+            // Not within a `tokio` thread pool.
+            Err(_) => Ok(Async::Ready(iter.next())),
+        }
+    }
+}
+
+impl<F> Stream for BoundedBody<F>
+where
+    F: 'static + Future<Item = (), Error = ()> + Send,
+{
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(mut feed) = self.feed.take() {
+            let mut executor = DefaultExecutor::current();
+            if executor.status().is_ok() {
+                executor.spawn(Box::new(feed))?;
+            } else if let Ok(Async::NotReady) = feed.poll() {
+                self.feed = Some(feed);
+            }
+        }
+        match self.chunks.poll() {
+            Ok(Async::Ready(Some(result))) => result.map(|chunk| Async::Ready(Some(chunk))),
+            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(()) => Err("the stream ended unexpectedly".into()),
+        }
+    }
+}
+
+impl<F> Drop for BoundedBody<F> {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl<S> Coalesce<S> {
+    /// Queue the event, coalescing it with any pending event for the same key.
+    ///
+    /// The coalesced event moves to the back of the queue as it has the greatest sequence number,
+    /// though it remains due when the first of the coalesced events was due.
+    fn push(&mut self, event: request::Event) {
+        let position = self.pending.iter().position(|&(_, ref pending)| pending.key == event.key);
+        let (due, event) = match position.and_then(|i| self.pending.remove(i)) {
+            None => (Instant::now() + self.window, event),
+            Some((due, first)) => (due, request::Event { old: first.old, ..event }),
+        };
+        self.pending.push_back((due, event));
+    }
+}
+
+impl<S> Stream for Coalesce<S>
+where
+    S: Stream<Item = request::Event, Error = ()>,
+{
+    type Item = request::Event;
+    type Error = ();
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        while !self.done {
+            match self.events.poll()? {
+                Async::Ready(Some(event)) => self.push(event),
+                Async::Ready(None) => self.done = true,
+                Async::NotReady => break,
+            }
+        }
+        let due = match self.pending.front() {
+            None if self.done => return Ok(Async::Ready(None)),
+            None => return Ok(Async::NotReady),
+            Some(&(due, _)) => due,
+        };
+        // Once the source ends, the remaining events are produced without waiting.
+        if !self.done && due > Instant::now() {
+            let delay = self.delay.get_or_insert_with(|| Delay::new(due));
+            delay.reset(due);
+            // A timer error, e.g. due to a lack of a timer, produces the event immediately.
+            if let Ok(Async::NotReady) = delay.poll() {
+                return Ok(Async::NotReady);
+            }
+        }
+        Ok(Async::Ready(self.pending.pop_front().map(|(_, event)| event)))
+    }
+}
+
 impl StdError for UnknownRequest {
     fn description(&self) -> &str {
         "no known valid response for the given request"
@@ -263,73 +1142,741 @@ impl fmt::Display for UnknownRequest {
     }
 }
 
+/// The maximum number of entries to yield for the given optional request `limit`.
+fn limit(limit: Option<u64>) -> usize {
+    match limit {
+        None => usize::max_value(),
+        Some(limit) if limit > usize::max_value() as u64 => usize::max_value(),
+        Some(limit) => limit as usize,
+    }
+}
+
+/// Whether or not the given iterator item should be streamed back under the given value filter.
+///
+/// Errors always pass so that they reach the client.
+fn filter_matches<E>(
+    filter: Option<&request::ValueFilter>,
+    res: &Result<(Vec<u8>, Vec<u8>), E>,
+) -> bool {
+    match (filter, res) {
+        (Some(filter), &Ok((_, ref value))) => filter.matches(value),
+        _ => true,
+    }
+}
+
+/// Produce a streamed response body from the given iterator via a bounded channel.
+///
+/// The iterator is driven by a task spawned upon the first poll of the body, advancing it within
+/// blocking sections as described for `blocking`. It is spawned lazily, so that no entries
+/// are read for responses that are never sent, e.g. those replaced due to the stream limit. The
+/// task parks once `STREAM_BUFFER` chunks await the client, and so the iterator is advanced only
+/// as fast as the client consumes the body. Slow clients do not cause the remaining entries to be
+/// buffered in memory. Without an executor, e.g. for a `Client::local` driven via `wait`, the
+/// iterator is driven along with the body instead.
+///
+/// Once the body is dropped, e.g. as the client has disconnected, the given `cancel` is
+/// cancelled and the task ends, dropping the iterator along with its `Arc<Tree>` rather than
+/// scanning to completion for nobody.
+fn bounded_body<I>(iter: I, cancel: Cancel) -> Body
+where
+    I: 'static + Send + Iterator<Item = Result<Chunk, Box<StdError + Send + Sync>>>,
+{
+    let (sender, chunks) = mpsc::channel(STREAM_BUFFER);
+    let feed = BlockingIter { iter }
+        .forward(sender.sink_map_err(|_| ()))
+        .map(|_| ());
+    Body::wrap_stream(BoundedBody { feed: Some(feed), chunks, cancel })
+}
+
+/// A response streaming only the keys of the entries yielded by the given iterator, ending after
+/// at most `max` keys if `Some`.
+fn keys_response(iter: Iter, max: Option<u64>, encoding: Encoding) -> Response<Body> {
+    let cancel = Cancel::default();
+    let keys = cancel.wrap(iter)
+        .take(limit(max))
+        .map(move |res| {
+            let (k, _) = res.map_err(|err| Box::new(err))?;
+            let bytes = encoding.to_vec(&k)?;
+            Ok(Chunk::from(bytes))
+        });
+    Response::builder()
+        .body(bounded_body(keys, cancel))
+        .expect("failed to construct keys response")
+}
+
+/// Encode the key at which a page begins as an opaque cursor.
+fn encode_cursor(key: &[u8]) -> String {
+    request::encode_key(key)
+}
+
+/// Decode a cursor produced by `encode_cursor`, or return `None` if it is invalid.
+fn decode_cursor(cursor: &str) -> Option<Vec<u8>> {
+    request::decode_key(cursor)
+}
+
 /// Produce an iterator over all elements within the given `Tree` with a static lifetime.
-fn tree_iter(tree: Arc<sled::Tree>) -> Iter {
+fn tree_iter(tree: Arc<Tree>) -> Iter {
     let _tree = tree.clone();
-    let iter: sled::Iter = tree.iter();
-    let iter: sled::Iter<'static> = unsafe { mem::transmute(iter) };
+    let iter: tree::Iter = tree.iter();
+    let iter: tree::Iter<'static> = unsafe { mem::transmute(iter) };
     Iter { _tree, iter }
 }
 
 /// Produce a `scan` iterator over all elements within the given `Tree` with a static lifetime.
-fn tree_scan(tree: Arc<sled::Tree>, key: &[u8]) -> Iter {
+fn tree_scan(tree: Arc<Tree>, key: &[u8]) -> Iter {
     let _tree = tree.clone();
-    let iter: sled::Iter = tree.scan(key);
-    let iter: sled::Iter<'static> = unsafe { mem::transmute(iter) };
+    let iter: tree::Iter = tree.scan(key);
+    let iter: tree::Iter<'static> = unsafe { mem::transmute(iter) };
     Iter { _tree, iter }
 }
 
-/// Deserialize a request of type `T` and produce a response.
-fn deserialize_and_respond<T>(bytes: &[u8], tree: Arc<sled::Tree>) -> Response<Body>
-where
-    T: IntoResponse + for<'de> Deserialize<'de>,
-{
-    serde_json::from_slice(bytes)
-        .map(|req: T| req.into_response(tree))
-        .unwrap_or_else(|err| deserialization_err_response(&err))
+/// Produce a `scan_suffix` iterator over the given `Tree` with a static lifetime.
+///
+/// Returns `None` if the `Tree` does not maintain a suffix index.
+fn tree_scan_suffix(tree: Arc<Tree>, suffix: &[u8]) -> Option<ScanSuffix> {
+    let _tree = tree.clone();
+    let iter: tree::ScanSuffix = tree.scan_suffix(suffix)?;
+    let iter: tree::ScanSuffix<'static> = unsafe { mem::transmute(iter) };
+    Some(ScanSuffix { _tree, iter })
 }
 
 /// Concatenate the given request body into a request of type `T` and produce a response.
+///
+/// Successful responses are serialized directly into bincode if the client prefers it, and
+/// otherwise into JSON, which `encoding::encode_response` converts to any other preference.
 fn concat_and_respond<T>(
     request: Request<Body>,
-    tree: Arc<sled::Tree>,
+    tree: Arc<Tree>,
 ) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send
 where
     T: IntoResponse + for<'de> Deserialize<'de>,
 {
+    let encoding = response_encoding(&request);
+    concat_and_then(request, move |req: T| {
+        with_content_type(req.into_response(tree, encoding), encoding)
+    })
+}
+
+/// The encoding in which `IntoResponse` implementations serialize the body of their response.
+///
+/// Self-describing encodings are converted from JSON at the edge of the `server::Router`, so JSON
+/// is produced for these.
+fn response_encoding(request: &Request<Body>) -> Encoding {
+    match encoding::accepted_encoding(request.headers()) {
+        encoding if !encoding.is_self_describing() => encoding,
+        _ => Encoding::Json,
+    }
+}
+
+/// Specify the `Content-Type` of a successful response serialized in the given encoding, unless
+/// the response already specifies its own.
+fn with_content_type(mut response: Response<Body>, encoding: Encoding) -> Response<Body> {
+    let is_encoded = encoding != Encoding::Json
+        && response.status().is_success()
+        && !response.headers().contains_key(header::CONTENT_TYPE);
+    if is_encoded {
+        let media_type = header::HeaderValue::from_static(encoding.media_type());
+        response.headers_mut().insert(header::CONTENT_TYPE, media_type);
+    }
+    response
+}
+
+/// Concatenate the given request body, deserialize it into a `T` and produce a response via `f`.
+///
+/// This is useful for implementing custom endpoints that follow the same conventions as those
+/// handled by `response`. Bodies are deserialized from bincode if so specified by the request's
+/// `Content-Type`, and from JSON otherwise. A `GET` or `HEAD` request without a body is
+/// deserialized from its query parameters instead, if any. See the `query` module. Requests that
+/// fail to deserialize result in a `400 Bad Request` response.
+///
+/// If the client specified a deadline that has passed by the time the body has been received, the
+/// request is not processed and a `504 Gateway Timeout` response is produced instead.
+pub fn concat_and_then<T, F>(
+    request: Request<Body>,
+    f: F,
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send
+where
+    T: for<'de> Deserialize<'de>,
+    F: FnOnce(T) -> Response<Body> + Send,
+{
+    let deadline = request_deadline(&request);
+    let decoding = request_decoding(request.method(), request.uri(), request.headers());
     request
         .into_body()
         .concat2()
-        .map(move |chunk| deserialize_and_respond::<T>(&chunk, tree))
+        .and_then(move |chunk| blocking(move || {
+            if deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false) {
+                return deadline_exceeded_response();
+            }
+            decoding
+                .decode(&chunk)
+                .map(f)
+                .unwrap_or_else(|err| deserialization_err_response(&*err))
+        }))
 }
 
-/// Convert an error into a JSON string.
-fn err_to_json_bytes(err: &StdError) -> Vec<u8> {
-    let string = format!("{}", err);
-    serde_json::to_vec(&string)
-        .expect("failed to serialize error string")
+/// Determine how the body of a request with the given head is deserialized.
+///
+/// Bodies are deserialized from bincode if so specified by the `Content-Type`, and from JSON
+/// otherwise, as other self-describing encodings are converted to JSON at the edge of the
+/// `server::Router`. A `GET` or `HEAD` request without a body is deserialized from its query
+/// parameters instead, if any. See `concat_and_then`.
+pub fn request_decoding(method: &Method, uri: &Uri, headers: &HeaderMap) -> RequestDecoding {
+    let encoding = match encoding::content_encoding(headers) {
+        Some(encoding) if !encoding.is_self_describing() => encoding,
+        _ => Encoding::Json,
+    };
+    let query = match *method {
+        Method::GET | Method::HEAD => uri.query().map(str::to_string),
+        _ => None,
+    };
+    RequestDecoding { encoding, query }
+}
+
+/// Produce a value, e.g. a response, via `f` within a blocking section of the `tokio` thread pool.
+///
+/// `sled` operations block the calling thread on disk IO. Within a blocking section, the other
+/// tasks of the worker thread are handed to another worker for the duration, so that slow IO does
+/// not stall unrelated connections. The number of blocking sections in progress at once is
+/// capped by the runtime's `blocking_threads`, beyond which requests wait for capacity. See
+/// `server::ConfigBuilder::blocking_threads`.
+///
+/// Outside of a `tokio` thread pool, e.g. on a current-thread runtime, `f` is invoked directly.
+/// The `Future` never produces an error.
+pub fn blocking<F, T>(f: F) -> impl Future<Item = T, Error = hyper::Error> + Send
+where
+    F: FnOnce() -> T + Send,
+{
+    let mut f = Some(f);
+    futures::future::poll_fn(move || {
+        let result = tokio_threadpool::blocking(|| {
+            let f = f.take().expect("`blocking` polled after completion");
+            f()
+        });
+        match result {
+            Ok(Async::Ready(value)) => Ok(Async::Ready(value)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            // Not within a `tokio` thread pool.
+            Err(_) => {
+                let f = f.take().expect("`blocking` polled after completion");
+                Ok(Async::Ready(f()))
+            }
+        }
+    })
+}
+
+/// The instant by which the client expects a response, if it specified one.
+///
+/// See `request::DEADLINE` for the expected header format. Malformed values are ignored.
+pub fn request_deadline(request: &Request<Body>) -> Option<Instant> {
+    request
+        .headers()
+        .get(request::DEADLINE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(|ms| Instant::now() + Duration::from_millis(ms))
+}
+
+/// A response with the given error status and the given `request::ErrorBody` serialized to JSON.
+///
+/// Used for all error responses. See `request::ErrorBody`.
+pub fn error_response(status: StatusCode, body: &request::ErrorBody) -> Response<Body> {
+    let bytes = serde_json::to_vec(body).expect("failed to serialize error body");
+    Response::builder()
+        .status(status)
+        .body(bytes.into())
+        .expect("failed to construct error response")
+}
+
+/// A response with the given error status described by an `ErrorBody` with the given message.
+pub fn status_err_response(status: StatusCode, msg: &str) -> Response<Body> {
+    error_response(status, &request::ErrorBody::new(status, msg))
 }
 
 /// A response to a request that resulted in a sled DB error of some kind.
 ///
 /// Status: INTERNAL_SERVER_ERROR
-/// Body: `String` of error description.
+/// Body: `ErrorBody` with the `Database` code.
 fn db_err_response(err: &StdError) -> Response<Body> {
-    Response::builder()
-        .status(StatusCode::INTERNAL_SERVER_ERROR)
-        .body(err_to_json_bytes(err).into())
-        .expect("failed to construct INTERNAL_SERVER_ERROR response")
+    let body = request::ErrorBody::with_code(request::ErrorCode::Database, format!("{}", err));
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, &body)
+}
+
+/// A response to a `GET` of an entry that does not exist.
+///
+/// Status: NOT_FOUND
+/// Body: `ErrorBody` with the `NotFound` code and the key.
+fn missing_entry_response(key: Vec<u8>) -> Response<Body> {
+    let body = request::ErrorBody::new(StatusCode::NOT_FOUND, "no entry exists at the key");
+    error_response(StatusCode::NOT_FOUND, &body.key(key))
 }
 
 /// A response to a request that could not be successfully deserialized.
 ///
 /// Status: BAD_REQUEST
-/// Body: `String` of error description.
+/// Body: `ErrorBody` of error description.
 fn deserialization_err_response(err: &StdError) -> Response<Body> {
+    status_err_response(StatusCode::BAD_REQUEST, &format!("{}", err))
+}
+
+/// A response to a request that is well-formed but cannot be satisfied as described.
+///
+/// Status: BAD_REQUEST
+/// Body: `ErrorBody` of error description.
+fn bad_request_response(msg: &str) -> Response<Body> {
+    status_err_response(StatusCode::BAD_REQUEST, msg)
+}
+
+/// A response to a `Cas` that failed as the entry holds some other value.
+///
+/// Status: CONFLICT
+/// Body: `Option<Value>` of the current value.
+fn cas_conflict_response(current: Option<Vec<u8>>, encoding: Encoding) -> Response<Body> {
+    let bytes = encoding.to_vec(&current).expect("failed to serialize value");
+    let mut response = Response::builder()
+        .status(StatusCode::CONFLICT)
+        .body(bytes.into())
+        .expect("failed to construct CONFLICT response");
+    if encoding != Encoding::Json {
+        let media_type = header::HeaderValue::from_static(encoding.media_type());
+        response.headers_mut().insert(header::CONTENT_TYPE, media_type);
+    }
+    response
+}
+
+/// A response to an `Incr` whose delta would overflow the value of the entry.
+///
+/// Status: CONFLICT
+/// Body: `ErrorBody` of error description and the key of the entry.
+fn incr_overflow_response(key: &[u8]) -> Response<Body> {
+    let status = StatusCode::CONFLICT;
+    let msg = "adding the delta would overflow the value";
+    error_response(status, &request::ErrorBody::new(status, msg).key(key.to_vec()))
+}
+
+/// A response to a write made conditional via `If-Match` where the entry does not match.
+///
+/// Status: PRECONDITION_FAILED
+/// Body: `ErrorBody` of error description and the key of the entry.
+fn precondition_failed_response(key: &[u8]) -> Response<Body> {
+    let status = StatusCode::PRECONDITION_FAILED;
+    let msg = "the entry does not match the `If-Match` header";
+    error_response(status, &request::ErrorBody::new(status, msg).key(key.to_vec()))
+}
+
+/// A response to a `ScanSuffix` request for a `Tree` that does not maintain a suffix index.
+///
+/// Status: NOT_IMPLEMENTED
+/// Body: `ErrorBody` of error description.
+fn no_suffix_index_response() -> Response<Body> {
+    let msg = "no suffix index is maintained for this tree";
+    status_err_response(StatusCode::NOT_IMPLEMENTED, msg)
+}
+
+/// A response to an `ExportSince` request for changes that are no longer retained.
+///
+/// Status: GONE
+/// Body: `ErrorBody` of error description.
+fn changes_not_retained_response(since: u64) -> Response<Body> {
+    let msg = format!("the changes following {} are no longer retained", since);
+    status_err_response(StatusCode::GONE, &msg)
+}
+
+/// A response to a request whose client-specified deadline passed before its response began.
+///
+/// Status: GATEWAY_TIMEOUT
+/// Body: `ErrorBody` of error description.
+pub fn deadline_exceeded_response() -> Response<Body> {
+    status_err_response(StatusCode::GATEWAY_TIMEOUT, "request deadline exceeded")
+}
+
+/// Respond to a `Subscribe` request by upgrading the connection to a WebSocket and pushing every
+/// subsequent change to the `Tree` that matches the request's prefix and filter over it as a JSON
+/// `request::Event` text message.
+///
+/// The upgrade is only possible when the request was received by a hyper server. The WebSocket is
+/// served by a task spawned onto the current executor that ends when either side closes it.
+fn subscribe(request: Request<Body>, tree: Arc<Tree>) -> Response<Body> {
+    let accept = match websocket_accept(&request) {
+        Some(accept) => accept,
+        None => return bad_request_response("expected a WebSocket handshake"),
+    };
+    let (prefix, filter) = match (watch_prefix(&request), event_filter_query(&request)) {
+        (Some(prefix), Some(filter)) => (prefix, filter),
+        _ => return bad_request_response("invalid `Subscribe` query parameters"),
+    };
+    let window = match coalesce_window(&request) {
+        Some(window) => window,
+        None => return bad_request_response("invalid `coalesce_ms` query parameter"),
+    };
+    let events = match tree.watch_filtered(&prefix, filter, None) {
+        Ok(watch) => coalesced(watch.events, window),
+        Err(err) => return db_err_response(&err),
+    };
+    let websocket = request
+        .into_body()
+        .on_upgrade()
+        .map_err(|_| ())
+        .and_then(move |upgraded| {
+            let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None);
+            let (sink, stream) = socket.split();
+            let outgoing = events
+                .map(|event| {
+                    let json = serde_json::to_string(&event)
+                        .expect("failed to serialize event to JSON");
+                    Message::Text(json)
+                })
+                .forward(sink.sink_map_err(|_| ()))
+                .map(|_| ());
+            // Incoming messages are discarded, though reading them allows pings to be answered
+            // and a close from the client to end the subscription.
+            let incoming = stream.for_each(|_| Ok(())).map_err(|_| ());
+            outgoing.select(incoming).then(|_| Ok(()))
+        });
+    hyper::rt::spawn(websocket);
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_ACCEPT, accept.as_str())
+        .body(Body::empty())
+        .expect("failed to construct SWITCHING_PROTOCOLS response")
+}
+
+/// Respond to an `Import` request by applying each entry of the streamed body as it is decoded.
+///
+/// Responds with the number of entries imported once the end of the export is received.
+fn import(request: Request<Body>, tree: Arc<Tree>) -> ResponseFuture {
+    let mode = match query_param(&request, "mode") {
+        None => request::ImportMode::default(),
+        Some(mode) => match request::ImportMode::from_name(mode) {
+            Some(mode) => mode,
+            None => {
+                let response = bad_request_response("invalid `mode` query parameter");
+                return Box::new(futures::future::ok(response));
+            }
+        },
+    };
+    let response = request
+        .into_body()
+        .fold((request::ExportDecoder::new(), Ok(0)), move |(mut decoder, imported), chunk| {
+            let tree = tree.clone();
+            blocking(move || {
+                let imported = imported.and_then(|count| {
+                    decoder.extend(&chunk);
+                    import_items(&mut decoder, &tree, mode, count)
+                });
+                (decoder, imported)
+            })
+        })
+        .map(|(decoder, imported)| match imported {
+            Err(response) => response,
+            Ok(count) if !decoder.is_complete() => {
+                let msg = format!("the export ended early after {} entries were imported", count);
+                bad_request_response(&msg)
+            }
+            Ok(count) => {
+                let bytes = serde_json::to_vec(&count).expect("failed to serialize count to JSON");
+                Response::new(bytes.into())
+            }
+        });
+    Box::new(response)
+}
+
+/// Apply each item that may be decoded from the bytes received so far.
+///
+/// Returns the total number of entries imported, or the response describing the failure.
+fn import_items(
+    decoder: &mut request::ExportDecoder,
+    tree: &Tree,
+    mode: request::ImportMode,
+    mut count: u64,
+) -> Result<u64, Response<Body>> {
+    loop {
+        match decoder.next_item() {
+            Err(msg) => {
+                let msg = format!("{}, though {} entries were imported", msg, count);
+                return Err(bad_request_response(&msg));
+            }
+            Ok(None) => return Ok(count),
+            Ok(Some(request::ExportItem::Header { .. })) => {
+                if mode == request::ImportMode::Replace {
+                    tree.clear().map_err(|err| db_err_response(&err))?;
+                }
+            }
+            Ok(Some(request::ExportItem::DeltaHeader { .. })) => {
+                if mode == request::ImportMode::Replace {
+                    return Err(bad_request_response("an incremental export cannot replace"));
+                }
+            }
+            Ok(Some(request::ExportItem::Entry(key, value))) => {
+                tree.set(key, value).map_err(|err| db_err_response(&err))?;
+                count += 1;
+            }
+            Ok(Some(request::ExportItem::Removed(key))) => {
+                tree.del(&key).map_err(|err| db_err_response(&err))?;
+                count += 1;
+            }
+            Ok(Some(request::ExportItem::End { .. })) => (),
+        }
+    }
+}
+
+/// Respond to a `ChangesSince` request once a change follows `since` or the timeout elapses.
+///
+/// The request may be given either as a JSON body or, if a `from_seq` query parameter is present,
+/// entirely via query parameters. See `changes_since_query`.
+fn changes_since(request: Request<Body>, tree: Arc<Tree>) -> ResponseFuture {
+    if query_param(&request, "from_seq").is_some() {
+        return match changes_since_query(&request) {
+            Some(req) => Box::new(wait_for_changes(req, tree)),
+            None => {
+                let response = bad_request_response("invalid `/tree/changes` query parameters");
+                Box::new(futures::future::ok(response))
+            }
+        };
+    }
+    let response = request
+        .into_body()
+        .concat2()
+        .and_then(move |chunk| match serde_json::from_slice(&chunk) {
+            Ok(req) => Either::A(wait_for_changes(req, tree)),
+            Err(err) => Either::B(futures::future::ok(deserialization_err_response(&err))),
+        });
+    Box::new(response)
+}
+
+/// Read a `ChangesSince` from query parameters, e.g. `/tree/changes?from_seq=42&timeout_ms=0`.
+///
+/// `from_seq` is the sequence number of the first change to retrieve, i.e. `since + 1`. The
+/// optional `prefix`, `timeout_ms` and `limit` parameters correspond to the fields of the same
+/// name, with the `prefix` percent-encoded, while the `filter` is read via `event_filter_query`.
+/// Returns `None` if any parameter is malformed.
+fn changes_since_query(request: &Request<Body>) -> Option<request::ChangesSince> {
+    let from_seq: u64 = query_param(request, "from_seq")?.parse().ok()?;
+    let prefix = match query_param(request, "prefix") {
+        None => vec![],
+        Some(prefix) => request::percent_decode_key(prefix)?,
+    };
+    let timeout_ms = match query_param(request, "timeout_ms") {
+        None => request::DEFAULT_CHANGES_TIMEOUT_MS,
+        Some(timeout_ms) => timeout_ms.parse().ok()?,
+    };
+    let limit = match query_param(request, "limit") {
+        None => None,
+        Some(limit) => Some(limit.parse().ok()?),
+    };
+    let since = from_seq.saturating_sub(1);
+    let filter = event_filter_query(request)?;
+    Some(request::ChangesSince { since, prefix, timeout_ms, limit, filter })
+}
+
+/// Produce a `ChangeBatch` response as soon as at least one change is available, or an empty one
+/// once the timeout elapses.
+///
+/// All changes that are available by then are included, up to the request's `limit`.
+fn wait_for_changes(
+    req: request::ChangesSince,
+    tree: Arc<Tree>,
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send {
+    let request::ChangesSince { since, prefix, timeout_ms, limit: max, filter } = req;
+    let max = cmp::max(limit(max), 1);
+    let mut delay = Delay::new(Instant::now() + Duration::from_millis(timeout_ms));
+    let watched = blocking(move || {
+        let watch = tree.watch_filtered(&prefix, filter, Some(since));
+        (tree, watch)
+    });
+    watched.and_then(move |(tree, watch)| {
+        let tree::Watch { mut events, complete, .. } = match watch {
+            Ok(watch) => watch,
+            Err(err) => return Either::A(futures::future::ok(db_err_response(&err))),
+        };
+        let mut batch = vec![];
+        Either::B(futures::future::poll_fn(move || {
+            // Every change up to `current` has already been sent to `events`, so once they have
+            // been drained the changes outside of the prefix up to `current` may be skipped.
+            let current = tree.change_seq();
+            while batch.len() < max {
+                match events.poll() {
+                    Ok(Async::Ready(Some(event))) => batch.push(event),
+                    Ok(Async::Ready(None)) | Err(()) => break,
+                    Ok(Async::NotReady) => {
+                        // A timer error, e.g. due to a lack of a timer, is treated as a timeout.
+                        if batch.is_empty() {
+                            if let Ok(Async::NotReady) = delay.poll() {
+                                return Ok(Async::NotReady);
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+            let events = batch.split_off(0);
+            let last = events.last().map(|event| event.seq);
+            let seq = match last {
+                Some(last) if events.len() == max => last,
+                _ => cmp::max(current, last.unwrap_or(0)),
+            };
+            let batch = request::ChangeBatch { events, seq, complete };
+            let bytes = serde_json::to_vec(&batch)
+                .expect("failed to serialize change batch to JSON");
+            Ok(Async::Ready(Response::new(bytes.into())))
+        }))
+    })
+}
+
+/// Respond to a `Watch` request with a stream of Server-Sent Events describing every subsequent
+/// change to the entries beginning with the `prefix` query parameter.
+///
+/// If the request carries a `request::LAST_EVENT_ID` header, any retained changes since that
+/// event are sent first. Otherwise, if the request names a `cursor`, any retained changes since
+/// the sequence number acknowledged via the cursor are sent first.
+fn watch(request: Request<Body>, tree: Arc<Tree>) -> Response<Body> {
+    let prefix = match watch_prefix(&request) {
+        Some(prefix) => prefix,
+        None => return bad_request_response("invalid `prefix` query parameter"),
+    };
+    let filter = match event_filter_query(&request) {
+        Some(filter) => filter,
+        None => return bad_request_response("invalid `start`, `end` or `kinds` query parameter"),
+    };
+    let window = match coalesce_window(&request) {
+        Some(window) => window,
+        None => return bad_request_response("invalid `coalesce_ms` query parameter"),
+    };
+    let cursor = match query_param(&request, "cursor") {
+        None => None,
+        Some(cursor) => {
+            let name = request::percent_decode_key(cursor).and_then(|n| String::from_utf8(n).ok());
+            match name {
+                Some(ref name) if request::is_valid_view_name(name) => Some(name.clone()),
+                _ => return bad_request_response("invalid `cursor` query parameter"),
+            }
+        }
+    };
+    let last_event_id = request
+        .headers()
+        .get(request::LAST_EVENT_ID)
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.trim().parse::<u64>().ok());
+    let events = match (last_event_id, cursor) {
+        (None, Some(cursor)) => tree.watch_cursor(&cursor, &prefix, filter),
+        (last_event_id, _) => tree.watch_filtered(&prefix, filter, last_event_id),
+    };
+    let events = match events {
+        Ok(watch) => coalesced(watch.events, window),
+        Err(err) => return db_err_response(&err),
+    };
+    let events = events
+        .map(|event| {
+            let json = serde_json::to_string(&event).expect("failed to serialize event to JSON");
+            Chunk::from(format!("id: {}\ndata: {}\n\n", event.seq, json))
+        })
+        .map_err(|()| -> Box<StdError + Send + Sync> {
+            unreachable!("an unbounded receiver never produces an error")
+        });
+    let stream = Box::new(events) as Box<_>;
     Response::builder()
-        .status(StatusCode::BAD_REQUEST)
-        .body(err_to_json_bytes(err).into())
-        .expect("failed to construct BAD_REQUEST response")
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(stream))
+        .expect("failed to construct `Watch` response")
+}
+
+/// The decoded `prefix` query parameter of a `Watch` request, or `None` if it is malformed.
+///
+/// A missing `prefix` watches all entries.
+fn watch_prefix(request: &Request<Body>) -> Option<Vec<u8>> {
+    match query_param(request, "prefix") {
+        None => Some(vec![]),
+        Some(prefix) => request::percent_decode_key(prefix),
+    }
+}
+
+/// The `EventFilter` given via the `start`, `end` and `kinds` query parameters of a subscription
+/// request, or `None` if any are malformed. See `request::EventFilter::to_query`.
+fn event_filter_query(request: &Request<Body>) -> Option<request::EventFilter> {
+    let start = match query_param(request, "start") {
+        None => None,
+        Some(start) => Some(request::percent_decode_key(start)?),
+    };
+    let end = match query_param(request, "end") {
+        None => None,
+        Some(end) => Some(request::percent_decode_key(end)?),
+    };
+    let kinds = match query_param(request, "kinds") {
+        None => vec![],
+        Some(kinds) => kinds
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(request::EventKind::from_name)
+            .collect::<Option<_>>()?,
+    };
+    Some(request::EventFilter { start, end, kinds })
+}
+
+/// The window given via the `coalesce_ms` query parameter of a subscription request, or `None` if
+/// it is malformed.
+fn coalesce_window(request: &Request<Body>) -> Option<Option<Duration>> {
+    match query_param(request, "coalesce_ms") {
+        None => Some(None),
+        Some(ms) => ms.parse().ok().map(|ms| Some(Duration::from_millis(ms))),
+    }
+}
+
+/// Coalesce the given events within the window if there is one.
+fn coalesced<S>(
+    events: S,
+    window: Option<Duration>,
+) -> Box<Stream<Item = request::Event, Error = ()> + Send>
+where
+    S: 'static + Stream<Item = request::Event, Error = ()> + Send,
+{
+    match window {
+        None => Box::new(events),
+        Some(window) => {
+            let pending = VecDeque::new();
+            Box::new(Coalesce { events, window, pending, delay: None, done: false })
+        }
+    }
+}
+
+/// The raw value of the first query parameter with the given name, if any.
+fn query_param<'a>(request: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    let query = request.uri().query()?;
+    query
+        .split('&')
+        .filter_map(|param| {
+            let mut parts = param.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key == name => Some(value),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// The `Sec-WebSocket-Accept` value for the given WebSocket handshake request, or `None` if the
+/// request is not a valid handshake.
+fn websocket_accept(request: &Request<Body>) -> Option<String> {
+    /// Appended to the client's key before hashing, as specified by RFC 6455.
+    const GUID: &'static [u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    let headers = request.headers();
+    let is_websocket = headers
+        .get(header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    let is_version_13 = headers
+        .get(header::SEC_WEBSOCKET_VERSION)
+        .map_or(false, |version| version.as_bytes() == b"13");
+    if !is_websocket || !is_version_13 {
+        return None;
+    }
+    let key = headers.get(header::SEC_WEBSOCKET_KEY)?;
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(GUID);
+    Some(base64::encode(&sha1.digest().bytes()))
 }
 
 /// Create a response to the given request.
@@ -340,24 +1887,97 @@ fn deserialization_err_response(err: &StdError) -> Response<Body> {
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::get` returns `Ok`          | 200 OK            | `Option<Vec<u8>>`                 |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::get` `Ok` for all keys     | 200 OK            | `Vec<Option<Vec<u8>>>`            |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `GetPrefix`                       | 200 OK            | `EntryMap`                        |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::del` returns `Ok`          | 200 OK            | `Option<Vec<u8>>`                 |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `DelIfEqual`                      | 200 OK            | `bool` whether deleted            |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::del_prefix` returns `Ok`   | 200 OK            | `usize` number of removed entries |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::set` returns `Ok`          | 201 Created       | `()`                              |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::replace` returns `Ok`      | 201 Created       | `Option<Vec<u8>>` previous value  |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `SetMany`                         | 200 OK            | `Vec<Result<(), String>>`         |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::cas` returns `Ok`          | 200 Ok            | `Ok(())`                          |
 /// | --------------------------------- | ----------------- | --------------------------------- |
-/// | `Tree::cas` returns `CasFailed`   | 200 Ok            | `Err(Vec<u8>)`                    |
+/// | `Tree::cas` returns `CasFailed`   | 409 Conflict      | `Option<Vec<u8>>` current value   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::cas` returns `CasFailed`,  | 200 Ok            | `Err(Vec<u8>)`                    |
+/// | pinning a revision prior to 7     |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `BatchCas`                        | 200 Ok            | `Vec<Result<(), CasError>>`       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `BatchCas` DB error, pinning a    | 500 Server Error  | `ErrorBody`                       |
+/// | revision prior to 9               |                   |                                   |
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::merge` returns `Ok`        | 200 Ok            | `()`                              |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::incr` returns `Ok`         | 200 Ok            | `i64` new value                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::incr` would overflow       | 409 Conflict      | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::get_or_insert` `Ok`        | 200 Ok            | `Vec<u8>`                         |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::pop_min` returns `Ok`      | 200 Ok            | `Option<(Vec<u8>, Vec<u8>)>`      |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::pop_max` returns `Ok`      | 200 Ok            | `Option<(Vec<u8>, Vec<u8>)>`      |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::flush` returns `Ok`        | 200 Ok            | `()`                              |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::batch` returns `Ok`        | 200 Ok            | `()`                              |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::generate_id` returns `Ok`  | 200 OK            | `u64`                             |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::len` returns `Ok`          | 200 OK            | `usize`                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::stats` returns `Ok`        | 200 OK            | `TreeStats`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::clear` returns `Ok`        | 200 Ok            | `usize` number of removed entries |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Clear` without confirmation      | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::transaction` returns `Ok`  | 200 Ok            | `TransactionOutcome`              |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::rename` returns `Ok`       | 200 Ok            | `RenameOutcome`                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::iter`                      | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::scan`                      | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::scan_range`                | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::scan_suffix`               | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::count_range` returns `Ok`  | 200 OK            | `usize`                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::hash_prefix` returns `Ok`  | 200 OK            | `Vec<request::PrefixHash>`        |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::top_values` returns `Ok`   | 200 OK            | `Vec<(Vec<u8>, usize)>`           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::aggregate` returns `Ok`    | 200 OK            | `AggregateResult`                 |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `IterPage`                        | 200 OK            | `Page`                            |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `IterPage` with invalid cursor or | 400 Bad Request   | `ErrorBody`                       |
+/// | a zero page size                  |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::iter` keys only            | 200 OK            | Stream of `Vec<u8>`               |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::scan` keys only            | 200 OK            | Stream of `Vec<u8>`               |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `ScanMatching` with valid pattern | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `ScanMatching` invalid pattern    | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::iter` reversed             | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Tree::scan_range` reversed       | 200 OK            | Stream of `(Vec<u8>, Vec<u8>)`    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::pred` returns `Ok`         | 200 OK            | `Option<(Vec<u8>, Vec<u8>)>`      |
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::pred_incl` returns `Ok`    | 200 OK            | `Option<(Vec<u8>, Vec<u8>)>`      |
@@ -366,35 +1986,248 @@ fn deserialization_err_response(err: &StdError) -> Response<Body> {
 /// | --------------------------------- | ----------------- | --------------------------------- |
 /// | `Tree::succ_incl` returns `Ok`    | 200 OK            | `Option<(Vec<u8>, Vec<u8>)>`      |
 /// | --------------------------------- | ----------------- | --------------------------------- |
-/// | Deserialization Errors            | 400 Bad Request   | `String`                          |
+/// | `Subscribe` WebSocket handshake   | 101 Switching     | `request::Event` text messages    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Subscribe` without a handshake   | 400 Bad Request   | `ErrorBody`                       |
+/// | or an invalid filter or window    |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Watch` with a valid `prefix`     | 200 OK            | `text/event-stream` of `Event`s   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Watch` with an invalid `prefix`  | 400 Bad Request   | `ErrorBody`                       |
+/// | `cursor`, filter or window        |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `ChangesSince` change or timeout  | 200 OK            | `request::ChangeBatch`            |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Export`                          | 200 OK            | Binary, see `request::Export`     |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `ExportSince` retained changes    | 200 OK            | Binary, see `ExportSince`         |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `ExportSince` unretained changes  | 410 Gone          | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Import` of a complete export     | 200 OK            | `u64` number of entries           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Import` of a malformed export    | 400 Bad Request   | `ErrorBody`                       |
+/// | or a replacing incremental export |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `AckCursor` with a valid name     | 200 OK            | `Option<u64>` previous ack        |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `AckCursor` with an invalid name  | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `GetCursor` or `RemoveCursor`     | 200 OK            | `Option<u64>` acknowledged seq    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Deserialization Errors            | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `sled::DbResult` `Err`s           | 500 Server Error  | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `scan_suffix` without an index    | 501 Not Impl.     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `request::DEADLINE` exceeded      | 504 Timeout       | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Raw `GET` of an existing entry    | 200 OK            | Raw value bytes                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Raw `GET` of a missing entry      | 404 Not Found     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Raw `PUT`                         | 201 Created       | <empty>                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Raw request with an invalid key   | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Entry `GET` of an existing entry  | 200 OK            | `Value`                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Entry `GET` of a missing entry    | 404 Not Found     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Entry `PUT`                       | 201 Created       | `()`                              |
 /// | --------------------------------- | ----------------- | --------------------------------- |
-/// | `sled::DbResult` `Err`s           | 500 Server Error  | `String`                          |
+/// | Entry `DELETE`                    | 200 OK            | `Option<Value>` removed value     |
 /// | --------------------------------- | ----------------- | --------------------------------- |
-/// | <unknown request>                 | 404 Not Found     | <empty>                           |
+/// | Entry request with an invalid key | 400 Bad Request   | `ErrorBody`                       |
 /// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `HEAD` of an existing entry       | 200 OK            | <empty>, see `VALUE_LENGTH`       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `HEAD` of a missing entry         | 404 Not Found     | <empty>                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Get` of a missing key, given a   | 404 Not Found     | `ErrorBody`                       |
+/// | `MISSING` header of `404`         |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `GET` matching `If-None-Match`    | 304 Not Modified  | <empty>                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Write not matching `If-Match`     | 412 Precondition  | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | <unknown request>                 | 404 Not Found     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+///
+/// Responses to `GET` and `HEAD` requests for an existing entry include its `ETag`. If the
+/// request's `If-None-Match` header lists that tag, the response is `304 Not Modified` instead.
 pub fn response(
     request: Request<Body>,
-    tree: Arc<sled::Tree>,
+    tree: Arc<Tree>,
 ) -> Result<ResponseFuture, UnknownRequest> {
+    try_response(request, tree).map_err(|_| UnknownRequest)
+}
+
+/// The same as `response`, but returns the request itself if it does not match any known
+/// endpoint, allowing it to be handled elsewhere, e.g. by a fallback handler.
+pub fn try_response(
+    request: Request<Body>,
+    tree: Arc<Tree>,
+) -> Result<ResponseFuture, Request<Body>> {
+    let if_none_match = match *request.method() {
+        Method::GET | Method::HEAD => request.headers().get(header::IF_NONE_MATCH).cloned(),
+        _ => None,
+    };
+    let response = respond(request, tree)?;
+    match if_none_match {
+        None => Ok(response),
+        Some(tags) => {
+            let response = response.map(move |response| not_modified(response, Some(&tags)));
+            Ok(Box::new(response))
+        }
+    }
+}
+
+/// Route the given request to its response. See `response`.
+fn respond(
+    request: Request<Body>,
+    tree: Arc<Tree>,
+) -> Result<ResponseFuture, Request<Body>> {
+    if request.uri().path().starts_with(request::RAW_PATH) {
+        return raw_response(request, tree);
+    }
     match (request.method(), request.uri().path()) {
         (&request::Get::METHOD, request::Get::PATH_AND_QUERY) => {
-            Ok(Box::new(concat_and_respond::<request::Get>(request, tree)))
+            if !is_missing_not_found(&request) {
+                return Ok(Box::new(concat_and_respond::<request::Get>(request, tree)));
+            }
+            let encoding = response_encoding(&request);
+            let response = concat_and_then(request, move |req: request::Get| {
+                match tree.get(&req.key) {
+                    Err(err) => db_err_response(&err),
+                    Ok(None) => missing_entry_response(req.key),
+                    Ok(value) => with_content_type(get_response(value, encoding), encoding),
+                }
+            });
+            Ok(Box::new(response))
+        }
+        (&Method::HEAD, request::Get::PATH_AND_QUERY) => {
+            let encoding = response_encoding(&request);
+            let response = concat_and_then(request, move |req: request::Get| {
+                let response = head_response(&tree, &req.key, |v| encoding.to_vec(&Some(v)));
+                with_content_type(response, encoding)
+            });
+            Ok(Box::new(response))
         }
-        (&request::Del::METHOD, request::Del::PATH_AND_QUERY) => {
-            Ok(Box::new(concat_and_respond::<request::Del>(request, tree)))
+        (&request::GetMany::METHOD, request::GetMany::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::GetMany>(request, tree)))
         }
-        (&request::Set::METHOD, request::Set::PATH_AND_QUERY) => {
-            Ok(Box::new(concat_and_respond::<request::Set>(request, tree)))
+        (&request::GetPrefix::METHOD, request::GetPrefix::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::GetPrefix>(request, tree)))
+        }
+        (&request::Del::METHOD, request::Del::PATH_AND_QUERY) => match if_match(&request) {
+            None => Ok(Box::new(concat_and_respond::<request::Del>(request, tree))),
+            Some(tags) => {
+                let encoding = response_encoding(&request);
+                let response = concat_and_then(request, move |req: request::Del| {
+                    let response = if_match_response(&tree, req.key, None, &tags, encoding);
+                    with_content_type(response, encoding)
+                });
+                Ok(Box::new(response))
+            }
+        },
+        (&request::DelIfEqual::METHOD, request::DelIfEqual::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::DelIfEqual>(request, tree)))
+        }
+        (&request::DelPrefix::METHOD, request::DelPrefix::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::DelPrefix>(request, tree)))
+        }
+        (&request::Set::METHOD, request::Set::PATH_AND_QUERY) => match if_match(&request) {
+            None => Ok(Box::new(concat_and_respond::<request::Set>(request, tree))),
+            Some(tags) => {
+                let encoding = response_encoding(&request);
+                let response = concat_and_then(request, move |req: request::Set| {
+                    let request::Set { key, value } = req;
+                    let response = if_match_response(&tree, key, Some(value), &tags, encoding);
+                    with_content_type(response, encoding)
+                });
+                Ok(Box::new(response))
+            }
+        },
+        (&request::Replace::METHOD, request::Replace::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Replace>(request, tree)))
+        }
+        (&request::SetMany::METHOD, request::SetMany::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::SetMany>(request, tree)))
         }
         (&request::Cas::METHOD, request::Cas::PATH_AND_QUERY) => {
-            Ok(Box::new(concat_and_respond::<request::Cas>(request, tree)))
+            // Prior to wire format revision 7, a failed swap responds with `200 OK` and an `Err`.
+            if request::pinned_protocol(&request) < 7 {
+                return Ok(Box::new(concat_and_respond::<request::Cas>(request, tree)));
+            }
+            let encoding = response_encoding(&request);
+            let response = concat_and_then(request, move |req: request::Cas| {
+                let request::Cas { key, old, new } = req;
+                match tree.cas(key, old, new) {
+                    Ok(()) => {
+                        let res: Result<(), Option<Vec<u8>>> = Ok(());
+                        let bytes = encoding.to_vec(&res)
+                            .expect("failed to serialize result");
+                        with_content_type(Response::new(bytes.into()), encoding)
+                    }
+                    Err(sled::Error::CasFailed(current)) => {
+                        cas_conflict_response(current, encoding)
+                    }
+                    Err(err) => db_err_response(&err),
+                }
+            });
+            Ok(Box::new(response))
+        }
+        (&request::BatchCas::METHOD, request::BatchCas::PATH_AND_QUERY) => {
+            if request::pinned_protocol(&request) < 9 {
+                let encoding = response_encoding(&request);
+                let response = concat_and_then(request, move |req: request::BatchCas| {
+                    with_content_type(legacy_batch_cas_response(req, tree, encoding), encoding)
+                });
+                return Ok(Box::new(response));
+            }
+            Ok(Box::new(concat_and_respond::<request::BatchCas>(request, tree)))
         }
         (&request::Merge::METHOD, request::Merge::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::Merge>(request, tree)))
         }
+        (&request::Incr::METHOD, request::Incr::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Incr>(request, tree)))
+        }
+        (&request::GetOrInsert::METHOD, request::GetOrInsert::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::GetOrInsert>(request, tree)))
+        }
+        (&request::PopMin::METHOD, request::PopMin::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::PopMin>(request, tree)))
+        }
+        (&request::PopMax::METHOD, request::PopMax::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::PopMax>(request, tree)))
+        }
         (&request::Flush::METHOD, request::Flush::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::Flush>(request, tree)))
         }
+        (&request::Batch::METHOD, request::Batch::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Batch>(request, tree)))
+        }
+        (&request::GenerateId::METHOD, request::GenerateId::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::GenerateId>(request, tree)))
+        }
+        (&request::Len::METHOD, request::Len::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Len>(request, tree)))
+        }
+        (&request::Stats::METHOD, request::Stats::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Stats>(request, tree)))
+        }
+        (&request::Clear::METHOD, request::Clear::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Clear>(request, tree)))
+        }
+        (&request::Transaction::METHOD, request::Transaction::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Transaction>(request, tree)))
+        }
+        (&request::Rename::METHOD, request::Rename::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Rename>(request, tree)))
+        }
         (&request::Iter::METHOD, request::Iter::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::Iter>(request, tree)))
         }
@@ -404,6 +2237,39 @@ pub fn response(
         (&request::ScanRange::METHOD, request::ScanRange::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::ScanRange>(request, tree)))
         }
+        (&request::HashPrefix::METHOD, request::HashPrefix::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::HashPrefix>(request, tree)))
+        }
+        (&request::CountRange::METHOD, request::CountRange::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::CountRange>(request, tree)))
+        }
+        (&request::TopValues::METHOD, request::TopValues::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::TopValues>(request, tree)))
+        }
+        (&request::Aggregate::METHOD, request::Aggregate::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Aggregate>(request, tree)))
+        }
+        (&request::IterPage::METHOD, request::IterPage::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::IterPage>(request, tree)))
+        }
+        (&request::ScanSuffix::METHOD, request::ScanSuffix::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::ScanSuffix>(request, tree)))
+        }
+        (&request::IterKeys::METHOD, request::IterKeys::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::IterKeys>(request, tree)))
+        }
+        (&request::ScanKeys::METHOD, request::ScanKeys::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::ScanKeys>(request, tree)))
+        }
+        (&request::ScanMatching::METHOD, request::ScanMatching::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::ScanMatching>(request, tree)))
+        }
+        (&request::IterRev::METHOD, request::IterRev::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::IterRev>(request, tree)))
+        }
+        (&request::ScanRangeRev::METHOD, request::ScanRangeRev::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::ScanRangeRev>(request, tree)))
+        }
         (&request::Max::METHOD, request::Max::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::Max>(request, tree)))
         }
@@ -419,10 +2285,337 @@ pub fn response(
         (&request::SuccIncl::METHOD, request::SuccIncl::PATH_AND_QUERY) => {
             Ok(Box::new(concat_and_respond::<request::SuccIncl>(request, tree)))
         }
-        _ => Err(UnknownRequest)
+        (&request::Subscribe::METHOD, request::Subscribe::PATH_AND_QUERY) => {
+            Ok(Box::new(futures::future::ok(subscribe(request, tree))))
+        }
+        (&request::Watch::METHOD, request::Watch::PATH_AND_QUERY) => {
+            Ok(Box::new(futures::future::ok(watch(request, tree))))
+        }
+        (&request::ChangesSince::METHOD, request::ChangesSince::PATH_AND_QUERY) => {
+            Ok(changes_since(request, tree))
+        }
+        (&request::Export::METHOD, request::Export::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::Export>(request, tree)))
+        }
+        (&request::ExportSince::METHOD, request::ExportSince::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::ExportSince>(request, tree)))
+        }
+        (&request::Import::METHOD, request::Import::PATH_AND_QUERY) => {
+            Ok(import(request, tree))
+        }
+        (&request::AckCursor::METHOD, request::AckCursor::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::AckCursor>(request, tree)))
+        }
+        (&request::GetCursor::METHOD, request::GetCursor::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::GetCursor>(request, tree)))
+        }
+        (&request::RemoveCursor::METHOD, request::RemoveCursor::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_respond::<request::RemoveCursor>(request, tree)))
+        }
+        _ => entry_response(request, tree),
+    }
+}
+
+/// Respond to a resource-style `GET`, `PUT` or `DELETE` of an entry. See `request::ENTRIES_PATH`.
+fn entry_response(
+    request: Request<Body>,
+    tree: Arc<Tree>,
+) -> Result<ResponseFuture, Request<Body>> {
+    let is_entry_path = {
+        let path = request.uri().path();
+        path.starts_with(request::ENTRIES_PATH)
+            && !path[request::ENTRIES_PATH.len()..].contains('/')
+    };
+    match *request.method() {
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE if is_entry_path => (),
+        _ => return Err(request),
+    }
+    let path = request.uri().path();
+    let key = match request::entry_path_key(path) {
+        Some(key) => key,
+        None => {
+            let response = bad_request_response("the key must be URL-safe base64");
+            return Ok(Box::new(futures::future::ok(response)));
+        }
+    };
+    let encoding = response_encoding(&request);
+    let if_match = if_match(&request);
+    match *request.method() {
+        Method::GET => {
+            let response = blocking(move || match tree.get(&key) {
+                Err(err) => db_err_response(&err),
+                Ok(None) => missing_entry_response(key),
+                Ok(Some(value)) => {
+                    let bytes = encoding.to_vec(&value)
+                        .expect("failed to serialize value");
+                    let response = with_content_type(Response::new(bytes.into()), encoding);
+                    with_etag(response, &value)
+                }
+            });
+            Ok(Box::new(response))
+        }
+        Method::HEAD => {
+            let response = blocking(move || {
+                let response = head_response(&tree, &key, |value| encoding.to_vec(&value));
+                with_content_type(response, encoding)
+            });
+            Ok(Box::new(response))
+        }
+        Method::PUT => {
+            let response = concat_and_then(request, move |value: Vec<u8>| {
+                let response = match if_match {
+                    None => request::Set { key, value }.into_response(tree, encoding),
+                    Some(tags) => if_match_response(&tree, key, Some(value), &tags, encoding),
+                };
+                with_content_type(response, encoding)
+            });
+            Ok(Box::new(response))
+        }
+        _ => {
+            let response = blocking(move || {
+                let response = match if_match {
+                    None => request::Del { key }.into_response(tree, encoding),
+                    Some(tags) => if_match_response(&tree, key, None, &tags, encoding),
+                };
+                with_content_type(response, encoding)
+            });
+            Ok(Box::new(response))
+        }
     }
 }
 
+/// Respond to a `GET`, `HEAD` or `PUT` of the raw value of an entry. See `request::RAW_PATH`.
+fn raw_response(
+    request: Request<Body>,
+    tree: Arc<Tree>,
+) -> Result<ResponseFuture, Request<Body>> {
+    let key = match request::decode_raw_key(&request.uri().path()[request::RAW_PATH.len()..]) {
+        Some(key) => key,
+        None => {
+            let response = bad_request_response("the key must be URL-safe base64");
+            return Ok(Box::new(futures::future::ok(response)));
+        }
+    };
+    match *request.method() {
+        Method::GET => {
+            let response = blocking(move || match tree.get(&key) {
+                Err(err) => db_err_response(&err),
+                Ok(None) => missing_entry_response(key),
+                Ok(Some(value)) => {
+                    let etag = etag(&value);
+                    Response::builder()
+                        .header(header::CONTENT_TYPE, "application/octet-stream")
+                        .header(header::ETAG, etag.as_str())
+                        .body(value.into())
+                        .expect("failed to construct raw `GET` response")
+                }
+            });
+            Ok(Box::new(response))
+        }
+        Method::HEAD => {
+            let response = blocking(move || {
+                let mut response = head_response(&tree, &key, |value| Ok(value));
+                if response.status().is_success() {
+                    let media_type = header::HeaderValue::from_static("application/octet-stream");
+                    response.headers_mut().insert(header::CONTENT_TYPE, media_type);
+                }
+                response
+            });
+            Ok(Box::new(response))
+        }
+        Method::PUT => {
+            let deadline = request_deadline(&request);
+            let if_match = if_match(&request);
+            let response = request.into_body().concat2().and_then(move |chunk| blocking(move || {
+                if deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false) {
+                    return deadline_exceeded_response();
+                }
+                let result = match if_match {
+                    None => tree.set(key, chunk.to_vec()).map_err(|err| db_err_response(&err)),
+                    Some(tags) => if_match_cas(&tree, key, Some(chunk.to_vec()), &tags).map(|_| ()),
+                };
+                match result {
+                    Err(response) => response,
+                    Ok(()) => Response::builder()
+                        .status(StatusCode::CREATED)
+                        .body(Body::empty())
+                        .expect("failed to construct raw `PUT` response"),
+                }
+            }));
+            Ok(Box::new(response))
+        }
+        _ => Err(request),
+    }
+}
+
+/// Respond to a `HEAD` request for the entry with the given key. See `request::VALUE_LENGTH`.
+///
+/// `body` produces the body of the equivalent `GET` response from the value, the length of which
+/// is given via the `Content-Length` header.
+fn head_response<F>(tree: &Tree, key: &[u8], body: F) -> Response<Body>
+where
+    F: FnOnce(Vec<u8>) -> Result<Vec<u8>, Box<StdError + Send + Sync>>,
+{
+    let value = match tree.get(key) {
+        Err(err) => return db_err_response(&err),
+        Ok(None) => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .expect("failed to construct NOT_FOUND response");
+        }
+        Ok(Some(value)) => value,
+    };
+    let value_len = value.len();
+    let etag = etag(&value);
+    let body_len = body(value).expect("failed to serialize value").len();
+    Response::builder()
+        .header(header::ETAG, etag.as_str())
+        .header(request::VALUE_LENGTH, value_len)
+        .header(header::CONTENT_LENGTH, body_len)
+        .body(Body::empty())
+        .expect("failed to construct `HEAD` response")
+}
+
+/// Respond to a `Get` with the given value, including its `ETag` if there is one.
+fn get_response(value: Option<Vec<u8>>, encoding: Encoding) -> Response<Body> {
+    let bytes = encoding.to_vec(&value).expect("failed to serialize value");
+    let response = Response::new(bytes.into());
+    match value {
+        Some(value) => with_etag(response, &value),
+        None => response,
+    }
+}
+
+/// Whether the request asks for `404 Not Found` in response to a `Get` of a missing key. See
+/// `request::MISSING`.
+fn is_missing_not_found(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(request::MISSING)
+        .map_or(false, |missing| missing.as_bytes() == b"404")
+}
+
+/// The entity tag of the given value, as included via the `ETag` header of responses to `GET` and
+/// `HEAD` requests for an entry.
+///
+/// The tag is the quoted hexadecimal SHA-1 hash of the value, and so changes whenever the value
+/// does regardless of the route or encoding by which the value is retrieved.
+pub fn etag(value: &[u8]) -> String {
+    format!("\"{}\"", Sha1::from(value).digest())
+}
+
+/// Whether the given `If-None-Match` header value lists the given entity tag.
+///
+/// Tags are compared weakly, while the value `*` matches any tag.
+pub fn etag_matches(tags: &str, etag: &str) -> bool {
+    tags.split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag || (tag.starts_with("W/") && &tag[2..] == etag))
+}
+
+/// Include the `ETag` of the given value within the response if it is successful.
+pub fn with_etag(mut response: Response<Body>, value: &[u8]) -> Response<Body> {
+    if response.status().is_success() {
+        let etag = header::HeaderValue::from_str(&etag(value))
+            .expect("failed to construct `ETag` header");
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+/// The entity tags listed by the request's `If-Match` header, if any.
+fn if_match(request: &Request<Body>) -> Option<String> {
+    request
+        .headers()
+        .get(header::IF_MATCH)
+        .map(|tags| tags.to_str().unwrap_or("").to_string())
+}
+
+/// Set the entry with the given key to `new`, or delete it given `None`, via a compare-and-swap
+/// against its current value, provided that the `ETag` of the current value is listed within the
+/// given `If-Match` header value.
+///
+/// Tags are compared strongly, so weak tags never match, while `*` matches any existing entry.
+/// Produces the replaced value on success, or otherwise the response describing the failure,
+/// i.e. `412 Precondition Failed` if the entry does not match or changed in the meantime.
+fn if_match_cas(
+    tree: &Tree,
+    key: Vec<u8>,
+    new: Option<Vec<u8>>,
+    tags: &str,
+) -> Result<Vec<u8>, Response<Body>> {
+    let current = match tree.get(&key) {
+        Err(err) => return Err(db_err_response(&err)),
+        Ok(None) => return Err(precondition_failed_response(&key)),
+        Ok(Some(current)) => current,
+    };
+    let etag = etag(&current);
+    if !tags.split(',').map(str::trim).any(|tag| tag == "*" || tag == etag) {
+        return Err(precondition_failed_response(&key));
+    }
+    match tree.cas(key.clone(), Some(current.clone()), new) {
+        Ok(()) => Ok(current),
+        Err(sled::Error::CasFailed(_)) => Err(precondition_failed_response(&key)),
+        Err(err) => Err(db_err_response(&err)),
+    }
+}
+
+/// Respond to a `Set`, given `Some` value, or a `Del`, given `None`, made conditional via the
+/// request's `If-Match` header. See `if_match_cas`.
+///
+/// Successful responses are those of the unconditional request.
+fn if_match_response(
+    tree: &Tree,
+    key: Vec<u8>,
+    new: Option<Vec<u8>>,
+    tags: &str,
+    encoding: Encoding,
+) -> Response<Body> {
+    let is_set = new.is_some();
+    match if_match_cas(tree, key, new, tags) {
+        Err(response) => response,
+        Ok(_) if is_set => {
+            let bytes = encoding.to_vec(&()).expect("failed to serialize value");
+            Response::builder()
+                .status(StatusCode::CREATED)
+                .body(bytes.into())
+                .expect("failed to construct `Set` response")
+        }
+        Ok(previous) => {
+            let bytes = encoding.to_vec(&Some(previous)).expect("failed to serialize value");
+            Response::new(bytes.into())
+        }
+    }
+}
+
+/// Replace the given successful response with `304 Not Modified` if its `ETag` is listed within
+/// the given `If-None-Match` header value.
+///
+/// The `ETag` is retained so that clients may continue to use it.
+pub fn not_modified(
+    response: Response<Body>,
+    if_none_match: Option<&header::HeaderValue>,
+) -> Response<Body> {
+    let is_match = {
+        let tags = if_none_match.and_then(|tags| tags.to_str().ok());
+        let etag = response.headers().get(header::ETAG).and_then(|etag| etag.to_str().ok());
+        match (tags, etag) {
+            (Some(tags), Some(etag)) => response.status().is_success() && etag_matches(tags, etag),
+            _ => false,
+        }
+    };
+    if !is_match {
+        return response;
+    }
+    let mut builder = Response::builder();
+    builder.status(StatusCode::NOT_MODIFIED);
+    if let Some(etag) = response.headers().get(header::ETAG) {
+        builder.header(header::ETAG, etag.clone());
+    }
+    builder.body(Body::empty()).expect("failed to construct NOT_MODIFIED response")
+}
+
 /// A function for converting a `Result<ResponseFuture, UnknownRequest>` into a `ResponseFuture`
 /// where the `UnknownRequest` is translated into a `404` response.
 pub fn or_404(
@@ -430,10 +2623,7 @@ pub fn or_404(
 ) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send {
     result
         .unwrap_or_else(|UnknownRequest| {
-            let response = Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(vec![].into())
-                .expect("failed to construct NOT_FOUND response");
+            let response = status_err_response(StatusCode::NOT_FOUND, "no such endpoint");
             Box::new(futures::future::ok(response)) as _
         })
 }
@@ -1,13 +1,60 @@
-use hyper::{self, Server};
-use hyper::rt::Future;
+use backend::Store;
+use backup::{self, BackupTarget, DirTarget, Schedule};
+use base64_json;
+use client::{self, Client, Entry};
+use compression;
+use encoding::{self, Encoding};
+use federation::{self, Shard};
+use futures::{self, Async, Poll};
+use hyper::{self, Body, Chunk, Method, Request, Response, StatusCode, Uri};
+use hyper::body::Payload;
+use hyper::client::HttpConnector;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use hyper::header::{self, HeaderValue};
+use hyper::rt::{Future, Stream};
 use hyper::service::service_fn;
-use response::{or_404, response};
+#[cfg(feature = "tls")]
+use hyper::service::make_service_fn;
+use request::{self, BatchOp, ExportItem, MigrationState, MigrationStatus, RequestType};
+use request::{SledConfig, View, Webhook};
+use query;
+use resp;
+use response::{concat_and_then, not_modified, or_404, try_response, with_etag, ResponseFuture};
+use response::{self, blocking, status_err_response, UnknownRequest};
+use serde::Serialize;
+use serde_json;
 use sled;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::cmp;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::ops::Bound;
+use std::net::{self, SocketAddr};
+#[cfg(feature = "tls")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::reactor::Handle;
+use tokio::runtime::{self, Runtime};
+use tokio_timer::{Delay, Interval, Timeout};
+#[cfg(feature = "tls")]
+use rustls;
+#[cfg(feature = "tls")]
+use tls;
+use tree::Tree;
 
 // Request strings.
 
+/// The number of entries requested from the source of a `PullRange` migration at a time.
+const MIGRATION_PAGE_LEN: u64 = 1_000;
+
+/// The connections accepted by all of a `Server`'s listeners.
+type Incoming = Box<Stream<Item = TcpStream, Error = io::Error> + Send>;
+
 /// Configuration for the server.
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct Config {
@@ -15,12 +62,329 @@ pub struct Config {
     ///
     /// Defaults to localhost:3000. E.g. `([127, 0, 0, 1], 3000)`.
     pub addr: SocketAddr,
+    /// The socket addresses on which the server also listens for HTTP requests, e.g. an IPv6
+    /// address alongside an IPv4 `addr`.
+    ///
+    /// See `ConfigBuilder::additional_addr`.
+    pub additional_addrs: Vec<SocketAddr>,
+    /// The path beneath which all routes are served over HTTP, e.g. `/kv`, if any.
+    ///
+    /// See `ConfigBuilder::base_path`.
+    pub base_path: Option<String>,
+    /// Named views onto the tree that are available when the server starts.
+    ///
+    /// Views may also be added and removed at runtime via the `/admin/views` endpoints.
+    pub views: BTreeMap<String, View>,
+    /// Named webhooks to which changes to the tree are delivered.
+    ///
+    /// Webhooks may also be added and removed at runtime via the `/admin/webhooks` endpoints.
+    pub webhooks: BTreeMap<String, Webhook>,
+    /// The `sled` configuration reported via the `/db/info` endpoint, if any.
+    pub sled_config: Option<SledConfig>,
+    /// The URI of the upstream server from which the tree is replicated, if any.
+    ///
+    /// See `ConfigBuilder::replicate_from`.
+    pub upstream: Option<String>,
+    /// The URI of the remote server fronted by the server as a caching proxy, if any.
+    ///
+    /// See `ConfigBuilder::cache_for`.
+    pub origin: Option<String>,
+    /// The servers across which the keyspace is spread, if any.
+    ///
+    /// See `ConfigBuilder::shard`.
+    pub shards: Vec<Shard>,
+    /// The schedule by which the tree is periodically backed up, if any.
+    ///
+    /// See `ConfigBuilder::backup`.
+    pub backup: Option<Schedule>,
+    /// The socket address on which the server listens for Redis protocol connections, if any.
+    ///
+    /// See `ConfigBuilder::resp_addr`.
+    pub resp_addr: Option<SocketAddr>,
+    /// The greatest number of requests handled concurrently, if limited.
+    ///
+    /// See `ConfigBuilder::max_concurrent_requests`.
+    pub max_concurrent_requests: Option<usize>,
+    /// The greatest number of streamed responses in progress concurrently, if limited.
+    ///
+    /// See `ConfigBuilder::max_concurrent_streams`.
+    pub max_concurrent_streams: Option<usize>,
+    /// The greatest size of request body in bytes that the server reads.
+    ///
+    /// See `ConfigBuilder::max_body_size`.
+    pub max_body_size: usize,
+    /// How long the server may spend producing the response to a request, if limited.
+    ///
+    /// See `ConfigBuilder::request_timeout`.
+    pub request_timeout: Option<Duration>,
+    /// How long a streamed response may remain in progress, if limited.
+    ///
+    /// See `ConfigBuilder::stream_timeout`.
+    pub stream_timeout: Option<Duration>,
+    /// The greatest number of threads blocked on `sled` operations at once, if specified.
+    ///
+    /// See `ConfigBuilder::blocking_threads`.
+    pub blocking_threads: Option<usize>,
+    /// The certificate chain and private key with which the server listens over HTTPS, if any.
+    ///
+    /// See `ConfigBuilder::tls`.
+    #[cfg(feature = "tls")]
+    pub tls: Option<tls::Identity>,
 }
 
 /// A type used for building a `Config`.
 #[derive(Clone, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct ConfigBuilder {
     pub addr: Option<SocketAddr>,
+    pub additional_addrs: Vec<SocketAddr>,
+    pub base_path: Option<String>,
+    pub views: BTreeMap<String, View>,
+    pub webhooks: BTreeMap<String, Webhook>,
+    pub sled_config: Option<SledConfig>,
+    pub upstream: Option<String>,
+    pub origin: Option<String>,
+    pub shards: Vec<Shard>,
+    pub backup: Option<Schedule>,
+    pub resp_addr: Option<SocketAddr>,
+    pub max_concurrent_requests: Option<usize>,
+    pub max_concurrent_streams: Option<usize>,
+    pub max_body_size: Option<usize>,
+    pub request_timeout: Option<Duration>,
+    pub stream_timeout: Option<Duration>,
+    pub blocking_threads: Option<usize>,
+    #[cfg(feature = "tls")]
+    pub tls: Option<tls::Identity>,
+    #[cfg(feature = "tls")]
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+/// The state shared between all connections to the server.
+struct State {
+    tree: Arc<Tree>,
+    views: RwLock<BTreeMap<String, View>>,
+    webhooks: RwLock<BTreeMap<String, Webhook>>,
+    /// Whether or not changes are being delivered to webhooks.
+    delivering: AtomicBool,
+    /// The status of each migration started via `PullRange`.
+    migrations: RwLock<BTreeMap<String, MigrationStatus>>,
+    /// Opens the named trees, if named trees are supported.
+    store: Option<Arc<Store>>,
+    trees: RwLock<BTreeMap<String, Arc<Tree>>>,
+    sled_config: Option<SledConfig>,
+    /// The server from which the default tree is replicated, if any.
+    upstream: Option<Uri>,
+    /// Whether or not the default tree is being replicated from the `upstream`.
+    replicating: AtomicBool,
+    /// The server fronted by the default tree as a cache, if any.
+    origin: Option<Origin>,
+    /// The servers to which `/tree` requests are federated, if any.
+    federation: Option<Federation>,
+    /// The schedule by which the default tree is backed up, if any.
+    backup: Option<Schedule>,
+    /// The target to which scheduled backups are written.
+    backup_target: RwLock<Option<Arc<BackupTarget>>>,
+    backup_status: RwLock<request::BackupStatus>,
+    /// Whether or not scheduled backups have begun.
+    backing_up: AtomicBool,
+    /// The address on which to listen for Redis protocol connections, if any.
+    resp_addr: Option<SocketAddr>,
+    /// The normalized path beneath which all routes are served over HTTP, if any.
+    base_path: Option<String>,
+    /// The configuration with which to listen over HTTPS, if any.
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    /// Decides whether or not each request is permitted, if any.
+    authorizer: RwLock<Option<Arc<Authorizer>>>,
+    /// Handles requests that do not match any known endpoint, if any.
+    fallback: RwLock<Option<Arc<Fallback>>>,
+    /// User-defined endpoints keyed by method and path. See `Router::add_endpoint`.
+    endpoints: RwLock<BTreeMap<(String, String), Arc<Endpoint>>>,
+    /// Invoked around the handling of every request, in the order added.
+    middleware: RwLock<Vec<Arc<Middleware>>>,
+    /// Counts the requests handled concurrently, capping them if limited.
+    request_limit: Limit,
+    /// Counts the streamed responses in progress concurrently, capping them if limited.
+    stream_limit: Arc<Limit>,
+    /// The greatest size of request body in bytes that is read.
+    max_body_size: usize,
+    /// Bounds the time spent producing each response, if any.
+    request_timeout: Option<Duration>,
+    /// Bounds the time each streamed response remains in progress, if any.
+    stream_timeout: Option<Duration>,
+    started: Instant,
+}
+
+/// Counts the number of some kind of operation in progress at once, capping it if limited.
+struct Limit {
+    max: Option<usize>,
+    in_progress: Arc<AtomicUsize>,
+    /// A moving average of the milliseconds for which recent operations remained in progress.
+    mean_ms: Arc<AtomicUsize>,
+    /// Bounds the time for which each operation remains in progress, if any.
+    timeout: Option<Duration>,
+}
+
+/// Held while an operation counted by a `Limit` is in progress.
+struct Permit {
+    in_progress: Arc<AtomicUsize>,
+    mean_ms: Arc<AtomicUsize>,
+    acquired: Instant,
+}
+
+/// The remote server fronted by a caching proxy. See `ConfigBuilder::cache_for`.
+struct Origin {
+    uri: Uri,
+    client: hyper::Client<HttpConnector>,
+    cache: Mutex<CacheState>,
+    /// Whether or not the origin's changes are being followed.
+    invalidating: AtomicBool,
+}
+
+/// The servers across which the keyspace is spread. See `ConfigBuilder::shard`.
+struct Federation {
+    shards: Vec<Shard>,
+    /// The parsed URI of each shard, in the same order as `shards`.
+    uris: Vec<Uri>,
+    /// A client for each shard, in the same order as `shards`.
+    clients: Vec<Client>,
+    http: hyper::Client<HttpConnector>,
+}
+
+/// Guards the cached entries against being filled with values read before an invalidation.
+struct CacheState {
+    /// Incremented with each invalidation of the cached entries.
+    epoch: u64,
+    /// Whether or not the origin's change feed is up to date, in which case entries may be cached.
+    following: bool,
+}
+
+/// Routes requests to their associated responses.
+///
+/// The `Router` is cheap to clone and all clones share the same state. This allows for mounting
+/// the API within other servers and frameworks, e.g. via the `adapter` module.
+#[derive(Clone)]
+pub struct Router {
+    state: Arc<State>,
+}
+
+/// A server bound to its addresses, created via `server::new` or `server::new_from_listener`.
+///
+/// The `Server` is a `Future` representing the server's computation, which must be run, e.g. via
+/// `hyper::rt::run`, for the server to begin accepting connections.
+pub struct Server {
+    /// The address of each listener, beginning with the primary.
+    local_addrs: Vec<SocketAddr>,
+    router: Router,
+    /// The connections accepted by the listeners, taken once the server is first polled.
+    incoming: Option<Incoming>,
+    shutdown: Option<Shutdown>,
+    /// The server's computation, begun once the server is first polled.
+    future: Option<Box<Future<Item = (), Error = hyper::Error> + Send>>,
+}
+
+/// Stops a `Server` gracefully. See `Server::with_graceful_shutdown`.
+struct Shutdown {
+    signal: Signal,
+    timeout: Duration,
+}
+
+/// A future that completes to signal some event, e.g. a shutdown.
+type Signal = Box<Future<Item = (), Error = ()> + Send>;
+
+/// The connections accepted by a listener given to `server::new_from_listener`.
+///
+/// As with hyper's `AddrIncoming`, an error accepting a connection, e.g. as the process has run
+/// out of file descriptors, pauses accepting for a second rather than ending the server.
+struct ListenerIncoming {
+    listener: TcpListener,
+    pause: Option<Delay>,
+}
+
+/// Decides whether or not each request received by the `Router` is permitted.
+///
+/// Requests received over HTTPS from a client that presented a certificate carry the client's
+/// `tls::PeerCertificates` within their extensions, allowing for certificate-based access control.
+/// Requests made by other means, e.g. via the Redis protocol listener, carry no certificates.
+pub trait Authorizer: Send + Sync {
+    /// Returns `Err` with the reason if the request is forbidden.
+    ///
+    /// Forbidden requests receive a `403 Forbidden` response carrying the reason.
+    fn authorize(&self, request: &Request<Body>) -> Result<(), String>;
+}
+
+impl<F> Authorizer for F
+where
+    F: Fn(&Request<Body>) -> Result<(), String> + Send + Sync,
+{
+    fn authorize(&self, request: &Request<Body>) -> Result<(), String> {
+        (*self)(request)
+    }
+}
+
+/// A user-defined endpoint handling requests via the tree to which they are made.
+///
+/// Allows for adding domain-specific server-side operations to the API. See
+/// `Router::add_endpoint`.
+pub trait Endpoint: Send + Sync {
+    /// Respond to the request made via the given tree.
+    fn respond(&self, tree: Arc<Tree>, request: Request<Body>) -> ResponseFuture;
+}
+
+impl<F> Endpoint for F
+where
+    F: Fn(Arc<Tree>, Request<Body>) -> ResponseFuture + Send + Sync,
+{
+    fn respond(&self, tree: Arc<Tree>, request: Request<Body>) -> ResponseFuture {
+        (*self)(tree, request)
+    }
+}
+
+/// Hooks invoked by the `Router` around the handling of every request, e.g. for logging, metrics
+/// or injecting headers. See `Router::add_middleware`.
+///
+/// All methods do nothing by default.
+pub trait Middleware: Send + Sync {
+    /// Inspect or modify the request before it is routed.
+    ///
+    /// Returning `Err` responds with the given response without routing the request, skipping
+    /// all following middleware, including their `on_response`.
+    fn on_request(&self, _request: &mut Request<Body>) -> Result<(), Response<Body>> {
+        Ok(())
+    }
+
+    /// Inspect or modify the response to the request described by the `RequestInfo`.
+    fn on_response(&self, _info: &RequestInfo, _response: &mut Response<Body>) {}
+
+    /// Inspect the error that occurred while responding to the request described by the
+    /// `RequestInfo`, in which case no response is sent.
+    fn on_error(&self, _info: &RequestInfo, _error: &hyper::Error) {}
+}
+
+/// A description of a request handled by the `Router`, given to each `Middleware` alongside the
+/// outcome.
+#[derive(Clone, Debug)]
+pub struct RequestInfo {
+    /// The method of the request as received.
+    pub method: Method,
+    /// The URI of the request as received.
+    pub uri: Uri,
+    /// The moment at which the `Router` received the request, e.g. for measuring latency.
+    pub received: Instant,
+}
+
+/// Handles the requests received by the `Router` that do not match any known endpoint.
+pub trait Fallback: Send + Sync {
+    /// Respond to the unmatched request.
+    fn respond(&self, request: Request<Body>) -> ResponseFuture;
+}
+
+impl<F> Fallback for F
+where
+    F: Fn(Request<Body>) -> ResponseFuture + Send + Sync,
+{
+    fn respond(&self, request: Request<Body>) -> ResponseFuture {
+        (*self)(request)
+    }
 }
 
 /// Begin building the configuration for the server.
@@ -37,6 +401,271 @@ impl Config {
     pub const DEFAULT_PORT: u16 = 3000;
     /// The default socket address used if one is not specified.
     pub const DEFAULT_ADDR: ([u8; 4], u16) = (Self::DEFAULT_IP, Self::DEFAULT_PORT);
+    /// The greatest size of request body in bytes read if a maximum is not specified.
+    pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+}
+
+impl Router {
+    /// Create a `Router` serving the given `tree` along with the views within the given `config`.
+    ///
+    /// The `addr` of the `config` is ignored.
+    pub fn new<T>(config: Config, tree: T) -> Self
+    where
+        T: Into<Tree>,
+    {
+        Self::from_parts(config, tree, None)
+    }
+
+    /// Create a `Router` that also serves named trees opened via the given `Store`.
+    ///
+    /// Named trees are opened via the `OpenTree` request and are accessed via paths of the form
+    /// `/tree/{name}/entries/...`.
+    pub fn with_store<T, S>(config: Config, tree: T, store: S) -> Self
+    where
+        T: Into<Tree>,
+        S: 'static + Store,
+    {
+        Self::from_parts(config, tree, Some(Arc::new(store)))
+    }
+
+    fn from_parts<T>(config: Config, tree: T, store: Option<Arc<Store>>) -> Self
+    where
+        T: Into<Tree>,
+    {
+        let tree = Arc::new(tree.into());
+        let views = RwLock::new(config.views);
+        let webhooks = RwLock::new(config.webhooks);
+        let delivering = AtomicBool::new(false);
+        let migrations = RwLock::new(BTreeMap::new());
+        let trees = RwLock::new(BTreeMap::new());
+        let sled_config = config.sled_config;
+        let upstream = config.upstream.map(|upstream| {
+            upstream.parse().expect("failed to parse upstream URI")
+        });
+        let replicating = AtomicBool::new(false);
+        assert!(
+            upstream.is_none() || config.origin.is_none(),
+            "a server may not both replicate from an upstream and cache for an origin",
+        );
+        let origin = config.origin.map(|origin| {
+            let uri: Uri = origin.parse().expect("failed to parse origin URI");
+            assert!(uri.scheme_part().is_some(), "the origin URI must include a scheme");
+            let client = hyper::Client::new();
+            let cache = Mutex::new(CacheState { epoch: 0, following: false });
+            let invalidating = AtomicBool::new(false);
+            Origin { uri, client, cache, invalidating }
+        });
+        let federation = if config.shards.is_empty() {
+            None
+        } else {
+            let shards = config.shards;
+            let uris: Vec<Uri> = shards
+                .iter()
+                .map(|shard| shard.uri.parse().expect("failed to parse shard URI"))
+                .collect();
+            let clients = uris.iter().cloned().map(Client::new).collect();
+            let http = hyper::Client::new();
+            Some(Federation { shards, uris, clients, http })
+        };
+        let backup_target = config.backup.as_ref().map(|schedule| -> Arc<BackupTarget> {
+            Arc::new(DirTarget::new(schedule.dir.clone()))
+        });
+        let backup_status = request::BackupStatus {
+            backups: backup_target
+                .as_ref()
+                .and_then(|target| backup::list_backups(&**target).ok())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let backup_status = RwLock::new(backup_status);
+        let backup_target = RwLock::new(backup_target);
+        let backup = config.backup;
+        let backing_up = AtomicBool::new(false);
+        let resp_addr = config.resp_addr;
+        let base_path = config.base_path
+            .as_ref()
+            .and_then(|path| request::normalize_base_path(path));
+        #[cfg(feature = "tls")]
+        let tls = config.tls.map(|identity| {
+            let config = identity.server_config().expect("failed to load the TLS identity");
+            Arc::new(config)
+        });
+        let request_limit = Limit::new(config.max_concurrent_requests, config.request_timeout);
+        let stream_limit = Limit::new(config.max_concurrent_streams, config.stream_timeout);
+        let started = Instant::now();
+        let state = Arc::new(State {
+            tree,
+            views,
+            webhooks,
+            delivering,
+            migrations,
+            store,
+            trees,
+            sled_config,
+            upstream,
+            replicating,
+            origin,
+            federation,
+            backup,
+            backup_target,
+            backup_status,
+            backing_up,
+            resp_addr,
+            base_path,
+            #[cfg(feature = "tls")]
+            tls,
+            authorizer: RwLock::new(None),
+            fallback: RwLock::new(None),
+            endpoints: RwLock::new(BTreeMap::new()),
+            middleware: RwLock::new(Vec::new()),
+            request_limit,
+            stream_limit: Arc::new(stream_limit),
+            max_body_size: config.max_body_size,
+            request_timeout: config.request_timeout,
+            stream_timeout: config.stream_timeout,
+            started,
+        });
+        Router { state }
+    }
+
+    /// Write scheduled backups to the given target rather than to the directory of the backup
+    /// `Schedule`, e.g. to an object store.
+    ///
+    /// Has no effect on when backups are taken, which is still determined by the `Schedule`. The
+    /// listed backups are refreshed from the new target.
+    pub fn set_backup_target<B>(&self, target: B)
+    where
+        B: 'static + BackupTarget,
+    {
+        let target: Arc<BackupTarget> = Arc::new(target);
+        let backups = backup::list_backups(&*target).unwrap_or_default();
+        *self.state.backup_target
+            .write()
+            .expect("failed to acquire backup target lock") = Some(target);
+        self.state.backup_status
+            .write()
+            .expect("failed to acquire backup status lock")
+            .backups = backups;
+    }
+
+    /// Handle requests that do not match any known endpoint via the given `Fallback`, e.g. to
+    /// serve the application's own routes alongside the API.
+    ///
+    /// Only applies to requests handled via `Router::respond`, e.g. those received by the server.
+    pub fn set_fallback<F>(&self, fallback: F)
+    where
+        F: 'static + Fallback,
+    {
+        let fallback: Arc<Fallback> = Arc::new(fallback);
+        *self.state.fallback
+            .write()
+            .expect("failed to acquire fallback lock") = Some(fallback);
+    }
+
+    /// Respond to the unmatched request via the `Fallback`, if any.
+    fn fall_back(&self, request: Request<Body>) -> Result<ResponseFuture, UnknownRequest> {
+        let fallback = self.state.fallback
+            .read()
+            .expect("failed to acquire fallback lock")
+            .clone();
+        fallback.map(|fallback| fallback.respond(request)).ok_or(UnknownRequest)
+    }
+
+    /// Handle requests with the given method and path via the given `Endpoint`, replacing any
+    /// endpoint previously added for the same method and path.
+    ///
+    /// Built-in endpoints take precedence. As with the built-in `/tree` endpoints, requests made
+    /// via `/views/{name}` or `/tree/{name}` are matched against the remainder of the path and
+    /// handled via the view or named tree, e.g. an endpoint added for `/tree/top_scores` also
+    /// handles `/tree/users/top_scores` via the `users` tree. The path is matched exactly,
+    /// excluding the query.
+    pub fn add_endpoint<E>(&self, method: Method, path: &str, endpoint: E)
+    where
+        E: 'static + Endpoint,
+    {
+        let endpoint: Arc<Endpoint> = Arc::new(endpoint);
+        let key = (method.as_str().to_string(), path.to_string());
+        self.state.endpoints
+            .write()
+            .expect("failed to acquire endpoints lock")
+            .insert(key, endpoint);
+    }
+
+    /// Remove the endpoint added for the given method and path, returning whether or not one
+    /// existed.
+    pub fn remove_endpoint(&self, method: Method, path: &str) -> bool {
+        let key = (method.as_str().to_string(), path.to_string());
+        self.state.endpoints
+            .write()
+            .expect("failed to acquire endpoints lock")
+            .remove(&key)
+            .is_some()
+    }
+
+    /// Invoke the given `Middleware` around the handling of every request.
+    ///
+    /// Middleware see requests in the order in which they were added, and responses and errors in
+    /// the reverse order. Requests are seen after their bodies are decoded and responses before
+    /// they are encoded or compressed.
+    pub fn add_middleware<M>(&self, middleware: M)
+    where
+        M: 'static + Middleware,
+    {
+        let middleware: Arc<Middleware> = Arc::new(middleware);
+        self.state.middleware
+            .write()
+            .expect("failed to acquire middleware lock")
+            .push(middleware);
+    }
+
+    /// Decide whether or not each request is permitted via the given `Authorizer`.
+    ///
+    /// Forbidden requests receive a `403 Forbidden` response without being routed. By default, all
+    /// requests are permitted.
+    pub fn set_authorizer<A>(&self, authorizer: A)
+    where
+        A: 'static + Authorizer,
+    {
+        let authorizer: Arc<Authorizer> = Arc::new(authorizer);
+        *self.state.authorizer
+            .write()
+            .expect("failed to acquire authorizer lock") = Some(authorizer);
+    }
+
+    /// Route the given request to its associated response.
+    ///
+    /// Returns `Err(UnknownRequest)` if the request does not match any known endpoint, allowing
+    /// the caller to fall back to some other handler. Bodies are converted from and to the
+    /// encodings given via the request's `Content-Type` and `Accept` headers, or via its
+    /// `bytes=base64` query parameter. See the `encoding` module. Response bodies are compressed
+    /// as preferred via the request's `Accept-Encoding` header. See the `compression` module.
+    pub fn route(&self, request: Request<Body>) -> Result<ResponseFuture, UnknownRequest> {
+        with_encodings(request, |request| {
+            with_middleware(&self.state, request, |request| {
+                with_limits(&self.state, request, |request| {
+                    route(request, self.state.clone()).map_err(|_| UnknownRequest)
+                })
+            })
+        })
+    }
+
+    /// Route the given request to its associated response.
+    ///
+    /// Requests that do not match any known endpoint are handled by the `Fallback` given via
+    /// `Router::set_fallback`, or otherwise receive a `404 Not Found` response. Bodies are
+    /// converted and compressed as described for `Router::route`.
+    pub fn respond(&self, request: Request<Body>) -> ResponseFuture {
+        let routed = with_encodings(request, |request| {
+            with_middleware(&self.state, request, |request| {
+                with_limits(&self.state, request, |request| {
+                    let routed = route(request, self.state.clone())
+                        .or_else(|req| self.fall_back(req));
+                    Ok(Box::new(or_404(routed)) as ResponseFuture)
+                })
+            })
+        });
+        Box::new(or_404(routed))
+    }
 }
 
 impl ConfigBuilder {
@@ -51,35 +680,2351 @@ impl ConfigBuilder {
         self
     }
 
+    /// An additional socket address on which the server will listen for HTTP requests.
+    ///
+    /// All addresses are served by the same `Router` and runtime, e.g. to listen on both IPv4 and
+    /// IPv6, or on both localhost and a LAN address. May be called multiple times.
+    pub fn additional_addr<T>(&mut self, addr: T) -> &mut Self
+    where
+        T: Into<SocketAddr>,
+    {
+        self.additional_addrs.push(addr.into());
+        self
+    }
+
+    /// Serve all routes over HTTP beneath the given path, e.g. `/kv`, such that entries are set
+    /// via `/kv/tree/entries/set`.
+    ///
+    /// This allows for deploying the server behind reverse proxies and API gateways that route
+    /// requests by path. Requests whose paths are not beneath the base path are handled as
+    /// though unmatched. `Client`s must be given the same path via `Client::base_path`.
+    ///
+    /// Only affects the server's HTTP listeners. Requests handled directly via the `Router`, e.g.
+    /// via a `Client::local` or an `adapter`, are routed without a base path.
+    pub fn base_path<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.base_path = Some(path.into());
+        self
+    }
+
+    /// Define a named view onto the tree that is available when the server starts.
+    ///
+    /// See `request::View` for details.
+    pub fn view<S>(&mut self, name: S, view: View) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.views.insert(name.into(), view);
+        self
+    }
+
+    /// Register a named webhook to which changes to the tree are delivered.
+    ///
+    /// See `request::Webhook` for details.
+    pub fn webhook<S>(&mut self, name: S, webhook: Webhook) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.webhooks.insert(name.into(), webhook);
+        self
+    }
+
+    /// Replicate the served tree from the sled-web server at the given URI, e.g.
+    /// `http://primary:3000`, serving reads locally while rejecting writes.
+    ///
+    /// The replica begins with a snapshot of the upstream via `Export`, then follows its changes
+    /// via `ChangesSince`, taking a new snapshot whenever the upstream no longer retains the
+    /// changes following the last one applied. The upstream should retain its changes via
+    /// `Tree::with_change_history` or `Tree::with_changelog` to avoid repeated snapshots.
+    ///
+    /// Requests that may mutate any tree respond with `403 Forbidden`, while views, webhooks and
+    /// cursors may still be managed. The `Router` panics if the URI is invalid.
+    pub fn replicate_from<S>(&mut self, upstream: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.upstream = Some(upstream.into());
+        self
+    }
+
+    /// Front the sled-web server at the given URI, e.g. `http://origin:3000`, as a caching proxy.
+    ///
+    /// `Get` requests are served from the served tree, which acts as a local cache that is filled
+    /// from the origin upon each miss. All other requests, including writes, are forwarded to the
+    /// origin as they are. The origin's changes are followed via `ChangesSince`, removing each
+    /// changed entry from the cache, while the whole cache is cleared if the origin no longer
+    /// retains the changes since the last followed.
+    ///
+    /// A `Get` made shortly after a write via the proxy may observe the previous value until the
+    /// change is followed. WebSocket subscriptions are not forwarded. The served tree should be
+    /// dedicated to the cache, as its entries are removed freely. The `Router` panics if the URI
+    /// is invalid or if `replicate_from` is also given.
+    pub fn cache_for<S>(&mut self, origin: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    /// Serve the keys beginning with `prefix` from the sled-web server at the given URI, e.g.
+    /// `http://shard-a:3000`, federating the keyspace across several servers.
+    ///
+    /// Each key belongs to the shard with the longest prefix with which the key begins. Requests
+    /// to `/tree` with a `key` are forwarded to the server of the shard to which the key belongs,
+    /// while `Iter`, `Scan` and `ScanRange` requests are made to every shard, merging the streams
+    /// in order of their keys. Each shard's stream only includes the entries belonging to the
+    /// shard, so the shards' servers may share a tree. Other `/tree` requests respond with `501
+    /// Not Implemented`, while all other requests are served locally. Requests for a key that
+    /// belongs to no shard respond with `400 Bad Request`, which may be avoided by including a
+    /// shard with an empty prefix. The `Router` panics if the URI is invalid.
+    pub fn shard<K, S>(&mut self, prefix: K, uri: S) -> &mut Self
+    where
+        K: Into<Vec<u8>>,
+        S: Into<String>,
+    {
+        let prefix = prefix.into();
+        let uri = uri.into();
+        self.shards.push(Shard { prefix, uri });
+        self
+    }
+
+    /// Periodically back up the served tree to a directory, retaining only the most recent
+    /// backups.
+    ///
+    /// Backups are written by the server itself, so no external scheduling is required. A backup
+    /// is skipped if no change has been made since the last. The status of the backups is
+    /// reported via the `/admin/backups/status` endpoint. See the `backup` module for details.
+    pub fn backup(&mut self, schedule: Schedule) -> &mut Self {
+        self.backup = Some(schedule);
+        self
+    }
+
+    /// Also listen for connections speaking a subset of the Redis protocol on the given socket
+    /// address, e.g. `([127, 0, 0, 1], 6379)`.
+    ///
+    /// This allows Redis client libraries to `GET`, `SET`, `DEL`, `INCR` and `SCAN` the default
+    /// tree. The listener is started by `serve` alongside the HTTP server. See the `resp` module
+    /// for details.
+    pub fn resp_addr<T>(&mut self, addr: T) -> &mut Self
+    where
+        T: Into<SocketAddr>,
+    {
+        self.resp_addr = Some(addr.into());
+        self
+    }
+
+    /// Limit the number of requests handled concurrently, responding to those beyond the limit
+    /// with `429 Too Many Requests`.
+    ///
+    /// A request is counted until its response begins. Streamed responses, e.g. to `iter` or
+    /// `watch`, are limited separately via `max_concurrent_streams`. Unlimited by default.
+    pub fn max_concurrent_requests(&mut self, max: usize) -> &mut Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Limit the number of streamed responses in progress concurrently, e.g. to `iter`, `scan`,
+    /// `export` or `watch`, responding to requests for streams beyond the limit with
+    /// `429 Too Many Requests`.
+    ///
+    /// A stream is counted until its body ends or the client disconnects. This prevents a burst
+    /// of expensive scans from exhausting memory or starving other requests. The number in
+    /// progress is reported via the `/admin/streams/status` endpoint. Unlimited by default.
+    pub fn max_concurrent_streams(&mut self, max: usize) -> &mut Self {
+        self.max_concurrent_streams = Some(max);
+        self
+    }
+
+    /// The greatest size of request body in bytes that the server reads, responding to requests
+    /// with larger bodies, e.g. `set` or `merge` of oversized values, with
+    /// `413 Payload Too Large`.
+    ///
+    /// Bodies are buffered in memory before being processed, and so this bounds the memory that
+    /// any one request may consume. The bodies of `import` requests are applied as they are
+    /// streamed and so are not limited. Defaults to `Config::DEFAULT_MAX_BODY_SIZE`.
+    pub fn max_body_size(&mut self, max: usize) -> &mut Self {
+        self.max_body_size = Some(max);
+        self
+    }
+
+    /// Abort requests whose responses have not begun within the given duration, responding
+    /// with `504 Gateway Timeout` instead.
+    ///
+    /// Applies to unary operations, e.g. `get`, `set` or `compare_and_swap`, as well as the time
+    /// taken to begin streamed responses. Long-polls via `changes_since` waiting for longer are
+    /// aborted likewise. Unlimited by default.
+    pub fn request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Abort streamed responses, e.g. to `iter`, `scan`, `export` or `watch`, that remain in
+    /// progress for longer than the given duration.
+    ///
+    /// As the status has already been sent by then, the stream is ended with an error and the
+    /// client observes a truncated body. Clients of long-lived `watch` streams are expected to
+    /// reconnect, as `Subscription`s do. Unlimited by default.
+    pub fn stream_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.stream_timeout = Some(timeout);
+        self
+    }
+
+    /// The greatest number of threads blocked on `sled` operations at once.
+    ///
+    /// `sled` operations block on disk IO and so are run within blocking sections of the `tokio`
+    /// thread pool, leaving the pool's workers free to serve other connections in the meantime.
+    /// Operations beyond this number wait until a thread becomes available. See
+    /// `response::blocking`.
+    ///
+    /// Applies to the runtime started via `server::run` or `server::run_until`. Applications
+    /// running the `Server` on a runtime of their own may configure the same via
+    /// `tokio::runtime::Builder::blocking_threads`. Defaults to that of `tokio`.
+    pub fn blocking_threads(&mut self, threads: usize) -> &mut Self {
+        self.blocking_threads = Some(threads);
+        self
+    }
+
+    /// Listen over HTTPS rather than HTTP, identifying the server via the certificate chain and
+    /// private key within the given PEM files.
+    ///
+    /// Requires the `tls` feature. The `Router` panics if the files cannot be loaded. See the `tls`
+    /// module for details.
+    #[cfg(feature = "tls")]
+    pub fn tls<C, K>(&mut self, cert_chain: C, private_key: K) -> &mut Self
+    where
+        C: Into<PathBuf>,
+        K: Into<PathBuf>,
+    {
+        let cert_chain = cert_chain.into();
+        let private_key = private_key.into();
+        let client_ca = None;
+        self.tls = Some(tls::Identity { cert_chain, private_key, client_ca });
+        self
+    }
+
+    /// Require clients connecting over HTTPS to present a certificate signed by one of the
+    /// certificate authorities within the given PEM file.
+    ///
+    /// Has no effect unless the server listens over HTTPS via `ConfigBuilder::tls`. The verified
+    /// certificates of each client are made available to the `Authorizer` given via
+    /// `Router::set_authorizer`.
+    #[cfg(feature = "tls")]
+    pub fn tls_client_ca<P>(&mut self, client_ca: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.tls_client_ca = Some(client_ca.into());
+        self
+    }
+
+    /// The `sled` configuration of the served tree, reported via the `/db/info` endpoint.
+    pub fn sled_config(&mut self, config: &sled::ConfigBuilder) -> &mut Self {
+        self.sled_config = Some(SledConfig {
+            path: config.path.display().to_string(),
+            cache_capacity: config.cache_capacity as u64,
+            segment_size: config.io_buf_size as u64,
+            flush_every_ms: config.flush_every_ms,
+        });
+        self
+    }
+
     /// Build the `Config` type, replacing `None` values with defaults where necessary.
     pub fn build(&mut self) -> Config {
         let addr = self.addr.take().unwrap_or_else(|| Config::DEFAULT_ADDR.into());
-        Config { addr }
+        let additional_addrs = mem::replace(&mut self.additional_addrs, Default::default());
+        let base_path = self.base_path.take();
+        let views = mem::replace(&mut self.views, Default::default());
+        let webhooks = mem::replace(&mut self.webhooks, Default::default());
+        let sled_config = self.sled_config.take();
+        let upstream = self.upstream.take();
+        let origin = self.origin.take();
+        let shards = mem::replace(&mut self.shards, Default::default());
+        let backup = self.backup.take();
+        let resp_addr = self.resp_addr.take();
+        let max_concurrent_requests = self.max_concurrent_requests.take();
+        let max_concurrent_streams = self.max_concurrent_streams.take();
+        let max_body_size = self.max_body_size.take().unwrap_or(Config::DEFAULT_MAX_BODY_SIZE);
+        let request_timeout = self.request_timeout.take();
+        let stream_timeout = self.stream_timeout.take();
+        let blocking_threads = self.blocking_threads.take();
+        #[cfg(feature = "tls")]
+        let tls = {
+            let client_ca = self.tls_client_ca.take();
+            self.tls.take().map(|identity| tls::Identity {
+                client_ca: client_ca.or(identity.client_ca),
+                ..identity
+            })
+        };
+        Config {
+            addr,
+            additional_addrs,
+            base_path,
+            views,
+            webhooks,
+            sled_config,
+            upstream,
+            origin,
+            shards,
+            backup,
+            resp_addr,
+            max_concurrent_requests,
+            max_concurrent_streams,
+            max_body_size,
+            request_timeout,
+            stream_timeout,
+            blocking_threads,
+            #[cfg(feature = "tls")]
+            tls,
+        }
     }
 }
 
-// Pure functions.
+impl Limit {
+    fn new(max: Option<usize>, timeout: Option<Duration>) -> Self {
+        let in_progress = Arc::new(AtomicUsize::new(0));
+        let mean_ms = Arc::new(AtomicUsize::new(0));
+        Limit { max, in_progress, mean_ms, timeout }
+    }
 
-/// Build the hyper `Server` with the given configuration and `sled::Tree`.
-///
-/// Returns a `Future` representing the `Server`'s computation.
-///
-/// To create and run your own server you can use the `response` function which simply translates
-/// requests to response futures.
-pub fn new(config: Config, tree: Arc<sled::Tree>) -> impl Future<Item = (), Error = hyper::Error> {
-    Server::bind(&config.addr)
-        .serve(move || {
-            let tree = tree.clone();
-            service_fn(move |req| {
-                or_404(response(req, tree.clone()))
-            })
-        })
+    /// Count a new operation, or `None` if as many as permitted are already in progress.
+    fn acquire(&self) -> Option<Permit> {
+        let mut current = self.in_progress.load(Ordering::Acquire);
+        loop {
+            if self.max.map(|max| current >= max).unwrap_or(false) {
+                return None;
+            }
+            let exchanged = self.in_progress.compare_exchange(
+                current,
+                current + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            );
+            match exchanged {
+                Ok(_) => {
+                    let in_progress = self.in_progress.clone();
+                    let mean_ms = self.mean_ms.clone();
+                    let acquired = Instant::now();
+                    return Some(Permit { in_progress, mean_ms, acquired });
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// The number of operations currently in progress.
+    fn in_progress(&self) -> usize {
+        self.in_progress.load(Ordering::Acquire)
+    }
+
+    /// The number of seconds after which an operation turned away by the limit may be retried.
+    ///
+    /// With as many operations in progress as permitted, one is expected to end within the mean
+    /// time for which operations remain in progress divided by their number. This is rounded up
+    /// to at least a second, and never exceeds the `timeout` by which every operation will have
+    /// ended.
+    fn retry_after(&self) -> u64 {
+        let in_progress = cmp::max(self.in_progress(), 1);
+        let expected_ms = (self.mean_ms.load(Ordering::Acquire) / in_progress) as u64;
+        let secs = cmp::max((expected_ms + 999) / 1_000, 1);
+        match self.timeout {
+            None => secs,
+            Some(timeout) => {
+                let timeout_secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+                cmp::min(secs, cmp::max(timeout_secs, 1))
+            }
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let elapsed = self.acquired.elapsed();
+        let ms = elapsed.as_secs() * 1_000 + u64::from(elapsed.subsec_millis());
+        // Concurrent updates may overwrite one another, which is acceptable for an estimate.
+        let mean = self.mean_ms.load(Ordering::Acquire) as u64;
+        self.mean_ms.store(((mean * 7 + ms) / 8) as usize, Ordering::Release);
+        self.in_progress.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Server {
+    /// The default duration given to in-flight requests and streams to complete once a shutdown is
+    /// signalled via `server::run_until`.
+    pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Serve all requests via the `Router`, accepting connections via the given `incoming`
+    /// stream from the listeners bound to `local_addrs`, the first of which is the primary.
+    fn new(router: Router, local_addrs: Vec<SocketAddr>, incoming: Incoming) -> Self {
+        let incoming = Some(incoming);
+        let shutdown = None;
+        let future = None;
+        Server { local_addrs, router, incoming, shutdown, future }
+    }
+
+    /// Shut the server down gracefully once the given `signal` completes, successfully or not.
+    ///
+    /// Upon the signal, the server stops accepting connections and waits for in-flight requests and
+    /// streams, e.g. `iter` or `watch`, to complete. Those still in flight after the `timeout` are
+    /// dropped. All trees are then flushed before the server's computation completes, rather than
+    /// the process being killed mid-write.
+    ///
+    /// Background tasks spawned by the server, e.g. replication or the Redis protocol listener,
+    /// continue until the runtime is shut down. See `server::run_until`.
+    pub fn with_graceful_shutdown<F>(mut self, signal: F, timeout: Duration) -> Self
+    where
+        F: 'static + Future + Send,
+    {
+        let signal = Box::new(signal.then(|_| Ok::<_, ()>(())));
+        self.shutdown = Some(Shutdown { signal, timeout });
+        self
+    }
+
+    /// The address to which the server's primary listener is bound, i.e. that of `Config::addr`.
+    ///
+    /// Useful for discovering the port assigned by the OS when binding to port `0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+
+    /// The addresses to which each of the server's listeners are bound, beginning with that of
+    /// the primary listener and followed by those of `Config::additional_addrs` in order.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+}
+
+impl Stream for ListenerIncoming {
+    type Item = TcpStream;
+    type Error = io::Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(ref mut pause) = self.pause {
+                if let Ok(Async::NotReady) = pause.poll() {
+                    return Ok(Async::NotReady);
+                }
+            }
+            self.pause = None;
+            match self.listener.poll_accept() {
+                Ok(Async::Ready((stream, _))) => return Ok(Async::Ready(Some(stream))),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(_) => self.pause = Some(Delay::new(Instant::now() + Duration::from_secs(1))),
+            }
+        }
+    }
+}
+
+impl Future for Server {
+    type Item = ();
+    type Error = hyper::Error;
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.future.is_none() {
+            let incoming = self.incoming.take().expect("server polled after completion");
+            let shutdown = self.shutdown.take();
+            let server = serve_incoming(self.router.clone(), move || Ok(incoming), shutdown);
+            self.future = Some(Box::new(server));
+        }
+        let future = self.future.as_mut().expect("no server future");
+        future.poll()
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("local_addrs", &self.local_addrs)
+            .finish()
+    }
+}
+
+// Pure functions.
+
+/// Build the `Server` with the given configuration and `sled::Tree`, binding to `Config::addr`
+/// along with each of `Config::additional_addrs`.
+///
+/// The `tree` may be any type that can be converted into a `Tree`, e.g. an `Arc<sled::Tree>`, a
+/// `backend::MemoryBackend`, or a `Tree` maintaining additional indices.
+///
+/// The addresses are bound immediately, so that the addresses actually bound may be retrieved via
+/// `Server::local_addrs`, e.g. when binding to port `0`. Returns an `Err` if binding to any of
+/// the addresses fails.
+///
+/// To create and run your own server you can use the `Router` type which simply translates
+/// requests to response futures.
+pub fn new<T>(config: Config, tree: T) -> hyper::Result<Server>
+where
+    T: Into<Tree>,
+{
+    let incomings = Some(&config.addr)
+        .into_iter()
+        .chain(&config.additional_addrs)
+        .map(AddrIncoming::bind)
+        .collect::<hyper::Result<Vec<_>>>()?;
+    let local_addrs = incomings.iter().map(AddrIncoming::local_addr).collect();
+    let incoming = merge_incoming(incomings);
+    Ok(Server::new(Router::new(config, tree), local_addrs, incoming))
+}
+
+/// Build the `Server` with the given configuration and `sled::Tree`, accepting connections via
+/// the given already-bound listener rather than binding to `Config::addr` and
+/// `Config::additional_addrs`.
+///
+/// This allows the caller to control the socket's options, e.g. `SO_REUSEPORT` or the size of
+/// its backlog, or to bind to a privileged port before dropping privileges.
+///
+/// Returns an `Err` if the listener cannot be registered with the reactor.
+pub fn new_from_listener<T>(
+    config: Config,
+    listener: net::TcpListener,
+    tree: T,
+) -> io::Result<Server>
+where
+    T: Into<Tree>,
+{
+    let listener = TcpListener::from_std(listener, &Handle::default())?;
+    let local_addrs = vec![listener.local_addr()?];
+    let incoming = Box::new(ListenerIncoming { listener, pause: None });
+    Ok(Server::new(Router::new(config, tree), local_addrs, incoming))
+}
+
+/// Build the hyper `Server` bound to the given address, handling all requests via the `Router`.
+///
+/// If the `Router` replicates from an upstream server, caches for an origin or backs up the tree,
+/// replication, cache invalidation and backups begin as soon as the server is run rather than
+/// upon the first request. The Redis protocol listener is also started if configured via
+/// `ConfigBuilder::resp_addr`.
+///
+/// Connections may speak either HTTP/1.1 or HTTP/2, including HTTP/2 without TLS (h2c) given
+/// prior knowledge, allowing many concurrent requests and streams to share a single connection.
+///
+/// The server listens over HTTPS if configured via `ConfigBuilder::tls`. The certificates
+/// presented by each client, if any, are inserted into the extensions of its requests as
+/// `tls::PeerCertificates`.
+///
+/// Returns a `Future` representing the `Server`'s computation.
+pub fn serve(addr: &SocketAddr, router: Router) -> impl Future<Item = (), Error = hyper::Error> {
+    let addr = *addr;
+    let incoming = move || AddrIncoming::bind(&addr).map(|incoming| incoming.map(into_tcp_stream));
+    serve_incoming(router, incoming, None)
+}
+
+/// Merge the connections accepted by each of the listeners into a single stream.
+fn merge_incoming(incomings: Vec<AddrIncoming>) -> Incoming {
+    let empty: Incoming = Box::new(futures::stream::empty());
+    incomings.into_iter().fold(empty, |merged, incoming| {
+        Box::new(merged.select(incoming.map(into_tcp_stream)))
+    })
+}
+
+/// The TCP stream of a connection accepted via an `AddrIncoming`.
+fn into_tcp_stream(stream: AddrStream) -> TcpStream {
+    stream.into_inner()
+}
+
+/// Serve all requests via the `Router`, accepting connections via the stream produced by
+/// `incoming` once the server is run.
+///
+/// If given a `Shutdown`, the server is drained and all trees flushed upon its signal.
+fn serve_incoming<F, I>(
+    router: Router,
+    incoming: F,
+    shutdown: Option<Shutdown>,
+) -> impl Future<Item = (), Error = hyper::Error>
+where
+    F: 'static + FnOnce() -> hyper::Result<I> + Send,
+    I: 'static + Stream<Item = TcpStream, Error = io::Error> + Send,
+{
+    let (signal, deadline) = shutdown_signals(shutdown);
+    let state = router.state.clone();
+    futures::future::lazy(move || -> Result<_, hyper::Error> {
+        start_replication(&router.state);
+        start_invalidation(&router.state);
+        start_backups(&router.state);
+        start_resp_listener(&router);
+        let incoming = incoming()?;
+        Ok((router, incoming))
+    })
+    .and_then(move |(router, incoming)| -> Box<Future<Item = (), Error = hyper::Error> + Send> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(config) = router.state.tls.clone() {
+                let incoming = tls::accept(incoming, config);
+                let make_service = make_service_fn(move |conn: &tls::Connection| {
+                    let router = router.clone();
+                    let peer = tls::peer_certificates(conn);
+                    Ok::<_, hyper::Error>(service_fn(move |mut req: Request<Body>| {
+                        if let Some(ref peer) = peer {
+                            req.extensions_mut().insert(peer.clone());
+                        }
+                        respond_beneath_base_path(&router, req)
+                    }))
+                });
+                let server = hyper::Server::builder(incoming)
+                    .serve(make_service)
+                    .with_graceful_shutdown(signal);
+                return Box::new(server);
+            }
+        }
+        let server = hyper::Server::builder(incoming)
+            .serve(move || {
+                let router = router.clone();
+                service_fn(move |req| respond_beneath_base_path(&router, req))
+            })
+            .with_graceful_shutdown(signal);
+        Box::new(server)
+    })
+    .select2(deadline)
+    .then(|result| match result {
+        Ok(_) => Ok(()),
+        Err(futures::future::Either::A((err, _))) => Err(err),
+        Err(futures::future::Either::B(((), _))) => Ok(()),
+    })
+    .map(move |()| flush_trees(&state))
+}
+
+/// Respond to a request received via one of the server's listeners, stripping the base path from
+/// the request's path if configured.
+///
+/// Requests whose paths are not beneath the base path are handled by the `Fallback`, if any, or
+/// otherwise receive a `404 Not Found` response.
+fn respond_beneath_base_path(router: &Router, mut request: Request<Body>) -> ResponseFuture {
+    if let Some(ref base_path) = router.state.base_path {
+        let path = match request::strip_base_path(request.uri().path(), base_path) {
+            None => return Box::new(or_404(router.fall_back(request))),
+            Some(path) => path.to_string(),
+        };
+        set_request_path(&mut request, &path);
+    }
+    router.respond(request)
+}
+
+/// Split the shutdown into the signal upon which the server stops accepting connections and the
+/// deadline by which in-flight connections must complete.
+///
+/// Neither completes without a `Shutdown`.
+fn shutdown_signals(shutdown: Option<Shutdown>) -> (Signal, Signal) {
+    let Shutdown { signal, timeout } = match shutdown {
+        None => return (Box::new(futures::future::empty()), Box::new(futures::future::empty())),
+        Some(shutdown) => shutdown,
+    };
+    let signal = signal.shared();
+    let deadline = signal
+        .clone()
+        .then(move |_| Delay::new(Instant::now() + timeout))
+        .then(|_| Ok::<_, ()>(()));
+    let signal = signal.then(|_| Ok::<_, ()>(()));
+    (Box::new(signal), Box::new(deadline))
+}
+
+/// Flush the default tree along with all open named trees, logging any failures.
+fn flush_trees(state: &State) {
+    let trees = state.trees
+        .read()
+        .expect("failed to acquire trees lock");
+    let named = trees.iter().map(|(name, tree)| (&name[..], tree));
+    for (name, tree) in Some(("default", &state.tree)).into_iter().chain(named) {
+        if let Err(err) = tree.flush() {
+            eprintln!("failed to flush the {} tree: {:?}", name, err);
+        }
+    }
+}
+
+/// Build and run a hyper `Server` with the given configuration and `sled::Tree` on a new runtime.
+///
+/// Runs until the server and all of its background tasks complete.
+pub fn run<T>(config: Config, tree: T)
+where
+    T: Into<Tree>,
+{
+    let mut runtime = match new_runtime(&config) {
+        Ok(runtime) => runtime,
+        Err(e) => return eprintln!("failed to start the runtime: {}", e),
+    };
+    let server = match new(config, tree) {
+        Ok(server) => server,
+        Err(e) => return eprintln!("error occurred: {}", e),
+    };
+    runtime.spawn(server.map_err(|e| eprintln!("error occurred: {}", e)));
+    runtime.shutdown_on_idle().wait().ok();
+}
+
+/// Build and run a hyper `Server` with the given configuration and `sled::Tree` until the given
+/// `signal` completes, e.g. upon receiving `SIGTERM`.
+///
+/// The server is shut down gracefully via `Server::with_graceful_shutdown`, allowing up to
+/// `Server::DEFAULT_SHUTDOWN_TIMEOUT` for in-flight requests and streams to complete. Once all
+/// trees are flushed, the runtime is shut down along with all background tasks before returning.
+pub fn run_until<T, F>(config: Config, tree: T, signal: F)
+where
+    T: Into<Tree>,
+    F: 'static + Future + Send,
+{
+    let mut runtime = match new_runtime(&config) {
+        Ok(runtime) => runtime,
+        Err(e) => return eprintln!("failed to start the runtime: {}", e),
+    };
+    let server = match new(config, tree) {
+        Ok(server) => server,
+        Err(e) => return eprintln!("error occurred: {}", e),
+    };
+    let server = server.with_graceful_shutdown(signal, Server::DEFAULT_SHUTDOWN_TIMEOUT);
+    if let Err(e) = runtime.block_on(server) {
+        eprintln!("error occurred: {}", e);
+    }
+    runtime.shutdown_now().wait().ok();
+}
+
+/// The runtime on which `run` and `run_until` serve, configured via the given `config`.
+fn new_runtime(config: &Config) -> io::Result<Runtime> {
+    let mut builder = runtime::Builder::new();
+    if let Some(threads) = config.blocking_threads {
+        builder.blocking_threads(threads);
+    }
+    builder.build()
+}
+
+/// Route the given request to the associated response.
+///
+/// In addition to the requests handled by `response::response`, the server handles:
+///
+/// | **Description**                   | **Status**        | **Body**                          |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Version`                         | 200 OK            | `VersionInfo`                     |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Info`                            | 200 OK            | `DbInfo`                          |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `SetView` with a valid name       | 200 OK            | `Option<View>` replaced view      |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `SetView` with an invalid name    | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `RemoveView`                      | 200 OK            | `Option<View>` removed view       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Views`                           | 200 OK            | `BTreeMap<String, View>`          |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `SetWebhook` with a valid webhook | 200 OK            | `Option<Webhook>` replaced hook   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `SetWebhook` with an invalid name | 400 Bad Request   | `ErrorBody`                       |
+/// | or URL                            |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `RemoveWebhook`                   | 200 OK            | `Option<Webhook>` removed hook    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Webhooks`                        | 200 OK            | `BTreeMap<String, Webhook>`       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `PullRange` with a valid name and | 200 OK            | `Option<MigrationStatus>`         |
+/// | source                            |                   | replaced migration                |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `PullRange` with an invalid name  | 400 Bad Request   | `ErrorBody`                       |
+/// | or source, or a running name      |                   |                                   |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Migrations`                      | 200 OK            | `MigrationStatus` map by name     |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Streams`                         | 200 OK            | `StreamStatus`                    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Backups` with a backup schedule  | 200 OK            | `BackupStatus`                    |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Backups` without a schedule      | 501 Not Impl.     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Restore` with retained changes   | 200 OK            | `Restored`                        |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Restore` to a future `Seq`       | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Restore` past retained changes   | 410 Gone          | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `Restore` without a schedule      | 501 Not Impl.     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Unsupported `request::PROTOCOL`   | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Non-`GET` via a read-only view    | 403 Forbidden     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Mutation via a replica            | 403 Forbidden     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Request via an unknown view       | 404 Not Found     | <empty>                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `OpenTree` with a valid name      | 200 OK            | `bool` whether newly opened       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `OpenTree` with an invalid name   | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `DropTree` with a valid name      | 200 OK            | `bool` whether the tree was open  |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `DropTree` with an invalid name   | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Open or drop tree without a store | 501 Not Impl.     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `OpenTree` or `DropTree` DB error | 500 Server Error  | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `TreeNames`                       | 200 OK            | `Vec<String>`                     |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Request to an unopened tree       | 404 Not Found     | <empty>                           |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | `CopyEntries` or `MoveEntries`    | 200 OK            | `usize` number of entries         |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Copy or move within the same tree | 400 Bad Request   | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Copy or move via an unopened tree | 404 Not Found     | `ErrorBody`                       |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+/// | Copy or move DB error             | 500 Server Error  | `ErrorBody` with resumption `key` |
+/// | --------------------------------- | ----------------- | --------------------------------- |
+///
+/// Requests to paths beginning with `/views/{name}` are handled as though made directly to the
+/// remainder of the path, but via the named view of the tree.
+///
+/// Requests to paths beginning with `/tree/{name}` are handled as though made to the default
+/// tree, but via the named tree.
+///
+/// Delivery of changes to webhooks begins upon the first request once a webhook is registered,
+/// as delivery must be spawned onto the executor running the server. The same applies to
+/// replication from an upstream server, cache invalidation and scheduled backups when the
+/// `Router` is not run via `serve`.
+///
+/// Returns the request itself if it does not match any known endpoint.
+fn route(
+    mut request: Request<Body>,
+    state: Arc<State>,
+) -> Result<ResponseFuture, Request<Body>> {
+    start_webhook_delivery(&state);
+    start_replication(&state);
+    start_backups(&state);
+
+    if let Err(response) = check_protocol(&request) {
+        return Ok(Box::new(futures::future::ok(response)));
+    }
+
+    if let Err(reason) = authorize(&request, &state) {
+        let response = status_err_response(StatusCode::FORBIDDEN, &reason);
+        return Ok(Box::new(futures::future::ok(response)));
+    }
+
+    if state.origin.is_some() {
+        return Ok(proxy(request, state));
+    }
+
+    if state.federation.is_some() && request.uri().path().starts_with(request::TREE_PATH) {
+        return Ok(federate(request, state));
+    }
+
+    if state.upstream.is_some() && is_mutation(&request) {
+        let msg = "the server is a read-only replica";
+        let response = status_err_response(StatusCode::FORBIDDEN, msg);
+        return Ok(Box::new(futures::future::ok(response)));
+    }
+
+    let view_path = split_view_path(request.uri().path())
+        .map(|(name, path)| (name.to_string(), path.to_string()));
+    if let Some((name, path)) = view_path {
+        let view = match state.views
+            .read()
+            .expect("failed to acquire views lock")
+            .get(&name)
+            .cloned()
+        {
+            None => return Err(request),
+            Some(view) => view,
+        };
+        if view.read_only && !is_read(&request) {
+            let response = status_err_response(StatusCode::FORBIDDEN, "the view is read-only");
+            return Ok(Box::new(futures::future::ok(response)));
+        }
+        let uri = request.uri().clone();
+        set_request_path(&mut request, &path);
+        let tree = Arc::new(state.tree.view(view.prefix, view.read_only));
+        return try_response(request, tree.clone())
+            .or_else(|request| endpoint_response(request, tree, &state))
+            .map_err(|request| with_uri(request, uri));
+    }
+
+    let tree_path = split_tree_path(request.uri().path())
+        .map(|(name, path)| (name.to_string(), path.to_string()));
+    if let Some((name, path)) = tree_path {
+        let tree = match state.trees
+            .read()
+            .expect("failed to acquire trees lock")
+            .get(&name)
+            .cloned()
+        {
+            None => return Err(request),
+            Some(tree) => tree,
+        };
+        let uri = request.uri().clone();
+        set_request_path(&mut request, &path);
+        return try_response(request, tree.clone())
+            .or_else(|request| endpoint_response(request, tree, &state))
+            .map_err(|request| with_uri(request, uri));
+    }
+
+    // Each of the following handlers runs within a blocking section via `concat_and_then`, as many
+    // perform `sled` or filesystem IO, e.g. `restore`, `open_tree` and `move_entries`.
+    match (request.method(), request.uri().path()) {
+        (&request::Version::METHOD, request::Version::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, |_: request::Version| {
+                json_response(&request::VersionInfo::current())
+            })))
+        }
+        (&request::Info::METHOD, request::Info::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| info(req, &state))))
+        }
+        (&request::SetView::METHOD, request::SetView::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| set_view(req, &state))))
+        }
+        (&request::RemoveView::METHOD, request::RemoveView::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| remove_view(req, &state))))
+        }
+        (&request::Views::METHOD, request::Views::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| views(req, &state))))
+        }
+        (&request::SetWebhook::METHOD, request::SetWebhook::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| set_webhook(req, &state))))
+        }
+        (&request::RemoveWebhook::METHOD, request::RemoveWebhook::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| remove_webhook(req, &state))))
+        }
+        (&request::Webhooks::METHOD, request::Webhooks::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| webhooks(req, &state))))
+        }
+        (&request::Backups::METHOD, request::Backups::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| backups(req, &state))))
+        }
+        (&request::Restore::METHOD, request::Restore::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| restore(req, &state))))
+        }
+        (&request::PullRange::METHOD, request::PullRange::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| pull_range(req, &state))))
+        }
+        (&request::Migrations::METHOD, request::Migrations::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| migrations(req, &state))))
+        }
+        (&request::Streams::METHOD, request::Streams::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| streams(req, &state))))
+        }
+        (&request::OpenTree::METHOD, request::OpenTree::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| open_tree(req, &state))))
+        }
+        (&request::DropTree::METHOD, request::DropTree::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| drop_tree(req, &state))))
+        }
+        (&request::TreeNames::METHOD, request::TreeNames::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| tree_names(req, &state))))
+        }
+        (&request::CopyEntries::METHOD, request::CopyEntries::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| copy_entries(req, &state))))
+        }
+        (&request::MoveEntries::METHOD, request::MoveEntries::PATH_AND_QUERY) => {
+            Ok(Box::new(concat_and_then(request, move |req| move_entries(req, &state))))
+        }
+        _ => try_response(request, state.tree.clone())
+            .or_else(|request| endpoint_response(request, state.tree.clone(), &state)),
+    }
+}
+
+/// Respond to an `Info` request.
+fn info(_req: request::Info, state: &State) -> Response<Body> {
+    let uptime = state.started.elapsed();
+    let uptime_ms = uptime.as_secs() * 1_000 + u64::from(uptime.subsec_millis());
+    let info = request::DbInfo {
+        version: request::VersionInfo::current(),
+        sled_config: state.sled_config.clone(),
+        uptime_ms,
+    };
+    json_response(&info)
+}
+
+/// Respond to a `SetView` request.
+fn set_view(req: request::SetView, state: &State) -> Response<Body> {
+    let request::SetView { name, view } = req;
+    if !request::is_valid_view_name(&name) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid view name");
+    }
+    let replaced = state.views
+        .write()
+        .expect("failed to acquire views lock")
+        .insert(name, view);
+    json_response(&replaced)
+}
+
+/// Respond to a `RemoveView` request.
+fn remove_view(req: request::RemoveView, state: &State) -> Response<Body> {
+    let removed = state.views
+        .write()
+        .expect("failed to acquire views lock")
+        .remove(&req.name);
+    json_response(&removed)
+}
+
+/// Respond to a `Views` request.
+fn views(_req: request::Views, state: &State) -> Response<Body> {
+    let views = state.views
+        .read()
+        .expect("failed to acquire views lock");
+    json_response(&*views)
+}
+
+/// Respond to a `SetWebhook` request.
+fn set_webhook(req: request::SetWebhook, state: &Arc<State>) -> Response<Body> {
+    let request::SetWebhook { name, webhook } = req;
+    if !request::is_valid_view_name(&name) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid webhook name");
+    }
+    if webhook.url.parse::<Uri>().map(|uri| uri.scheme_part().is_none()).unwrap_or(true) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid webhook url");
+    }
+    let replaced = state.webhooks
+        .write()
+        .expect("failed to acquire webhooks lock")
+        .insert(name, webhook);
+    start_webhook_delivery(state);
+    json_response(&replaced)
+}
+
+/// Respond to a `RemoveWebhook` request.
+fn remove_webhook(req: request::RemoveWebhook, state: &State) -> Response<Body> {
+    let removed = state.webhooks
+        .write()
+        .expect("failed to acquire webhooks lock")
+        .remove(&req.name);
+    json_response(&removed)
+}
+
+/// Respond to a `Webhooks` request.
+fn webhooks(_req: request::Webhooks, state: &State) -> Response<Body> {
+    let webhooks = state.webhooks
+        .read()
+        .expect("failed to acquire webhooks lock");
+    json_response(&*webhooks)
+}
+
+/// Spawn the delivery of changes to the default tree to webhooks if any are registered and
+/// delivery has not yet begun.
+///
+/// Delivery ends once the state is dropped. The tree remains observed for as long as delivery
+/// continues, even if all webhooks are later removed.
+fn start_webhook_delivery(state: &Arc<State>) {
+    if state.delivering.load(Ordering::Acquire) {
+        return;
+    }
+    let registered = !state.webhooks
+        .read()
+        .expect("failed to acquire webhooks lock")
+        .is_empty();
+    if !registered || state.delivering.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let weak = Arc::downgrade(state);
+    let client = hyper::Client::new();
+    let delivery = state.tree
+        .subscribe()
+        .for_each(move |event| deliver_event(&weak, &client, &event))
+        .then(|_| Ok(()));
+    hyper::rt::spawn(delivery);
+}
+
+/// Spawn a delivery of the event to each matching webhook.
+///
+/// Returns `Err` once the state has been dropped, ending delivery.
+fn deliver_event(
+    state: &Weak<State>,
+    client: &hyper::Client<HttpConnector>,
+    event: &request::Event,
+) -> Result<(), ()> {
+    let state = state.upgrade().ok_or(())?;
+    let webhooks = state.webhooks
+        .read()
+        .expect("failed to acquire webhooks lock");
+    let mut matching = webhooks.values().filter(|webhook| webhook.matches(event)).peekable();
+    if matching.peek().is_none() {
+        return Ok(());
+    }
+    let body = serde_json::to_vec(event).expect("failed to serialize event");
+    for webhook in matching {
+        let delivery = post_with_retries(client.clone(), webhook.clone(), body.clone());
+        hyper::rt::spawn(delivery);
+    }
+    Ok(())
+}
+
+/// `POST` the body to the webhook's URL, retrying with exponential backoff until the webhook
+/// responds with a success status or `max_retries` is exceeded.
+///
+/// Deliveries of separate events are independent, so a webhook may receive events out of order
+/// while retrying. Receivers may reorder events via their `seq`.
+fn post_with_retries(
+    client: hyper::Client<HttpConnector>,
+    webhook: Webhook,
+    body: Vec<u8>,
+) -> impl Future<Item = (), Error = ()> + Send {
+    futures::future::loop_fn(0, move |attempt| {
+        let request = Request::post(&webhook.url[..])
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.clone()));
+        let posted = match request {
+            Err(_) => futures::future::Either::A(futures::future::err(())),
+            Ok(request) => {
+                let response = client.request(request).then(|res| match res {
+                    Ok(ref response) if response.status().is_success() => Ok(()),
+                    _ => Err(()),
+                });
+                futures::future::Either::B(response)
+            }
+        };
+        let max_retries = webhook.max_retries;
+        posted.then(move |res| {
+            if res.is_ok() || attempt >= max_retries {
+                let done = futures::future::Loop::Break(());
+                return futures::future::Either::A(futures::future::ok(done));
+            }
+            let retry = Delay::new(Instant::now() + retry_backoff(attempt))
+                .then(move |_| Ok(futures::future::Loop::Continue(attempt + 1)));
+            futures::future::Either::B(retry)
+        })
+    })
+}
+
+/// The delay before retrying a webhook delivery or replication request that has failed
+/// `attempt + 1` times.
+///
+/// Begins at 100ms and doubles with each attempt, up to a maximum of roughly two minutes.
+fn retry_backoff(attempt: u32) -> Duration {
+    Duration::from_millis(100 << cmp::min(attempt, 10))
+}
+
+/// Spawn the replication of the default tree from the upstream server if there is one and
+/// replication has not yet begun.
+///
+/// Each step of replication produces the sequence number of the upstream's most recent change
+/// that has been applied, or `None` if a new snapshot is required. Failed steps are retried with
+/// backoff. Replication ends once the state is dropped.
+fn start_replication(state: &Arc<State>) {
+    let upstream = match state.upstream {
+        None => return,
+        Some(ref upstream) => upstream.clone(),
+    };
+    if state.replicating.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let weak = Arc::downgrade(state);
+    let client = Client::new(upstream);
+    let replication = futures::future::loop_fn((None, 0), move |(seq, attempt)| {
+        let tree = match weak.upgrade() {
+            None => {
+                let done = futures::future::Loop::Break(());
+                return futures::future::Either::A(futures::future::ok(done));
+            }
+            Some(state) => state.tree.clone(),
+        };
+        let step = replicate(&client, tree, seq).then(move |res| match res {
+            Ok(seq) => {
+                let next = futures::future::Loop::Continue((seq, 0));
+                futures::future::Either::A(futures::future::ok(next))
+            }
+            Err(()) => {
+                let next = futures::future::Loop::Continue((seq, attempt + 1));
+                let retry = Delay::new(Instant::now() + retry_backoff(attempt))
+                    .then(move |_| Ok::<_, ()>(next));
+                futures::future::Either::B(retry)
+            }
+        });
+        futures::future::Either::B(step)
+    });
+    hyper::rt::spawn(replication);
+}
+
+/// Apply the upstream's changes following `seq` to the tree, or a snapshot of the upstream if
+/// `seq` is `None`.
+///
+/// Produces the sequence number of the upstream's most recent change that has been applied, or
+/// `None` if the changes following `seq` are no longer retained by the upstream.
+fn replicate(
+    client: &Client,
+    tree: Arc<Tree>,
+    seq: Option<u64>,
+) -> Box<Future<Item = Option<u64>, Error = ()> + Send> {
+    match seq {
+        None => {
+            let restored = client
+                .export_to_writer(vec![])
+                .map_err(|_| ())
+                .and_then(move |bytes| {
+                    blocking(move || restore_snapshot(&tree, &bytes).map(Some)).map_err(|_| ())
+                })
+                .and_then(|restored| restored);
+            Box::new(restored)
+        }
+        Some(seq) => {
+            let timeout = Duration::from_millis(request::DEFAULT_CHANGES_TIMEOUT_MS);
+            let applied = client
+                .changes_since(seq, timeout)
+                .map_err(|_| ())
+                .and_then(move |batch| {
+                    if !batch.complete {
+                        return futures::future::Either::A(futures::future::ok(None));
+                    }
+                    let seq = batch.seq;
+                    let ops = batch.events
+                        .into_iter()
+                        .map(|event| match event.new {
+                            Some(value) => BatchOp::Set { key: event.key, value },
+                            None => BatchOp::Del { key: event.key },
+                        })
+                        .collect();
+                    let applied = blocking(move || tree.batch(ops))
+                        .map_err(|_| ())
+                        .and_then(move |result| result.map(|_| Some(seq)).map_err(|_| ()));
+                    futures::future::Either::B(applied)
+                });
+            Box::new(applied)
+        }
+    }
+}
+
+/// Replace all entries of the tree with those of the given `Export` stream, producing the
+/// sequence number of the most recent change reflected within the stream.
+///
+/// Readers may observe the tree partially restored.
+fn restore_snapshot(tree: &Tree, bytes: &[u8]) -> Result<u64, ()> {
+    let mut decoder = request::ExportDecoder::new();
+    decoder.extend(bytes);
+    let mut seq = None;
+    let mut ops = vec![];
+    while let Some(item) = decoder.next_item().map_err(|_| ())? {
+        match item {
+            ExportItem::Header { seq: header_seq } => seq = Some(header_seq),
+            ExportItem::Entry(key, value) => ops.push(BatchOp::Set { key, value }),
+            _ => (),
+        }
+    }
+    let seq = match seq {
+        Some(seq) if decoder.is_complete() => seq,
+        _ => return Err(()),
+    };
+    tree.clear().map_err(|_| ())?;
+    tree.batch(ops).map_err(|_| ())?;
+    Ok(seq)
+}
+
+/// Respond to the given request as a caching proxy for the origin.
+///
+/// `Get` requests are served from the cache where possible, while all others are forwarded.
+fn proxy(request: Request<Body>, state: Arc<State>) -> ResponseFuture {
+    start_invalidation(&state);
+    // Bincode `Get`s are forwarded uncached, as the proxy only decodes JSON bodies.
+    let is_get = *request.method() == request::Get::METHOD
+        && request.uri().path() == request::Get::PATH_AND_QUERY
+        && encoding::content_encoding(request.headers()) == Some(Encoding::Json);
+    if !is_get {
+        let origin = state.origin.as_ref().expect("no origin for proxy");
+        return forward(&origin.client, &origin.uri, request);
+    }
+    let (parts, body) = request.into_parts();
+    let response = body.concat2().and_then(move |chunk| {
+        let req: Result<request::Get, Box<StdError + Send + Sync>> = match parts.uri.query() {
+            Some(q) if chunk.is_empty() => query::from_query(q).map_err(|err| err.into()),
+            _ => serde_json::from_slice(&chunk).map_err(|err| err.into()),
+        };
+        let req = match req {
+            Ok(req) => req,
+            Err(err) => {
+                let msg = format!("failed to deserialize request: {}", err);
+                let response = status_err_response(StatusCode::BAD_REQUEST, &msg);
+                return futures::future::Either::A(futures::future::ok(response));
+            }
+        };
+        let tree = state.tree.clone();
+        let key = req.key.clone();
+        let cached = blocking(move || tree.get(&key)).and_then(move |cached| {
+            if let Ok(Some(value)) = cached {
+                let response = with_etag(json_response(&Some(&value)), &value);
+                let response = not_modified(response, parts.headers.get(header::IF_NONE_MATCH));
+                return futures::future::Either::A(futures::future::ok(response));
+            }
+            let filled = fill_cache(state, Request::from_parts(parts, chunk.into()), req.key);
+            futures::future::Either::B(filled)
+        });
+        futures::future::Either::B(cached)
+    });
+    Box::new(response)
+}
+
+/// Forward the `Get` request that missed the cache to the origin, caching the value within the
+/// response unless an invalidation occurs in the meantime.
+fn fill_cache(
+    state: Arc<State>,
+    request: Request<Body>,
+    key: Vec<u8>,
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send {
+    let epoch = {
+        let origin = state.origin.as_ref().expect("no origin for proxy");
+        origin.cache.lock().expect("failed to acquire cache lock").epoch
+    };
+    let forwarded = {
+        let origin = state.origin.as_ref().expect("no origin for proxy");
+        forward(&origin.client, &origin.uri, request)
+    };
+    forwarded.and_then(move |response| {
+        let (parts, body) = response.into_parts();
+        body.concat2().and_then(move |chunk| {
+            let success = parts.status.is_success();
+            let filled = blocking(move || {
+                if success {
+                    if let Ok(Some(value)) = serde_json::from_slice::<Option<Vec<u8>>>(&chunk) {
+                        let origin = state.origin.as_ref().expect("no origin for proxy");
+                        let cache = origin.cache.lock().expect("failed to acquire cache lock");
+                        if cache.following && cache.epoch == epoch {
+                            let _ = state.tree.set(key, value);
+                        }
+                    }
+                }
+                chunk
+            });
+            filled.map(move |chunk| Response::from_parts(parts, chunk.into()))
+        })
+    })
+}
+
+/// Forward the request to the origin as it is, responding with `502 Bad Gateway` if the origin
+/// could not be reached.
+fn forward(
+    client: &hyper::Client<HttpConnector>,
+    origin: &Uri,
+    mut request: Request<Body>,
+) -> ResponseFuture {
+    let mut parts = request.uri().clone().into_parts();
+    let origin_parts = origin.clone().into_parts();
+    parts.scheme = origin_parts.scheme;
+    parts.authority = origin_parts.authority;
+    *request.uri_mut() = Uri::from_parts(parts)
+        .expect("failed to construct request URI from parts");
+    request.headers_mut().remove(header::HOST);
+    // The origin's responses must remain uncompressed so that the proxy may inspect them, and are
+    // compressed on their way out instead.
+    request.headers_mut().remove(header::ACCEPT_ENCODING);
+    let response = client.request(request).or_else(|err| {
+        let msg = format!("failed to reach the origin: {}", err);
+        Ok(status_err_response(StatusCode::BAD_GATEWAY, &msg))
+    });
+    Box::new(response)
+}
+
+/// Spawn the invalidation of cached entries by following the origin's changes if there is an
+/// origin and invalidation has not yet begun.
+///
+/// Invalidation ends once the state is dropped. No entries are cached until the origin's changes
+/// are being followed.
+fn start_invalidation(state: &Arc<State>) {
+    let origin = match state.origin {
+        None => return,
+        Some(ref origin) => origin,
+    };
+    if origin.invalidating.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let weak = Arc::downgrade(state);
+    let client = Client::new(origin.uri.clone());
+    let invalidation = futures::future::loop_fn((None, 0), move |(seq, attempt)| {
+        let state = match weak.upgrade() {
+            None => {
+                let done = futures::future::Loop::Break(());
+                return futures::future::Either::A(futures::future::ok(done));
+            }
+            Some(state) => state,
+        };
+        let step = invalidate(&client, state.clone(), seq).then(move |res| match res {
+            Ok(seq) => {
+                let next = futures::future::Loop::Continue((Some(seq), 0));
+                futures::future::Either::A(futures::future::ok(next))
+            }
+            Err(()) => {
+                if let Some(ref origin) = state.origin {
+                    origin.cache.lock().expect("failed to acquire cache lock").following = false;
+                }
+                let next = futures::future::Loop::Continue((seq, attempt + 1));
+                let retry = Delay::new(Instant::now() + retry_backoff(attempt))
+                    .then(move |_| Ok::<_, ()>(next));
+                futures::future::Either::B(retry)
+            }
+        });
+        futures::future::Either::B(step)
+    });
+    hyper::rt::spawn(invalidation);
+}
+
+/// Remove the cached entries changed at the origin following `seq`, producing the sequence number
+/// of the origin's most recent change that has been followed.
+///
+/// The whole cache is cleared if `seq` is `None` or if the changes following it are no longer
+/// retained by the origin.
+fn invalidate(
+    client: &Client,
+    state: Arc<State>,
+    seq: Option<u64>,
+) -> impl Future<Item = u64, Error = ()> + Send {
+    // Following from beyond the origin's latest change produces its current sequence number.
+    let (since, timeout) = match seq {
+        None => (::std::u64::MAX, Duration::from_millis(0)),
+        Some(seq) => (seq, Duration::from_millis(request::DEFAULT_CHANGES_TIMEOUT_MS)),
+    };
+    client
+        .changes_since(since, timeout)
+        .map_err(|_| ())
+        .and_then(move |batch| {
+            let invalidated = blocking(move || -> Result<u64, ()> {
+                let origin = state.origin.as_ref().expect("no origin for proxy");
+                let mut cache = origin.cache.lock().expect("failed to acquire cache lock");
+                if seq.is_none() || !batch.complete {
+                    state.tree.clear().map_err(|_| ())?;
+                    cache.epoch += 1;
+                } else if !batch.events.is_empty() {
+                    for event in &batch.events {
+                        state.tree.del(&event.key).map_err(|_| ())?;
+                    }
+                    cache.epoch += 1;
+                }
+                cache.following = true;
+                Ok(batch.seq)
+            });
+            invalidated.map_err(|_| ()).and_then(|invalidated| invalidated)
+        })
+}
+
+/// Respond to the given `/tree` request via the shards of the federation.
+fn federate(request: Request<Body>, state: Arc<State>) -> ResponseFuture {
+    let (parts, body) = request.into_parts();
+    let response = body.concat2().and_then(move |chunk| -> ResponseFuture {
+        let federated = state.federation.as_ref().expect("no federation");
+        let decoding = response::request_decoding(&parts.method, &parts.uri, &parts.headers);
+        let clients = &federated.clients;
+        let route = (parts.method.clone(), parts.uri.path().to_string());
+        let response = match (&route.0, &route.1[..]) {
+            (&request::Iter::METHOD, request::Iter::PATH_AND_QUERY) => {
+                decoding.decode(&chunk).map(|req: request::Iter| {
+                    let streams = clients
+                        .iter()
+                        .map(|client| client.entries(request::Iter { limit: None, ..req.clone() }))
+                        .collect();
+                    merged_response(&federated.shards, streams, req.limit)
+                })
+            }
+            (&request::Scan::METHOD, request::Scan::PATH_AND_QUERY) => {
+                decoding.decode(&chunk).map(|req: request::Scan| {
+                    let streams = clients
+                        .iter()
+                        .map(|client| client.entries(request::Scan { limit: None, ..req.clone() }))
+                        .collect();
+                    merged_response(&federated.shards, streams, req.limit)
+                })
+            }
+            (&request::ScanRange::METHOD, request::ScanRange::PATH_AND_QUERY) => {
+                decoding.decode(&chunk).map(|req: request::ScanRange| {
+                    let streams = clients
+                        .iter()
+                        .map(|client| {
+                            client.entries(request::ScanRange { limit: None, ..req.clone() })
+                        })
+                        .collect();
+                    merged_response(&federated.shards, streams, req.limit)
+                })
+            }
+            _ => {
+                let key = if route.1.starts_with(request::RAW_PATH) {
+                    request::decode_raw_key(&route.1[request::RAW_PATH.len()..])
+                } else {
+                    // Resource-style entry routes carry the key within the path instead.
+                    serde_json::from_slice::<serde_json::Value>(&chunk)
+                        .ok()
+                        .and_then(|value| value.get("key").cloned())
+                        .and_then(|key| base64_json::from_value::<Vec<u8>>(key).ok())
+                        .or_else(|| request::entry_path_key(&route.1))
+                        .or_else(|| {
+                            let q = parts.uri.query().filter(|_| chunk.is_empty())?;
+                            query::from_query::<request::Get>(q).ok().map(|req| req.key)
+                        })
+                };
+                let key = match key {
+                    Some(key) => key,
+                    None => {
+                        let msg = "the request is not supported by a federated server";
+                        let response = status_err_response(StatusCode::NOT_IMPLEMENTED, msg);
+                        return Box::new(futures::future::ok(response));
+                    }
+                };
+                Ok(match federation::owner(&federated.shards, &key) {
+                    Some(ix) => {
+                        let request = Request::from_parts(parts, chunk.into());
+                        return forward(&federated.http, &federated.uris[ix], request);
+                    }
+                    None => {
+                        let msg = "the key belongs to no shard";
+                        status_err_response(StatusCode::BAD_REQUEST, msg)
+                    }
+                })
+            }
+        };
+        let response = response.unwrap_or_else(|err| {
+            let msg = format!("failed to deserialize request: {}", err);
+            status_err_response(StatusCode::BAD_REQUEST, &msg)
+        });
+        Box::new(futures::future::ok(response))
+    });
+    Box::new(response)
+}
+
+/// Respond with the entries of the given streams, one for each shard, merged in order of their
+/// keys and limited to `limit` entries.
+///
+/// Only the entries belonging to the shard are taken from each stream.
+fn merged_response<S>(shards: &[Shard], streams: Vec<S>, limit: Option<u64>) -> Response<Body>
+where
+    S: 'static + Send + Stream<Item = Entry, Error = client::Error>,
+{
+    let owned = streams
+        .into_iter()
+        .enumerate()
+        .map(|(ix, stream)| {
+            let shards = shards.to_vec();
+            stream.filter(move |&(ref key, _)| federation::owner(&shards, key) == Some(ix))
+        })
+        .collect::<Vec<_>>();
+    let entries = client::merge_sorted(owned)
+        .take(limit.unwrap_or(::std::u64::MAX))
+        .map(|entry| {
+            let bytes = serde_json::to_vec(&entry).expect("failed to serialize entry to JSON");
+            Chunk::from(bytes)
+        })
+        .map_err(|err| -> Box<StdError + Send + Sync> { format!("{}", err).into() });
+    Response::new(Body::from(Box::new(entries) as Box<_>))
+}
+
+/// Respond to a `Backups` request.
+fn backups(_req: request::Backups, state: &State) -> Response<Body> {
+    if state.backup.is_none() {
+        let msg = "scheduled backups are not configured for this server";
+        return status_err_response(StatusCode::NOT_IMPLEMENTED, msg);
+    }
+    let status = state.backup_status
+        .read()
+        .expect("failed to acquire backup status lock");
+    json_response(&*status)
+}
+
+/// Respond to a `Restore` request by replacing the entries of the default tree with those
+/// reconstructed from the backups and the changelog.
+///
+/// Readers may observe the tree partially restored.
+fn restore(req: request::Restore, state: &State) -> Response<Body> {
+    let target = state.backup_target
+        .read()
+        .expect("failed to acquire backup target lock")
+        .clone();
+    let target = match target {
+        Some(ref target) if state.backup.is_some() => target.clone(),
+        _ => {
+            let msg = "scheduled backups are not configured for this server";
+            return status_err_response(StatusCode::NOT_IMPLEMENTED, msg);
+        }
+    };
+    if let request::RestorePoint::Seq(seq) = req.to {
+        if seq > state.tree.change_seq() {
+            let msg = "the sequence number follows the most recent change";
+            return status_err_response(StatusCode::BAD_REQUEST, msg);
+        }
+    }
+    let reconstruction = match backup::reconstruct(&state.tree, &*target, req.to) {
+        Err(err) => {
+            return status_err_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", err));
+        }
+        Ok(None) => {
+            let msg = "the changes following the preceding backup are no longer retained";
+            return status_err_response(StatusCode::GONE, msg);
+        }
+        Ok(Some(reconstruction)) => reconstruction,
+    };
+    let backup::Reconstruction { backup, seq, replayed, entries } = reconstruction;
+    let restored = request::Restored { backup, seq, replayed, entries: entries.len() as u64 };
+    let ops = entries
+        .into_iter()
+        .map(|(key, value)| BatchOp::Set { key, value })
+        .collect();
+    if let Err(err) = state.tree.clear().and_then(|_| state.tree.batch(ops)) {
+        return status_err_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", err));
+    }
+    json_response(&restored)
+}
+
+/// Spawn the periodic backup of the default tree if a schedule is configured and backups have not
+/// yet begun.
+///
+/// Backups end once the state is dropped.
+fn start_backups(state: &Arc<State>) {
+    let schedule = match state.backup {
+        None => return,
+        Some(ref schedule) => schedule.clone(),
+    };
+    if state.backing_up.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let weak = Arc::downgrade(state);
+    let backups = Interval::new(Instant::now() + schedule.interval, schedule.interval)
+        .map_err(|_| ())
+        .for_each(move |_| {
+            let state = match weak.upgrade() {
+                None => return futures::future::Either::A(futures::future::err(())),
+                Some(state) => state,
+            };
+            let schedule = schedule.clone();
+            // Writing the backup reads every entry, so it must not stall a worker thread.
+            let backed_up = blocking(move || back_up(&state, &schedule)).map_err(|_| ());
+            futures::future::Either::B(backed_up)
+        });
+    hyper::rt::spawn(backups);
+}
+
+/// Spawn the Redis protocol listener if an address is configured.
+///
+/// The listener runs until it fails, e.g. if the address is already in use.
+fn start_resp_listener(router: &Router) {
+    let addr = match router.state.resp_addr {
+        None => return,
+        Some(addr) => addr,
+    };
+    let max_len = router.state.max_body_size;
+    let listener = resp::serve(&addr, router.clone(), max_len).map_err(move |err| {
+        eprintln!("failed to listen for RESP connections on {}: {}", addr, err)
+    });
+    hyper::rt::spawn(listener);
+}
+
+/// Write a backup of the default tree and prune old backups, recording the outcome within the
+/// backup status.
+///
+/// The backup is skipped if no change has been made since the last backup.
+fn back_up(state: &State, schedule: &Schedule) {
+    let last_seq = state.backup_status
+        .read()
+        .expect("failed to acquire backup status lock")
+        .last_seq;
+    if last_seq == Some(state.tree.change_seq()) {
+        return;
+    }
+    let target = state.backup_target
+        .read()
+        .expect("failed to acquire backup target lock")
+        .clone();
+    let target = match target {
+        None => return,
+        Some(target) => target,
+    };
+    let result = backup::write_backup(&state.tree, &*target).and_then(|backup| {
+        backup::prune_backups(&*target, schedule.keep).map(|backups| (backup, backups))
+    });
+    let mut status = state.backup_status
+        .write()
+        .expect("failed to acquire backup status lock");
+    match result {
+        Ok((backup, backups)) => {
+            status.backups = backups;
+            status.last_seq = Some(backup.seq);
+            status.last_backup_ms = Some(backup.created_ms);
+            status.last_error = None;
+        }
+        Err(err) => status.last_error = Some(format!("{}", err)),
+    }
+}
+
+/// Respond to a `PullRange` request by spawning the migration.
+fn pull_range(req: request::PullRange, state: &Arc<State>) -> Response<Body> {
+    let request::PullRange { name, source, start, end } = req;
+    if !request::is_valid_view_name(&name) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid migration name");
+    }
+    let source_uri = match source.parse::<Uri>() {
+        Ok(ref uri) if uri.scheme_part().is_none() => None,
+        Ok(uri) => Some(uri),
+        Err(_) => None,
+    };
+    let source_uri = match source_uri {
+        Some(uri) => uri,
+        None => return status_err_response(StatusCode::BAD_REQUEST, "invalid migration source"),
+    };
+    let replaced = {
+        let mut migrations = state.migrations
+            .write()
+            .expect("failed to acquire migrations lock");
+        let running = migrations
+            .get(&name)
+            .map_or(false, |status| status.state == MigrationState::Running);
+        if running {
+            return status_err_response(StatusCode::BAD_REQUEST, "the migration is already running");
+        }
+        let status = MigrationStatus {
+            source,
+            start: start.clone(),
+            end: end.clone(),
+            copied: 0,
+            last_key: None,
+            state: MigrationState::Running,
+        };
+        migrations.insert(name.clone(), status)
+    };
+    start_migration(state, name, Client::new(source_uri), start, end);
+    json_response(&replaced)
+}
+
+/// Respond to a `Migrations` request.
+fn migrations(_req: request::Migrations, state: &State) -> Response<Body> {
+    let migrations = state.migrations
+        .read()
+        .expect("failed to acquire migrations lock");
+    json_response(&*migrations)
+}
+
+/// Respond to a `Streams` request.
+fn streams(_req: request::Streams, state: &State) -> Response<Body> {
+    let status = request::StreamStatus {
+        in_progress: state.stream_limit.in_progress() as u64,
+        max: state.stream_limit.max.map(|max| max as u64),
+    };
+    json_response(&status)
+}
+
+/// Spawn the copying of the entries within `start..end` via the `source` into the default tree,
+/// a page at a time, updating the status of the named migration after each page.
+///
+/// The migration ends early if a page could not be retrieved or written, or once the state is
+/// dropped.
+fn start_migration(
+    state: &Arc<State>,
+    name: String,
+    source: Client,
+    start: Vec<u8>,
+    end: Option<Vec<u8>>,
+) {
+    let weak = Arc::downgrade(state);
+    let migration = futures::future::loop_fn(Bound::Included(start), move |from| {
+        let to = match end {
+            Some(ref end) => Bound::Excluded(end.clone()),
+            None => Bound::Unbounded,
+        };
+        let weak = weak.clone();
+        let name = name.clone();
+        source
+            .scan_range_limit((from, to), MIGRATION_PAGE_LEN)
+            .collect()
+            .then(move |page| {
+                let step = blocking(move || -> Result<_, ()> {
+                    let state = weak.upgrade().ok_or(())?;
+                    let page = page.map_err(|err| fail_migration(&state, &name, &err))?;
+                    let len = page.len() as u64;
+                    let last = page.last().map(|&(ref key, _)| key.clone());
+                    let ops = page
+                        .into_iter()
+                        .map(|(key, value)| BatchOp::Set { key, value })
+                        .collect();
+                    state.tree.batch(ops).map_err(|err| fail_migration(&state, &name, &err))?;
+                    let done = len < MIGRATION_PAGE_LEN;
+                    update_migration(&state, &name, |status| {
+                        status.copied += len;
+                        if last.is_some() {
+                            status.last_key = last.clone();
+                        }
+                        if done {
+                            status.state = MigrationState::Complete;
+                        }
+                    });
+                    match last {
+                        Some(last) if !done => {
+                            Ok(futures::future::Loop::Continue(Bound::Excluded(last)))
+                        }
+                        _ => Ok(futures::future::Loop::Break(())),
+                    }
+                });
+                step.map_err(|_| ()).and_then(|step| step)
+            })
+    });
+    hyper::rt::spawn(migration);
+}
+
+/// Apply the given update to the status of the named migration, if it still exists.
+fn update_migration<F>(state: &State, name: &str, update: F)
+where
+    F: FnOnce(&mut MigrationStatus),
+{
+    let mut migrations = state.migrations
+        .write()
+        .expect("failed to acquire migrations lock");
+    if let Some(status) = migrations.get_mut(name) {
+        update(status);
+    }
+}
+
+/// Mark the named migration as failed due to the given error.
+fn fail_migration<E>(state: &State, name: &str, err: &E)
+where
+    E: fmt::Display,
+{
+    let error = format!("{}", err);
+    update_migration(state, name, |status| status.state = MigrationState::Failed { error });
+}
+
+/// Respond to an `OpenTree` request.
+fn open_tree(req: request::OpenTree, state: &State) -> Response<Body> {
+    let request::OpenTree { name } = req;
+    if !request::is_valid_tree_name(&name) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid tree name");
+    }
+    let store = match state.store {
+        None => return no_store_response(),
+        Some(ref store) => store,
+    };
+    let mut trees = state.trees
+        .write()
+        .expect("failed to acquire trees lock");
+    if trees.contains_key(&name) {
+        return json_response(&false);
+    }
+    match store.open(&name) {
+        Err(err) => status_err_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", err)),
+        Ok(backend) => {
+            trees.insert(name, Arc::new(Tree::new(backend)));
+            json_response(&true)
+        }
+    }
+}
+
+/// Respond to a `DropTree` request.
+fn drop_tree(req: request::DropTree, state: &State) -> Response<Body> {
+    if !request::is_valid_tree_name(&req.name) {
+        return status_err_response(StatusCode::BAD_REQUEST, "invalid tree name");
+    }
+    let store = match state.store {
+        None => return no_store_response(),
+        Some(ref store) => store,
+    };
+    let mut trees = state.trees
+        .write()
+        .expect("failed to acquire trees lock");
+    let was_open = trees.remove(&req.name).is_some();
+    match store.remove(&req.name) {
+        Err(err) => status_err_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{}", err)),
+        Ok(()) => json_response(&was_open),
+    }
+}
+
+/// Respond to a `TreeNames` request.
+fn tree_names(_req: request::TreeNames, state: &State) -> Response<Body> {
+    let trees = state.trees
+        .read()
+        .expect("failed to acquire trees lock");
+    let names: Vec<&String> = trees.keys().collect();
+    json_response(&names)
+}
+
+/// Respond to a `CopyEntries` request.
+fn copy_entries(req: request::CopyEntries, state: &State) -> Response<Body> {
+    let request::CopyEntries { source, destination, start, end } = req;
+    transfer_entries(state, source, destination, start, end, false)
+}
+
+/// Respond to a `MoveEntries` request.
+fn move_entries(req: request::MoveEntries, state: &State) -> Response<Body> {
+    let request::MoveEntries { source, destination, start, end } = req;
+    transfer_entries(state, source, destination, start, end, true)
+}
+
+/// Copy or move the entries within `start..end` between the given trees.
+fn transfer_entries(
+    state: &State,
+    source: Option<String>,
+    destination: Option<String>,
+    start: Vec<u8>,
+    end: Option<Vec<u8>>,
+    remove: bool,
+) -> Response<Body> {
+    if source == destination {
+        let msg = "the source and destination must differ";
+        return status_err_response(StatusCode::BAD_REQUEST, msg);
+    }
+    let (source, destination) = match (named_tree(state, source), named_tree(state, destination)) {
+        (Some(source), Some(destination)) => (source, destination),
+        _ => return status_err_response(StatusCode::NOT_FOUND, "no such tree is open"),
+    };
+    match source.transfer_range(&destination, &start, end.as_ref().map(|end| &end[..]), remove) {
+        Ok(count) => json_response(&count),
+        Err(err) => {
+            let msg = format!("{} (after transferring {} entries)", err.error, err.transferred);
+            let body = request::ErrorBody::with_code(request::ErrorCode::Database, msg)
+                .key(err.resume_from);
+            response::error_response(StatusCode::INTERNAL_SERVER_ERROR, &body)
+        }
+    }
+}
+
+/// Retrieve the open tree with the given name, or the default tree if the name is `None`.
+fn named_tree(state: &State, name: Option<String>) -> Option<Arc<Tree>> {
+    match name {
+        None => Some(state.tree.clone()),
+        Some(name) => state.trees
+            .read()
+            .expect("failed to acquire trees lock")
+            .get(&name)
+            .cloned(),
+    }
+}
+
+/// A response to a request for opening or dropping a named tree without a `Store`.
+fn no_store_response() -> Response<Body> {
+    status_err_response(StatusCode::NOT_IMPLEMENTED, "named trees are not supported by this server")
+}
+
+/// Decide whether or not the request is permitted via the `Authorizer`, if any.
+fn authorize(request: &Request<Body>, state: &State) -> Result<(), String> {
+    let authorizer = state.authorizer
+        .read()
+        .expect("failed to acquire authorizer lock")
+        .clone();
+    match authorizer {
+        None => Ok(()),
+        Some(authorizer) => authorizer.authorize(request),
+    }
+}
+
+/// Produce a `400 Bad Request` response if the request pins an unsupported wire format revision.
+///
+/// See `request::PROTOCOL`.
+fn check_protocol(request: &Request<Body>) -> Result<(), Response<Body>> {
+    let value = match request.headers().get(request::PROTOCOL) {
+        None => return Ok(()),
+        Some(value) => value,
+    };
+    let supported = value
+        .to_str()
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .map(|v| request::VersionInfo::current().supports(v))
+        .unwrap_or(false);
+    if !supported {
+        let msg = format!(
+            "unsupported wire format revision, expected {}-{}",
+            request::MIN_WIRE_FORMAT,
+            request::WIRE_FORMAT,
+        );
+        return Err(status_err_response(StatusCode::BAD_REQUEST, &msg));
+    }
+    Ok(())
+}
+
+/// Reduce the `request::ErrorBody` of an error response to its bare message if `legacy`, as
+/// expected by clients pinning a wire format revision prior to 8.
+fn with_legacy_errors(
+    response: Response<Body>,
+    legacy: bool,
+) -> impl Future<Item = Response<Body>, Error = hyper::Error> + Send {
+    let status = response.status();
+    let is_error = status.is_client_error() || status.is_server_error();
+    let is_json = encoding::content_encoding(response.headers()) == Some(Encoding::Json);
+    if !legacy || !is_error || !is_json {
+        return futures::future::Either::A(futures::future::ok(response));
+    }
+    let (mut parts, body) = response.into_parts();
+    let response = body.concat2().map(move |chunk| {
+        let bytes = match serde_json::from_slice::<request::ErrorBody>(&chunk) {
+            Ok(error) => serde_json::to_vec(&error.message).expect("failed to serialize message"),
+            Err(_) => chunk.to_vec(),
+        };
+        parts.headers.remove(header::CONTENT_LENGTH);
+        Response::from_parts(parts, bytes.into())
+    });
+    futures::future::Either::B(response)
+}
+
+/// Advertise the range of supported wire format revisions via the response headers.
+///
+/// See `request::PROTOCOL_SUPPORTED`.
+fn with_protocol_supported(mut response: Response<Body>) -> Response<Body> {
+    let range = format!("{}-{}", request::MIN_WIRE_FORMAT, request::WIRE_FORMAT);
+    let value = HeaderValue::from_str(&range).expect("failed to construct header value");
+    response.headers_mut().insert(request::PROTOCOL_SUPPORTED, value);
+    response
+}
+
+/// Whether or not the request may mutate a tree and so must be rejected by a replica.
+///
+/// Every non-`GET` request is assumed to mutate a tree, except for those managing views, webhooks
+/// and cursors.
+fn is_mutation(request: &Request<Body>) -> bool {
+    let path = request.uri().path();
+    !is_read(request)
+        && !["/admin/views/", "/admin/webhooks/", "/tree/cursors/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+}
+
+/// Whether or not the request only reads, i.e. is a `GET` or `HEAD`.
+fn is_read(request: &Request<Body>) -> bool {
+    *request.method() == Method::GET || *request.method() == Method::HEAD
+}
+
+/// Split a path of the form `/views/{name}/{path}` into the view name and the remaining path.
+fn split_view_path(path: &str) -> Option<(&str, &str)> {
+    if !path.starts_with(request::VIEWS_PATH) {
+        return None;
+    }
+    let rest = &path[request::VIEWS_PATH.len()..];
+    let slash = rest.find('/')?;
+    Some((&rest[..slash], &rest[slash..]))
+}
+
+/// Split a path of the form `/tree/{name}/{path}` into the tree name and the path that would be
+/// used for the same request to the default tree, i.e. `/tree/{path}`.
+fn split_tree_path(path: &str) -> Option<(&str, String)> {
+    if !path.starts_with(request::TREE_PATH) {
+        return None;
+    }
+    let rest = &path[request::TREE_PATH.len()..];
+    let slash = rest.find('/')?;
+    let name = &rest[..slash];
+    if !request::is_valid_tree_name(name) {
+        return None;
+    }
+    Some((name, format!("/tree{}", &rest[slash..])))
+}
+
+/// Respond to the request via `respond`, converting its body from and the response body to the
+/// encodings preferred by the client.
+///
+/// Applies the request's `bytes=base64` query parameter and encodes the response in the
+/// `Accept`ed encoding, compressing it as preferred via the `Accept-Encoding` header. Request
+/// bodies are decoded by `with_limits` once limited in size. Error responses are translated for
+/// clients pinned to wire format revisions prior to 8. Shared by `Router::route` and
+/// `Router::respond`, and so by the server's listeners, the `adapter`s and `Client::local`.
+fn with_encodings<F>(request: Request<Body>, respond: F) -> Result<ResponseFuture, UnknownRequest>
+where
+    F: FnOnce(Request<Body>) -> Result<ResponseFuture, UnknownRequest>,
+{
+    let request = encoding::apply_base64_query(request);
+    let encoding = encoding::accepted_encoding(request.headers());
+    let compression = compression::accepted_compression(request.headers());
+    let legacy_errors = request::pinned_protocol(&request) < 8;
+    respond(request).map(|future| -> ResponseFuture {
+        let response = future
+            .and_then(move |response| with_legacy_errors(response, legacy_errors))
+            .map(with_protocol_supported)
+            .map(move |response| encoding::encode_response(response, encoding))
+            .map(move |response| compression::compress_response(response, compression));
+        Box::new(response)
+    })
+}
+
+/// Respond to the request via `respond` within the configured concurrency limits, if any.
+///
+/// Requests beyond `ConfigBuilder::max_concurrent_requests` receive a `429 Too Many Requests`
+/// response without being routed. Responses whose bodies are streamed, i.e. of unknown length,
+/// are replaced by the same response if beyond `ConfigBuilder::max_concurrent_streams`. As
+/// streams are produced lazily, no iteration has begun by then.
+///
+/// Self-describing request bodies are decoded to JSON via `encoding::decode_request` only once
+/// limited by `ConfigBuilder::max_body_size`, so that oversized bodies are never buffered.
+fn with_limits<F>(
+    state: &State,
+    request: Request<Body>,
+    respond: F,
+) -> Result<ResponseFuture, UnknownRequest>
+where
+    F: FnOnce(Request<Body>) -> Result<ResponseFuture, UnknownRequest>,
+{
+    let permit = match state.request_limit.acquire() {
+        None => {
+            let response = too_many_requests_response(state.request_limit.retry_after());
+            return Ok(Box::new(futures::future::ok(response)));
+        }
+        Some(permit) => permit,
+    };
+    let stream_limit = state.stream_limit.clone();
+    let deadline = response::request_deadline(&request);
+    let response = with_body_limit(state, request, |request| {
+        respond(encoding::decode_request(request))
+    })?;
+    let response = with_timeouts(state, deadline, response).map(move |response| {
+        drop(permit);
+        with_stream_limit(response, &stream_limit)
+    });
+    Ok(Box::new(response))
+}
+
+/// Count the response's body against the stream limit until it ends, if it is streamed.
+fn with_stream_limit(response: Response<Body>, limit: &Limit) -> Response<Body> {
+    if response.body().content_length().is_some() {
+        return response;
+    }
+    let permit = match limit.acquire() {
+        None => return too_many_requests_response(limit.retry_after()),
+        Some(permit) => permit,
+    };
+    let (parts, body) = response.into_parts();
+    let body = body.map(move |chunk| {
+        let _permit = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::wrap_stream(body))
+}
+
+/// Respond to the request via `respond`, answering `413 Payload Too Large` instead if its body
+/// is larger than `ConfigBuilder::max_body_size`.
+///
+/// Bodies declaring a greater `Content-Length` are rejected without being read. All others are
+/// counted as they are read, with the response replaced once the limit is passed.
+fn with_body_limit<F>(
+    state: &State,
+    request: Request<Body>,
+    respond: F,
+) -> Result<ResponseFuture, UnknownRequest>
+where
+    F: FnOnce(Request<Body>) -> Result<ResponseFuture, UnknownRequest>,
+{
+    let is_import = request.uri().path().ends_with(request::Import::PATH_AND_QUERY);
+    if is_import || request.body().is_end_stream() {
+        return respond(request);
+    }
+    let max = state.max_body_size;
+    let content_length = request.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok())
+        .and_then(|len| len.parse::<u64>().ok());
+    if content_length.map(|len| len > max as u64).unwrap_or(false) {
+        return Ok(Box::new(futures::future::ok(payload_too_large_response())));
+    }
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let (parts, body) = request.into_parts();
+    let body = BodyLimit { body, remaining: max, exceeded: exceeded.clone() };
+    let request = Request::from_parts(parts, Body::wrap_stream(body));
+    let response = respond(request)?.then(move |result| {
+        if exceeded.load(Ordering::SeqCst) {
+            return Ok(payload_too_large_response());
+        }
+        result
+    });
+    Ok(Box::new(response))
+}
+
+/// A request body that ends with an error once more than `remaining` bytes have been read.
+struct BodyLimit {
+    body: Body,
+    remaining: usize,
+    /// Set once the limit has been exceeded.
+    exceeded: Arc<AtomicBool>,
+}
+
+impl Stream for BodyLimit {
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.body.poll()? {
+            Async::Ready(Some(chunk)) => {
+                if chunk.len() > self.remaining {
+                    self.exceeded.store(true, Ordering::SeqCst);
+                    return Err("the request body is too large".into());
+                }
+                self.remaining -= chunk.len();
+                Ok(Async::Ready(Some(chunk)))
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+/// A `413 Payload Too Large` response to a request whose body exceeds the maximum size.
+fn payload_too_large_response() -> Response<Body> {
+    status_err_response(StatusCode::PAYLOAD_TOO_LARGE, "the request body is too large")
+}
+
+/// Bound the time spent producing the response and streaming its body by the configured
+/// timeouts and the client's `deadline`, if any.
+///
+/// Responses not begun within `ConfigBuilder::request_timeout` or by the `deadline` are replaced
+/// by a `504 Gateway Timeout` response, and the work of producing them is abandoned. Streamed
+/// bodies still in progress after `ConfigBuilder::stream_timeout` or at the `deadline` are ended
+/// with an error. See `request::DEADLINE`.
+fn with_timeouts(
+    state: &State,
+    deadline: Option<Instant>,
+    response: ResponseFuture,
+) -> ResponseFuture {
+    let response = match state.request_timeout {
+        None => response,
+        Some(timeout) => {
+            let response = Timeout::new(response, timeout).or_else(|err| match err.into_inner() {
+                Some(err) => Err(err),
+                None => Ok(timed_out_response()),
+            });
+            Box::new(response)
+        }
+    };
+    let response = match deadline {
+        None => response,
+        Some(deadline) => {
+            let now = Instant::now();
+            let remaining = if deadline > now { deadline - now } else { Duration::from_millis(0) };
+            let response = Timeout::new(response, remaining).or_else(|err| match err.into_inner() {
+                Some(err) => Err(err),
+                None => Ok(response::deadline_exceeded_response()),
+            });
+            Box::new(response)
+        }
+    };
+    let stream_timeout = state.stream_timeout;
+    if stream_timeout.is_none() && deadline.is_none() {
+        return response;
+    }
+    Box::new(response.map(move |response| {
+        if response.body().content_length().is_some() {
+            return response;
+        }
+        let timeout = stream_timeout.map(|timeout| Instant::now() + timeout);
+        let end = match (timeout, deadline) {
+            (Some(timeout), Some(deadline)) => cmp::min(timeout, deadline),
+            (timeout, deadline) => timeout.or(deadline).expect("no bound on the stream"),
+        };
+        let (parts, body) = response.into_parts();
+        let delay = Delay::new(end);
+        let body = StreamTimeout { body, delay };
+        Response::from_parts(parts, Body::wrap_stream(body))
+    }))
+}
+
+/// A `504 Gateway Timeout` response to a request that exceeded `ConfigBuilder::request_timeout`.
+fn timed_out_response() -> Response<Body> {
+    status_err_response(StatusCode::GATEWAY_TIMEOUT, "the request timed out")
+}
+
+/// A streamed response body that ends with an error once its `Delay` elapses.
+struct StreamTimeout {
+    body: Body,
+    delay: Delay,
+}
+
+impl Stream for StreamTimeout {
+    type Item = Chunk;
+    type Error = Box<StdError + Send + Sync>;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        // The delay is polled first so that a body that is always ready cannot outlive it.
+        match self.delay.poll() {
+            Ok(Async::NotReady) => self.body.poll().map_err(|err| err.into()),
+            _ => Err("the stream timed out".into()),
+        }
+    }
+}
+
+/// A `429 Too Many Requests` response to a request beyond one of the concurrency limits.
+///
+/// Clients are asked via `Retry-After` to wait for the given number of seconds before retrying.
+/// See `Limit::retry_after`.
+fn too_many_requests_response(retry_after: u64) -> Response<Body> {
+    let msg = "the server is handling too many concurrent requests";
+    let mut response = status_err_response(StatusCode::TOO_MANY_REQUESTS, msg);
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+/// Invoke each `Middleware` around the response to the request produced via `respond`.
+///
+/// Only the middleware whose `on_request` was invoked have their `on_response` or `on_error`
+/// invoked, in reverse order. Returns `Err(UnknownRequest)` if `respond` does, in which case no
+/// `on_response` is invoked.
+fn with_middleware<F>(
+    state: &State,
+    mut request: Request<Body>,
+    respond: F,
+) -> Result<ResponseFuture, UnknownRequest>
+where
+    F: FnOnce(Request<Body>) -> Result<ResponseFuture, UnknownRequest>,
+{
+    let middleware = state.middleware
+        .read()
+        .expect("failed to acquire middleware lock")
+        .clone();
+    if middleware.is_empty() {
+        return respond(request);
+    }
+    let info = RequestInfo {
+        method: request.method().clone(),
+        uri: request.uri().clone(),
+        received: Instant::now(),
+    };
+    let mut invoked = 0;
+    let mut rejected = None;
+    for m in middleware.iter() {
+        invoked += 1;
+        if let Err(response) = m.on_request(&mut request) {
+            rejected = Some(response);
+            break;
+        }
+    }
+    let response: ResponseFuture = match rejected {
+        Some(response) => Box::new(futures::future::ok(response)),
+        None => respond(request)?,
+    };
+    let response = response.then(move |result| {
+        match result {
+            Ok(mut response) => {
+                for m in middleware[..invoked].iter().rev() {
+                    m.on_response(&info, &mut response);
+                }
+                Ok(response)
+            }
+            Err(err) => {
+                for m in middleware[..invoked].iter().rev() {
+                    m.on_error(&info, &err);
+                }
+                Err(err)
+            }
+        }
+    });
+    Ok(Box::new(response))
+}
+
+/// Respond to the request via the user-defined endpoint matching its method and path, if any.
+///
+/// Returns the request itself if there is no such endpoint.
+fn endpoint_response(
+    request: Request<Body>,
+    tree: Arc<Tree>,
+    state: &State,
+) -> Result<ResponseFuture, Request<Body>> {
+    let key = (request.method().as_str().to_string(), request.uri().path().to_string());
+    let endpoint = state.endpoints
+        .read()
+        .expect("failed to acquire endpoints lock")
+        .get(&key)
+        .cloned();
+    match endpoint {
+        None => Err(request),
+        Some(endpoint) => Ok(endpoint.respond(tree, request)),
+    }
+}
+
+/// Restore the original URI of a request whose path was replaced prior to routing.
+fn with_uri(mut request: Request<Body>, uri: Uri) -> Request<Body> {
+    *request.uri_mut() = uri;
+    request
+}
+
+/// Replace the path of the given request's URI, retaining the query.
+fn set_request_path(request: &mut Request<Body>, path: &str) {
+    let path_and_query = match request.uri().query() {
+        None => path.to_string(),
+        Some(query) => format!("{}?{}", path, query),
+    };
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query
+        .parse()
+        .expect("failed to parse path and query for request URI"));
+    *request.uri_mut() = Uri::from_parts(parts)
+        .expect("failed to construct request URI from parts");
 }
 
-/// Build and run a hyper `Server` using the default runtime with the given configuration and
-/// `sled::Tree`.
-pub fn run(config: Config, tree: Arc<sled::Tree>) {
-    let server = new(config, tree)
-        .map_err(|e| eprintln!("error occurred: {}", e));
-    hyper::rt::run(server);
+/// A `200 OK` response with the given value serialized to JSON as the body.
+fn json_response<T>(value: &T) -> Response<Body>
+where
+    T: ?Sized + Serialize,
+{
+    let bytes = serde_json::to_vec(value).expect("failed to serialize value to JSON");
+    Response::new(bytes.into())
 }
@@ -0,0 +1,137 @@
+//! Serving the API over HTTPS via rustls, enabled by the `tls` feature.
+//!
+//! The server listens over HTTPS when given an `Identity` via `server::ConfigBuilder::tls`,
+//! removing the need for a TLS-terminating reverse proxy in front of deployments reachable beyond
+//! localhost. Handshakes are completed concurrently, and connections whose handshake fails are
+//! dropped without affecting the others. HTTP/2 is negotiated via ALPN where supported by the
+//! client, falling back to HTTP/1.1.
+//!
+//! Clients may be required to authenticate via certificates signed by the authorities within
+//! `Identity::client_ca`. The certificate chain presented by the client is then inserted into the
+//! extensions of each of its requests as `PeerCertificates`, allowing a `server::Authorizer` to
+//! perform certificate-based access control.
+
+use futures::{Future, Stream};
+use hyper;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use rustls::{AllowAnyAuthenticatedClient, Certificate, NoClientAuth, PrivateKey};
+use rustls::{RootCertStore, ServerConfig, Session};
+use rustls::internal::pemfile;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+/// The greatest number of TLS handshakes completed concurrently.
+pub const MAX_HANDSHAKES: usize = 128;
+
+/// The certificate chain and private key with which the server identifies itself.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    /// The PEM file containing the certificate chain, beginning with the server's certificate.
+    pub cert_chain: PathBuf,
+    /// The PEM file containing the private key, either PKCS#8 or RSA.
+    pub private_key: PathBuf,
+    /// The PEM file containing the certificate authorities by which clients are authenticated, if
+    /// any.
+    ///
+    /// If given, clients must present a certificate chain signed by one of the authorities.
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
+
+/// The certificate chain presented by an authenticated client, beginning with the client's own
+/// certificate.
+///
+/// Inserted into the extensions of every request received via a connection on which the client
+/// presented certificates.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PeerCertificates(pub Vec<Certificate>);
+
+/// A connection accepted via `incoming`.
+pub type Connection = TlsStream<TcpStream>;
+
+impl Identity {
+    /// Load the certificate chain and private key into a rustls `ServerConfig`.
+    pub fn server_config(&self) -> io::Result<ServerConfig> {
+        let mut reader = BufReader::new(File::open(&self.cert_chain)?);
+        let certs = pemfile::certs(&mut reader)
+            .map_err(|()| invalid_data("failed to parse the certificate chain".to_string()))?;
+        let key = private_key(&self.private_key)?;
+        let verifier = match self.client_ca {
+            None => NoClientAuth::new(),
+            Some(ref client_ca) => {
+                let mut roots = RootCertStore::empty();
+                let mut reader = BufReader::new(File::open(client_ca)?);
+                roots.add_pem_file(&mut reader).map_err(|()| {
+                    invalid_data("failed to parse the client certificate authorities".to_string())
+                })?;
+                AllowAnyAuthenticatedClient::new(roots)
+            }
+        };
+        let mut config = ServerConfig::new(verifier);
+        config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+        config
+            .set_single_cert(certs, key)
+            .map_err(|err| invalid_data(format!("invalid certificate or private key: {}", err)))?;
+        Ok(config)
+    }
+}
+
+/// Load the first private key from the given PEM file, trying PKCS#8 before RSA.
+fn private_key(path: &Path) -> io::Result<PrivateKey> {
+    let parse_err = |()| invalid_data("failed to parse the private key".to_string());
+    let mut reader = BufReader::new(File::open(path)?);
+    if let Some(key) = pemfile::pkcs8_private_keys(&mut reader).map_err(parse_err)?.pop() {
+        return Ok(key);
+    }
+    let mut reader = BufReader::new(File::open(path)?);
+    pemfile::rsa_private_keys(&mut reader)
+        .map_err(parse_err)?
+        .pop()
+        .ok_or_else(|| invalid_data("no private key found".to_string()))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The certificate chain presented by the client on the given connection, if any.
+pub fn peer_certificates(connection: &Connection) -> Option<PeerCertificates> {
+    let (_, session) = connection.get_ref();
+    session.get_peer_certificates().map(PeerCertificates)
+}
+
+/// Accept TLS connections on the given address, producing each once its handshake completes.
+///
+/// The stream is suitable for serving via `hyper::Server::builder`.
+pub fn incoming(
+    addr: &SocketAddr,
+    config: Arc<ServerConfig>,
+) -> hyper::Result<impl Stream<Item = Connection, Error = io::Error> + Send> {
+    Ok(accept(AddrIncoming::bind(addr)?.map(AddrStream::into_inner), config))
+}
+
+/// Accept TLS connections via the given TCP connections, producing each once its handshake
+/// completes.
+pub fn accept<S>(
+    incoming: S,
+    config: Arc<ServerConfig>,
+) -> impl Stream<Item = Connection, Error = io::Error> + Send
+where
+    S: Stream<Item = TcpStream, Error = io::Error> + Send,
+{
+    let acceptor = TlsAcceptor::from(config);
+    incoming
+        .map(move |socket| {
+            acceptor
+                .accept(socket)
+                .then(|result| Ok::<_, io::Error>(result.ok()))
+        })
+        .buffer_unordered(MAX_HANDSHAKES)
+        .filter_map(|connection| connection)
+}
@@ -0,0 +1,1590 @@
+//! The `Tree` served by the server along with any indices that are maintained alongside it.
+
+use backend::{self, KvBackend, MemoryBackend};
+use futures::sync::mpsc;
+use request::{self, AggregateOp, AggregateResult, BatchOp, Condition, Event, EventFilter};
+use request::{EventKind, Number};
+use request::{PrefixHash, RenameOutcome, TransactionOutcome, TreeStats, ValueType};
+use serde_json;
+use sha1::Sha1;
+use sled;
+use std::cmp::{self, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A `KvBackend` served by the server along with any indices maintained alongside it.
+///
+/// All requests are handled via the methods on this type rather than via the backend directly,
+/// ensuring that indices remain up to date with every mutation.
+#[derive(Clone)]
+pub struct Tree {
+    tree: Arc<KvBackend>,
+    suffix_index: Option<SuffixIndex>,
+    /// Only entries whose keys begin with this prefix are visible via this `Tree`.
+    ///
+    /// The prefix is prepended to all given keys and stripped from all produced keys.
+    prefix: Vec<u8>,
+    read_only: bool,
+    /// Held for reading by each mutation and for writing while a batch is applied.
+    batch_lock: Arc<RwLock<()>>,
+    /// Held by each mutation from reading the previous value of the entry until the change is
+    /// recorded, so that changes are recorded in the order in which they are applied.
+    write_lock: Arc<Mutex<()>>,
+    /// The most recently computed statistics along with the moment they were computed.
+    stats_cache: Arc<Mutex<Option<(Instant, TreeStats)>>>,
+    /// Records every mutation applied via this `Tree` or any of its views.
+    changes: Arc<Mutex<Changes>>,
+    /// Holds the counter of each view from which IDs are generated, outside of the keyspace of
+    /// the tree. See `Tree::with_id_store`.
+    ids: Arc<KvBackend>,
+}
+
+/// The changes applied via a `Tree` and its views along with their subscribers.
+#[derive(Default)]
+struct Changes {
+    /// The sequence number of the most recent change, or `0` if there have been none.
+    seq: u64,
+    /// The most recent changes in order of their sequence numbers, with unstripped keys.
+    history: VecDeque<Event>,
+    /// The maximum number of changes retained within `history`.
+    history_capacity: usize,
+    /// Persists every change keyed by its big-endian sequence number. See `Tree::with_changelog`.
+    log: Option<Arc<KvBackend>>,
+    /// The acknowledged sequence number of each named cursor. See `Tree::with_cursors`.
+    cursors: Option<Arc<KvBackend>>,
+    subscribers: Vec<Subscriber>,
+    /// Receive every change. See `Tree::with_change_sink`.
+    sinks: Vec<Arc<ChangeSink>>,
+}
+
+/// A destination for every change applied via a `Tree` and its views.
+///
+/// Allows for forwarding changes to an external message broker, e.g. Kafka or NATS, or to a local
+/// file. Implemented for closures and for the sending half of a `futures` channel.
+pub trait ChangeSink: Send + Sync {
+    /// Receive the given change, whose key includes any view prefix.
+    ///
+    /// Called once for each change in order of sequence number. As this is called while the
+    /// change is recorded, slow work such as network I/O should be handed off to another thread
+    /// or task, e.g. via a channel.
+    fn send(&self, event: &Event);
+}
+
+/// The entries visible via a `Tree`, produced by `Tree::snapshot`.
+pub struct Snapshot<'a> {
+    /// The sequence number of the most recent change at the moment the snapshot was taken.
+    ///
+    /// The entries reflect every change up to and including `seq`, along with any that follow it
+    /// and are applied while the entries are read.
+    pub seq: u64,
+    /// Yields the visible entries in ascending order of their keys.
+    pub entries: Iter<'a>,
+}
+
+/// The net effect of the changes following some sequence number, produced by `Tree::delta`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Delta {
+    /// The sequence number following which changes are reflected.
+    pub since: u64,
+    /// The sequence number of the most recent change reflected within the entries.
+    pub seq: u64,
+    /// The final value of each changed entry in ascending order of their keys, or `None` if the
+    /// entry was removed.
+    pub entries: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    /// Whether or not all changes following `since` were retained, i.e. whether applying the
+    /// entries to a snapshot taken at `since` reproduces the entries at `seq`.
+    pub complete: bool,
+}
+
+/// The failure of a `Tree::transfer_range` part way through.
+#[derive(Debug)]
+pub struct TransferError {
+    /// The number of entries transferred in full prior to the failure.
+    pub transferred: usize,
+    /// The key from which the transfer may be resumed. All entries preceding it within the range
+    /// have been transferred in full.
+    pub resume_from: Vec<u8>,
+    /// The error that interrupted the transfer.
+    pub error: sled::Error<()>,
+}
+
+/// A subscription to the changes following some sequence number, produced by `Tree::watch_after`.
+#[derive(Debug)]
+pub struct Watch {
+    /// Produces the retained changes following the sequence number, then all subsequent changes.
+    pub events: mpsc::UnboundedReceiver<Event>,
+    /// The sequence number of the most recent change at the moment of subscribing.
+    pub seq: u64,
+    /// Whether or not all changes following the sequence number were retained, i.e. whether
+    /// `events` produces every change without gaps.
+    pub complete: bool,
+}
+
+/// A subscription to the changes made to all entries whose keys begin with `prefix`.
+struct Subscriber {
+    prefix: Vec<u8>,
+    /// The length of the view prefix, stripped from the keys of all events sent.
+    view_prefix_len: usize,
+    /// Evaluated against each event after the view prefix is stripped.
+    filter: EventFilter,
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+/// A `KvBackend` whose keys are the reversed keys of the indexed tree.
+///
+/// The lock is held while mutating both the indexed tree and the index, ensuring the index always
+/// contains at least every key within the indexed tree.
+#[derive(Clone)]
+struct SuffixIndex {
+    tree: Arc<KvBackend>,
+    lock: Arc<Mutex<()>>,
+}
+
+/// An iterator yielding the entries of a `Tree` in order of their keys.
+pub struct Iter<'a> {
+    iter: backend::Iter<'a>,
+    prefix: Vec<u8>,
+}
+
+/// An iterator yielding all entries whose keys end with some suffix.
+///
+/// Entries are yielded in order of their reversed keys.
+pub struct ScanSuffix<'a> {
+    tree: &'a KvBackend,
+    index: backend::Iter<'a>,
+    reversed_suffix: Vec<u8>,
+    prefix: Vec<u8>,
+}
+
+impl Tree {
+    /// Serve the given backend with no additional indices.
+    pub fn new(tree: Arc<KvBackend>) -> Self {
+        let suffix_index = None;
+        let prefix = vec![];
+        let read_only = false;
+        let batch_lock = Arc::new(RwLock::new(()));
+        let write_lock = Arc::new(Mutex::new(()));
+        let stats_cache = Arc::new(Mutex::new(None));
+        let changes = Arc::new(Mutex::new(Changes::default()));
+        let ids = Arc::new(MemoryBackend::default());
+        Tree {
+            tree,
+            suffix_index,
+            prefix,
+            read_only,
+            batch_lock,
+            write_lock,
+            stats_cache,
+            changes,
+            ids,
+        }
+    }
+
+    /// Produce a view onto the entries of this `Tree` whose keys begin with the given prefix.
+    ///
+    /// The prefix is prepended to all keys given to the view and stripped from all keys produced
+    /// by it. If `read_only` is `true`, all attempts to mutate the tree via the view will fail.
+    ///
+    /// Views of views are nested, i.e. their prefixes are concatenated.
+    pub fn view(&self, prefix: Vec<u8>, read_only: bool) -> Tree {
+        let mut view = self.clone();
+        view.prefix.extend(prefix);
+        view.read_only |= read_only;
+        view.stats_cache = Arc::new(Mutex::new(None));
+        view
+    }
+
+    /// The prefix of all keys visible via this `Tree`.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Whether or not mutations via this `Tree` are disallowed.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Prepend the view prefix to the given key.
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        let mut prefixed = self.prefix.clone();
+        prefixed.extend(key);
+        prefixed
+    }
+
+    /// Exclude any batches from being applied while the guard is held.
+    fn batch_guard(&self) -> RwLockReadGuard<()> {
+        self.batch_lock.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Exclude all other mutations via this `Tree` from being applied and recorded while the
+    /// guard is held.
+    ///
+    /// Must be acquired after the batch guard, if at all.
+    fn write_guard(&self) -> MutexGuard<()> {
+        self.write_lock.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Subscribe to all subsequent changes to the entries visible via this `Tree`.
+    ///
+    /// The same as `watch_prefix(&[], None)`, which cannot fail as no changes are replayed.
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<Event> {
+        self.watch(&[], EventFilter::default(), None)
+            .map(|watch| watch.events)
+            .expect("subscribing without replaying changes cannot fail")
+    }
+
+    /// Subscribe to all subsequent changes to the visible entries whose keys begin with `prefix`.
+    ///
+    /// An `Event` is produced for each change applied via this `Tree`, any of its views or the
+    /// `Tree` from which it was viewed. Keys are stripped of the view prefix but not of the given
+    /// `prefix`. The subscription ends once the returned receiver is dropped.
+    ///
+    /// If `after` is `Some`, all retained changes with a greater sequence number are produced
+    /// before any subsequent changes. Changes are retained via `with_change_history` or
+    /// `with_changelog`. Fails only if the changelog could not be read.
+    pub fn watch_prefix(
+        &self,
+        prefix: &[u8],
+        after: Option<u64>,
+    ) -> sled::Result<mpsc::UnboundedReceiver<Event>, ()> {
+        self.watch(prefix, EventFilter::default(), after).map(|watch| watch.events)
+    }
+
+    /// The same as `watch_prefix`, but also reports whether any changes following `after` are no
+    /// longer retained and so will not be produced.
+    pub fn watch_after(&self, prefix: &[u8], after: u64) -> sled::Result<Watch, ()> {
+        self.watch(prefix, EventFilter::default(), Some(after))
+    }
+
+    /// The same as `watch_prefix`, but only producing the changes that satisfy the `filter` and
+    /// also reporting whether any changes following `after` are no longer retained.
+    ///
+    /// The filter is evaluated before the changes are queued for the subscriber, so changes that
+    /// fail to satisfy it cost the subscriber nothing.
+    pub fn watch_filtered(
+        &self,
+        prefix: &[u8],
+        filter: EventFilter,
+        after: Option<u64>,
+    ) -> sled::Result<Watch, ()> {
+        self.watch(prefix, filter, after)
+    }
+
+    /// Subscribe to the changes to the entries beginning with `prefix` that satisfy `filter`,
+    /// first producing retained changes following `after` if it is `Some`.
+    fn watch(
+        &self,
+        prefix: &[u8],
+        filter: EventFilter,
+        after: Option<u64>,
+    ) -> sled::Result<Watch, ()> {
+        let (sender, events) = mpsc::unbounded();
+        let subscriber = Subscriber {
+            prefix: self.key(prefix),
+            view_prefix_len: self.prefix.len(),
+            filter,
+            sender,
+        };
+        // Ensure that no change is applied before subscribing but counted after.
+        let _batch_guard = self.batch_guard();
+        let _write_guard = self.write_guard();
+        let mut changes = self.changes();
+        let seq = changes.seq;
+        let complete = match after {
+            None => true,
+            Some(after) => changes.retained_after(after, |event| {
+                subscriber.send(event);
+            })?,
+        };
+        changes.subscribers.push(subscriber);
+        Ok(Watch { events, seq, complete })
+    }
+
+    /// Collect the net effect of all retained changes to the visible entries following the
+    /// sequence number `since`, allowing for an incremental backup of the entries.
+    ///
+    /// All mutations via this `Tree` are excluded while the changes are read, so the delta
+    /// reflects every change up to and including its `seq`. Changes are retained via
+    /// `with_change_history` or `with_changelog`.
+    pub fn delta(&self, since: u64) -> sled::Result<Delta, ()> {
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let changes = self.changes();
+        let mut entries = BTreeMap::new();
+        let complete = changes.retained_after(since, |event| {
+            if let Some(key) = strip_prefix(event.key.clone(), &self.prefix) {
+                entries.insert(key, event.new.clone());
+            }
+        })?;
+        let seq = changes.seq;
+        let entries = entries.into_iter().collect();
+        Ok(Delta { since, seq, entries, complete })
+    }
+
+    /// Call `f` with each retained change to the visible entries following the sequence number
+    /// `after` in order, returning whether or not all such changes were retained.
+    ///
+    /// Keys are stripped of the view prefix. Changes are read while recording new changes is
+    /// excluded, so `f` should not mutate the `Tree`.
+    pub fn replay_changes<F>(&self, after: u64, mut f: F) -> sled::Result<bool, ()>
+    where
+        F: FnMut(&Event),
+    {
+        let changes = self.changes();
+        changes.retained_after(after, |event| {
+            if let Some(key) = strip_prefix(event.key.clone(), &self.prefix) {
+                f(&Event { key, ..event.clone() });
+            }
+        })
+    }
+
+    /// Retain up to `capacity` of the most recent changes, allowing subscribers to resume from the
+    /// sequence number of the last change they observed.
+    ///
+    /// The history is shared with all views of this `Tree`. While changes are retained, the
+    /// previous value of each entry is read before it is set.
+    pub fn with_change_history(self, capacity: usize) -> Self {
+        {
+            let mut changes = self.changes();
+            changes.history_capacity = capacity;
+            while changes.history.len() > capacity {
+                changes.history.pop_front();
+            }
+        }
+        self
+    }
+
+    /// Persist every change within the given `log` tree, keyed by its big-endian sequence number
+    /// with the JSON-serialized `request::Event` as the value.
+    ///
+    /// Sequence numbers continue from the greatest within the `log`, so they remain monotonic
+    /// across restarts and subscribers may resume from any change within the `log`. The log is
+    /// shared with all views of this `Tree` and should not be mutated by anything else. Event keys
+    /// within the log include any view prefix.
+    pub fn with_changelog(self, log: Arc<KvBackend>) -> sled::Result<Self, ()> {
+        let last = log.max()?.and_then(|(key, _)| decode_seq(&key)).unwrap_or(0);
+        {
+            let mut changes = self.changes();
+            changes.seq = cmp::max(changes.seq, last);
+            changes.log = Some(log);
+        }
+        Ok(self)
+    }
+
+    /// Persist the acknowledged sequence number of each named subscription cursor within the
+    /// given `store`, keyed by the cursor name with the big-endian sequence number as the value.
+    ///
+    /// Without a store, cursors are kept in memory and are lost when the `Tree` is dropped. The
+    /// cursors are shared with all views of this `Tree`.
+    pub fn with_cursors(self, store: Arc<KvBackend>) -> Self {
+        self.changes().cursors = Some(store);
+        self
+    }
+
+    /// Persist the counters from which IDs are generated via `generate_id` within the given
+    /// `store`, keyed by the view prefix followed by `request::ID_KEY`.
+    ///
+    /// Without a store, the counters are kept in memory, so IDs begin again from `0` once the
+    /// `Tree` is dropped. Either way, the counters are kept outside of the tree so that they are
+    /// never visible to nor removed by requests for its entries. A counter left within the tree at
+    /// `request::ID_KEY` by an earlier version is moved into the store if the store has none. The
+    /// store is shared with all views of this `Tree` and should not be mutated by anything else.
+    pub fn with_id_store(mut self, store: Arc<KvBackend>) -> sled::Result<Self, ()> {
+        if store.get(request::ID_KEY)?.is_none() {
+            if let Some(counter) = self.tree.get(request::ID_KEY)? {
+                store.set(request::ID_KEY.to_vec(), counter)?;
+                self.tree.del(request::ID_KEY)?;
+            }
+        }
+        self.ids = store;
+        Ok(self)
+    }
+
+    /// The sequence number most recently acknowledged via the named cursor, or `None` if there is
+    /// no cursor with the given name.
+    pub fn cursor(&self, name: &str) -> sled::Result<Option<u64>, ()> {
+        let cursors = self.changes().cursor_store();
+        let seq = cursors.get(name.as_bytes())?;
+        Ok(seq.and_then(|seq| decode_seq(&seq)))
+    }
+
+    /// Acknowledge every change up to and including `seq` via the named cursor, creating the
+    /// cursor if it does not yet exist.
+    ///
+    /// Cursors never move backwards, so acknowledging an earlier sequence number has no effect.
+    /// Returns the previously acknowledged sequence number, if any.
+    pub fn ack_cursor(&self, name: &str, seq: u64) -> sled::Result<Option<u64>, ()> {
+        let mut changes = self.changes();
+        let cursors = changes.cursor_store();
+        let acked = cursors.get(name.as_bytes())?.and_then(|seq| decode_seq(&seq));
+        if acked.map_or(true, |acked| acked < seq) {
+            cursors.set(name.as_bytes().to_vec(), encode_seq(seq))?;
+        }
+        Ok(acked)
+    }
+
+    /// Remove the named cursor, returning its acknowledged sequence number if it existed.
+    pub fn remove_cursor(&self, name: &str) -> sled::Result<Option<u64>, ()> {
+        let cursors = self.changes().cursor_store();
+        let seq = cursors.del(name.as_bytes())?;
+        Ok(seq.and_then(|seq| decode_seq(&seq)))
+    }
+
+    /// Subscribe to the changes to the entries beginning with `prefix` that follow the sequence
+    /// number acknowledged via the named cursor.
+    ///
+    /// A cursor that does not yet exist is created at the sequence number of the most recent
+    /// change. Changes that have not been acknowledged are replayed so long as they are retained,
+    /// so each change is delivered at least once across reconnections. Only changes satisfying
+    /// the `filter` are produced.
+    pub fn watch_cursor(
+        &self,
+        name: &str,
+        prefix: &[u8],
+        filter: EventFilter,
+    ) -> sled::Result<Watch, ()> {
+        let acked = {
+            let mut changes = self.changes();
+            let cursors = changes.cursor_store();
+            match cursors.get(name.as_bytes())?.and_then(|seq| decode_seq(&seq)) {
+                Some(acked) => acked,
+                None => {
+                    cursors.set(name.as_bytes().to_vec(), encode_seq(changes.seq))?;
+                    changes.seq
+                }
+            }
+        };
+        self.watch(prefix, filter, Some(acked))
+    }
+
+    /// Send every subsequent change to the given sink.
+    ///
+    /// Sinks are shared with all views of this `Tree`. While any sink is present, the previous
+    /// value of each entry is read before it is set.
+    pub fn with_change_sink<S>(self, sink: S) -> Self
+    where
+        S: 'static + ChangeSink,
+    {
+        self.changes().sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// The sequence number of the most recent change, or `0` if there have been none.
+    pub fn change_seq(&self) -> u64 {
+        self.changes().seq
+    }
+
+    /// Exclusive access to the record of changes.
+    fn changes(&self) -> MutexGuard<Changes> {
+        self.changes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Whether or not changes must be described via `record`.
+    ///
+    /// Otherwise, changes need only be counted via `count_change`.
+    fn is_observed(&self) -> bool {
+        let changes = self.changes();
+        !changes.subscribers.is_empty()
+            || !changes.sinks.is_empty()
+            || changes.history_capacity > 0
+            || changes.log.is_some()
+    }
+
+    /// Record a change to the entry at the given already-prefixed key, notifying all subscribers.
+    ///
+    /// Subscribers whose receivers have been dropped are removed. Fails only if the change could
+    /// not be written to the changelog, in which case the change is not assigned a sequence number.
+    /// See `record_applied`.
+    fn record(
+        &self,
+        kind: EventKind,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), ()> {
+        let mut changes = self.changes();
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let time_ms = since_epoch.as_secs() * 1_000 + u64::from(since_epoch.subsec_millis());
+        let event = Event { seq: changes.seq + 1, kind, key, old, new, time_ms };
+        if let Some(ref log) = changes.log {
+            let json = serde_json::to_vec(&event).expect("failed to serialize event to JSON");
+            log.set(encode_seq(event.seq), json)?;
+        }
+        changes.seq = event.seq;
+        changes.subscribers.retain(|subscriber| subscriber.send(&event));
+        for sink in &changes.sinks {
+            sink.send(&event);
+        }
+        if changes.history_capacity > 0 {
+            if changes.history.len() == changes.history_capacity {
+                changes.history.pop_front();
+            }
+            changes.history.push_back(event);
+        }
+        Ok(())
+    }
+
+    /// Record a change that has just been applied to the entry at the given already-prefixed key.
+    ///
+    /// If the change could not be written to the changelog, the entry is restored to its `old`
+    /// value and the error is produced, so that no change remains applied without being recorded.
+    /// Readers may observe the change in the meantime. The write lock, or the batch lock for
+    /// writing, must be held from before `old` was read.
+    fn record_applied(
+        &self,
+        kind: EventKind,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), ()> {
+        let restore = if self.changes().log.is_some() {
+            Some((key.clone(), old.clone()))
+        } else {
+            None
+        };
+        let err = match self.record(kind, key, old, new) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        if let Some((key, old)) = restore {
+            // The change failed to be recorded regardless of whether the entry is restored.
+            let _ = match old {
+                None => self.del_indexed(&key).map(|_| ()),
+                Some(old) => self.set_indexed(key, old),
+            };
+        }
+        Err(err)
+    }
+
+    /// Count a change that need not be described as it is not observed.
+    fn count_change(&self) {
+        self.changes().seq += 1;
+    }
+
+    /// Strip the view prefix from the given entry, or discard it if it is not visible.
+    fn visible(&self, entry: Option<(Vec<u8>, Vec<u8>)>) -> Option<(Vec<u8>, Vec<u8>)> {
+        entry.and_then(|(k, v)| strip_prefix(k, &self.prefix).map(|k| (k, v)))
+    }
+
+    /// Maintain an index of reversed keys within the given `index` tree, allowing for efficient
+    /// `scan_suffix` queries.
+    ///
+    /// The index is populated with all keys that already exist within the tree. The `index` tree
+    /// should not be mutated by anything other than this `Tree`.
+    pub fn with_suffix_index(mut self, index: Arc<KvBackend>) -> sled::Result<Self, ()> {
+        for res in self.tree.iter() {
+            let (key, _) = res?;
+            index.set(reversed(&key), vec![])?;
+        }
+        let lock = Arc::new(Mutex::new(()));
+        self.suffix_index = Some(SuffixIndex { tree: index, lock });
+        Ok(self)
+    }
+
+    /// The backend being served.
+    pub fn backend(&self) -> &Arc<KvBackend> {
+        &self.tree
+    }
+
+    /// Retrieve the value associated with the given key.
+    pub fn get(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        self.tree.get(&self.key(key))
+    }
+
+    /// Delete the entry associated with the given key, returning the removed value.
+    pub fn del(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_guard();
+        let _write_guard = self.write_guard();
+        self.del_prefixed(self.key(key))
+    }
+
+    /// Delete the entry with the given already-prefixed key.
+    ///
+    /// The write lock, or the batch lock for writing, must be held.
+    fn del_prefixed(&self, key: Vec<u8>) -> sled::Result<Option<Vec<u8>>, ()> {
+        let value = self.del_indexed(&key)?;
+        if value.is_some() {
+            if self.is_observed() {
+                self.record_applied(EventKind::Del, key, value.clone(), None)?;
+            } else {
+                self.count_change();
+            }
+        }
+        Ok(value)
+    }
+
+    /// Delete the entry with the given already-prefixed key without recording the change.
+    fn del_indexed(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        match self.suffix_index {
+            None => self.tree.del(key),
+            Some(ref index) => {
+                let _guard = index.lock.lock().unwrap_or_else(|err| err.into_inner());
+                let value = self.tree.del(key)?;
+                index.tree.del(&reversed(key))?;
+                Ok(value)
+            }
+        }
+    }
+
+    /// Set the entry with the given key and value, replacing the original if one exists.
+    pub fn set(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_guard();
+        let _write_guard = self.write_guard();
+        self.set_prefixed(self.key(&key), value)
+    }
+
+    /// Set the entry with the given already-prefixed key.
+    ///
+    /// The write lock, or the batch lock for writing, must be held.
+    fn set_prefixed(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        if !self.is_observed() {
+            self.set_indexed(key, value)?;
+            self.count_change();
+            return Ok(());
+        }
+        let old = self.tree.get(&key)?;
+        self.set_indexed(key.clone(), value.clone())?;
+        self.record_applied(EventKind::Set, key, old, Some(value))
+    }
+
+    /// Set the entry with the given already-prefixed key without recording the change.
+    fn set_indexed(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        match self.suffix_index {
+            None => self.tree.set(key, value),
+            Some(ref index) => {
+                let _guard = index.lock.lock().unwrap_or_else(|err| err.into_inner());
+                index.tree.set(reversed(&key), vec![])?;
+                self.tree.set(key, value)
+            }
+        }
+    }
+
+    /// Compare and swap the value associated with the given key.
+    ///
+    /// See `sled::Tree::cas` for details.
+    pub fn cas(
+        &self,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), Option<Vec<u8>>> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_guard();
+        let _write_guard = self.write_guard();
+        let key = self.key(&key);
+        let changed = old != new;
+        let change = if changed && self.is_observed() {
+            Some((key.clone(), old.clone(), new.clone()))
+        } else {
+            None
+        };
+        match self.suffix_index {
+            None => self.tree.cas(key, old, new)?,
+            Some(ref index) => {
+                let _guard = index.lock.lock().unwrap_or_else(|err| err.into_inner());
+                let reversed_key = reversed(&key);
+                let deleting = new.is_none();
+                if !deleting {
+                    index.tree.set(reversed_key.clone(), vec![]).map_err(|e| e.danger_cast())?;
+                }
+                self.tree.cas(key, old, new)?;
+                if deleting {
+                    index.tree.del(&reversed_key).map_err(|e| e.danger_cast())?;
+                }
+            }
+        }
+        match change {
+            Some((key, old, new)) => {
+                let kind = if new.is_some() { EventKind::Set } else { EventKind::Del };
+                self.record_applied(kind, key, old, new).map_err(|err| err.danger_cast())?;
+            }
+            None if changed => self.count_change(),
+            None => (),
+        }
+        Ok(())
+    }
+
+    /// Merge the given value into the total state for the key.
+    pub fn merge(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_guard();
+        let _write_guard = self.write_guard();
+        let key = self.key(&key);
+        let old = if self.is_observed() { Some(self.tree.get(&key)?) } else { None };
+        match self.suffix_index {
+            None => self.tree.merge(key.clone(), value)?,
+            Some(ref index) => {
+                let _guard = index.lock.lock().unwrap_or_else(|err| err.into_inner());
+                index.tree.set(reversed(&key), vec![])?;
+                self.tree.merge(key.clone(), value)?;
+            }
+        }
+        match old {
+            Some(old) => {
+                let new = self.tree.get(&key)?;
+                self.record_applied(EventKind::Merge, key, old, new)?;
+            }
+            None => self.count_change(),
+        }
+        Ok(())
+    }
+
+    /// Apply the given operations in order while excluding all other mutations via this `Tree`.
+    ///
+    /// The backend offers no atomic batches, so readers may observe a partially applied batch
+    /// and an error part way through leaves all preceding operations applied.
+    pub fn batch(&self, ops: Vec<BatchOp>) -> sled::Result<(), ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        self.apply(ops)
+    }
+
+    /// Apply the given writes only if all of the given conditions hold.
+    ///
+    /// The conditions are checked and the writes applied while excluding all other mutations via
+    /// this `Tree`. The backend offers no atomic batches, so the previous value of each entry is
+    /// read before it is written. If a write fails, every entry written by the transaction is
+    /// restored to its previous value before the error is returned, so that either all writes
+    /// commit or none do. Readers may observe writes that are later undone, and the undoing is
+    /// recorded as changes like any other. Should the undoing also fail, the error says so and
+    /// the entries written so far may remain changed.
+    pub fn transaction(
+        &self,
+        conditions: Vec<Condition>,
+        writes: Vec<BatchOp>,
+    ) -> sled::Result<TransactionOutcome, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let mut conflicts = vec![];
+        for Condition { key, expected } in conditions {
+            let current = self.tree.get(&self.key(&key))?;
+            if current != expected {
+                conflicts.push((key, current));
+            }
+        }
+        if !conflicts.is_empty() {
+            return Ok(TransactionOutcome::Aborted { conflicts });
+        }
+        let mut previous = vec![];
+        for op in writes {
+            let key = match op {
+                BatchOp::Set { ref key, .. } | BatchOp::Del { ref key } => self.key(key),
+            };
+            let res = match self.tree.get(&key) {
+                Err(err) => Err(err),
+                Ok(old) => {
+                    // Kept before the write so that a write failing part way through is undone.
+                    previous.push((key, old));
+                    self.apply(vec![op])
+                }
+            };
+            if let Err(err) = res {
+                return Err(match self.undo(previous) {
+                    Ok(()) => err,
+                    Err(undo_err) => {
+                        let msg = format!("{}, after which undoing the transaction's writes also \
+                                           failed: {}", err, undo_err);
+                        sled::Error::Unsupported(msg)
+                    }
+                });
+            }
+        }
+        Ok(TransactionOutcome::Committed)
+    }
+
+    /// Restore each of the given prefixed keys to its previous value in the reverse of the order
+    /// given, undoing the writes of a failed `transaction`.
+    fn undo(&self, previous: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> sled::Result<(), ()> {
+        for (key, old) in previous.into_iter().rev() {
+            match old {
+                Some(value) => self.set_prefixed(key, value)?,
+                None => {
+                    self.del_prefixed(key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the value at `from` to the key `to`, removing the entry at `from`.
+    ///
+    /// Unless `overwrite` is `true`, nothing is changed if an entry already exists at `to`. All
+    /// other mutations via this `Tree` are excluded during the rename. The value is written to
+    /// `to` before `from` is removed, so a failure part way through may leave the value at both
+    /// keys but never at neither.
+    pub fn rename(
+        &self,
+        from: Vec<u8>,
+        to: Vec<u8>,
+        overwrite: bool,
+    ) -> sled::Result<RenameOutcome, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let from = self.key(&from);
+        let to = self.key(&to);
+        let value = match self.tree.get(&from)? {
+            None => return Ok(RenameOutcome::SourceMissing),
+            Some(value) => value,
+        };
+        if from == to {
+            return Ok(RenameOutcome::Renamed);
+        }
+        if !overwrite && self.tree.get(&to)?.is_some() {
+            return Ok(RenameOutcome::DestinationExists);
+        }
+        self.set_prefixed(to, value)?;
+        self.del_prefixed(from)?;
+        Ok(RenameOutcome::Renamed)
+    }
+
+    /// Count the entries visible via this `Tree`.
+    ///
+    /// This requires iterating over every entry.
+    pub fn len(&self) -> sled::Result<usize, ()> {
+        let mut count = 0;
+        for res in self.iter() {
+            res?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Count the entries whose keys lie within the range `start..end`.
+    pub fn count_range(&self, start: &[u8], end: &[u8]) -> sled::Result<usize, ()> {
+        let mut count = 0;
+        for res in self.scan(start) {
+            let (k, _) = res?;
+            if &k[..] >= end {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Hash the entries whose keys begin with `prefix`, grouped by the byte following the prefix.
+    ///
+    /// Produces a `PrefixHash` for each non-empty group in ascending order. See
+    /// `request::HashPrefix`.
+    pub fn hash_prefix(&self, prefix: &[u8]) -> sled::Result<Vec<PrefixHash>, ()> {
+        let mut hashes = vec![];
+        let mut group: Option<(Vec<u8>, u64, Sha1)> = None;
+        for res in self.scan(prefix) {
+            let (k, v) = res?;
+            if !k.starts_with(prefix) {
+                break;
+            }
+            if k.len() == prefix.len() {
+                continue;
+            }
+            let child = &k[..prefix.len() + 1];
+            if group.as_ref().map_or(true, |&(ref group_prefix, _, _)| &group_prefix[..] != child) {
+                hashes.extend(group.take().map(prefix_hash));
+                group = Some((child.to_vec(), 0, Sha1::new()));
+            }
+            if let Some((_, ref mut count, ref mut sha1)) = group {
+                *count += 1;
+                sha1.update(&request::encode_export_entry(&k, &v));
+            }
+        }
+        hashes.extend(group.map(prefix_hash));
+        Ok(hashes)
+    }
+
+    /// Find the `n` entries with the largest values, producing the key and value length of each in
+    /// descending order of value length.
+    ///
+    /// This requires iterating over every entry, though at most `n` keys are retained at a time.
+    pub fn top_values(&self, n: usize) -> sled::Result<Vec<(Vec<u8>, usize)>, ()> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        let mut heap = BinaryHeap::new();
+        for res in self.iter() {
+            let (k, v) = res?;
+            heap.push(Reverse((v.len(), k)));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+        let top = heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((len, k))| (k, len))
+            .collect();
+        Ok(top)
+    }
+
+    /// Aggregate the numeric values of all entries within `start..end` via the given operation.
+    ///
+    /// Produces a `sled::Error::Unsupported` if any value is not an 8-byte little-endian number or
+    /// if a `U64` sum overflows.
+    pub fn aggregate(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        op: AggregateOp,
+        value_type: ValueType,
+    ) -> sled::Result<AggregateResult, ()> {
+        let mut count = 0;
+        let mut acc: Option<Number> = None;
+        let mut sum = 0.0;
+        for res in self.scan(start) {
+            let (k, v) = res?;
+            if &k[..] >= end {
+                break;
+            }
+            let n = decode_number(&v, value_type).ok_or_else(|| {
+                let msg = "the value is not an 8-byte little-endian number".to_string();
+                sled::Error::Unsupported(msg)
+            })?;
+            count += 1;
+            if op == AggregateOp::Avg {
+                sum += n.as_f64();
+                continue;
+            }
+            acc = match acc {
+                None => Some(n),
+                Some(acc) => Some(combine(op, acc, n).ok_or_else(|| {
+                    sled::Error::Unsupported("the sum overflowed a `u64`".to_string())
+                })?),
+            };
+        }
+        let value = match op {
+            AggregateOp::Avg if count > 0 => Some(Number::F64(sum / count as f64)),
+            AggregateOp::Avg => None,
+            _ => acc,
+        };
+        Ok(AggregateResult { count, value })
+    }
+
+    /// Compute summary statistics over the entries visible via this `Tree`.
+    ///
+    /// This requires iterating over every entry unless `max_age` is `Some` and statistics were
+    /// computed within that duration, in which case those are produced instead. Each view begins
+    /// with no cached statistics.
+    pub fn stats(&self, max_age: Option<Duration>) -> sled::Result<TreeStats, ()> {
+        if let Some(max_age) = max_age {
+            let cache = self.stats_cache.lock().unwrap_or_else(|err| err.into_inner());
+            if let Some((ref computed_at, ref stats)) = *cache {
+                if computed_at.elapsed() <= max_age {
+                    return Ok(stats.clone());
+                }
+            }
+        }
+        let computed_at = Instant::now();
+        let mut stats = TreeStats::default();
+        for res in self.iter() {
+            let (k, v) = res?;
+            let size = v.len() as u64;
+            stats.entries += 1;
+            stats.key_bytes += k.len() as u64;
+            stats.value_bytes += size;
+            stats.min_value_size = Some(stats.min_value_size.map_or(size, |min| min.min(size)));
+            stats.max_value_size = Some(stats.max_value_size.map_or(size, |max| max.max(size)));
+        }
+        if stats.entries > 0 {
+            stats.avg_value_size = Some(stats.value_bytes as f64 / stats.entries as f64);
+        }
+        let mut cache = self.stats_cache.lock().unwrap_or_else(|err| err.into_inner());
+        *cache = Some((computed_at, stats.clone()));
+        Ok(stats)
+    }
+
+    /// Whether or not there are no entries visible via this `Tree`.
+    pub fn is_empty(&self) -> sled::Result<bool, ()> {
+        match self.iter().next() {
+            None => Ok(true),
+            Some(res) => res.map(|_| false),
+        }
+    }
+
+    /// Iterate over all entries visible via this `Tree` along with the sequence number of the
+    /// most recent change.
+    ///
+    /// The backend offers no snapshots of its own and mutations are not excluded while the entries
+    /// are read, so the entries may also reflect changes following `seq`. As every change is
+    /// recorded only once applied and each `Event` describes the resulting value in full,
+    /// replaying the changes following `seq` onto the entries reproduces the `Tree`.
+    pub fn snapshot(&self) -> Snapshot {
+        let seq = self.change_seq();
+        let entries = self.iter();
+        Snapshot { seq, entries }
+    }
+
+    /// Remove all entries visible via this `Tree`, returning the number of entries removed.
+    ///
+    /// All other mutations via this `Tree` are excluded while the entries are removed.
+    pub fn clear(&self) -> sled::Result<usize, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let keys = self.iter()
+            .map(|res| res.map(|(k, _)| self.key(&k)))
+            .collect::<sled::Result<Vec<_>, ()>>()?;
+        self.del_all(keys)
+    }
+
+    /// Remove all entries whose keys begin with the given prefix, returning the number of entries
+    /// removed.
+    ///
+    /// All other mutations via this `Tree` are excluded while the entries are removed.
+    pub fn del_prefix(&self, prefix: &[u8]) -> sled::Result<usize, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let _batch_guard = self.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let mut keys = vec![];
+        for res in self.scan(prefix) {
+            let (k, _) = res?;
+            if !k.starts_with(prefix) {
+                break;
+            }
+            keys.push(self.key(&k));
+        }
+        self.del_all(keys)
+    }
+
+    /// Copy the entries within `start..end` to the `dest` tree, returning the number of entries
+    /// copied. An `end` of `None` leaves the range unbounded.
+    ///
+    /// If `remove` is `true`, the entries are also removed from this tree, i.e. moved. All other
+    /// mutations via both trees are excluded throughout. The backend offers no atomic batches, so
+    /// entries are transferred one at a time in order of their keys without being read into
+    /// memory. Each is written to `dest` before being removed, so a failure part way through may
+    /// leave the entry at `TransferError::resume_from` within both trees but never within neither.
+    /// Repeating the transfer from that key completes it.
+    ///
+    /// Transfers between views of the same backend whose ranges overlap are refused, as entries
+    /// written to `dest` would then be transferred again.
+    pub fn transfer_range(
+        &self,
+        dest: &Tree,
+        start: &[u8],
+        end: Option<&[u8]>,
+        remove: bool,
+    ) -> Result<usize, TransferError> {
+        let mut transferred = 0;
+        let mut resume_from = start.to_vec();
+        let fail = |transferred, resume_from, error| {
+            TransferError { transferred, resume_from, error }
+        };
+        if dest.read_only || (remove && self.read_only) {
+            return Err(fail(transferred, resume_from, read_only_error()));
+        }
+        // Views of the same backend share a batch lock. Otherwise, acquire both locks in order of
+        // their address so that concurrent transfers in opposing directions cannot deadlock.
+        let same_backend = Arc::ptr_eq(&self.batch_lock, &dest.batch_lock);
+        let lock_addr = |tree: &Tree| &*tree.batch_lock as *const RwLock<()> as usize;
+        let (first, second) = if lock_addr(self) <= lock_addr(dest) {
+            (self, dest)
+        } else {
+            (dest, self)
+        };
+        let _first_guard = first.batch_lock.write().unwrap_or_else(|err| err.into_inner());
+        let _second_guard = if same_backend {
+            None
+        } else {
+            Some(second.batch_lock.write().unwrap_or_else(|err| err.into_inner()))
+        };
+        let same_prefix = self.prefix == dest.prefix;
+        if same_backend && !same_prefix {
+            let source_range = prefixed_range(&self.prefix, start, end);
+            let dest_range = prefixed_range(&dest.prefix, start, end);
+            if ranges_overlap(&source_range, &dest_range) {
+                let msg = "the source and destination ranges overlap".to_string();
+                return Err(fail(transferred, resume_from, sled::Error::Unsupported(msg)));
+            }
+        }
+        for res in self.scan(start) {
+            let (k, v) = res.map_err(|err| fail(transferred, resume_from.clone(), err))?;
+            if end.map_or(false, |end| &k[..] >= end) {
+                break;
+            }
+            dest.set_prefixed(dest.key(&k), v)
+                .map_err(|err| fail(transferred, k.clone(), err))?;
+            // Don't remove an entry that was just written over itself.
+            if remove && !(same_backend && same_prefix) {
+                self.del_prefixed(self.key(&k))
+                    .map_err(|err| fail(transferred, k.clone(), err))?;
+            }
+            transferred += 1;
+            // The least key following `k`.
+            resume_from = k;
+            resume_from.push(0);
+        }
+        Ok(transferred)
+    }
+
+    /// Remove the entries at the given prefixed keys, returning the number of entries removed.
+    fn del_all(&self, keys: Vec<Vec<u8>>) -> sled::Result<usize, ()> {
+        let mut count = 0;
+        for key in keys {
+            if self.del_prefixed(key)?.is_some() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Apply the given operations in order. The batch lock must be held for writing.
+    fn apply(&self, ops: Vec<BatchOp>) -> sled::Result<(), ()> {
+        for op in ops {
+            match op {
+                BatchOp::Set { key, value } => self.set_prefixed(self.key(&key), value)?,
+                BatchOp::Del { key } => {
+                    self.del_prefixed(self.key(&key))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Atomically add `delta` to the little-endian `i64` value associated with the given key,
+    /// returning the new value.
+    ///
+    /// A missing entry is treated as `0`. Produces `None` without changing the entry if the
+    /// addition would overflow, or a `sled::Error::Unsupported` if the existing value is not 8
+    /// bytes long.
+    pub fn incr(&self, key: Vec<u8>, delta: i64) -> sled::Result<Option<i64>, ()> {
+        loop {
+            let current = self.get(&key)?;
+            let n = match current {
+                None => 0,
+                Some(ref bytes) => decode_i64(bytes).ok_or_else(|| {
+                    let msg = "the value is not an 8-byte little-endian integer".to_string();
+                    sled::Error::Unsupported(msg)
+                })?,
+            };
+            let new = match n.checked_add(delta) {
+                None => return Ok(None),
+                Some(new) => new,
+            };
+            match self.cas(key.clone(), current, Some(encode_i64(new))) {
+                Ok(()) => return Ok(Some(new)),
+                Err(sled::Error::CasFailed(_)) => continue,
+                Err(err) => return Err(err.danger_cast()),
+            }
+        }
+    }
+
+    /// Set the entry with the given key and value, returning the value that was replaced, if any.
+    pub fn replace(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<Option<Vec<u8>>, ()> {
+        loop {
+            let current = self.get(&key)?;
+            match self.cas(key.clone(), current.clone(), Some(value.clone())) {
+                Ok(()) => return Ok(current),
+                Err(sled::Error::CasFailed(_)) => continue,
+                Err(err) => return Err(err.danger_cast()),
+            }
+        }
+    }
+
+    /// Atomically retrieve the value associated with the given key, first inserting `default` if
+    /// there is no entry.
+    pub fn get_or_insert(&self, key: Vec<u8>, default: Vec<u8>) -> sled::Result<Vec<u8>, ()> {
+        loop {
+            if let Some(value) = self.get(&key)? {
+                return Ok(value);
+            }
+            match self.cas(key.clone(), None, Some(default.clone())) {
+                Ok(()) => return Ok(default),
+                Err(sled::Error::CasFailed(Some(value))) => return Ok(value),
+                Err(sled::Error::CasFailed(None)) => continue,
+                Err(err) => return Err(err.danger_cast()),
+            }
+        }
+    }
+
+    /// Atomically remove and return the entry with the smallest key.
+    pub fn pop_min(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        self.pop_with(|tree| tree.iter().next().map_or(Ok(None), |res| res.map(Some)))
+    }
+
+    /// Atomically remove and return the entry with the greatest key.
+    pub fn pop_max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        self.pop_with(|tree| tree.max())
+    }
+
+    /// Remove the entry produced by `find` via `cas`, retrying if it is concurrently modified.
+    fn pop_with<F>(&self, find: F) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()>
+    where
+        F: Fn(&Self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()>,
+    {
+        loop {
+            let (key, value) = match find(self)? {
+                None => return Ok(None),
+                Some(entry) => entry,
+            };
+            match self.cas(key.clone(), Some(value.clone()), None) {
+                Ok(()) => return Ok(Some((key, value))),
+                Err(sled::Error::CasFailed(_)) => continue,
+                Err(err) => return Err(err.danger_cast()),
+            }
+        }
+    }
+
+    /// Generate a unique, monotonically increasing ID using this view's counter within the ID
+    /// store. See `Tree::with_id_store`.
+    pub fn generate_id(&self) -> sled::Result<u64, ()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let key = self.key(request::ID_KEY);
+        loop {
+            let current = self.ids.get(&key)?;
+            let n = match current {
+                None => 0,
+                Some(ref bytes) => decode_i64(bytes).ok_or_else(|| {
+                    sled::Error::Unsupported("the ID counter is corrupt".to_string())
+                })?,
+            };
+            let next = n.checked_add(1).ok_or_else(|| {
+                sled::Error::Unsupported("the ID counter overflowed an `i64`".to_string())
+            })?;
+            match self.ids.cas(key.clone(), current, Some(encode_i64(next))) {
+                Ok(()) => return Ok(n as u64),
+                Err(sled::Error::CasFailed(_)) => continue,
+                Err(err) => return Err(err.danger_cast()),
+            }
+        }
+    }
+
+    /// Flush any pending IO buffers for the tree and its indices to disk.
+    pub fn flush(&self) -> sled::Result<(), ()> {
+        self.tree.flush()?;
+        if let Some(ref index) = self.suffix_index {
+            index.tree.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over all entries within the tree.
+    pub fn iter(&self) -> Iter {
+        self.scan(&[])
+    }
+
+    /// Iterate over all entries within the tree starting from the given key.
+    pub fn scan(&self, key: &[u8]) -> Iter {
+        let iter = self.tree.scan(&self.key(key));
+        let prefix = self.prefix.clone();
+        Iter { iter, prefix }
+    }
+
+    /// Iterate over all entries whose keys end with the given suffix.
+    ///
+    /// Returns `None` if no suffix index is maintained for this tree.
+    pub fn scan_suffix(&self, suffix: &[u8]) -> Option<ScanSuffix> {
+        self.suffix_index.as_ref().map(|index| {
+            let reversed_suffix = reversed(suffix);
+            let index = index.tree.scan(&reversed_suffix);
+            let prefix = self.prefix.clone();
+            ScanSuffix { tree: &*self.tree, index, reversed_suffix, prefix }
+        })
+    }
+
+    /// Retrieve the entry with the greatest key.
+    pub fn max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let entry = match prefix_successor(&self.prefix) {
+            Some(end) => self.tree.pred(&end)?,
+            // All keys following the prefix begin with the prefix.
+            None => self.tree.max()?,
+        };
+        Ok(self.visible(entry))
+    }
+
+    /// Retrieve the entry preceding the given key.
+    pub fn pred(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let entry = self.tree.pred(&self.key(key))?;
+        Ok(self.visible(entry))
+    }
+
+    /// Retrieve the entry preceding or including the given key.
+    pub fn pred_incl(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        let entry = self.tree.pred_incl(&self.key(key))?;
+        Ok(self.visible(entry))
+    }
+}
+
+impl<F> ChangeSink for F
+where
+    F: Fn(&Event) + Send + Sync,
+{
+    fn send(&self, event: &Event) {
+        (*self)(event)
+    }
+}
+
+impl ChangeSink for mpsc::UnboundedSender<Event> {
+    /// Changes are discarded once the receiver has been dropped.
+    fn send(&self, event: &Event) {
+        let _ = self.unbounded_send(event.clone());
+    }
+}
+
+impl Changes {
+    /// The store of cursors, falling back to an in-memory store if none was given.
+    fn cursor_store(&mut self) -> Arc<KvBackend> {
+        self.cursors
+            .get_or_insert_with(|| Arc::new(MemoryBackend::default()))
+            .clone()
+    }
+
+    /// Call `f` with each retained change following the sequence number `after` in order,
+    /// returning whether or not all such changes were retained.
+    ///
+    /// Fails only if the changelog could not be read.
+    fn retained_after<F>(&self, after: u64, mut f: F) -> sled::Result<bool, ()>
+    where
+        F: FnMut(&Event),
+    {
+        // The sequence number of the oldest retained change, if any.
+        let first = match self.log {
+            Some(ref log) => {
+                let first = log.iter().next().map_or(Ok(None), |res| {
+                    res.map(|(key, _)| decode_seq(&key))
+                })?;
+                for res in log.scan(&encode_seq(after.saturating_add(1))) {
+                    let (_, value) = res?;
+                    let event = serde_json::from_slice(&value).map_err(|err| {
+                        let msg = format!("failed to decode changelog entry: {}", err);
+                        sled::Error::Unsupported(msg)
+                    })?;
+                    f(&event);
+                }
+                first
+            }
+            None => {
+                for event in self.history.iter().filter(|event| event.seq > after) {
+                    f(event);
+                }
+                self.history.front().map(|event| event.seq)
+            }
+        };
+        let retained = match first {
+            None => after == self.seq,
+            Some(first) => first - 1 <= after,
+        };
+        Ok(after <= self.seq && retained)
+    }
+}
+
+impl Subscriber {
+    /// Send the given event, whose key includes any view prefix, if it is within the subscribed
+    /// prefix and satisfies the filter.
+    ///
+    /// Returns `false` if the receiver has been dropped.
+    fn send(&self, event: &Event) -> bool {
+        if !event.key.starts_with(&self.prefix) {
+            return true;
+        }
+        let mut event = event.clone();
+        event.key.drain(..self.view_prefix_len);
+        if !self.filter.matches(&event) {
+            return true;
+        }
+        self.sender.unbounded_send(event).is_ok()
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next()? {
+            Err(err) => Some(Err(err)),
+            Ok((k, v)) => strip_prefix(k, &self.prefix).map(|k| Ok((k, v))),
+        }
+    }
+}
+
+impl<'a> Iterator for ScanSuffix<'a> {
+    type Item = sled::Result<(Vec<u8>, Vec<u8>), ()>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (reversed_key, _) = match self.index.next()? {
+                Err(err) => return Some(Err(err)),
+                Ok(kv) => kv,
+            };
+            if !reversed_key.starts_with(&self.reversed_suffix) {
+                return None;
+            }
+            // Skip keys outside of the view, or whose suffix overlaps the view's prefix.
+            let key = match strip_prefix(reversed(&reversed_key), &self.prefix) {
+                Some(ref key) if key.len() < self.reversed_suffix.len() => continue,
+                None => continue,
+                Some(key) => key,
+            };
+            // The index may contain keys that have since been removed from the tree.
+            let full_key = reversed(&reversed_key);
+            match self.tree.get(&full_key) {
+                Err(err) => return Some(Err(err)),
+                Ok(None) => continue,
+                Ok(Some(value)) => return Some(Ok((key, value))),
+            }
+        }
+    }
+}
+
+impl From<Arc<sled::Tree>> for Tree {
+    fn from(tree: Arc<sled::Tree>) -> Self {
+        Tree::new(tree)
+    }
+}
+
+impl From<sled::Tree> for Tree {
+    fn from(tree: sled::Tree) -> Self {
+        Tree::new(Arc::new(tree))
+    }
+}
+
+impl From<Arc<KvBackend>> for Tree {
+    fn from(tree: Arc<KvBackend>) -> Self {
+        Tree::new(tree)
+    }
+}
+
+impl From<MemoryBackend> for Tree {
+    fn from(tree: MemoryBackend) -> Self {
+        Tree::new(Arc::new(tree))
+    }
+}
+
+/// The error produced upon attempting to mutate a read-only `Tree`.
+fn read_only_error<T>() -> sled::Error<T> {
+    let msg = "attempted to mutate a read-only view of the tree".to_string();
+    sled::Error::Unsupported(msg)
+}
+
+/// Strip the given prefix from the key, or return `None` if the key does not begin with it.
+fn strip_prefix(mut key: Vec<u8>, prefix: &[u8]) -> Option<Vec<u8>> {
+    if !key.starts_with(prefix) {
+        return None;
+    }
+    key.drain(..prefix.len());
+    Some(key)
+}
+
+/// The smallest key that is greater than every key beginning with the given prefix.
+///
+/// Returns `None` if there is no such key, i.e. if the prefix is empty or consists only of `0xFF`.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(byte) = end.pop() {
+        if byte < 0xFF {
+            end.push(byte + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// The range of keys `prefix + start..prefix + end` as its inclusive start and exclusive end,
+/// where an `end` of `None` extends the range to the last key beginning with the prefix.
+fn prefixed_range(prefix: &[u8], start: &[u8], end: Option<&[u8]>) -> (Vec<u8>, Option<Vec<u8>>) {
+    let join = |key: &[u8]| {
+        let mut prefixed = prefix.to_vec();
+        prefixed.extend(key);
+        prefixed
+    };
+    let end = match end {
+        Some(end) => Some(join(end)),
+        None => prefix_successor(prefix),
+    };
+    (join(start), end)
+}
+
+/// Whether or not the given ranges produced by `prefixed_range` share any key.
+fn ranges_overlap(a: &(Vec<u8>, Option<Vec<u8>>), b: &(Vec<u8>, Option<Vec<u8>>)) -> bool {
+    let precedes = |start: &[u8], end: &Option<Vec<u8>>| {
+        end.as_ref().map_or(true, |end| start < &end[..])
+    };
+    precedes(&a.0, &b.1) && precedes(&b.0, &a.1) && precedes(&a.0, &a.1) && precedes(&b.0, &b.1)
+}
+
+/// Decode a little-endian `i64`, or return `None` if there are not exactly 8 bytes.
+fn decode_i64(bytes: &[u8]) -> Option<i64> {
+    decode_u64(bytes).map(|n| n as i64)
+}
+
+/// Decode a little-endian `u64`, or return `None` if there are not exactly 8 bytes.
+fn decode_u64(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() != 8 {
+        return None;
+    }
+    Some(bytes.iter().rev().fold(0u64, |n, &b| (n << 8) | u64::from(b)))
+}
+
+/// Decode a little-endian number of the given type.
+fn decode_number(bytes: &[u8], value_type: ValueType) -> Option<Number> {
+    let n = decode_u64(bytes)?;
+    match value_type {
+        ValueType::U64 => Some(Number::U64(n)),
+        ValueType::F64 => Some(Number::F64(f64::from_bits(n))),
+    }
+}
+
+/// Combine two numbers of the same type via the given operation, returning `None` on overflow.
+///
+/// `Avg` combines as `Sum`, leaving division by the count to the caller.
+fn combine(op: AggregateOp, a: Number, b: Number) -> Option<Number> {
+    match (a, b) {
+        (Number::U64(a), Number::U64(b)) => match op {
+            AggregateOp::Sum | AggregateOp::Avg => a.checked_add(b).map(Number::U64),
+            AggregateOp::Min => Some(Number::U64(a.min(b))),
+            AggregateOp::Max => Some(Number::U64(a.max(b))),
+        },
+        (a, b) => {
+            let (a, b) = (a.as_f64(), b.as_f64());
+            match op {
+                AggregateOp::Sum | AggregateOp::Avg => Some(Number::F64(a + b)),
+                AggregateOp::Min => Some(Number::F64(a.min(b))),
+                AggregateOp::Max => Some(Number::F64(a.max(b))),
+            }
+        }
+    }
+}
+
+/// Complete the hash of a group of entries produced by `Tree::hash_prefix`.
+fn prefix_hash((prefix, count, sha1): (Vec<u8>, u64, Sha1)) -> PrefixHash {
+    let hash = sha1.digest().to_string();
+    PrefixHash { prefix, count, hash }
+}
+
+/// Encode the given `i64` as little-endian bytes.
+fn encode_i64(n: i64) -> Vec<u8> {
+    (0..8).map(|i| ((n as u64) >> (i * 8)) as u8).collect()
+}
+
+/// Encode the given sequence number as a changelog key, i.e. as big-endian bytes.
+fn encode_seq(seq: u64) -> Vec<u8> {
+    (0..8).rev().map(|i| (seq >> (i * 8)) as u8).collect()
+}
+
+/// Decode a changelog key produced by `encode_seq`.
+fn decode_seq(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() != 8 {
+        return None;
+    }
+    Some(bytes.iter().fold(0, |seq, &b| (seq << 8) | u64::from(b)))
+}
+
+/// Produce the given key with its bytes in reverse order.
+fn reversed(key: &[u8]) -> Vec<u8> {
+    key.iter().rev().cloned().collect()
+}
@@ -0,0 +1,203 @@
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::{Iter, KvBackend, MemoryBackend};
+use sled_web::client;
+use sled_web::request::{BatchOp, Cas, CasError, Condition, ErrorCode, TransactionOutcome};
+use sled_web::request::ID_KEY;
+use sled_web::server::{self, Router};
+use sled_web::sled;
+use sled_web::tree::Tree;
+use sled_web::Client;
+use std::i64;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+#[test]
+fn incr_overflow() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for &(key, start, delta) in &[(1, i64::MAX - 1, 1), (2, i64::MIN + 1, -1)] {
+        assert_eq!(runtime.block_on(client.incr(vec![key], start)).unwrap(), start);
+        let end = start + delta;
+        assert_eq!(runtime.block_on(client.incr(vec![key], delta)).unwrap(), end);
+        match runtime.block_on(client.incr(vec![key], delta)) {
+            Err(client::Error::Response(ref body)) if body.code == ErrorCode::Conflict => (),
+            other => panic!("expected the increment to overflow, got {:?}", other),
+        }
+        // The entry is left unchanged.
+        assert_eq!(runtime.block_on(client.incr(vec![key], 0)).unwrap(), end);
+    }
+}
+
+#[test]
+fn cas_conflict() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.cas(vec![1], None, Some(vec![1]))).unwrap().unwrap();
+    let res = runtime.block_on(client.cas(vec![1], None, Some(vec![2]))).unwrap();
+    assert_eq!(res, Err(Some(vec![1])));
+    runtime.block_on(client.cas(vec![1], Some(vec![1]), None)).unwrap().unwrap();
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), None);
+}
+
+/// Appends each merged value to the existing value.
+fn concat(_key: &[u8], old: Option<&[u8]>, merged: &[u8]) -> Option<Vec<u8>> {
+    let mut value = old.map(|old| old.to_vec()).unwrap_or_default();
+    value.extend(merged);
+    Some(value)
+}
+
+#[test]
+fn merge_values() {
+    let backend = MemoryBackend::new().merge_operator(concat);
+    let router = Router::new(server::config().build(), backend);
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.merge(vec![1], vec![1, 2])).unwrap();
+    runtime.block_on(client.merge(vec![1], vec![3])).unwrap();
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), Some(vec![1, 2, 3]));
+}
+
+#[test]
+fn transaction_all_or_nothing() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![1], vec![1])).unwrap();
+    let writes = vec![
+        BatchOp::Set { key: vec![2], value: vec![2] },
+        BatchOp::Del { key: vec![1] },
+    ];
+
+    let conditions = vec![Condition { key: vec![1], expected: Some(vec![0]) }];
+    let outcome = runtime.block_on(client.transaction(conditions, writes.clone())).unwrap();
+    let conflicts = vec![(vec![1], Some(vec![1]))];
+    assert_eq!(outcome, TransactionOutcome::Aborted { conflicts });
+    assert_eq!(runtime.block_on(client.get(vec![2])).unwrap(), None);
+
+    let conditions = vec![Condition { key: vec![1], expected: Some(vec![1]) }];
+    let outcome = runtime.block_on(client.transaction(conditions, writes)).unwrap();
+    assert_eq!(outcome, TransactionOutcome::Committed);
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), None);
+    assert_eq!(runtime.block_on(client.get(vec![2])).unwrap(), Some(vec![2]));
+}
+
+/// A `MemoryBackend` that fails to set or swap the entry with the key `[9]`.
+struct Failing(MemoryBackend);
+
+impl KvBackend for Failing {
+    fn get(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        self.0.get(key)
+    }
+    fn del(&self, key: &[u8]) -> sled::Result<Option<Vec<u8>>, ()> {
+        self.0.del(key)
+    }
+    fn set(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        if key == [9] {
+            return Err(sled::Error::Unsupported("failing set".to_string()));
+        }
+        self.0.set(key, value)
+    }
+    fn cas(
+        &self,
+        key: Vec<u8>,
+        old: Option<Vec<u8>>,
+        new: Option<Vec<u8>>,
+    ) -> sled::Result<(), Option<Vec<u8>>> {
+        if key == [9] {
+            return Err(sled::Error::Unsupported("failing cas".to_string()));
+        }
+        self.0.cas(key, old, new)
+    }
+    fn merge(&self, key: Vec<u8>, value: Vec<u8>) -> sled::Result<(), ()> {
+        self.0.merge(key, value)
+    }
+    fn flush(&self) -> sled::Result<(), ()> {
+        self.0.flush()
+    }
+    fn scan<'a>(&'a self, key: &[u8]) -> Iter<'a> {
+        self.0.scan(key)
+    }
+    fn max(&self) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        self.0.max()
+    }
+    fn pred(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        self.0.pred(key)
+    }
+    fn pred_incl(&self, key: &[u8]) -> sled::Result<Option<(Vec<u8>, Vec<u8>)>, ()> {
+        self.0.pred_incl(key)
+    }
+}
+
+#[test]
+fn transaction_undone_on_failure() {
+    let tree = Tree::new(Arc::new(Failing(MemoryBackend::new())));
+    tree.set(vec![1], vec![1]).unwrap();
+    let writes = vec![
+        BatchOp::Set { key: vec![1], value: vec![10] },
+        BatchOp::Set { key: vec![2], value: vec![2] },
+        BatchOp::Del { key: vec![1] },
+        BatchOp::Set { key: vec![9], value: vec![9] },
+    ];
+    assert!(tree.transaction(vec![], writes).is_err());
+    assert_eq!(tree.get(&[1]).unwrap(), Some(vec![1]));
+    assert_eq!(tree.get(&[2]).unwrap(), None);
+    assert_eq!(tree.get(&[9]).unwrap(), None);
+}
+
+#[test]
+fn batch_cas_failure() {
+    let tree = Tree::new(Arc::new(Failing(MemoryBackend::new())));
+    let client = Client::local(Router::new(server::config().build(), tree));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![2], vec![2])).unwrap();
+    let ops = vec![
+        Cas { key: vec![1], old: None, new: Some(vec![1]) },
+        Cas { key: vec![9], old: None, new: Some(vec![9]) },
+        Cas { key: vec![2], old: None, new: Some(vec![3]) },
+        Cas { key: vec![3], old: None, new: Some(vec![3]) },
+    ];
+    let results = runtime.block_on(client.batch_cas(ops.clone())).unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0], Ok(()));
+    match results[1] {
+        Err(CasError::Failed(_)) => (),
+        ref other => panic!("expected the swap to fail, got {:?}", other),
+    }
+    assert_eq!(results[2], Err(CasError::Conflict(Some(vec![2]))));
+    assert_eq!(results[3], Ok(()));
+
+    // Prior to revision 9 the request fails as a whole, leaving the following swaps unperformed.
+    let legacy = client.clone().protocol(8);
+    let ops = vec![ops[1].clone(), Cas { key: vec![4], old: None, new: Some(vec![4]) }];
+    assert!(runtime.block_on(legacy.batch_cas(ops)).is_err());
+    assert_eq!(runtime.block_on(client.get(vec![4])).unwrap(), None);
+}
+
+#[test]
+fn generated_ids_outside_keyspace() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    assert_eq!(runtime.block_on(client.generate_id()).unwrap(), 0);
+    assert_eq!(runtime.block_on(client.generate_id()).unwrap(), 1);
+    assert_eq!(runtime.block_on(client.len()).unwrap(), 0);
+    runtime.block_on(client.set(vec![1], vec![1])).unwrap();
+    runtime.block_on(client.clear()).unwrap();
+    assert_eq!(runtime.block_on(client.generate_id()).unwrap(), 2);
+}
+
+#[test]
+fn id_counter_moved_into_store() {
+    let backend = Arc::new(MemoryBackend::new());
+    backend.set(ID_KEY.to_vec(), vec![5, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+    let store: Arc<KvBackend> = Arc::new(MemoryBackend::new());
+    let tree = Tree::new(backend).with_id_store(store.clone()).unwrap();
+    assert_eq!(tree.get(ID_KEY).unwrap(), None);
+    assert_eq!(tree.generate_id().unwrap(), 5);
+    let tree = Tree::new(Arc::new(MemoryBackend::new())).with_id_store(store).unwrap();
+    assert_eq!(tree.generate_id().unwrap(), 6);
+}
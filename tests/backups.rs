@@ -0,0 +1,63 @@
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::MemoryBackend;
+use sled_web::backup::Schedule;
+use sled_web::request::RestorePoint;
+use sled_web::server::{self, Router};
+use sled_web::tree::Tree;
+use sled_web::Client;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
+use tokio::runtime::Runtime;
+
+#[test]
+fn back_up_and_restore() {
+    let dir = env::temp_dir().join(format!("sled-web-backups-{}", process::id()));
+    let schedule = Schedule::new(dir.clone(), Duration::from_millis(200), 10);
+    let config = server::config().backup(schedule).build();
+    let tree = Tree::new(Arc::new(MemoryBackend::new()))
+        .with_changelog(Arc::new(MemoryBackend::new()))
+        .unwrap();
+    let client = Client::local(Router::new(config, tree.clone()));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+
+    runtime.block_on(client.set(vec![1], vec![1])).unwrap();
+    let first_seq = tree.change_seq();
+    runtime.block_on(client.set(vec![2], vec![2])).unwrap();
+    let backed_up_seq = tree.change_seq();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let status = runtime.block_on(client.backups()).unwrap();
+        assert_eq!(status.last_error, None);
+        if status.last_seq == Some(backed_up_seq) {
+            assert!(!status.backups.is_empty());
+            break;
+        }
+        assert!(Instant::now() < deadline, "no backup was taken");
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    runtime.block_on(client.set(vec![3], vec![3])).unwrap();
+    let point_seq = tree.change_seq();
+    runtime.block_on(client.set(vec![1], vec![10])).unwrap();
+    runtime.block_on(client.del(vec![2])).unwrap();
+
+    // Restored from a backup along with the changes that followed it up to the point.
+    let restored = runtime.block_on(client.restore(RestorePoint::Seq(point_seq))).unwrap();
+    assert!(restored.backup.is_some());
+    assert_eq!((restored.seq, restored.entries), (point_seq, 3));
+    for key in 1..4 {
+        assert_eq!(runtime.block_on(client.get(vec![key])).unwrap(), Some(vec![key]));
+    }
+
+    // Replayed from an empty tree, as the point precedes every backup.
+    let restored = runtime.block_on(client.restore(RestorePoint::Seq(first_seq))).unwrap();
+    assert_eq!(restored.backup, None);
+    assert_eq!((restored.seq, restored.entries), (first_seq, 1));
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), Some(vec![1]));
+    assert_eq!(runtime.block_on(client.get(vec![2])).unwrap(), None);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
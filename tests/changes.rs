@@ -0,0 +1,79 @@
+extern crate serde_json;
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::{KvBackend, MemoryBackend};
+use sled_web::request::Event;
+use sled_web::server::{self, Router};
+use sled_web::tree::Tree;
+use sled_web::Client;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+#[test]
+fn concurrent_changes_logged_in_order() {
+    let log: Arc<KvBackend> = Arc::new(MemoryBackend::new());
+    let tree = Tree::new(Arc::new(MemoryBackend::new()))
+        .with_changelog(log.clone())
+        .unwrap();
+    let threads: Vec<_> = (0..8u8)
+        .map(|thread| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for i in 0..100u8 {
+                    tree.set(vec![1], vec![thread, i]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let events: Vec<Event> = log.iter()
+        .map(|res| serde_json::from_slice(&res.unwrap().1).unwrap())
+        .collect();
+    assert_eq!(events.len(), 800);
+    // Each change was applied over the value produced by the change preceding it.
+    for pair in events.windows(2) {
+        assert_eq!(pair[0].seq + 1, pair[1].seq);
+        assert_eq!(pair[0].new, pair[1].old);
+    }
+    assert_eq!(events.last().unwrap().new, tree.get(&[1]).unwrap());
+}
+
+#[test]
+fn follow_changes_via_client() {
+    let tree = Tree::new(Arc::new(MemoryBackend::new())).with_change_history(16);
+    let router = Router::new(server::config().build(), tree);
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let timeout = Duration::from_millis(10);
+    let start = runtime.block_on(client.changes_since(0, timeout)).unwrap();
+    assert!(start.events.is_empty());
+
+    for key in 1..4 {
+        runtime.block_on(client.set(vec![key], vec![key])).unwrap();
+    }
+    runtime.block_on(client.del(vec![2])).unwrap();
+    let batch = runtime.block_on(client.changes_since(start.seq, timeout)).unwrap();
+    assert!(batch.complete);
+    let changes: Vec<_> = batch.events
+        .iter()
+        .map(|event| (event.key.clone(), event.old.clone(), event.new.clone()))
+        .collect();
+    let expected = vec![
+        (vec![1], None, Some(vec![1])),
+        (vec![2], None, Some(vec![2])),
+        (vec![3], None, Some(vec![3])),
+        (vec![2], Some(vec![2]), None),
+    ];
+    assert_eq!(changes, expected);
+    assert_eq!(batch.seq, batch.events.last().unwrap().seq);
+
+    let next = runtime.block_on(client.changes_since(batch.seq, timeout)).unwrap();
+    assert!(next.events.is_empty());
+    assert_eq!(next.seq, batch.seq);
+}
@@ -0,0 +1,101 @@
+extern crate futures;
+extern crate sled_web;
+extern crate tokio;
+
+use futures::{Future, Stream};
+use sled_web::backend::MemoryBackend;
+use sled_web::compression::Compression;
+use sled_web::encoding::{self, Encoding};
+use sled_web::hyper::{header, Body, Request};
+use sled_web::server::{self, Router};
+use sled_web::Client;
+use std::str;
+use tokio::runtime::Runtime;
+
+/// A `Client` submitting requests directly to a `Router` serving an empty in-memory tree.
+fn local_client() -> Client {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    Client::local(router)
+}
+
+/// Set, get and iterate over entries via a client of the given encoding.
+fn round_trip(client: Client) {
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![1], vec![2, 3])).unwrap();
+    runtime.block_on(client.set(vec![4], vec![5, 6])).unwrap();
+    let value = runtime.block_on(client.get(vec![1])).unwrap();
+    assert_eq!(value, Some(vec![2, 3]));
+    let entries = runtime.block_on(client.iter().collect()).unwrap();
+    assert_eq!(entries, vec![(vec![1], vec![2, 3]), (vec![4], vec![5, 6])]);
+}
+
+#[test]
+fn cbor_round_trip() {
+    round_trip(local_client().encoding(Encoding::Cbor));
+}
+
+#[test]
+fn message_pack_round_trip() {
+    round_trip(local_client().encoding(Encoding::MessagePack));
+}
+
+#[test]
+fn base64_query() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router.clone());
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![1], vec![2, 3])).unwrap();
+    let request = Request::get("http://localhost/tree/entries/AQ?bytes=base64")
+        .body(Body::empty())
+        .unwrap();
+    let response = runtime.block_on(router.respond(request)).unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], encoding::BASE64_JSON);
+    let body = runtime.block_on(response.into_body().concat2()).unwrap();
+    assert!(str::from_utf8(&body).unwrap().contains("AgM"));
+}
+
+#[test]
+fn compressed_responses() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router.clone()).compression(Some(Compression::Brotli));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let value = vec![7; 4096];
+    runtime.block_on(client.set(vec![1], value.clone())).unwrap();
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), Some(value));
+    let request = Request::get("http://localhost/tree/entries/AQ")
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = runtime.block_on(router.respond(request)).unwrap();
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+}
+
+/// Iterate over many entries via a client requesting compressed responses in the given encoding.
+fn compressed_iter(encoding: Encoding) {
+    let client = local_client().encoding(encoding).compression(Some(Compression::Gzip));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let expected: Vec<_> = (0..2_000u32)
+        .map(|i| (vec![(i >> 8) as u8, i as u8], vec![i as u8; 16]))
+        .collect();
+    for &(ref key, ref value) in &expected {
+        runtime.block_on(client.set(key.clone(), value.clone())).unwrap();
+    }
+    let entries = runtime.block_on(client.iter().collect()).unwrap();
+    assert_eq!(entries.len(), expected.len());
+    assert_eq!(entries, expected);
+}
+
+#[test]
+fn compressed_json_iter() {
+    compressed_iter(Encoding::Json);
+}
+
+#[test]
+fn compressed_cbor_iter() {
+    compressed_iter(Encoding::Cbor);
+}
+
+#[test]
+fn compressed_message_pack_iter() {
+    compressed_iter(Encoding::MessagePack);
+}
@@ -0,0 +1,36 @@
+extern crate futures;
+extern crate sled_web;
+extern crate tokio;
+
+use futures::Stream;
+use sled_web::backend::MemoryBackend;
+use sled_web::hyper::{header, Body, Request, StatusCode};
+use sled_web::server::{self, Router};
+use sled_web::Client;
+use tokio::runtime::Runtime;
+
+#[test]
+fn too_many_requests_retry_after() {
+    let config = server::config().max_concurrent_requests(0).build();
+    let router = Router::new(config, MemoryBackend::new());
+    let request = Request::get("http://localhost/tree/entries/AQ").body(Body::empty()).unwrap();
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let response = runtime.block_on(router.respond(request)).unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    // No request has completed from which to estimate a longer wait.
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+}
+
+#[test]
+fn key_stream_limits() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for key in 1..6 {
+        runtime.block_on(client.set(vec![key], vec![])).unwrap();
+    }
+    let keys = runtime.block_on(client.iter_keys_limit(2).collect()).unwrap();
+    assert_eq!(keys, vec![vec![1], vec![2]]);
+    let keys = runtime.block_on(client.scan_keys_limit(vec![3], 2).collect()).unwrap();
+    assert_eq!(keys, vec![vec![3], vec![4]]);
+}
@@ -0,0 +1,97 @@
+extern crate futures;
+extern crate serde_json;
+extern crate sled_web;
+extern crate tokio;
+
+use futures::Stream;
+use sled_web::backend::MemoryBackend;
+use sled_web::client;
+use sled_web::hyper::{Body, Request, Response, StatusCode};
+use sled_web::request::{ErrorBody, ErrorCode};
+use sled_web::server::{self, Middleware, RequestInfo, Router};
+use sled_web::Client;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+
+/// Logs each invocation, rejecting every request if `reject` is `true`.
+struct Logged {
+    name: &'static str,
+    reject: bool,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Middleware for Logged {
+    fn on_request(&self, _request: &mut Request<Body>) -> Result<(), Response<Body>> {
+        self.log.lock().unwrap().push(format!("{} request", self.name));
+        if self.reject {
+            let response = Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())
+                .unwrap();
+            return Err(response);
+        }
+        Ok(())
+    }
+
+    fn on_response(&self, _info: &RequestInfo, _response: &mut Response<Body>) {
+        self.log.lock().unwrap().push(format!("{} response", self.name));
+    }
+}
+
+/// Respond to a `Get` via a router with middleware `a` followed by `b`, producing the status and
+/// the log of middleware invocations.
+fn respond_via_middleware(reject_a: bool) -> (StatusCode, Vec<String>) {
+    let log = Arc::new(Mutex::new(vec![]));
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    router.add_middleware(Logged { name: "a", reject: reject_a, log: log.clone() });
+    router.add_middleware(Logged { name: "b", reject: false, log: log.clone() });
+    let request = Request::get("http://localhost/tree/entries/AQ").body(Body::empty()).unwrap();
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let response = runtime.block_on(router.respond(request)).unwrap();
+    let log = log.lock().unwrap().clone();
+    (response.status(), log)
+}
+
+#[test]
+fn middleware_order() {
+    let (status, log) = respond_via_middleware(false);
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(log, vec!["a request", "b request", "b response", "a response"]);
+}
+
+#[test]
+fn rejecting_middleware_skips_following() {
+    let (status, log) = respond_via_middleware(true);
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(log, vec!["a request", "a response"]);
+}
+
+#[test]
+fn client_rejected_with_empty_body() {
+    let log = Arc::new(Mutex::new(vec![]));
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    router.add_middleware(Logged { name: "a", reject: true, log });
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    match runtime.block_on(client.get(vec![1])) {
+        Err(client::Error::Server(_)) => (),
+        other => panic!("expected an error describing the empty body, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_entry_error_body() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for uri in &["http://localhost/tree/entries/AQ", "http://localhost/tree/raw/AQ"] {
+        let request = Request::get(*uri).body(Body::empty()).unwrap();
+        let response = runtime.block_on(router.respond(request)).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = runtime.block_on(response.into_body().concat2()).unwrap();
+        let body: ErrorBody = serde_json::from_slice(&body).unwrap();
+        assert_eq!((body.code, body.key), (ErrorCode::NotFound, Some(vec![1])));
+    }
+
+    let client = Client::local(router).missing_not_found(true);
+    assert_eq!(runtime.block_on(client.get(vec![1])).unwrap(), None);
+}
@@ -0,0 +1,51 @@
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::MemoryBackend;
+use sled_web::client;
+use sled_web::request::ErrorCode;
+use sled_web::server::{self, Router};
+use sled_web::Client;
+use tokio::runtime::Runtime;
+
+#[test]
+fn page_through_tree() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for key in 1..6 {
+        runtime.block_on(client.set(vec![key], vec![key * 10])).unwrap();
+    }
+    let mut entries = vec![];
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = runtime.block_on(client.iter_page(cursor, 2)).unwrap();
+        assert!(page.entries.len() <= 2);
+        entries.extend(page.entries);
+        pages += 1;
+        cursor = match page.next_cursor {
+            None => break,
+            Some(cursor) => Some(cursor),
+        };
+        assert!(pages <= 3, "paging did not end");
+    }
+    let expected: Vec<_> = (1..6).map(|key| (vec![key], vec![key * 10])).collect();
+    assert_eq!(entries, expected);
+
+    let page = runtime.block_on(client.iter_page(None, ::std::u64::MAX)).unwrap();
+    assert_eq!(page.entries, expected);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn zero_page_size() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![1], vec![1])).unwrap();
+    match runtime.block_on(client.iter_page(None, 0)) {
+        Err(client::Error::Response(ref body)) if body.code == ErrorCode::BadRequest => (),
+        other => panic!("expected a zero page size to be rejected, got {:?}", other),
+    }
+}
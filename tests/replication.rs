@@ -0,0 +1,50 @@
+extern crate futures;
+extern crate sled_web;
+extern crate tokio;
+
+use futures::Future;
+use sled_web::backend::MemoryBackend;
+use sled_web::client;
+use sled_web::request::ErrorCode;
+use sled_web::server::{self, Router};
+use sled_web::tree::Tree;
+use sled_web::Client;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+/// Wait for the entry at `key` to hold `value` via the given client, panicking after 5 seconds.
+fn wait_for(runtime: &mut Runtime, client: &Client, key: Vec<u8>, value: Option<Vec<u8>>) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while runtime.block_on(client.get(key.clone())).ok() != Some(value.clone()) {
+        assert!(Instant::now() < deadline, "the replica did not receive {:?}", value);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn replica_follows_upstream() {
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    let config = server::config().addr(([127, 0, 0, 1], 0)).build();
+    let tree = Tree::new(Arc::new(MemoryBackend::new())).with_change_history(64);
+    let upstream = server::new(config, tree).expect("failed to bind upstream");
+    let uri = format!("http://{}", upstream.local_addr());
+    runtime.spawn(upstream.map_err(|err| panic!("upstream failed: {}", err)));
+    let upstream = Client::new(uri.parse().unwrap());
+    runtime.block_on(upstream.set(vec![1], vec![1])).unwrap();
+
+    // The replica begins with a snapshot, then follows the upstream's changes.
+    let config = server::config().replicate_from(uri).build();
+    let replica = Client::local(Router::new(config, MemoryBackend::new()));
+    wait_for(&mut runtime, &replica, vec![1], Some(vec![1]));
+    runtime.block_on(upstream.set(vec![2], vec![2])).unwrap();
+    runtime.block_on(upstream.del(vec![1])).unwrap();
+    wait_for(&mut runtime, &replica, vec![2], Some(vec![2]));
+    wait_for(&mut runtime, &replica, vec![1], None);
+
+    match runtime.block_on(replica.set(vec![3], vec![3])) {
+        Err(client::Error::Response(ref body)) if body.code == ErrorCode::Forbidden => (),
+        other => panic!("expected the replica to refuse the write, got {:?}", other),
+    }
+}
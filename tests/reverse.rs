@@ -0,0 +1,26 @@
+extern crate futures;
+extern crate sled_web;
+extern crate tokio;
+
+use futures::Stream;
+use sled_web::backend::MemoryBackend;
+use sled_web::server::{self, Router};
+use sled_web::Client;
+use tokio::runtime::Runtime;
+
+#[test]
+fn reverse_limits() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for key in 1..6 {
+        runtime.block_on(client.set(vec![key], vec![key * 10])).unwrap();
+    }
+    let entries = runtime.block_on(client.iter_rev_limit(2).collect()).unwrap();
+    assert_eq!(entries, vec![(vec![5], vec![50]), (vec![4], vec![40])]);
+    let entries = client.scan_range_rev_limit(vec![2], vec![5], 2).collect();
+    let entries = runtime.block_on(entries).unwrap();
+    assert_eq!(entries, vec![(vec![4], vec![40]), (vec![3], vec![30])]);
+    let entries = runtime.block_on(client.scan_range_last(vec![1], vec![5], 3)).unwrap();
+    assert_eq!(entries, vec![(vec![2], vec![20]), (vec![3], vec![30]), (vec![4], vec![40])]);
+}
@@ -0,0 +1,47 @@
+extern crate futures;
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::MemoryBackend;
+use sled_web::client::{self, Client, Transport};
+use sled_web::hyper::{Body, Request};
+use sled_web::request::ErrorCode;
+use sled_web::response::ResponseFuture;
+use sled_web::server::{self, Router};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// A `Transport` whose responses never begin.
+struct Unresponsive;
+
+impl Transport for Unresponsive {
+    fn request(&self, _request: Request<Body>) -> ResponseFuture {
+        Box::new(futures::future::empty())
+    }
+}
+
+#[test]
+fn client_timeout() {
+    let uri = Client::LOCAL_URI.parse().unwrap();
+    let client = Client::with_transport(uri, Unresponsive)
+        .timeout(Some(Duration::from_millis(10)));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    match runtime.block_on(client.get(vec![1])) {
+        Err(client::Error::TimedOut) => (),
+        other => panic!("expected the request to time out, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn deadline_abandons_long_poll() {
+    let router = Router::new(server::config().build(), MemoryBackend::new());
+    let client = Client::local(router).deadline(Duration::from_millis(50));
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    runtime.block_on(client.set(vec![1], vec![2])).unwrap();
+    // No further changes are made, so only the deadline may end the wait.
+    let changes = client.changes_since(1, Duration::from_secs(30));
+    match runtime.block_on(changes) {
+        Err(client::Error::Response(ref body)) if body.code == ErrorCode::DeadlineExceeded => (),
+        other => panic!("expected the deadline to be exceeded, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,80 @@
+extern crate sled_web;
+extern crate tokio;
+
+use sled_web::backend::{MemoryBackend, MemoryStore, SledStore, Store};
+use sled_web::client;
+use sled_web::request::ErrorCode;
+use sled_web::server::{self, Router};
+use sled_web::sled;
+use sled_web::tree::Tree;
+use sled_web::Client;
+use std::sync::Arc;
+use std::{env, fs, process};
+use tokio::runtime::Runtime;
+
+#[test]
+fn drop_tree_outside_store() {
+    let dir = env::temp_dir().join(format!("sled-web-drop-tree-{}", process::id()));
+    let outside = dir.join("x");
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(outside.join("entry"), b"entry").unwrap();
+    let store = SledStore::new(sled::ConfigBuilder::new(), dir.join("store"));
+    assert!(store.remove("../x").is_err());
+    assert!(store.open("../x").is_err());
+
+    let router = Router::with_store(server::config().build(), MemoryBackend::new(), store);
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    for name in &["../x", "/tmp", ".."] {
+        match runtime.block_on(client.drop_tree(name.to_string())) {
+            Err(client::Error::Response(ref body)) if body.code == ErrorCode::BadRequest => (),
+            other => panic!("expected {:?} to be rejected, got {:?}", name, other),
+        }
+    }
+    assert!(outside.join("entry").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn transfer_between_views() {
+    let tree = Tree::new(Arc::new(MemoryBackend::new()));
+    for key in 1..5 {
+        tree.set(vec![key], vec![key]).unwrap();
+    }
+    // Entries moved into `[2]` would fall within the range being moved.
+    let view = tree.view(vec![2], false);
+    let err = tree.transfer_range(&view, &[], None, true).unwrap_err();
+    assert_eq!((err.transferred, err.resume_from), (0, vec![]));
+    assert_eq!(tree.transfer_range(&view, &[3], None, true).unwrap(), 2);
+    assert_eq!(view.get(&[3]).unwrap(), Some(vec![3]));
+    assert_eq!(view.get(&[4]).unwrap(), Some(vec![4]));
+    assert_eq!(tree.get(&[3]).unwrap(), None);
+    assert_eq!(tree.get(&[2, 4]).unwrap(), Some(vec![4]));
+}
+
+#[test]
+fn named_trees() {
+    let router = Router::with_store(server::config().build(), MemoryBackend::new(), MemoryStore);
+    let client = Client::local(router);
+    let mut runtime = Runtime::new().expect("failed to start runtime");
+    assert!(runtime.block_on(client.open_tree("a".to_string())).unwrap());
+    assert!(!runtime.block_on(client.open_tree("a".to_string())).unwrap());
+    assert_eq!(runtime.block_on(client.tree_names()).unwrap(), vec!["a".to_string()]);
+
+    for key in 1..5 {
+        runtime.block_on(client.set(vec![key], vec![key])).unwrap();
+    }
+    let a = client.tree("a");
+    let copied = client.copy_entries(None, Some("a".to_string()), vec![1], Some(vec![3]));
+    assert_eq!(runtime.block_on(copied).unwrap(), 2);
+    let moved = client.move_entries(None, Some("a".to_string()), vec![3], None);
+    assert_eq!(runtime.block_on(moved).unwrap(), 2);
+    assert_eq!(runtime.block_on(a.len()).unwrap(), 4);
+    assert_eq!(runtime.block_on(client.len()).unwrap(), 2);
+    assert_eq!(runtime.block_on(a.get(vec![4])).unwrap(), Some(vec![4]));
+    assert_eq!(runtime.block_on(client.get(vec![4])).unwrap(), None);
+
+    assert!(runtime.block_on(client.drop_tree("a".to_string())).unwrap());
+    assert!(runtime.block_on(a.get(vec![4])).is_err());
+    assert!(runtime.block_on(client.tree_names()).unwrap().is_empty());
+}